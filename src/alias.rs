@@ -0,0 +1,236 @@
+//! This module finds the exact linear dependencies among the columns of a model matrix, the way
+//! R's `alias()` does for a fitted linear model.
+//!
+//! When the pivoted QR decomposition drops columns (rank < p), the leading `rank x rank` block
+//! R11 of the R factor spans the retained columns, and the block R12 above the dropped columns
+//! expresses each dropped column in terms of the retained ones. Solving R11 B = R12 gives the
+//! coefficients of those combinations.
+
+// src/alias.rs
+
+use crate::least_squares::QrDecomposition;
+use crate::types::{Data, RealMatrix, Tolerance};
+use std::fmt;
+
+/// The complete aliasing pattern of a model matrix.
+///
+/// Each aliased column `a` satisfies `x[a] = sum_j complete[[a, j]] * x[retained[j]]` exactly
+/// (up to the tolerance used to determine the rank).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Alias {
+    /// The names of the columns kept in the fit, in pivoted order.
+    pub retained: Vec<String>,
+    /// The names of the columns dropped from the fit, in pivoted order.
+    pub aliased: Vec<String>,
+    /// A matrix with one row per aliased column and one column per retained column.
+    pub complete: RealMatrix,
+}
+
+impl Alias {
+    /// Compute the aliasing pattern of the x matrix of `data`.
+    ///
+    /// # Arguments
+    /// * `data` - The data whose x matrix is checked for linear dependencies.
+    /// * `tol` - The tolerance used by the QR decomposition to determine the rank.
+    pub fn new(data: &Data, tol: &Tolerance) -> Self {
+        Alias::from_qr(&QrDecomposition::new(data.x(), tol), data.column_names())
+    }
+
+    /// Compute the aliasing pattern from an existing QR decomposition, naming the columns of the
+    /// decomposed matrix by `column_names` (given in the original column order).
+    pub fn from_qr(qr: &QrDecomposition, column_names: &[String]) -> Self {
+        let rank = qr.rank;
+        let r = qr.r();
+        let names: Vec<String> = qr.pivot.iter().map(|&j| column_names[j].clone()).collect();
+        let aliased = names[rank..].to_vec();
+
+        let mut complete = RealMatrix::with_shape(aliased.len(), rank);
+        for (a, j) in (rank..qr.n_cols()).enumerate() {
+            let column: Vec<f64> = (0..rank).map(|i| r.values[[i, j]]).collect();
+            let beta = zap_small(qr.backsolve(&column));
+            for (i, value) in beta.into_iter().enumerate() {
+                complete.values[[a, i]] = value;
+            }
+        }
+
+        Alias {
+            retained: names[..rank].to_vec(),
+            aliased,
+            complete,
+        }
+    }
+
+    /// Return `true` if no column is aliased.
+    pub fn is_empty(&self) -> bool {
+        self.aliased.is_empty()
+    }
+
+    /// Return, for each aliased column, the retained columns (with non-zero coefficients) whose
+    /// linear combination reproduces it.
+    pub fn dependencies(&self) -> Vec<(&str, Vec<(&str, f64)>)> {
+        self.aliased
+            .iter()
+            .enumerate()
+            .map(|(a, name)| {
+                let terms = self
+                    .retained
+                    .iter()
+                    .enumerate()
+                    .filter(|&(j, _)| self.complete.values[[a, j]] != 0.0)
+                    .map(|(j, retained)| (retained.as_str(), self.complete.values[[a, j]]))
+                    .collect();
+                (name.as_str(), terms)
+            })
+            .collect()
+    }
+}
+
+/// Print the complete aliasing pattern as a table, like R's `print.listof` output for `alias()`.
+impl fmt::Display for Alias {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "No aliased coefficients.");
+        }
+
+        let cells: Vec<Vec<String>> = (0..self.aliased.len())
+            .map(|a| {
+                (0..self.retained.len())
+                    .map(|j| format!("{}", self.complete.values[[a, j]]))
+                    .collect()
+            })
+            .collect();
+        let row_width = self.aliased.iter().map(String::len).max().unwrap_or(0);
+        let widths: Vec<usize> = self
+            .retained
+            .iter()
+            .enumerate()
+            .map(|(j, name)| {
+                cells
+                    .iter()
+                    .map(|row| row[j].len())
+                    .fold(name.len(), usize::max)
+            })
+            .collect();
+
+        writeln!(f, "Complete :")?;
+        write!(f, "{:row_width$}", "")?;
+        for (name, width) in self.retained.iter().zip(&widths) {
+            write!(f, " {name:>width$}")?;
+        }
+        writeln!(f)?;
+        for (name, row) in self.aliased.iter().zip(&cells) {
+            write!(f, "{name:row_width$}")?;
+            for (cell, width) in row.iter().zip(&widths) {
+                write!(f, " {cell:>width$}")?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// Round values that are negligible relative to the largest value to zero, like R's `zapsmall`.
+fn zap_small(values: Vec<f64>) -> Vec<f64> {
+    let scale = values.iter().fold(1.0_f64, |acc, v| acc.max(v.abs()));
+    values
+        .into_iter()
+        .map(|v| if v.abs() < 1e-7 * scale { 0.0 } else { v })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn collinear_data() -> Data {
+        // x3 = x1 + 2 * x2 and x4 = x2, so both are aliased.
+        let x = RealMatrix::from_vec(
+            vec![
+                1.0, 0.0, 1.0, 0.0, //
+                1.0, 1.0, 3.0, 1.0, //
+                1.0, 2.0, 5.0, 2.0, //
+                1.0, 0.0, 1.0, 0.0, //
+                1.0, 5.0, 11.0, 5.0,
+            ],
+            5,
+            Some(4),
+        );
+        let y = RealMatrix::from_vec(vec![1.0, 2.0, 2.0, 3.0, 4.0], 5, None);
+        Data::new(x, y).with_column_names(
+            ["(Intercept)", "a", "b", "c"]
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_alias_reports_linear_combinations_of_retained_columns() {
+        let alias = Alias::new(&collinear_data(), &Tolerance::new(Some(1e-7)));
+
+        assert_eq!(alias.retained, vec!["(Intercept)", "a"]);
+        assert_eq!(alias.aliased, vec!["b", "c"]);
+
+        let dependencies = alias.dependencies();
+        assert_eq!(dependencies[0].0, "b");
+        assert_eq!(dependencies[0].1.len(), 2);
+        assert!((dependencies[0].1[0].1 - 1.0).abs() < 1e-10);
+        assert!((dependencies[0].1[1].1 - 2.0).abs() < 1e-10);
+        assert_eq!(dependencies[1].1.len(), 1);
+        assert_eq!(dependencies[1].1[0].0, "a");
+        assert!((dependencies[1].1[0].1 - 1.0).abs() < 1e-10);
+    }
+
+    #[test]
+    fn test_alias_is_empty_for_full_rank_data() {
+        let x = RealMatrix::from_vec(vec![1.0, 0.0, 1.0, 1.0, 1.0, 2.0], 3, Some(2));
+        let y = RealMatrix::from_vec(vec![1.0, 2.0, 3.0], 3, None);
+
+        let alias = Alias::new(&Data::new(x, y), &Tolerance::default());
+
+        assert!(alias.is_empty());
+        assert_eq!(alias.to_string(), "No aliased coefficients.\n");
+    }
+
+    #[test]
+    fn test_model_alias_uses_the_tolerance_of_the_fit() {
+        use crate::fitters::qr_decomposition_fitter::QrDecompositionFitter;
+        use crate::linear_model::LinearModel;
+
+        // b differs from a by 1e-8 in one row: aliased at tolerance 1e-6, but not by default.
+        let x = RealMatrix::from_vec(
+            vec![
+                1.0,
+                0.0,
+                0.0, //
+                1.0,
+                1.0,
+                1.0, //
+                1.0,
+                2.0,
+                2.0, //
+                1.0,
+                3.0,
+                3.0 + 1e-8,
+            ],
+            4,
+            Some(3),
+        );
+        let y = RealMatrix::from_vec(vec![1.0, 2.0, 2.0, 4.0], 4, None);
+        let data =
+            Data::new(x, y).with_column_names(["(Intercept)", "a", "b"].map(String::from).to_vec());
+        assert!(Alias::new(&data, &Tolerance::default()).is_empty());
+
+        let mut model = LinearModel::new(&data);
+        model.fit(&QrDecompositionFitter::new(
+            &data,
+            Some(Tolerance::new(Some(1e-6))),
+        ));
+        let LinearModel::Fitted(fitted) = &model else {
+            panic!("expected a fitted model");
+        };
+        assert_eq!(fitted.rank(), 2);
+        assert_eq!(fitted.alias().aliased, vec!["b"]);
+        assert_eq!(fitted.least_squares().unwrap().rank(), 2);
+    }
+}
@@ -0,0 +1,217 @@
+//! This module computes the sequential analysis of deviance of a generalized linear model, like
+//! R's `anova.glm`.
+//!
+//! Terms are added one at a time, first to last, and each submodel is refitted by IRLS. A row
+//! reports the reduction in the deviance from adding its term. Scaled by the dispersion of the
+//! full model, the reduction is compared with the chi-squared distribution, or divided by its
+//! degrees of freedom for an F test on the residual degrees of freedom of the full model, which
+//! suits families whose dispersion is estimated.
+
+// src/anova/deviance.rs
+
+use super::nested::{step_test, ComparisonTest};
+use super::{format_number, format_p_value, write_table};
+use crate::errors::GlmError;
+use crate::fitters::glm_fitter::GlmFit;
+use std::fmt;
+
+/// One row of an analysis of deviance table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DevianceRow {
+    /// The label of the term, or `NULL` for the null model.
+    pub term: String,
+    /// The degrees of freedom of the term. `None` for the null model.
+    pub df: Option<usize>,
+    /// The reduction in the deviance from adding the term. `None` for the null model.
+    pub deviance: Option<f64>,
+    /// The residual degrees of freedom after adding the term.
+    pub resid_df: usize,
+    /// The residual deviance after adding the term.
+    pub resid_dev: f64,
+    /// The F statistic of the term, for F tests only.
+    pub f_value: Option<f64>,
+    /// The upper tail probability of the test. `None` for the null model and for terms that
+    /// are entirely aliased.
+    pub p_value: Option<f64>,
+}
+
+/// A sequential analysis of deviance table for a generalized linear model.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DevianceTable {
+    /// The heading printed above the table.
+    pub heading: String,
+    /// The test used for each term.
+    pub test: ComparisonTest,
+    /// The dispersion the deviances are scaled by.
+    pub dispersion: f64,
+    /// The rows of the table, starting with the null model.
+    pub rows: Vec<DevianceRow>,
+}
+
+impl DevianceTable {
+    /// Return the row of the table for `term`, if there is one.
+    pub fn row(&self, term: &str) -> Option<&DevianceRow> {
+        self.rows.iter().find(|row| row.term == term)
+    }
+}
+
+impl fmt::Display for DevianceTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}\n", self.heading)?;
+        let mut header = vec!["Df", "Deviance", "Resid. Df", "Resid. Dev"];
+        match self.test {
+            ComparisonTest::F => header.extend(["F", "Pr(>F)"]),
+            ComparisonTest::Chisq => header.push("Pr(>Chi)"),
+        }
+        let cells: Vec<Vec<String>> = self
+            .rows
+            .iter()
+            .map(|row| {
+                let mut cells = vec![
+                    row.df.map(|df| df.to_string()).unwrap_or_default(),
+                    row.deviance.map(format_number).unwrap_or_default(),
+                    row.resid_df.to_string(),
+                    format_number(row.resid_dev),
+                ];
+                if self.test == ComparisonTest::F {
+                    cells.push(row.f_value.map(format_number).unwrap_or_default());
+                }
+                cells.push(row.p_value.map(format_p_value).unwrap_or_default());
+                cells
+            })
+            .collect();
+        let labels: Vec<&str> = self.rows.iter().map(|row| row.term.as_str()).collect();
+        write_table(f, &labels, &header, &cells)
+    }
+}
+
+/// Compute the sequential analysis of deviance of a fitted generalized linear model.
+///
+/// # Arguments
+/// * `fit` - The fitted model. Its terms are taken from the data it was fitted to.
+/// * `test` - The test to report for each term.
+///
+/// # Errors
+/// Returns an error if refitting one of the submodels fails.
+pub fn analysis_of_deviance(
+    fit: &GlmFit<'_>,
+    test: ComparisonTest,
+) -> Result<DevianceTable, GlmError> {
+    let terms = fit.data.terms();
+    let n = fit.y.len();
+    let dispersion = fit.dispersion();
+
+    let mut rows = vec![DevianceRow {
+        term: "NULL".to_string(),
+        df: None,
+        deviance: None,
+        resid_df: fit.df_null,
+        resid_dev: fit.null_deviance,
+        f_value: None,
+        p_value: None,
+    }];
+    let mut previous = (fit.null_deviance, usize::from(fit.intercept));
+    for (k, label) in terms.labels.iter().enumerate() {
+        let (resid_dev, rank) = if k + 1 == terms.n_terms() {
+            (fit.deviance, fit.rank)
+        } else {
+            let columns: Vec<usize> = (0..terms.assign.len())
+                .filter(|&j| terms.assign[j] <= k + 1)
+                .collect();
+            fit.refit(&columns, &fit.offset)?
+        };
+        let df = rank - previous.1;
+        let deviance = previous.0 - resid_dev;
+        let mut row = DevianceRow {
+            term: label.clone(),
+            df: Some(df),
+            deviance: Some(deviance),
+            resid_df: n - rank,
+            resid_dev,
+            f_value: None,
+            p_value: None,
+        };
+        if df > 0 {
+            let (statistic, p_value) =
+                step_test(test, deviance, df as i64, dispersion, fit.df_residual);
+            row.f_value = (test == ComparisonTest::F).then_some(statistic);
+            row.p_value = Some(p_value);
+        }
+        rows.push(row);
+        previous = (resid_dev, rank);
+    }
+
+    let heading = format!(
+        "Analysis of Deviance Table\n\nModel: {}, link: {}\n\nTerms added sequentially (first to last)",
+        fit.family.name(),
+        fit.family.link()
+    );
+    Ok(DevianceTable {
+        heading,
+        test,
+        dispersion,
+        rows,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::family::{Poisson, QuasiPoisson};
+    use crate::fitters::glm_fitter::GlmFitter;
+    use crate::types::{Data, RealMatrix};
+    use statrs::distribution::{ChiSquared, ContinuousCDF};
+
+    fn data() -> Data {
+        let n = 30;
+        let x: Vec<f64> = (0..n)
+            .flat_map(|i| [1.0, i as f64 / 10.0, ((i * 7) % 5) as f64])
+            .collect();
+        let y: Vec<f64> = (0..n)
+            .map(|i| {
+                ((0.4 + 0.1 * i as f64).exp() + 2.0 * (i as f64 * 1.7).sin())
+                    .round()
+                    .max(0.0)
+            })
+            .collect();
+        Data::new(
+            RealMatrix::from_vec(x, n, Some(3)),
+            RealMatrix::from_vec(y, n, None),
+        )
+        .with_column_names(vec![
+            "(Intercept)".to_string(),
+            "t".to_string(),
+            "z".to_string(),
+        ])
+    }
+
+    #[test]
+    fn test_deviance_table_adds_terms_sequentially() {
+        let data = data();
+        let fitter = GlmFitter::new(&data, Poisson::default());
+        let fit = fitter.glm().unwrap();
+        let table = analysis_of_deviance(&fit, ComparisonTest::Chisq).unwrap();
+
+        assert_eq!(table.rows.len(), 3);
+        assert_eq!(table.rows[0].resid_df, 29);
+        assert_eq!(table.rows[0].resid_dev, fit.null_deviance);
+        assert_eq!(table.rows[2].resid_dev, fit.deviance);
+        let t = table.row("t").unwrap();
+        let z = table.row("z").unwrap();
+        assert_eq!((t.df, t.resid_df, z.resid_df), (Some(1), 28, 27));
+        let total = t.deviance.unwrap() + z.deviance.unwrap();
+        assert!((total - (fit.null_deviance - fit.deviance)).abs() < 1e-8);
+        let expected = ChiSquared::new(1.0).unwrap().sf(z.deviance.unwrap());
+        assert!((z.p_value.unwrap() - expected).abs() < 1e-12);
+        assert!(table.to_string().contains("Pr(>Chi)"));
+
+        // The quasi-Poisson table has the same deviances, with F tests scaled by the dispersion.
+        let fitter = GlmFitter::new(&data, QuasiPoisson::default());
+        let quasi = fitter.glm().unwrap();
+        let table = analysis_of_deviance(&quasi, ComparisonTest::F).unwrap();
+        let quasi_z = table.row("z").unwrap();
+        assert!((quasi_z.deviance.unwrap() - z.deviance.unwrap()).abs() < 1e-8);
+        let f_value = z.deviance.unwrap() / quasi.dispersion();
+        assert!((quasi_z.f_value.unwrap() - f_value).abs() < 1e-8);
+    }
+}
@@ -0,0 +1,331 @@
+//! This module computes Type II and Type III analysis of variance tables, like `car::Anova` for
+//! linear models. Both are Wald tests of linear hypotheses on the coefficient vector, so they
+//! can use either the classical covariance matrix or a heteroskedasticity-robust sandwich.
+//!
+//! * Type II tests each term after every other term that does not contain it, respecting
+//!   marginality: a main effect is not adjusted for the interactions it takes part in. The
+//!   hypothesis matrix is the conjugate complement of the relatives' coefficients within the
+//!   span of the term and its relatives, with respect to the classical covariance (X'X)^-1, like
+//!   `car::Anova`; a sandwich covariance only enters the Wald test of that hypothesis.
+//! * Type III tests each term (including the intercept) after all other terms. These tests only
+//!   make sense when the factors are coded with sum-to-zero contrasts, so columns that look like
+//!   0/1 treatment dummies produce a warning on the table.
+
+// src/anova/marginal.rs
+
+use super::{AnovaRow, AnovaTable};
+use crate::distributions::f_upper_tail;
+use crate::errors::AnovaError;
+use crate::hypothesis::wald_statistic;
+use crate::least_squares::{LeastSquaresFit, QrDecomposition};
+use crate::terms::Terms;
+use crate::types::{RealMatrix, Tolerance};
+use crate::vcov::{unscaled_covariance, vcov, CovarianceType};
+
+/// An enum representing the types of marginal sums of squares.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SumOfSquaresType {
+    /// Each term is adjusted for all terms that do not contain it.
+    II,
+    /// Each term is adjusted for all other terms.
+    III,
+}
+
+/// Compute a Type II or Type III analysis of variance table for a single-response fit.
+///
+/// # Arguments
+/// * `fit` - The result of `dqrls` for the model.
+/// * `x` - The model matrix the fit was computed from.
+/// * `terms` - The model terms of the columns of x.
+/// * `ss_type` - Whether to compute Type II or Type III tests.
+/// * `covariance` - The coefficient covariance used by the Wald tests. With a sandwich
+///   estimator, the tests are robust to heteroskedasticity and no sums of squares are reported.
+/// * `tol` - The tolerance the model was fitted with, used for the rank of the Type II
+///   hypotheses.
+///
+/// # Errors
+/// Returns an error if the model has several responses or aliased coefficients, or if the
+/// hypothesis of a term is singular.
+pub fn marginal_anova(
+    fit: &LeastSquaresFit,
+    x: &RealMatrix,
+    terms: &Terms,
+    ss_type: SumOfSquaresType,
+    covariance: CovarianceType,
+    tol: &Tolerance,
+) -> Result<AnovaTable, AnovaError> {
+    if fit.effects.n_cols() != 1 {
+        return Err(AnovaError::MultipleResponses {
+            found: fit.effects.n_cols(),
+        });
+    }
+    let (n, p) = (x.n_rows(), x.n_cols());
+    if fit.rank() < p {
+        return Err(AnovaError::AliasedCoefficients);
+    }
+
+    let coefficients: Vec<f64> = fit.coefficients.values.column(0).to_vec();
+    let v = vcov(fit, x, 0, covariance);
+    let df_residual = n - p;
+    let rss: f64 = fit.residuals.values.iter().map(|e| e * e).sum();
+    let ms_residual = rss / df_residual as f64;
+    let classical = covariance == CovarianceType::Classical;
+    // The Type II hypotheses only depend on the classical covariance, up to scale.
+    let unscaled = unscaled_covariance(&fit.qr);
+
+    let tested: Vec<usize> = match ss_type {
+        SumOfSquaresType::II => (1..=terms.n_terms()).collect(),
+        SumOfSquaresType::III => (0..=terms.n_terms()).collect(),
+    };
+
+    let mut rows = Vec::new();
+    for term in tested {
+        let columns = terms.columns(term);
+        if columns.is_empty() {
+            continue;
+        }
+        let l = match ss_type {
+            SumOfSquaresType::II => type_ii_hypothesis(terms, term, &unscaled, tol),
+            SumOfSquaresType::III => selection_matrix(&columns, p),
+        };
+        let df = l.n_rows();
+        let wald = wald_statistic(&l, &coefficients, &v, &vec![0.0; df]).ok_or_else(|| {
+            AnovaError::SingularHypothesis {
+                term: terms.label(term).to_string(),
+            }
+        })?;
+        let f_value = wald / df as f64;
+        let sum_sq = if classical {
+            wald * ms_residual
+        } else {
+            f64::NAN
+        };
+        rows.push(AnovaRow {
+            term: terms.label(term).to_string(),
+            df,
+            sum_sq,
+            mean_sq: sum_sq / df as f64,
+            f_value: Some(f_value),
+            p_value: Some(f_upper_tail(f_value, df as f64, df_residual as f64)),
+        });
+    }
+    rows.push(AnovaRow {
+        term: "Residuals".to_string(),
+        df: df_residual,
+        sum_sq: if classical { rss } else { f64::NAN },
+        mean_sq: if classical { ms_residual } else { f64::NAN },
+        f_value: None,
+        p_value: None,
+    });
+
+    let mut heading = match ss_type {
+        SumOfSquaresType::II => "Anova Table (Type II tests)".to_string(),
+        SumOfSquaresType::III => "Anova Table (Type III tests)".to_string(),
+    };
+    if !classical {
+        heading.push_str(&format!(
+            "\n\nCoefficient covariances computed by {covariance:?}"
+        ));
+    }
+
+    let warnings = match ss_type {
+        SumOfSquaresType::II => Vec::new(),
+        SumOfSquaresType::III => treatment_coded_terms(x, terms)
+            .into_iter()
+            .map(|label| {
+                format!(
+                    "term {label} has 0/1 coded columns; Type III tests assume sum-to-zero \
+                     contrasts"
+                )
+            })
+            .collect(),
+    };
+
+    Ok(AnovaTable {
+        heading,
+        rows,
+        warnings,
+    })
+}
+
+/// Return the matrix whose rows select the coefficients of `columns` out of `p` coefficients.
+fn selection_matrix(columns: &[usize], p: usize) -> RealMatrix {
+    let mut l = RealMatrix::with_shape(columns.len(), p);
+    for (row, &column) in columns.iter().enumerate() {
+        l.values[[row, column]] = 1.0;
+    }
+    l
+}
+
+/// Return the Type II hypothesis matrix of `term`, following `car:::Anova.II.lm`: the conjugate
+/// complement, with respect to the inner product `v`, of the coefficients of the relatives of
+/// the term within the coefficients of the term and its relatives. `v` is the classical
+/// (unscaled) covariance (X'X)^-1, whatever covariance the Wald tests use.
+fn type_ii_hypothesis(terms: &Terms, term: usize, v: &RealMatrix, tol: &Tolerance) -> RealMatrix {
+    let p = v.n_rows();
+    let relative_columns: Vec<usize> = (1..=terms.n_terms())
+        .filter(|&other| terms.contains(other, term))
+        .flat_map(|other| terms.columns(other))
+        .collect();
+    let term_columns = terms.columns(term);
+    if relative_columns.is_empty() {
+        return selection_matrix(&term_columns, p);
+    }
+
+    // A = Z' V X, where the columns of Z select the term and its relatives and the columns of X
+    // select the relatives only.
+    let span: Vec<usize> = relative_columns
+        .iter()
+        .chain(&term_columns)
+        .copied()
+        .collect();
+    let mut a = RealMatrix::with_shape(span.len(), relative_columns.len());
+    for (i, &row) in span.iter().enumerate() {
+        for (j, &column) in relative_columns.iter().enumerate() {
+            a.values[[i, j]] = v.values[[row, column]];
+        }
+    }
+
+    // The columns of Q beyond the rank of A span its orthogonal complement; Z Q2 expresses them
+    // as coefficient combinations.
+    let qr = QrDecomposition::new(&a, tol);
+    let complement: Vec<usize> = (qr.rank..span.len()).collect();
+    let mut l = RealMatrix::with_shape(complement.len(), p);
+    for (row, &k) in complement.iter().enumerate() {
+        let mut unit = vec![0.0; span.len()];
+        unit[k] = 1.0;
+        for (i, value) in qr.qy(&unit).into_iter().enumerate() {
+            l.values[[row, span[i]]] = value;
+        }
+    }
+    l
+}
+
+/// Return the labels of the non-intercept terms whose columns only take the values 0 and 1,
+/// which is how treatment (dummy) contrasts code a factor.
+fn treatment_coded_terms(x: &RealMatrix, terms: &Terms) -> Vec<String> {
+    (1..=terms.n_terms())
+        .filter(|&term| {
+            let columns = terms.columns(term);
+            !columns.is_empty()
+                && columns.iter().all(|&j| {
+                    x.values
+                        .column(j)
+                        .iter()
+                        .all(|&value| value == 0.0 || value == 1.0)
+                })
+        })
+        .map(|term| terms.label(term).to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::anova::sequential::sequential_anova;
+    use crate::anova::Anova;
+    use crate::least_squares::dqrls;
+    use crate::types::Data;
+
+    /// An unbalanced two-factor design with an interaction, coded with sum-to-zero contrasts.
+    fn unbalanced_data() -> Data {
+        let a = [1.0, 1.0, 1.0, 1.0, 1.0, -1.0, -1.0, -1.0, -1.0];
+        let b = [1.0, 1.0, -1.0, -1.0, -1.0, 1.0, 1.0, 1.0, -1.0];
+        let y = [5.0, 5.0, 7.0, 8.5, 7.5, 3.0, 2.5, 4.0, 9.0];
+        let mut values = Vec::new();
+        for i in 0..9 {
+            values.extend([1.0, a[i], b[i], a[i] * b[i]]);
+        }
+        Data::new(
+            RealMatrix::from_vec(values, 9, Some(4)),
+            RealMatrix::from_vec(y.to_vec(), 9, None),
+        )
+        .with_terms(Terms::new(
+            vec!["a".to_string(), "b".to_string(), "a:b".to_string()],
+            vec![0, 1, 2, 3],
+        ))
+    }
+
+    #[test]
+    fn test_type_ii_matches_sequential_for_the_last_main_effect() {
+        let data = unbalanced_data();
+        let fit = dqrls(data.x(), data.y(), &Tolerance::default()).unwrap();
+
+        let type_ii = marginal_anova(
+            &fit,
+            data.x(),
+            &data.terms(),
+            SumOfSquaresType::II,
+            CovarianceType::Classical,
+            &Tolerance::default(),
+        )
+        .unwrap();
+        let Anova::Univariate(type_i) = sequential_anova(&fit, &data.terms()) else {
+            panic!("expected a univariate table");
+        };
+
+        // b enters after a and before a:b, which is exactly what Type II adjusts it for.
+        let ss_b = type_ii.row("b").unwrap().sum_sq;
+        assert!((ss_b - type_i.row("b").unwrap().sum_sq).abs() < 1e-8);
+        // The highest-order term is adjusted for everything in both tables.
+        let ss_ab = type_ii.row("a:b").unwrap().sum_sq;
+        assert!((ss_ab - type_i.row("a:b").unwrap().sum_sq).abs() < 1e-8);
+        assert!(type_ii.row("(Intercept)").is_none());
+
+        // a is adjusted for b but not for a:b: compare y ~ b with y ~ a + b.
+        let rss = |columns: &[usize]| {
+            let x = data.x().values.select(ndarray::Axis(1), columns);
+            let fit = dqrls(&RealMatrix::new(x), data.y(), &Tolerance::default()).unwrap();
+            fit.residuals.values.iter().map(|e| e * e).sum::<f64>()
+        };
+        let ss_a = type_ii.row("a").unwrap().sum_sq;
+        assert!((ss_a - (rss(&[0, 2]) - rss(&[0, 1, 2]))).abs() < 1e-8);
+    }
+
+    #[test]
+    fn test_type_iii_includes_the_intercept_and_robust_tests_omit_sums_of_squares() {
+        let data = unbalanced_data();
+        let fit = dqrls(data.x(), data.y(), &Tolerance::default()).unwrap();
+
+        let table = marginal_anova(
+            &fit,
+            data.x(),
+            &data.terms(),
+            SumOfSquaresType::III,
+            CovarianceType::HC1,
+            &Tolerance::default(),
+        )
+        .unwrap();
+
+        assert_eq!(table.rows.len(), 5);
+        assert_eq!(table.rows[0].term, "(Intercept)");
+        assert!(table.rows[1].sum_sq.is_nan());
+        assert!(table.rows[1].f_value.unwrap() > 0.0);
+        assert!(table.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_robust_type_ii_tests_the_classical_hypothesis() {
+        let data = unbalanced_data();
+        let fit = dqrls(data.x(), data.y(), &Tolerance::default()).unwrap();
+        let terms = data.terms();
+        let table = marginal_anova(
+            &fit,
+            data.x(),
+            &terms,
+            SumOfSquaresType::II,
+            CovarianceType::HC3,
+            &Tolerance::default(),
+        )
+        .unwrap();
+
+        // The hypothesis for a comes from (X'X)^-1; only the Wald test uses the HC3 covariance.
+        let tol = Tolerance::default();
+        let l = type_ii_hypothesis(&terms, 1, &unscaled_covariance(&fit.qr), &tol);
+        let robust = vcov(&fit, data.x(), 0, CovarianceType::HC3);
+        let coefficients = fit.coefficients.values.column(0).to_vec();
+        let wald = wald_statistic(&l, &coefficients, &robust, &[0.0]).unwrap();
+        assert!((table.row("a").unwrap().f_value.unwrap() - wald).abs() < 1e-10);
+        assert_ne!(l, type_ii_hypothesis(&terms, 1, &robust, &tol));
+    }
+}
@@ -0,0 +1,263 @@
+//! This module contains the analysis of variance tables for fitted linear models, mirroring R's
+//! `anova.lm` and `anova.mlm`.
+//!
+//! * `sequential`: Type I (sequential) sums of squares computed from the effects vector Q'y.
+//! * `marginal`: Type II and Type III tests computed as Wald tests on the coefficients, like
+//!   `car::Anova`.
+//! * `nested`: F and chi-squared comparisons of a sequence of nested models.
+//! * `deviance`: the sequential analysis of deviance of a generalized linear model, like
+//!   `anova.glm`.
+
+// src/anova/mod.rs
+
+pub mod deviance;
+pub mod marginal;
+pub mod nested;
+pub mod sequential;
+
+use crate::distributions::t_upper_tail;
+use std::fmt;
+
+/// One row of an analysis of variance table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnovaRow {
+    /// The label of the term, or `Residuals` for the residual row.
+    pub term: String,
+    /// The degrees of freedom of the term.
+    pub df: usize,
+    /// The sum of squares attributed to the term.
+    pub sum_sq: f64,
+    /// The mean square, `sum_sq / df`.
+    pub mean_sq: f64,
+    /// The F statistic of the term. `None` for the residual row.
+    pub f_value: Option<f64>,
+    /// The upper tail probability of the F statistic. `None` for the residual row.
+    pub p_value: Option<f64>,
+}
+
+/// An analysis of variance table for a single-response linear model, like R's `anova.lm`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnovaTable {
+    /// The heading printed above the table.
+    pub heading: String,
+    /// The rows of the table, ending with the residual row.
+    pub rows: Vec<AnovaRow>,
+    /// Warnings about the interpretation of the table, printed below it.
+    pub warnings: Vec<String>,
+}
+
+impl AnovaTable {
+    /// Return the row of the table for `term`, if there is one.
+    pub fn row(&self, term: &str) -> Option<&AnovaRow> {
+        self.rows.iter().find(|row| row.term == term)
+    }
+
+    /// Return the residual row of the table.
+    pub fn residuals(&self) -> Option<&AnovaRow> {
+        self.rows.last().filter(|row| row.f_value.is_none())
+    }
+}
+
+impl fmt::Display for AnovaTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}\n", self.heading)?;
+        let header = ["Df", "Sum Sq", "Mean Sq", "F value", "Pr(>F)"];
+        let cells: Vec<Vec<String>> = self
+            .rows
+            .iter()
+            .map(|row| {
+                vec![
+                    row.df.to_string(),
+                    format_optional(row.sum_sq),
+                    format_optional(row.mean_sq),
+                    row.f_value.map(format_number).unwrap_or_default(),
+                    row.p_value.map(format_p_value).unwrap_or_default(),
+                ]
+            })
+            .collect();
+        let labels: Vec<&str> = self.rows.iter().map(|row| row.term.as_str()).collect();
+        write_table(f, &labels, &header, &cells)?;
+        for warning in &self.warnings {
+            writeln!(f, "Warning: {warning}")?;
+        }
+        Ok(())
+    }
+}
+
+/// One row of a multivariate analysis of variance table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MultivariateAnovaRow {
+    /// The label of the term, or `Residuals` for the residual row.
+    pub term: String,
+    /// The degrees of freedom of the term.
+    pub df: usize,
+    /// Pillai's trace for the term. `None` for the residual row.
+    pub pillai: Option<f64>,
+    /// The F approximation to Pillai's trace. `None` for the residual row.
+    pub approx_f: Option<f64>,
+    /// The numerator degrees of freedom of the F approximation.
+    pub num_df: Option<f64>,
+    /// The denominator degrees of freedom of the F approximation.
+    pub den_df: Option<f64>,
+    /// The upper tail probability of the F approximation.
+    pub p_value: Option<f64>,
+}
+
+/// A multivariate analysis of variance table for a multi-response linear model, using Pillai's
+/// trace like the default of R's `anova.mlm`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MultivariateAnovaTable {
+    /// The heading printed above the table.
+    pub heading: String,
+    /// The rows of the table, ending with the residual row.
+    pub rows: Vec<MultivariateAnovaRow>,
+}
+
+impl MultivariateAnovaTable {
+    /// Return the row of the table for `term`, if there is one.
+    pub fn row(&self, term: &str) -> Option<&MultivariateAnovaRow> {
+        self.rows.iter().find(|row| row.term == term)
+    }
+}
+
+impl fmt::Display for MultivariateAnovaTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}\n", self.heading)?;
+        let header = ["Df", "Pillai", "approx F", "num Df", "den Df", "Pr(>F)"];
+        let cells: Vec<Vec<String>> = self
+            .rows
+            .iter()
+            .map(|row| {
+                vec![
+                    row.df.to_string(),
+                    row.pillai.map(format_number).unwrap_or_default(),
+                    row.approx_f.map(format_number).unwrap_or_default(),
+                    row.num_df.map(format_number).unwrap_or_default(),
+                    row.den_df.map(format_number).unwrap_or_default(),
+                    row.p_value.map(format_p_value).unwrap_or_default(),
+                ]
+            })
+            .collect();
+        let labels: Vec<&str> = self.rows.iter().map(|row| row.term.as_str()).collect();
+        write_table(f, &labels, &header, &cells)
+    }
+}
+
+/// The analysis of variance of a fitted linear model: univariate for a single response and
+/// multivariate for several responses.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Anova {
+    /// The table for a single-response model.
+    Univariate(AnovaTable),
+    /// The table for a multi-response model.
+    Multivariate(MultivariateAnovaTable),
+}
+
+impl fmt::Display for Anova {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Anova::Univariate(table) => table.fmt(f),
+            Anova::Multivariate(table) => table.fmt(f),
+        }
+    }
+}
+
+/// Format a statistic with five significant digits.
+pub(crate) fn format_number(value: f64) -> String {
+    format_significant(value, 5)
+}
+
+/// Format a value with `digits` significant digits, without switching to scientific notation.
+fn format_significant(value: f64, digits: i32) -> String {
+    if !value.is_finite() || value == 0.0 {
+        return value.to_string();
+    }
+    let decimals = (digits - 1 - value.abs().log10().floor() as i32).max(0) as usize;
+    format!("{value:.decimals$}")
+}
+
+/// Format a statistic that is `NaN` when it is not available (such as the sums of squares of
+/// a robust test) as a blank cell.
+fn format_optional(value: f64) -> String {
+    if value.is_nan() {
+        String::new()
+    } else {
+        format_number(value)
+    }
+}
+
+/// Format a p-value the way R's `format.pval` does, with a floor at machine precision.
+pub(crate) fn format_p_value(value: f64) -> String {
+    if value < 2.2e-16 {
+        "< 2.2e-16".to_string()
+    } else if value < 1e-4 {
+        format!("{value:.3e}")
+    } else {
+        format_significant(value, 4)
+    }
+}
+
+/// Write a right-aligned table with a header row and one labelled row per entry of `cells`.
+pub(crate) fn write_table(
+    f: &mut fmt::Formatter<'_>,
+    labels: &[&str],
+    header: &[&str],
+    cells: &[Vec<String>],
+) -> fmt::Result {
+    let label_width = labels.iter().map(|label| label.len()).max().unwrap_or(0);
+    let widths: Vec<usize> = header
+        .iter()
+        .enumerate()
+        .map(|(j, name)| {
+            cells
+                .iter()
+                .map(|row| row[j].len())
+                .fold(name.len(), usize::max)
+        })
+        .collect();
+
+    write!(f, "{:label_width$}", "")?;
+    for (name, width) in header.iter().zip(&widths) {
+        write!(f, " {name:>width$}")?;
+    }
+    writeln!(f)?;
+    for (label, row) in labels.iter().zip(cells) {
+        write!(f, "{label:label_width$}")?;
+        for (cell, width) in row.iter().zip(&widths) {
+            write!(f, " {cell:>width$}")?;
+        }
+        writeln!(f)?;
+    }
+    Ok(())
+}
+
+/// Write a coefficient table with t tests on `df` degrees of freedom, with columns `header`
+/// for the estimate, its standard error, the t statistic and the two-sided p-value. Rows whose
+/// estimate is `NaN`, such as aliased columns, are printed as NA.
+pub(crate) fn write_coefficients(
+    f: &mut fmt::Formatter<'_>,
+    names: &[String],
+    estimates: &[f64],
+    std_errors: &[f64],
+    df: usize,
+    header: &[&str; 4],
+) -> fmt::Result {
+    let labels: Vec<&str> = names.iter().map(String::as_str).collect();
+    let cells: Vec<Vec<String>> = estimates
+        .iter()
+        .zip(std_errors)
+        .map(|(&estimate, &std_error)| {
+            if estimate.is_nan() {
+                return vec!["NA".to_string(); 4];
+            }
+            let t = estimate / std_error;
+            vec![
+                format_number(estimate),
+                format_number(std_error),
+                format_number(t),
+                format_p_value(2.0 * t_upper_tail(t.abs(), df as f64)),
+            ]
+        })
+        .collect();
+    write_table(f, &labels, header, &cells)
+}
@@ -0,0 +1,379 @@
+//! This module compares a sequence of nested linear models fitted to the same observations,
+//! like R's `anova(m1, m2, m3)`.
+//!
+//! Each step reports the residual degrees of freedom and residual sum of squares of a model,
+//! and the change in both from the previous model. The change in the residual sum of squares is
+//! scaled by the residual mean square of the largest model, giving either an F test or the
+//! chi-squared (likelihood-ratio) test that R reports for `test = "Chisq"` / `test = "LRT"`.
+//!
+//! `anova(&[&m1, &m2, &m3])` is the F comparison; `compare_models` chooses the test. Models must
+//! have the same observations, response and weights, and each smaller model matrix must lie in
+//! the column span of the larger one.
+
+// src/anova/nested.rs
+
+use super::{format_number, format_p_value, write_table};
+use crate::distributions::{chi_squared_upper_tail, f_upper_tail};
+use crate::errors::AnovaError;
+use crate::least_squares::dqrls;
+use crate::linear_model::FittedLinearModel;
+use crate::types::RealMatrix;
+use std::fmt;
+
+/// The relative tolerance used to decide whether a column lies in the span of a model matrix.
+const SPAN_TOL: f64 = 1e-7;
+
+/// An enum representing the tests available for comparing nested models.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ComparisonTest {
+    /// The F test of the change in the residual sum of squares.
+    #[default]
+    F,
+    /// The chi-squared (likelihood-ratio) test of the scaled change in the residual sum of
+    /// squares.
+    Chisq,
+}
+
+/// One row of a model comparison table, describing one model and its change from the previous
+/// model.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModelComparisonRow {
+    /// The residual degrees of freedom of the model.
+    pub res_df: usize,
+    /// The residual sum of squares of the model.
+    pub rss: f64,
+    /// The change in degrees of freedom from the previous model. `None` for the first model.
+    pub df: Option<i64>,
+    /// The reduction in the residual sum of squares from the previous model.
+    pub sum_sq: Option<f64>,
+    /// The F or chi-squared statistic of the step.
+    pub statistic: Option<f64>,
+    /// The upper tail probability of the statistic.
+    pub p_value: Option<f64>,
+}
+
+/// A table comparing a sequence of nested models.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModelComparisonTable {
+    /// The heading printed above the table, describing each model.
+    pub heading: String,
+    /// The test used for each step.
+    pub test: ComparisonTest,
+    /// One row per model, in the order the models were given.
+    pub rows: Vec<ModelComparisonRow>,
+}
+
+impl fmt::Display for ModelComparisonTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}\n", self.heading)?;
+        let header = match self.test {
+            ComparisonTest::F => ["Res.Df", "RSS", "Df", "Sum of Sq", "F", "Pr(>F)"],
+            ComparisonTest::Chisq => ["Res.Df", "RSS", "Df", "Sum of Sq", "Chisq", "Pr(>Chi)"],
+        };
+        let cells: Vec<Vec<String>> = self
+            .rows
+            .iter()
+            .map(|row| {
+                vec![
+                    row.res_df.to_string(),
+                    format_number(row.rss),
+                    row.df.map(|df| df.to_string()).unwrap_or_default(),
+                    row.sum_sq.map(format_number).unwrap_or_default(),
+                    row.statistic.map(format_number).unwrap_or_default(),
+                    row.p_value.map(format_p_value).unwrap_or_default(),
+                ]
+            })
+            .collect();
+        let labels: Vec<String> = (1..=self.rows.len()).map(|i| i.to_string()).collect();
+        let labels: Vec<&str> = labels.iter().map(String::as_str).collect();
+        write_table(f, &labels, &header, &cells)
+    }
+}
+
+/// Compare a sequence of nested linear models fitted to the same observations with F tests,
+/// like R's `anova(m1, m2, m3)`.
+///
+/// # Errors
+/// Returns the errors of `compare_models`.
+pub fn anova(models: &[&FittedLinearModel]) -> Result<ModelComparisonTable, AnovaError> {
+    compare_models(models, ComparisonTest::F)
+}
+
+/// Compare a sequence of nested linear models fitted to the same observations.
+///
+/// # Arguments
+/// * `models` - The fitted models, in the order they should be compared. Consecutive models must
+///   be nested, in either direction.
+/// * `test` - The test to report for each step.
+///
+/// # Errors
+/// Returns an error if fewer than two models are given, if the models do not have the same
+/// number of observations and the same single response and weights, or if two consecutive
+/// models are not nested.
+pub fn compare_models(
+    models: &[&FittedLinearModel],
+    test: ComparisonTest,
+) -> Result<ModelComparisonTable, AnovaError> {
+    if models.len() < 2 {
+        return Err(AnovaError::TooFewModels {
+            found: models.len(),
+        });
+    }
+    let y = models[0].data.y();
+    for (i, model) in models.iter().enumerate() {
+        let found = model.data.x().n_rows();
+        if found != y.n_rows() {
+            return Err(AnovaError::DifferentObservations {
+                model: i + 1,
+                expected: y.n_rows(),
+                found,
+            });
+        }
+    }
+    if y.n_cols() != 1 {
+        return Err(AnovaError::MultipleResponses { found: y.n_cols() });
+    }
+    let weights = models[0].data.weights();
+    if models
+        .iter()
+        .any(|model| model.data.y() != y || model.data.weights() != weights)
+    {
+        return Err(AnovaError::DifferentResponses);
+    }
+    for (i, pair) in models.windows(2).enumerate() {
+        if !is_nested(pair[0], pair[1])? && !is_nested(pair[1], pair[0])? {
+            return Err(AnovaError::NotNested {
+                first: i + 1,
+                second: i + 2,
+            });
+        }
+    }
+
+    // The residual degrees of freedom and sum of squares of each model.
+    let mut summaries = Vec::with_capacity(models.len());
+    for model in models {
+        let fit = model.least_squares()?;
+        let rss: f64 = fit.residuals.values.iter().map(|e| e * e).sum();
+        summaries.push((y.n_rows() - fit.rank(), rss));
+    }
+
+    // Scale every step by the residual mean square of the largest model.
+    let (big_df, big_rss) = summaries
+        .iter()
+        .copied()
+        .min_by_key(|&(res_df, _)| res_df)
+        .expect("there are at least two models");
+    let scale = big_rss / big_df as f64;
+
+    let mut rows = Vec::with_capacity(models.len());
+    for (i, &(res_df, rss)) in summaries.iter().enumerate() {
+        let mut row = ModelComparisonRow {
+            res_df,
+            rss,
+            df: None,
+            sum_sq: None,
+            statistic: None,
+            p_value: None,
+        };
+        if i > 0 {
+            let (previous_df, previous_rss) = summaries[i - 1];
+            let df = previous_df as i64 - res_df as i64;
+            let sum_sq = previous_rss - rss;
+            row.df = Some(df);
+            row.sum_sq = Some(sum_sq);
+            if df != 0 {
+                let (statistic, p_value) = step_test(test, sum_sq, df, scale, big_df);
+                row.statistic = Some(statistic);
+                row.p_value = Some(p_value);
+            }
+        }
+        rows.push(row);
+    }
+
+    let mut heading = "Analysis of Variance Table\n".to_string();
+    for (i, model) in models.iter().enumerate() {
+        heading.push_str(&format!("\nModel {}: {}", i + 1, describe(model)));
+    }
+
+    Ok(ModelComparisonTable {
+        heading,
+        test,
+        rows,
+    })
+}
+
+/// Return the statistic and p-value of one comparison step, following R's `stat.anova`.
+pub(super) fn step_test(
+    test: ComparisonTest,
+    sum_sq: f64,
+    df: i64,
+    scale: f64,
+    big_df: usize,
+) -> (f64, f64) {
+    let df = df.unsigned_abs() as f64;
+    let sum_sq = sum_sq.abs();
+    match test {
+        ComparisonTest::F => {
+            let f_value = sum_sq / df / scale;
+            (f_value, f_upper_tail(f_value, df, big_df as f64))
+        }
+        ComparisonTest::Chisq => {
+            let chisq = sum_sq / scale;
+            (chisq, chi_squared_upper_tail(chisq, df))
+        }
+    }
+}
+
+/// Return `true` if `small` is nested in `big`: every column of the smaller model matrix must
+/// lie in the column span of the larger one, which also catches models fitted to different rows.
+/// When both models carry explicit terms, the term labels of `small` must also be a subset of
+/// those of `big`.
+///
+/// # Errors
+/// Returns `AnovaError::LeastSquares` if the span cannot be computed.
+fn is_nested(small: &FittedLinearModel, big: &FittedLinearModel) -> Result<bool, AnovaError> {
+    if let (Some(small_terms), Some(big_terms)) = (&small.data.terms, &big.data.terms) {
+        let labels_nested = small_terms.has_intercept() <= big_terms.has_intercept()
+            && small_terms
+                .labels
+                .iter()
+                .all(|label| big_terms.labels.contains(label));
+        if !labels_nested {
+            return Ok(false);
+        }
+    }
+
+    let x_small: &RealMatrix = small.data.x();
+    let fit = dqrls(big.data.x(), x_small, &big.tol)?;
+    Ok((0..x_small.n_cols()).all(|j| {
+        let norm = x_small
+            .values
+            .column(j)
+            .dot(&x_small.values.column(j))
+            .sqrt();
+        let residual = fit.residuals.values.column(j);
+        residual.dot(&residual).sqrt() <= SPAN_TOL * norm.max(1.0)
+    }))
+}
+
+/// Describe a model by its term labels, like the formula R prints in the heading.
+fn describe(model: &FittedLinearModel) -> String {
+    let terms = model.data.terms();
+    let mut parts: Vec<&str> = terms.labels.iter().map(String::as_str).collect();
+    if !terms.has_intercept() {
+        parts.push("0");
+    }
+    if parts.is_empty() {
+        "1".to_string()
+    } else {
+        parts.join(" + ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Data, Tolerance};
+
+    fn data(columns: &[usize]) -> Data {
+        let full = [
+            [1.0, 1.0, 2.0, 0.5],
+            [1.0, 2.0, 1.0, 1.5],
+            [1.0, 3.0, 4.0, 0.0],
+            [1.0, 4.0, 3.0, 2.0],
+            [1.0, 5.0, 6.0, 1.0],
+            [1.0, 6.0, 5.0, 0.5],
+            [1.0, 7.0, 8.0, 2.5],
+            [1.0, 8.0, 7.0, 1.0],
+        ];
+        let names = ["(Intercept)", "x1", "x2", "x3"];
+        let values = full
+            .iter()
+            .flat_map(|row| columns.iter().map(move |&j| row[j]))
+            .collect();
+        let y = vec![1.2, 2.3, 2.9, 4.8, 5.1, 5.9, 7.4, 8.2];
+        Data::new(
+            RealMatrix::from_vec(values, 8, Some(columns.len())),
+            RealMatrix::from_vec(y, 8, None),
+        )
+        .with_column_names(columns.iter().map(|&j| names[j].to_string()).collect())
+    }
+
+    fn fitted(data: &Data) -> FittedLinearModel<'_> {
+        let fit = dqrls(data.x(), data.y(), &Tolerance::default()).unwrap();
+        FittedLinearModel::new(data, fit.coefficients)
+    }
+
+    #[test]
+    fn test_compare_models_reports_each_step() {
+        let (d1, d2, d3) = (data(&[0]), data(&[0, 1]), data(&[0, 1, 2, 3]));
+        let (m1, m2, m3) = (fitted(&d1), fitted(&d2), fitted(&d3));
+
+        let table = compare_models(&[&m1, &m2, &m3], ComparisonTest::F).unwrap();
+
+        assert_eq!(
+            table.rows.iter().map(|row| row.res_df).collect::<Vec<_>>(),
+            vec![7, 6, 4]
+        );
+        assert_eq!(table.rows[2].df, Some(2));
+        let scale = table.rows[2].rss / 4.0;
+        let expected_f = (table.rows[1].rss - table.rows[2].rss) / 2.0 / scale;
+        assert!((table.rows[2].statistic.unwrap() - expected_f).abs() < 1e-10);
+        assert!(table.heading.contains("Model 2: x1"));
+    }
+
+    #[test]
+    fn test_compare_models_rejects_non_nested_models() {
+        let (d1, d2) = (data(&[0, 1]), data(&[0, 3]));
+        let (m1, m2) = (fitted(&d1), fitted(&d2));
+
+        let result = compare_models(&[&m1, &m2], ComparisonTest::Chisq);
+
+        assert!(matches!(
+            result,
+            Err(AnovaError::NotNested {
+                first: 1,
+                second: 2
+            })
+        ));
+    }
+
+    #[test]
+    fn test_anova_rejects_models_fitted_to_different_rows() {
+        let (d1, d2) = (data(&[0, 1]), data(&[0, 1, 2]));
+        let m1 = fitted(&d1);
+
+        // The same response and term labels, but x1 of the larger model comes from other rows.
+        let mut shuffled = d2.clone();
+        shuffled.x.values.swap([0, 1], [2, 1]);
+        let labelled = |data: &Data| {
+            data.clone().with_terms(crate::terms::Terms::new(
+                data.column_names()[1..].to_vec(),
+                (0..data.x().n_cols()).collect(),
+            ))
+        };
+        let (l1, l2) = (labelled(&d1), labelled(&d2));
+        let (m2, m3) = (fitted(&l1), fitted(&l2));
+        assert!(anova(&[&m2, &m3]).is_ok());
+        let l3 = labelled(&shuffled);
+        let m4 = fitted(&l3);
+        assert!(matches!(
+            anova(&[&m2, &m4]),
+            Err(AnovaError::NotNested {
+                first: 1,
+                second: 2
+            })
+        ));
+
+        let subset = d1.select_rows(&[0, 1, 2, 3, 4, 5]);
+        let m5 = fitted(&subset);
+        assert!(matches!(
+            anova(&[&m5, &m1]),
+            Err(AnovaError::DifferentObservations {
+                model: 2,
+                expected: 6,
+                found: 8
+            })
+        ));
+    }
+}
@@ -0,0 +1,236 @@
+//! This module computes sequential (Type I) analysis of variance tables from the effects vector
+//! of a least squares fit, the way R's `anova.lm` and `anova.mlm` do.
+//!
+//! The first `rank` elements of the effects Q'y belong to the retained columns in pivoted order,
+//! so the sum of squares of a term is the sum of the squared effects of its columns, and the
+//! residual sum of squares is the sum of the squared remaining effects. Terms are therefore
+//! adjusted for every term before them, and nothing after them.
+
+// src/anova/sequential.rs
+
+use super::{Anova, AnovaRow, AnovaTable, MultivariateAnovaRow, MultivariateAnovaTable};
+use crate::distributions::f_upper_tail;
+use crate::least_squares::LeastSquaresFit;
+use crate::terms::Terms;
+use crate::types::RealMatrix;
+use std::collections::BTreeMap;
+
+/// The heading of a sequential analysis of variance table.
+const HEADING: &str = "Analysis of Variance Table";
+
+/// Compute the sequential analysis of variance of a least squares fit. A single-response fit
+/// gives the F tests of `anova.lm`, and a multi-response fit gives the Pillai tests of
+/// `anova.mlm`.
+///
+/// # Arguments
+/// * `fit` - The result of `dqrls` for the model.
+/// * `terms` - The model terms of the columns of x.
+pub fn sequential_anova(fit: &LeastSquaresFit, terms: &Terms) -> Anova {
+    if fit.effects.n_cols() == 1 {
+        Anova::Univariate(univariate(fit, terms))
+    } else {
+        Anova::Multivariate(multivariate(fit, terms))
+    }
+}
+
+/// Group the positions of the retained columns in the pivoted decomposition by model term.
+fn effects_by_term(fit: &LeastSquaresFit, terms: &Terms) -> BTreeMap<usize, Vec<usize>> {
+    let mut groups: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+    for (position, &column) in fit.qr.pivot.iter().enumerate().take(fit.rank()) {
+        groups
+            .entry(terms.assign[column])
+            .or_default()
+            .push(position);
+    }
+    groups
+}
+
+fn univariate(fit: &LeastSquaresFit, terms: &Terms) -> AnovaTable {
+    let effects: Vec<f64> = fit.effects.values.column(0).to_vec();
+    let df_residual = fit.effects.n_rows() - fit.rank();
+    let ss_residual: f64 = effects[fit.rank()..].iter().map(|e| e * e).sum();
+    let ms_residual = ss_residual / df_residual as f64;
+
+    let mut rows: Vec<AnovaRow> = effects_by_term(fit, terms)
+        .into_iter()
+        .filter(|&(term, _)| term != 0)
+        .map(|(term, positions)| {
+            let df = positions.len();
+            let sum_sq: f64 = positions.iter().map(|&i| effects[i] * effects[i]).sum();
+            let mean_sq = sum_sq / df as f64;
+            let f_value = mean_sq / ms_residual;
+            AnovaRow {
+                term: terms.label(term).to_string(),
+                df,
+                sum_sq,
+                mean_sq,
+                f_value: Some(f_value),
+                p_value: Some(f_upper_tail(f_value, df as f64, df_residual as f64)),
+            }
+        })
+        .collect();
+    rows.push(AnovaRow {
+        term: "Residuals".to_string(),
+        df: df_residual,
+        sum_sq: ss_residual,
+        mean_sq: ms_residual,
+        f_value: None,
+        p_value: None,
+    });
+
+    AnovaTable {
+        heading: HEADING.to_string(),
+        rows,
+        warnings: Vec::new(),
+    }
+}
+
+fn multivariate(fit: &LeastSquaresFit, terms: &Terms) -> MultivariateAnovaTable {
+    let df_residual = fit.effects.n_rows() - fit.rank();
+    let residual_positions: Vec<usize> = (fit.rank()..fit.effects.n_rows()).collect();
+    let e = cross_product(&fit.effects, &residual_positions);
+
+    let mut rows: Vec<MultivariateAnovaRow> = effects_by_term(fit, terms)
+        .into_iter()
+        .map(|(term, positions)| {
+            let df = positions.len();
+            let h = cross_product(&fit.effects, &positions);
+            let (pillai, approx_f, num_df, den_df) = match h.plus(&e).inverse() {
+                Some(total_inverse) => {
+                    let trace = h.dot(&total_inverse).values.diag().sum();
+                    pillai(trace, fit.effects.n_cols(), df, df_residual)
+                }
+                None => (f64::NAN, f64::NAN, f64::NAN, f64::NAN),
+            };
+            MultivariateAnovaRow {
+                term: terms.label(term).to_string(),
+                df,
+                pillai: Some(pillai),
+                approx_f: Some(approx_f),
+                num_df: Some(num_df),
+                den_df: Some(den_df),
+                p_value: Some(f_upper_tail(approx_f, num_df, den_df)),
+            }
+        })
+        .collect();
+    rows.push(MultivariateAnovaRow {
+        term: "Residuals".to_string(),
+        df: df_residual,
+        pillai: None,
+        approx_f: None,
+        num_df: None,
+        den_df: None,
+        p_value: None,
+    });
+
+    MultivariateAnovaTable {
+        heading: HEADING.to_string(),
+        rows,
+    }
+}
+
+/// Return the sum of squares and products matrix of the rows `positions` of `effects`.
+fn cross_product(effects: &RealMatrix, positions: &[usize]) -> RealMatrix {
+    let ny = effects.n_cols();
+    let mut ssp = RealMatrix::with_shape(ny, ny);
+    for &i in positions {
+        for a in 0..ny {
+            for b in 0..ny {
+                ssp.values[[a, b]] += effects.values[[i, a]] * effects.values[[i, b]];
+            }
+        }
+    }
+    ssp
+}
+
+/// Return Pillai's trace and its F approximation `(test, F, num Df, den Df)`, following R's
+/// `stats:::Pillai`.
+///
+/// # Arguments
+/// * `test` - The trace of H (H + E)^-1.
+/// * `p` - The number of responses.
+/// * `q` - The degrees of freedom of the term.
+/// * `df_residual` - The residual degrees of freedom.
+fn pillai(test: f64, p: usize, q: usize, df_residual: usize) -> (f64, f64, f64, f64) {
+    let s = p.min(q) as f64;
+    let n = 0.5 * (df_residual as f64 - p as f64 - 1.0);
+    let m = 0.5 * ((p as f64 - q as f64).abs() - 1.0);
+    let tmp1 = 2.0 * m + s + 1.0;
+    let tmp2 = 2.0 * n + s + 1.0;
+    (test, (tmp2 / tmp1 * test) / (s - test), s * tmp1, s * tmp2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::least_squares::dqrls;
+    use crate::types::{Data, Tolerance};
+
+    fn data(y: Vec<f64>, ny: usize) -> Data {
+        // An intercept, a numeric covariate and a three-level factor coded as two dummies.
+        let x = RealMatrix::from_vec(
+            vec![
+                1.0, 1.0, 0.0, 0.0, //
+                1.0, 2.0, 1.0, 0.0, //
+                1.0, 3.0, 0.0, 1.0, //
+                1.0, 4.0, 0.0, 0.0, //
+                1.0, 5.0, 1.0, 0.0, //
+                1.0, 6.0, 0.0, 1.0, //
+                1.0, 7.0, 0.0, 0.0, //
+                1.0, 8.0, 1.0, 0.0,
+            ],
+            8,
+            Some(4),
+        );
+        let y = RealMatrix::from_vec(y, 8, Some(ny));
+        Data::new(x, y).with_terms(Terms::new(
+            vec!["x".to_string(), "g".to_string()],
+            vec![0, 1, 2, 2],
+        ))
+    }
+
+    #[test]
+    fn test_sequential_anova_partitions_the_total_sum_of_squares() {
+        let y = vec![2.0, 3.5, 3.0, 5.5, 6.0, 6.5, 8.0, 9.5];
+        let data = data(y.clone(), 1);
+        let fit = dqrls(data.x(), data.y(), &Tolerance::default()).unwrap();
+
+        let Anova::Univariate(table) = sequential_anova(&fit, &data.terms()) else {
+            panic!("expected a univariate table");
+        };
+
+        assert_eq!(table.rows.len(), 3);
+        assert_eq!(table.row("x").unwrap().df, 1);
+        assert_eq!(table.row("g").unwrap().df, 2);
+        assert_eq!(table.residuals().unwrap().df, 4);
+
+        let mean = y.iter().sum::<f64>() / y.len() as f64;
+        let total: f64 = y.iter().map(|v| (v - mean).powi(2)).sum();
+        let partitioned: f64 = table.rows.iter().map(|row| row.sum_sq).sum();
+        assert!((total - partitioned).abs() < 1e-10);
+
+        let x_row = table.row("x").unwrap();
+        let expected_f = x_row.mean_sq / table.residuals().unwrap().mean_sq;
+        assert!((x_row.f_value.unwrap() - expected_f).abs() < 1e-10);
+        assert!(x_row.p_value.unwrap() < 0.001);
+    }
+
+    #[test]
+    fn test_sequential_anova_multivariate_includes_the_intercept() {
+        let y = vec![
+            2.0, 1.0, 3.5, 4.0, 3.0, 2.0, 5.5, 0.5, //
+            6.0, 3.0, 6.5, 2.5, 8.0, 1.0, 9.5, 6.0,
+        ];
+        let data = data(y, 2);
+        let fit = dqrls(data.x(), data.y(), &Tolerance::default()).unwrap();
+
+        let Anova::Multivariate(table) = sequential_anova(&fit, &data.terms()) else {
+            panic!("expected a multivariate table");
+        };
+
+        let labels: Vec<&str> = table.rows.iter().map(|row| row.term.as_str()).collect();
+        assert_eq!(labels, vec!["(Intercept)", "x", "g", "Residuals"]);
+        let pillai = table.row("x").unwrap().pillai.unwrap();
+        assert!((0.0..=1.0).contains(&pillai));
+    }
+}
@@ -0,0 +1,494 @@
+//! This module estimates the out-of-sample error of a model by cross-validation.
+//!
+//! A model is specified by a fitter factory: a function that fits coefficients to a `Data`
+//! struct, such as `|data| QrDecompositionFitter::new(data, None).fit()`, so any strategy that
+//! implements `FitModel` can be cross-validated. The folds can be drawn at random, within strata,
+//! or by whole groups, and repeated, all from a seeded generator so that results are
+//! reproducible.
+//!
+//! For ordinary and weighted least squares, `leave_one_out` computes leave-one-out residuals and
+//! the PRESS statistic in closed form as e / (1 - h) from the hat values, without refitting.
+
+// src/cv.rs
+
+use crate::errors::{CrossValidationError, LmFitterError};
+use crate::linear_model::FittedLinearModel;
+use crate::types::{Data, RealMatrix, Tolerance};
+use crate::vcov::hat_values;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+
+/// An enum representing how observations are assigned to folds.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum Split {
+    /// Assign observations to folds completely at random.
+    #[default]
+    Random,
+    /// Spread the observations of each stratum evenly over the folds. Holds one stratum label
+    /// per observation.
+    Stratified(Vec<usize>),
+    /// Keep all observations of a group in the same fold. Holds one group label per observation.
+    Grouped(Vec<usize>),
+}
+
+/// A cross-validation design: the number of folds, how often the split is repeated, and how
+/// observations are assigned to folds.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CrossValidation {
+    /// The number of folds.
+    pub folds: usize,
+    /// The number of times the folds are drawn again.
+    pub repeats: usize,
+    /// The seed of the generator used to draw the folds.
+    pub seed: u64,
+    /// How observations are assigned to folds.
+    pub split: Split,
+}
+
+/// The out-of-sample scores of the model fitted without one fold.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FoldScore {
+    /// The repeat the fold belongs to, starting from 0.
+    pub repeat: usize,
+    /// The index of the fold within its repeat.
+    pub fold: usize,
+    /// The number of observations in the fold.
+    pub n_test: usize,
+    /// The mean squared prediction error on the fold.
+    pub mse: f64,
+    /// The mean absolute prediction error on the fold.
+    pub mae: f64,
+    /// 1 - SSE / SST on the fold, with SST taken around the mean of the fold. `NaN` when the
+    /// fold has no variation in the response.
+    pub r_squared: f64,
+}
+
+/// The result of a cross-validation run.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CrossValidationResult {
+    /// The scores of every fold of every repeat.
+    pub folds: Vec<FoldScore>,
+    /// The out-of-fold prediction of every observation, one vector per repeat.
+    pub predictions: Vec<Vec<f64>>,
+    /// The mean of the fold mean squared errors.
+    pub mse: f64,
+    /// The standard error of `mse`, the standard deviation of the fold errors over the square
+    /// root of the number of folds.
+    pub mse_std_error: f64,
+    /// The mean of the fold mean absolute errors.
+    pub mae: f64,
+    /// The mean of the fold R² values that are defined.
+    pub r_squared: f64,
+}
+
+impl CrossValidation {
+    /// Create a single, random `folds`-fold cross-validation with seed 0.
+    ///
+    /// # Errors
+    /// Returns `CrossValidationError::TooFewFolds` if `folds` is less than 2.
+    pub fn new(folds: usize) -> Result<Self, CrossValidationError> {
+        if folds < 2 {
+            return Err(CrossValidationError::TooFewFolds { folds });
+        }
+        Ok(CrossValidation {
+            folds,
+            repeats: 1,
+            seed: 0,
+            split: Split::Random,
+        })
+    }
+
+    /// Repeat the cross-validation `repeats` times with freshly drawn folds.
+    ///
+    /// # Errors
+    /// Returns `CrossValidationError::NoRepeats` if `repeats` is zero.
+    pub fn with_repeats(mut self, repeats: usize) -> Result<Self, CrossValidationError> {
+        if repeats == 0 {
+            return Err(CrossValidationError::NoRepeats);
+        }
+        self.repeats = repeats;
+        Ok(self)
+    }
+
+    /// Set the seed of the generator used to draw the folds.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Stratify the folds by the label of each observation in `strata`.
+    pub fn stratified(mut self, strata: Vec<usize>) -> Self {
+        self.split = Split::Stratified(strata);
+        self
+    }
+
+    /// Keep the observations with the same label in `groups` in the same fold.
+    pub fn grouped(mut self, groups: Vec<usize>) -> Self {
+        self.split = Split::Grouped(groups);
+        self
+    }
+
+    /// Draw the folds for `n` observations, returning the fold of each observation for every
+    /// repeat.
+    ///
+    /// # Errors
+    /// Returns `CrossValidationError::TooFewFolds` or `CrossValidationError::NoRepeats` if the
+    /// public fields were set to fewer than two folds or no repeats,
+    /// `CrossValidationError::DimensionMismatch` if the strata or groups do not have one label
+    /// per observation, and `CrossValidationError::InvalidFolds` if there are fewer observations
+    /// (or groups) than folds.
+    pub fn fold_assignments(&self, n: usize) -> Result<Vec<Vec<usize>>, CrossValidationError> {
+        if self.folds < 2 {
+            return Err(CrossValidationError::TooFewFolds { folds: self.folds });
+        }
+        if self.repeats == 0 {
+            return Err(CrossValidationError::NoRepeats);
+        }
+        let labels = match &self.split {
+            Split::Random => None,
+            Split::Stratified(labels) | Split::Grouped(labels) => Some(labels),
+        };
+        if let Some(labels) = labels.filter(|labels| labels.len() != n) {
+            return Err(CrossValidationError::DimensionMismatch {
+                expected: n,
+                found: labels.len(),
+            });
+        }
+        let units = match &self.split {
+            Split::Grouped(groups) => distinct(groups).len(),
+            _ => n,
+        };
+        if units < self.folds {
+            return Err(CrossValidationError::InvalidFolds {
+                folds: self.folds,
+                units,
+            });
+        }
+
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        Ok((0..self.repeats)
+            .map(|_| match &self.split {
+                Split::Random => self.random_folds(n, &mut rng),
+                Split::Stratified(strata) => self.stratified_folds(strata, &mut rng),
+                Split::Grouped(groups) => self.grouped_folds(groups, &mut rng),
+            })
+            .collect())
+    }
+
+    /// Cross-validate the model fitted by `fit`, predicting every fold from the coefficients
+    /// fitted to the other folds.
+    ///
+    /// The scores are unweighted, even when `data` has weights (which are still used by the
+    /// fits).
+    ///
+    /// # Errors
+    /// Returns the errors of `fold_assignments`, `CrossValidationError::MultipleResponses` if
+    /// `data` has more than one response, and `CrossValidationError::Fit` if a fit fails.
+    pub fn run<F>(&self, data: &Data, fit: F) -> Result<CrossValidationResult, CrossValidationError>
+    where
+        F: Fn(&Data) -> Result<RealMatrix, LmFitterError>,
+    {
+        check_single_response(data)?;
+        let n = data.x().n_rows();
+        let y = data.y().values.column(0);
+
+        let mut folds = Vec::new();
+        let mut predictions = Vec::new();
+        for (repeat, assignment) in self.fold_assignments(n)?.into_iter().enumerate() {
+            let mut predicted = vec![f64::NAN; n];
+            for fold in 0..self.folds {
+                let (test, train): (Vec<usize>, Vec<usize>) =
+                    (0..n).partition(|&i| assignment[i] == fold);
+                let training = data.select_rows(&train);
+                let model = FittedLinearModel::new(&training, fit(&training)?);
+                let x_test = RealMatrix::new(data.x().values.select(ndarray::Axis(0), &test));
+                let fold_predictions = model.predict(Some(&x_test));
+
+                let pairs: Vec<(f64, f64)> = test
+                    .iter()
+                    .zip(fold_predictions.values.column(0))
+                    .map(|(&i, &prediction)| {
+                        predicted[i] = prediction;
+                        (y[i], prediction)
+                    })
+                    .collect();
+                let (mse, mae, r_squared) = scores(&pairs);
+                folds.push(FoldScore {
+                    repeat,
+                    fold,
+                    n_test: test.len(),
+                    mse,
+                    mae,
+                    r_squared,
+                });
+            }
+            predictions.push(predicted);
+        }
+
+        let k = folds.len() as f64;
+        let mse = folds.iter().map(|f| f.mse).sum::<f64>() / k;
+        let variance = folds.iter().map(|f| (f.mse - mse).powi(2)).sum::<f64>() / (k - 1.0);
+        let defined: Vec<f64> = folds
+            .iter()
+            .map(|f| f.r_squared)
+            .filter(|r| r.is_finite())
+            .collect();
+        Ok(CrossValidationResult {
+            mse,
+            mse_std_error: (variance / k).sqrt(),
+            mae: folds.iter().map(|f| f.mae).sum::<f64>() / k,
+            r_squared: defined.iter().sum::<f64>() / defined.len() as f64,
+            folds,
+            predictions,
+        })
+    }
+
+    fn random_folds(&self, n: usize, rng: &mut StdRng) -> Vec<usize> {
+        let mut order: Vec<usize> = (0..n).collect();
+        order.shuffle(rng);
+        let mut assignment = vec![0; n];
+        for (position, &i) in order.iter().enumerate() {
+            assignment[i] = position % self.folds;
+        }
+        assignment
+    }
+
+    /// Deal the shuffled members of each stratum to the folds in turn, carrying on from where
+    /// the previous stratum stopped so that the folds also stay balanced in size.
+    fn stratified_folds(&self, strata: &[usize], rng: &mut StdRng) -> Vec<usize> {
+        let mut assignment = vec![0; strata.len()];
+        let mut next = 0;
+        for stratum in distinct(strata) {
+            let mut members: Vec<usize> = (0..strata.len())
+                .filter(|&i| strata[i] == stratum)
+                .collect();
+            members.shuffle(rng);
+            for i in members {
+                assignment[i] = next % self.folds;
+                next += 1;
+            }
+        }
+        assignment
+    }
+
+    /// Shuffle the groups, then give each group, largest first, to the smallest fold so far.
+    fn grouped_folds(&self, groups: &[usize], rng: &mut StdRng) -> Vec<usize> {
+        let mut labels = distinct(groups);
+        labels.shuffle(rng);
+        let size = |label: &usize| groups.iter().filter(|&g| g == label).count();
+        labels.sort_by_key(|label| std::cmp::Reverse(size(label)));
+
+        let mut fold_sizes = vec![0; self.folds];
+        let mut assignment = vec![0; groups.len()];
+        for label in labels {
+            let fold = (0..self.folds).min_by_key(|&f| fold_sizes[f]).unwrap();
+            for (i, _) in groups.iter().enumerate().filter(|&(_, g)| *g == label) {
+                assignment[i] = fold;
+                fold_sizes[fold] += 1;
+            }
+        }
+        assignment
+    }
+}
+
+/// Leave-one-out residuals and the PRESS statistic of a least squares fit.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LeaveOneOut {
+    /// The residual of each observation when it is predicted from the other observations.
+    pub residuals: Vec<f64>,
+    /// The predicted residual sum of squares, the sum of the squared leave-one-out residuals.
+    pub press: f64,
+    /// The leave-one-out mean squared error, `press / n`.
+    pub mse: f64,
+    /// The leave-one-out mean absolute error.
+    pub mae: f64,
+    /// The predicted R², 1 - PRESS / SST.
+    pub r_squared: f64,
+}
+
+/// Compute the leave-one-out residuals e / (1 - h) of the (weighted) least squares fit to `data`
+/// from its hat values. An observation with leverage 1 has an infinite residual.
+///
+/// # Errors
+/// Returns `CrossValidationError::MultipleResponses` if `data` has more than one response, and
+/// `CrossValidationError::LeastSquares` if the fit fails.
+pub fn leave_one_out(data: &Data) -> Result<LeaveOneOut, CrossValidationError> {
+    check_single_response(data)?;
+    let fit = data.least_squares(&Tolerance::default())?;
+    let hat = hat_values(&fit.qr);
+    let weights = data.weights();
+
+    // The residuals of the fit are scaled by sqrt(w); undo that to get residuals on the scale
+    // of y.
+    let residuals: Vec<f64> = (0..hat.len())
+        .map(|i| {
+            let scale = weights.map_or(1.0, |w| w[i].sqrt());
+            fit.residuals.values[[i, 0]] / scale / (1.0 - hat[i])
+        })
+        .collect();
+    let y = data.y().values.column(0);
+    let pairs: Vec<(f64, f64)> = y
+        .iter()
+        .zip(&residuals)
+        .map(|(&y, &e)| (y, y - e))
+        .collect();
+    let (mse, mae, r_squared) = scores(&pairs);
+
+    Ok(LeaveOneOut {
+        press: mse * residuals.len() as f64,
+        residuals,
+        mse,
+        mae,
+        r_squared,
+    })
+}
+
+fn check_single_response(data: &Data) -> Result<(), CrossValidationError> {
+    match data.y().n_cols() {
+        1 => Ok(()),
+        found => Err(CrossValidationError::MultipleResponses { found }),
+    }
+}
+
+/// Return the mean squared error, mean absolute error and R² of (observed, predicted) pairs.
+fn scores(pairs: &[(f64, f64)]) -> (f64, f64, f64) {
+    let n = pairs.len() as f64;
+    let mean = pairs.iter().map(|(y, _)| y).sum::<f64>() / n;
+    let sse: f64 = pairs.iter().map(|(y, p)| (y - p).powi(2)).sum();
+    let sst: f64 = pairs.iter().map(|(y, _)| (y - mean).powi(2)).sum();
+    let mae = pairs.iter().map(|(y, p)| (y - p).abs()).sum::<f64>() / n;
+    let r_squared = if sst > 0.0 { 1.0 - sse / sst } else { f64::NAN };
+    (sse / n, mae, r_squared)
+}
+
+/// Return the distinct labels in order of first appearance.
+fn distinct(labels: &[usize]) -> Vec<usize> {
+    let mut seen = Vec::new();
+    for &label in labels {
+        if !seen.contains(&label) {
+            seen.push(label);
+        }
+    }
+    seen
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fitters::fit::FitModel;
+    use crate::fitters::qr_decomposition_fitter::QrDecompositionFitter;
+
+    fn data() -> Data {
+        let n = 12;
+        let x: Vec<f64> = (0..n)
+            .flat_map(|i| [1.0, i as f64, ((i * i) % 5) as f64])
+            .collect();
+        let y: Vec<f64> = (0..n)
+            .map(|i| 1.0 + 0.5 * i as f64 + (i as f64 * 1.3).sin())
+            .collect();
+        Data::new(
+            RealMatrix::from_vec(x, n, Some(3)),
+            RealMatrix::from_vec(y, n, None),
+        )
+    }
+
+    fn ols(data: &Data) -> Result<RealMatrix, LmFitterError> {
+        QrDecompositionFitter::new(data, None).fit()
+    }
+
+    #[test]
+    fn test_closed_form_leave_one_out_matches_refitting() {
+        let data = data();
+        let closed_form = leave_one_out(&data).unwrap();
+        let refitted = CrossValidation::new(12).unwrap().run(&data, ols).unwrap();
+
+        assert!((closed_form.mse - refitted.mse).abs() < 1e-10);
+        assert!((closed_form.mae - refitted.mae).abs() < 1e-10);
+        let y = data.y().values.column(0);
+        for (i, e) in closed_form.residuals.iter().enumerate() {
+            assert!((y[i] - refitted.predictions[0][i] - e).abs() < 1e-10);
+        }
+    }
+
+    #[test]
+    fn test_folds_are_seeded_balanced_and_respect_groups_and_strata() {
+        let cv = CrossValidation::new(3)
+            .unwrap()
+            .with_repeats(2)
+            .unwrap()
+            .with_seed(7);
+        let assignments = cv.fold_assignments(12).unwrap();
+        assert_eq!(assignments, cv.fold_assignments(12).unwrap());
+        assert_ne!(assignments[0], assignments[1]);
+        for fold in 0..3 {
+            assert_eq!(assignments[0].iter().filter(|&&f| f == fold).count(), 4);
+        }
+
+        let strata = vec![0, 0, 0, 1, 1, 1, 0, 0, 0, 1, 1, 1];
+        let assignment = &CrossValidation::new(3)
+            .unwrap()
+            .stratified(strata.clone())
+            .fold_assignments(12)
+            .unwrap()[0];
+        for fold in 0..3 {
+            let members: Vec<usize> = (0..12).filter(|&i| assignment[i] == fold).collect();
+            assert_eq!(members.iter().filter(|&&i| strata[i] == 0).count(), 2);
+        }
+
+        let groups = vec![0, 0, 1, 1, 1, 2, 3, 3, 4, 4, 4, 4];
+        let assignment = &CrossValidation::new(3)
+            .unwrap()
+            .grouped(groups.clone())
+            .fold_assignments(12)
+            .unwrap()[0];
+        for i in 1..12 {
+            if groups[i] == groups[i - 1] {
+                assert_eq!(assignment[i], assignment[i - 1]);
+            }
+        }
+        assert!(matches!(
+            CrossValidation::new(6)
+                .unwrap()
+                .grouped(groups)
+                .fold_assignments(12),
+            Err(CrossValidationError::InvalidFolds { folds: 6, units: 5 })
+        ));
+        assert!(matches!(
+            CrossValidation::new(13).unwrap().fold_assignments(12),
+            Err(CrossValidationError::InvalidFolds {
+                folds: 13,
+                units: 12
+            })
+        ));
+        assert!(matches!(
+            CrossValidation::new(0),
+            Err(CrossValidationError::TooFewFolds { folds: 0 })
+        ));
+        assert!(matches!(
+            CrossValidation::new(3).unwrap().with_repeats(0),
+            Err(CrossValidationError::NoRepeats)
+        ));
+        let mut cv = CrossValidation::new(3).unwrap();
+        cv.folds = 0;
+        assert!(matches!(
+            cv.run(&data(), ols),
+            Err(CrossValidationError::TooFewFolds { folds: 0 })
+        ));
+    }
+
+    #[test]
+    fn test_repeated_k_fold_reports_every_fold() {
+        let result = CrossValidation::new(4)
+            .unwrap()
+            .with_repeats(3)
+            .unwrap()
+            .run(&data(), ols)
+            .unwrap();
+
+        assert_eq!(result.folds.len(), 12);
+        assert_eq!(result.predictions.len(), 3);
+        assert!(result.predictions[2].iter().all(|p| p.is_finite()));
+        assert!(result.mse > 0.0 && result.mse_std_error > 0.0);
+    }
+}
@@ -1,6 +1,10 @@
 // src/data.rs
 
+use crate::errors::LeastSquaresError;
+use crate::least_squares::{dqrls, LeastSquaresFit};
 use crate::real_matrix::RealMatrix;
+use crate::terms::Terms;
+use crate::types::Tolerance;
 
 /// A struct representing the data for a linear regression model. This struct always maintains
 /// ownership of the data, and is used to pass the data safely between functions. This is
@@ -9,12 +13,70 @@ use crate::real_matrix::RealMatrix;
 pub struct Data {
     pub x: RealMatrix,
     pub y: RealMatrix,
+    pub column_names: Vec<String>,
+    pub terms: Option<Terms>,
+    pub weights: Option<Vec<f64>>,
 }
 
 impl Data {
-    /// Create a new `Data` struct.
+    /// Create a new `Data` struct. The columns of x are named `x1`, `x2`, ... until names are
+    /// supplied with `with_column_names`.
     pub fn new(x: RealMatrix, y: RealMatrix) -> Self {
-        Data { x, y }
+        let column_names = (1..=x.n_cols()).map(|j| format!("x{j}")).collect();
+        Data {
+            x,
+            y,
+            column_names,
+            terms: None,
+            weights: None,
+        }
+    }
+
+    /// Return the same data with the columns of x named by `column_names`.
+    ///
+    /// # Panics
+    /// Panics if the number of names does not match the number of columns of x.
+    pub fn with_column_names(mut self, column_names: Vec<String>) -> Self {
+        assert_eq!(
+            column_names.len(),
+            self.x.n_cols(),
+            "There must be exactly one name per column of x."
+        );
+        self.column_names = column_names;
+        self
+    }
+
+    /// Return the same data with the columns of x grouped into the model terms `terms`.
+    ///
+    /// # Panics
+    /// Panics if `terms` does not assign exactly one term to each column of x.
+    pub fn with_terms(mut self, terms: Terms) -> Self {
+        assert_eq!(
+            terms.assign.len(),
+            self.x.n_cols(),
+            "There must be exactly one term assignment per column of x."
+        );
+        self.terms = Some(terms);
+        self
+    }
+
+    /// Return the same data with prior weights on the observations, so that the model is fitted
+    /// by weighted least squares, minimizing sum(w * (y - xb)^2).
+    ///
+    /// # Panics
+    /// Panics if there is not exactly one weight per row of x, or if a weight is not positive.
+    pub fn with_weights(mut self, weights: Vec<f64>) -> Self {
+        assert_eq!(
+            weights.len(),
+            self.x.n_rows(),
+            "There must be exactly one weight per row of x."
+        );
+        assert!(
+            weights.iter().all(|&w| w > 0.0 && w.is_finite()),
+            "Weights must be positive and finite."
+        );
+        self.weights = Some(weights);
+        self
     }
 
     /// Return a reference to the x matrix.
@@ -26,4 +88,111 @@ impl Data {
     pub fn y(&self) -> &RealMatrix {
         &self.y
     }
+
+    /// Return the names of the columns of the x matrix.
+    pub fn column_names(&self) -> &[String] {
+        &self.column_names
+    }
+
+    /// Return the model terms of the columns of x. Unless terms were supplied with `with_terms`,
+    /// each column is its own term, labelled by its column name.
+    pub fn terms(&self) -> Terms {
+        self.terms
+            .clone()
+            .unwrap_or_else(|| Terms::one_per_column(&self.column_names))
+    }
+
+    /// Return the prior weights of the observations, if any.
+    pub fn weights(&self) -> Option<&[f64]> {
+        self.weights.as_deref()
+    }
+
+    /// Return x with each row scaled by the square root of its weight. Without weights, this is
+    /// a copy of x.
+    pub fn weighted_x(&self) -> RealMatrix {
+        self.scale_rows(&self.x)
+    }
+
+    /// Return y with each row scaled by the square root of its weight. Without weights, this is
+    /// a copy of y.
+    pub fn weighted_y(&self) -> RealMatrix {
+        self.scale_rows(&self.y)
+    }
+
+    /// Solve the (weighted) least squares problem for this data with `dqrls`. With weights, the
+    /// decomposition, residuals and effects are those of the rows scaled by sqrt(w), so sums of
+    /// squares computed from them are weighted sums of squares.
+    pub fn least_squares(&self, tol: &Tolerance) -> Result<LeastSquaresFit, LeastSquaresError> {
+        match self.weights {
+            Some(_) => dqrls(&self.weighted_x(), &self.weighted_y(), tol),
+            None => dqrls(&self.x, &self.y, tol),
+        }
+    }
+
+    /// Return the columns of x that belong to the intercept or to the model terms `terms`
+    /// (indices into `terms()`), in their original order.
+    pub fn term_columns(&self, terms: &[usize]) -> Vec<usize> {
+        let all_terms = self.terms();
+        (0..self.x.n_cols())
+            .filter(|&j| all_terms.assign[j] == 0 || terms.contains(&all_terms.assign[j]))
+            .collect()
+    }
+
+    /// Return the data restricted to the intercept and the model terms `terms` (indices into
+    /// `terms()`), keeping the column names, weights and term labels of the selected columns.
+    pub fn select_terms(&self, terms: &[usize]) -> Data {
+        let all_terms = self.terms();
+        let columns = self.term_columns(terms);
+
+        let kept: Vec<usize> = (1..=all_terms.n_terms())
+            .filter(|term| terms.contains(term))
+            .collect();
+        let assign = columns
+            .iter()
+            .map(|&j| match all_terms.assign[j] {
+                0 => 0,
+                term => kept.iter().position(|&k| k == term).unwrap() + 1,
+            })
+            .collect();
+        let labels = kept
+            .iter()
+            .map(|&term| all_terms.label(term).to_string())
+            .collect();
+
+        Data {
+            x: RealMatrix::new(self.x.values.select(ndarray::Axis(1), &columns)),
+            y: self.y.clone(),
+            column_names: columns
+                .iter()
+                .map(|&j| self.column_names[j].clone())
+                .collect(),
+            terms: Some(Terms::new(labels, assign)),
+            weights: self.weights.clone(),
+        }
+    }
+
+    /// Return the data restricted to the observations `rows`, keeping the column names, terms and
+    /// the weights of those observations.
+    pub fn select_rows(&self, rows: &[usize]) -> Data {
+        Data {
+            x: RealMatrix::new(self.x.values.select(ndarray::Axis(0), rows)),
+            y: RealMatrix::new(self.y.values.select(ndarray::Axis(0), rows)),
+            column_names: self.column_names.clone(),
+            terms: self.terms.clone(),
+            weights: self
+                .weights
+                .as_ref()
+                .map(|weights| rows.iter().map(|&i| weights[i]).collect()),
+        }
+    }
+
+    fn scale_rows(&self, matrix: &RealMatrix) -> RealMatrix {
+        let mut scaled = matrix.clone();
+        if let Some(weights) = &self.weights {
+            for (mut row, w) in scaled.values.rows_mut().into_iter().zip(weights) {
+                row *= w.sqrt();
+            }
+        }
+        scaled
+    }
 }
@@ -0,0 +1,83 @@
+//! This module contains thin wrappers around the `statrs` distributions, returning the tail
+//! probabilities used to compute p-values throughout the library.
+
+// src/distributions.rs
+
+use statrs::distribution::{
+    ChiSquared, Continuous, ContinuousCDF, FisherSnedecor, Normal, StudentsT,
+};
+use statrs::function::gamma::{digamma as statrs_digamma, ln_gamma};
+
+/// Return P(F > f) for an F distribution with `df1` and `df2` degrees of freedom, like R's
+/// `pf(f, df1, df2, lower.tail = FALSE)`. Returns `NaN` if the statistic or the degrees of
+/// freedom are not valid.
+pub fn f_upper_tail(f: f64, df1: f64, df2: f64) -> f64 {
+    match FisherSnedecor::new(df1, df2) {
+        Ok(dist) if f.is_finite() && f >= 0.0 => dist.sf(f),
+        _ => f64::NAN,
+    }
+}
+
+/// Return P(T > t) for a Student t distribution with `df` degrees of freedom, like R's
+/// `pt(t, df, lower.tail = FALSE)`. Returns `NaN` if the degrees of freedom are not valid.
+pub fn t_upper_tail(t: f64, df: f64) -> f64 {
+    match StudentsT::new(0.0, 1.0, df) {
+        Ok(dist) if !t.is_nan() => dist.sf(t),
+        _ => f64::NAN,
+    }
+}
+
+/// Return P(X > x) for a chi-squared distribution with `df` degrees of freedom, like R's
+/// `pchisq(x, df, lower.tail = FALSE)`. Returns `NaN` if the statistic or the degrees of freedom
+/// are not valid.
+pub fn chi_squared_upper_tail(x: f64, df: f64) -> f64 {
+    match ChiSquared::new(df) {
+        Ok(dist) if !x.is_nan() => dist.sf(x),
+        _ => f64::NAN,
+    }
+}
+
+/// Return P(Z <= z) for a standard normal Z, like R's `pnorm(z)`.
+pub fn normal_cdf(z: f64) -> f64 {
+    standard_normal().cdf(z)
+}
+
+/// Return the density of the standard normal at `z`, like R's `dnorm(z)`.
+pub fn normal_density(z: f64) -> f64 {
+    standard_normal().pdf(z)
+}
+
+/// Return the `p` quantile of the standard normal, like R's `qnorm(p)`.
+pub fn normal_quantile(p: f64) -> f64 {
+    standard_normal().inverse_cdf(p)
+}
+
+/// Return the logarithm of the gamma function, like R's `lgamma(x)`.
+pub fn log_gamma(x: f64) -> f64 {
+    ln_gamma(x)
+}
+
+/// Return the digamma function, the derivative of the log-gamma function, like R's
+/// `digamma(x)`.
+pub fn digamma(x: f64) -> f64 {
+    statrs_digamma(x)
+}
+
+/// Return the trigamma function, the second derivative of the log-gamma function, like R's
+/// `trigamma(x)`, for positive `x`. The argument is shifted above 10 with the recurrence
+/// trigamma(x) = trigamma(x + 1) + 1 / x^2 before the asymptotic series is summed.
+pub fn trigamma(x: f64) -> f64 {
+    let (mut x, mut shifted) = (x, 0.0);
+    while x < 10.0 {
+        shifted += 1.0 / (x * x);
+        x += 1.0;
+    }
+    let inverse_square = 1.0 / (x * x);
+    let series = 1.0 / 6.0
+        - inverse_square * (1.0 / 30.0 - inverse_square * (1.0 / 42.0 - inverse_square / 30.0));
+    shifted + 1.0 / x + inverse_square / 2.0 + series * inverse_square / x
+}
+
+fn standard_normal() -> Normal {
+    Normal::new(0.0, 1.0).expect("the standard normal is valid")
+}
@@ -36,4 +36,299 @@ pub enum LmFitterError {
     Unknown,
     #[error("Failed to allocate memory for Fortran arrays")]
     MemoryAllocationFailure,
+    #[error("This fitter needs a single response, found {found} responses")]
+    MultipleResponses { found: usize },
+    #[error("Penalties must be non-negative and finite, found {lambda}")]
+    InvalidPenalty { lambda: f64 },
+    #[error("At least one penalty is needed")]
+    NoPenalties,
+    #[error("The mixing parameter alpha must lie in [0, 1], found {alpha}")]
+    InvalidAlpha { alpha: f64 },
+    #[error("The limits of column {column} must include zero")]
+    InvalidLimits { column: usize },
+    #[error("Expected one {name} entry per column of x ({expected}), found {found}")]
+    LengthMismatch {
+        name: String,
+        expected: usize,
+        found: usize,
+    },
+    #[error("The coverage alpha must lie in [0.5, 1], found {alpha}")]
+    InvalidCoverage { alpha: f64 },
+    #[error("At least one random subset must be drawn")]
+    NoSubsets,
+    #[error("This fitter does not support prior weights")]
+    PriorWeights,
+    #[error("At least {needed} observations are needed, found {found}")]
+    TooFewObservations { needed: usize, found: usize },
+    #[error("The quantile tau must lie in (0, 1), found {tau}")]
+    InvalidQuantile { tau: f64 },
+    #[error(
+        "This fitter needs a full-rank model matrix, found rank {rank} with {columns} columns"
+    )]
+    RankDeficient { rank: usize, columns: usize },
+    #[error(
+        "The kernel bandwidth at tau = {tau} reaches past 0 or 1; use rank or bootstrap inference"
+    )]
+    BandwidthTooWide { tau: f64 },
+    #[error("The bootstrap needs at least two non-singular resamples, found {found}")]
+    TooFewReplicates { found: usize },
+    #[error("Clustered standard errors need at least two clusters, found {found}")]
+    TooFewClusters { found: usize },
+    #[error("Responses must be category codes 0, 1, 2, ..., found {value} in row {row}")]
+    InvalidCategory { row: usize, value: f64 },
+    #[error("At least {needed} categories are needed, found {found}")]
+    TooFewCategories { needed: usize, found: usize },
+    #[error("Category {category} has no observations")]
+    EmptyCategory { category: usize },
+    #[error(
+        "The Hessian of the log-likelihood is singular; check for aliased columns or separation"
+    )]
+    SingularHessian,
+    #[error("Expected one {name} entry per observation ({expected}), found {found}")]
+    ObservationMismatch {
+        name: String,
+        expected: usize,
+        found: usize,
+    },
+    #[error("The {structure} structure has {expected} parameters, found {found}")]
+    InvalidParameters {
+        structure: String,
+        expected: usize,
+        found: usize,
+    },
+    #[error("The {structure} structure gives a covariance matrix that is not positive definite")]
+    NotPositiveDefinite { structure: String },
+    #[error("The estimated autocorrelation must lie in (-1, 1), found {rho}")]
+    NonStationary { rho: f64 },
+    #[error("At least one fixed-effect factor is needed")]
+    NoFixedEffects,
+    #[error(transparent)]
+    CrossValidation(Box<CrossValidationError>),
+    #[error(transparent)]
+    Glm(Box<GlmError>),
+    #[error(transparent)]
+    Mixed(Box<MixedModelError>),
+    #[error(transparent)]
+    Iv(Box<IvError>),
+    #[error(transparent)]
+    Panel(Box<PanelError>),
+    #[error(transparent)]
+    Nls(Box<NlsError>),
+    #[error(transparent)]
+    LeastSquares(#[from] LeastSquaresError),
+}
+
+#[derive(Debug, Error)]
+pub enum AnovaError {
+    #[error("There are aliased coefficients in the model")]
+    AliasedCoefficients,
+    #[error("Marginal tests need a single-response model, found {found} responses")]
+    MultipleResponses { found: usize },
+    #[error("The hypothesis for term {term} is singular")]
+    SingularHypothesis { term: String },
+    #[error("At least two models are needed for a comparison, found {found}")]
+    TooFewModels { found: usize },
+    #[error("Model {model} has {found} observations, expected {expected}")]
+    DifferentObservations {
+        model: usize,
+        expected: usize,
+        found: usize,
+    },
+    #[error("The models were not all fitted to the same response and weights")]
+    DifferentResponses,
+    #[error("Models {first} and {second} are not nested")]
+    NotNested { first: usize, second: usize },
+    #[error(transparent)]
+    LeastSquares(#[from] LeastSquaresError),
+}
+
+#[derive(Debug, Error)]
+pub enum HypothesisError {
+    #[error("Unknown coefficient {name} in hypothesis {spec}")]
+    UnknownCoefficient { name: String, spec: String },
+    #[error("Could not parse hypothesis {spec}")]
+    InvalidSpecification { spec: String },
+    #[error(
+        "The hypothesis matrix has {found} columns, expected one per coefficient ({expected})"
+    )]
+    DimensionMismatch { expected: usize, found: usize },
+    #[error("The right-hand side has {found} elements, expected one per restriction ({expected})")]
+    RhsMismatch { expected: usize, found: usize },
+    #[error("The single-step adjustment needs at least one simulation draw")]
+    NoDraws,
+    #[error("The hypothesis involves the aliased coefficient {name}")]
+    AliasedCoefficient { name: String },
+    #[error("The hypothesis is singular")]
+    SingularHypothesis,
+    #[error("Hypothesis tests need a single-response model, found {found} responses")]
+    MultipleResponses { found: usize },
+    #[error(transparent)]
+    LeastSquares(#[from] LeastSquaresError),
+}
+
+#[derive(Debug, Error)]
+pub enum SelectionError {
+    #[error("Unknown term {label} in the scope")]
+    UnknownTerm { label: String },
+    #[error("Model selection needs a single-response model, found {found} responses")]
+    MultipleResponses { found: usize },
+    #[error("Best-subset selection supports at most {max} candidate columns, found {found}")]
+    TooManyColumns { found: usize, max: usize },
+    #[error("At least one model per size must be kept")]
+    NoModelsKept,
+    #[error(transparent)]
+    LeastSquares(#[from] LeastSquaresError),
+}
+
+#[derive(Debug, Error)]
+pub enum GlmError {
+    #[error("The {family} family does not support the {link} link")]
+    UnsupportedLink { family: String, link: String },
+    #[error("Invalid response for the {family} family: {reason}")]
+    InvalidResponse { family: String, reason: String },
+    #[error("Expected one {name} entry per {per} ({expected}), found {found}")]
+    LengthMismatch {
+        name: String,
+        per: String,
+        expected: usize,
+        found: usize,
+    },
+    #[error("No valid set of coefficients has been found: please supply starting values")]
+    NoValidCoefficients,
+    #[error("Inner loop {stage}; cannot correct step size")]
+    StepHalvingFailed { stage: String },
+    #[error("A generalized linear model needs a single response, found {found} responses")]
+    MultipleResponses { found: usize },
+    #[error("The confidence level must lie in (0, 1), found {level}")]
+    InvalidLevel { level: f64 },
+    #[error("The negative binomial theta must be positive and finite, found {theta}")]
+    InvalidTheta { theta: f64 },
+    #[error(transparent)]
+    Fit(#[from] LmFitterError),
+}
+
+#[derive(Debug, Error)]
+pub enum CrossValidationError {
+    #[error("Cross-validation needs at least two folds, found {folds}")]
+    TooFewFolds { folds: usize },
+    #[error("Cross-validation needs at least one repeat")]
+    NoRepeats,
+    #[error("Cannot split {units} observations or groups into {folds} folds")]
+    InvalidFolds { folds: usize, units: usize },
+    #[error("Expected one stratum or group label per observation ({expected}), found {found}")]
+    DimensionMismatch { expected: usize, found: usize },
+    #[error("Cross-validation needs a single-response model, found {found} responses")]
+    MultipleResponses { found: usize },
+    #[error(transparent)]
+    Fit(#[from] LmFitterError),
+    #[error(transparent)]
+    LeastSquares(#[from] LeastSquaresError),
+}
+
+#[derive(Debug, Error)]
+pub enum MixedModelError {
+    #[error("Could not parse random-effects term {spec}")]
+    InvalidSpecification { spec: String },
+    #[error("Unknown column {name} in random-effects term {spec}")]
+    UnknownColumn { name: String, spec: String },
+    #[error("Unknown grouping factor {name} in random-effects term {spec}")]
+    UnknownFactor { name: String, spec: String },
+    #[error("A mixed model needs at least one random-effects term")]
+    NoRandomEffects,
+    #[error(
+        "Expected one level of grouping factor {name} per observation ({expected}), found {found}"
+    )]
+    LevelMismatch {
+        name: String,
+        expected: usize,
+        found: usize,
+    },
+    #[error(
+        "The term for {group} has {effects} random effects, which must be fewer than the {observations} observations"
+    )]
+    TooManyRandomEffects {
+        group: String,
+        effects: usize,
+        observations: usize,
+    },
+    #[error("The penalized least squares system is singular")]
+    Singular,
+    #[error("The confidence level must lie in (0, 1), found {level}")]
+    InvalidLevel { level: f64 },
+    #[error(transparent)]
+    Fit(#[from] LmFitterError),
+}
+
+#[derive(Debug, Error)]
+pub enum IvError {
+    #[error("Could not parse instrumental-variables formula {spec}")]
+    InvalidFormula { spec: String },
+    #[error("Unknown name {name} in formula {spec}")]
+    UnknownName { name: String, spec: String },
+    #[error("Column {name} of x is missing from the regressors of formula {spec}")]
+    MissingRegressor { name: String, spec: String },
+    #[error("Expected one row of instruments per observation ({expected}), found {found}")]
+    DimensionMismatch { expected: usize, found: usize },
+    #[error("Column {column} of x is not a valid endogenous regressor")]
+    InvalidEndogenous { column: usize },
+    #[error("At least one regressor must be endogenous")]
+    NoEndogenous,
+    #[error("The model is underidentified: {endogenous} endogenous regressors but only {instruments} excluded instruments")]
+    Underidentified {
+        endogenous: usize,
+        instruments: usize,
+    },
+    #[error("The instruments or regressors are collinear")]
+    Singular,
+    #[error(transparent)]
+    Fit(#[from] LmFitterError),
+}
+
+#[derive(Debug, Error)]
+pub enum PanelError {
+    #[error("Expected one entity and time per observation ({expected}), found {found}")]
+    IndexMismatch { expected: usize, found: usize },
+    #[error("Entity {entity} has more than one observation at time {time}")]
+    DuplicateObservation { entity: usize, time: usize },
+    #[error("The {estimator} estimator needs {needed} observations or entities, found {found}")]
+    TooFewObservations {
+        estimator: String,
+        needed: usize,
+        found: usize,
+    },
+    #[error("The {test} test needs {needed}")]
+    InvalidTest { test: String, needed: String },
+    #[error(transparent)]
+    Fit(#[from] LmFitterError),
+}
+
+#[derive(Debug, Error)]
+pub enum NlsError {
+    #[error("Expected one {name} entry per parameter ({expected}), found {found}")]
+    LengthMismatch {
+        name: String,
+        expected: usize,
+        found: usize,
+    },
+    #[error("The model returned {found} values for {expected} observations")]
+    ModelLength { expected: usize, found: usize },
+    #[error("The Jacobian must be {rows} x {columns}, found {found_rows} x {found_columns}")]
+    JacobianShape {
+        rows: usize,
+        columns: usize,
+        found_rows: usize,
+        found_columns: usize,
+    },
+    #[error("Parameter {parameter} starts outside its bounds")]
+    InfeasibleStart { parameter: String },
+    #[error("The model or its Jacobian is not finite")]
+    NonFinite,
+    #[error("Singular gradient matrix at iteration {iteration}")]
+    SingularGradient { iteration: usize },
+    #[error("No step reduces the residual sum of squares at iteration {iteration}")]
+    NoProgress { iteration: usize },
+    #[error("At least {needed} observations are needed, found {found}")]
+    TooFewObservations { needed: usize, found: usize },
+    #[error(transparent)]
+    Fit(#[from] LmFitterError),
 }
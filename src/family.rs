@@ -0,0 +1,883 @@
+//! This module contains the link functions and error distributions of generalized linear
+//! models, following R's `family` objects.
+//!
+//! A `Link` maps the mean mu to the linear predictor eta and back. A `Family` supplies the
+//! variance function, the deviance residuals, the AIC and the starting values of a
+//! distribution, together with its link. The five families of R's `stats` package are provided
+//! with their canonical links as defaults and the alternative links R accepts, along with the
+//! quasi-binomial and quasi-Poisson families, which share their means and variances but
+//! estimate the dispersion, and the negative binomial family of `MASS` for a known theta.
+
+// src/family.rs
+
+use crate::distributions::{log_gamma, normal_cdf, normal_density, normal_quantile};
+use crate::errors::GlmError;
+use std::f64::consts::PI;
+use std::fmt;
+
+const EPS: f64 = f64::EPSILON;
+
+/// An enum representing the link functions eta = g(mu).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Link {
+    /// log(mu / (1 - mu)), canonical for the binomial.
+    Logit,
+    /// The standard normal quantile of mu.
+    Probit,
+    /// log(-log(1 - mu)).
+    Cloglog,
+    /// log(mu), canonical for the Poisson.
+    Log,
+    /// mu, canonical for the Gaussian.
+    Identity,
+    /// 1 / mu, canonical for the Gamma.
+    Inverse,
+    /// sqrt(mu).
+    Sqrt,
+    /// 1 / mu^2, canonical for the inverse Gaussian.
+    InverseSquare,
+}
+
+impl Link {
+    /// Return the name R gives the link.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Link::Logit => "logit",
+            Link::Probit => "probit",
+            Link::Cloglog => "cloglog",
+            Link::Log => "log",
+            Link::Identity => "identity",
+            Link::Inverse => "inverse",
+            Link::Sqrt => "sqrt",
+            Link::InverseSquare => "1/mu^2",
+        }
+    }
+
+    /// Return eta = g(mu).
+    pub fn link(&self, mu: f64) -> f64 {
+        match self {
+            Link::Logit => (mu / (1.0 - mu)).ln(),
+            Link::Probit => normal_quantile(mu),
+            Link::Cloglog => (-(-mu).ln_1p()).ln(),
+            Link::Log => mu.ln(),
+            Link::Identity => mu,
+            Link::Inverse => 1.0 / mu,
+            Link::Sqrt => mu.sqrt(),
+            Link::InverseSquare => 1.0 / (mu * mu),
+        }
+    }
+
+    /// Return mu = g^-1(eta), kept inside (0, 1) or (0, inf) by machine epsilon where R does so.
+    pub fn inverse(&self, eta: f64) -> f64 {
+        match self {
+            Link::Logit => {
+                let e = if eta < -30.0 {
+                    EPS
+                } else if eta > 30.0 {
+                    1.0 / EPS
+                } else {
+                    eta.exp()
+                };
+                e / (1.0 + e)
+            }
+            Link::Probit => {
+                let threshold = -normal_quantile(EPS);
+                normal_cdf(eta.clamp(-threshold, threshold))
+            }
+            Link::Cloglog => (-(-eta.exp()).exp_m1()).clamp(EPS, 1.0 - EPS),
+            Link::Log => eta.exp().max(EPS),
+            Link::Identity => eta,
+            Link::Inverse => 1.0 / eta,
+            Link::Sqrt => eta * eta,
+            Link::InverseSquare => 1.0 / eta.sqrt(),
+        }
+    }
+
+    /// Return d mu / d eta.
+    pub fn mu_eta(&self, eta: f64) -> f64 {
+        match self {
+            Link::Logit => {
+                if eta.abs() > 30.0 {
+                    EPS
+                } else {
+                    let e = eta.exp();
+                    e / (1.0 + e).powi(2)
+                }
+            }
+            Link::Probit => normal_density(eta).max(EPS),
+            Link::Cloglog => {
+                let eta = eta.min(700.0);
+                (eta.exp() * (-eta.exp()).exp()).max(EPS)
+            }
+            Link::Log => eta.exp().max(EPS),
+            Link::Identity => 1.0,
+            Link::Inverse => -1.0 / (eta * eta),
+            Link::Sqrt => 2.0 * eta,
+            Link::InverseSquare => -1.0 / (2.0 * eta.powf(1.5)),
+        }
+    }
+
+    /// Return `true` if eta is in the domain of the inverse link.
+    pub fn valid_eta(&self, eta: f64) -> bool {
+        match self {
+            Link::Inverse => eta.is_finite() && eta != 0.0,
+            Link::Sqrt | Link::InverseSquare => eta.is_finite() && eta > 0.0,
+            _ => true,
+        }
+    }
+}
+
+impl fmt::Display for Link {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// An enum representing the warnings `glm.fit` can raise about a fit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GlmWarning {
+    /// The deviance did not settle within `max_iter` iterations.
+    NotConverged,
+    /// A step had to be halved to keep the fit inside the domain of the family.
+    Boundary,
+    /// Some binomial fitted probabilities are within machine precision of 0 or 1, a sign of
+    /// complete or quasi-complete separation.
+    FittedProbabilitiesZeroOrOne,
+    /// Some Poisson fitted rates are within machine precision of 0.
+    FittedRatesZero,
+}
+
+impl fmt::Display for GlmWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            GlmWarning::NotConverged => "glm.fit: algorithm did not converge",
+            GlmWarning::Boundary => "glm.fit: algorithm stopped at boundary value",
+            GlmWarning::FittedProbabilitiesZeroOrOne => {
+                "glm.fit: fitted probabilities numerically 0 or 1 occurred"
+            }
+            GlmWarning::FittedRatesZero => "glm.fit: fitted rates numerically 0 occurred",
+        })
+    }
+}
+
+/// A trait for the error distributions of generalized linear models.
+pub trait Family: fmt::Debug {
+    /// Return the name R gives the family.
+    fn name(&self) -> &'static str;
+
+    /// Return the link function.
+    fn link(&self) -> Link;
+
+    /// Return the variance of an observation with mean mu, up to the dispersion.
+    fn variance(&self, mu: f64) -> f64;
+
+    /// Return the contribution w d(y, mu) of an observation to the deviance.
+    fn deviance_residual(&self, y: f64, mu: f64, weight: f64) -> f64;
+
+    /// Return the AIC of R's family object: minus twice the log-likelihood at the fitted means,
+    /// plus 2 when the dispersion is estimated. The parameters are added by the fitter.
+    fn aic(&self, y: &[f64], mu: &[f64], weights: &[f64], deviance: f64) -> f64;
+
+    /// Check the response and return the starting means.
+    ///
+    /// # Errors
+    /// Returns `GlmError::InvalidResponse` if the response is outside the support.
+    fn initialize(&self, y: &[f64], weights: &[f64]) -> Result<Vec<f64>, GlmError>;
+
+    /// Return `true` if mu is a valid mean.
+    fn valid_mu(&self, _mu: f64) -> bool {
+        true
+    }
+
+    /// Return `true` if the dispersion is fixed at 1 rather than estimated.
+    fn fixed_dispersion(&self) -> bool {
+        false
+    }
+
+    /// Return the warning `glm.fit` raises when fitted means reach the boundary of the family,
+    /// a sign of separation, or `None` if they do not or the family has no such check.
+    fn boundary_warning(&self, _mu: &[f64]) -> Option<GlmWarning> {
+        None
+    }
+}
+
+/// The fitted means within this distance of the boundary count as numerically 0 or 1.
+const BOUNDARY_EPS: f64 = 10.0 * EPS;
+
+/// Return `link` if `family` accepts it, otherwise `GlmError::UnsupportedLink`.
+fn check_link(family: &str, link: Link, allowed: &[Link]) -> Result<Link, GlmError> {
+    if allowed.contains(&link) {
+        Ok(link)
+    } else {
+        Err(GlmError::UnsupportedLink {
+            family: family.to_string(),
+            link: link.name().to_string(),
+        })
+    }
+}
+
+/// Return `GlmError::InvalidResponse` for `family` if any response fails `valid`.
+fn check_response(
+    family: &str,
+    y: &[f64],
+    valid: impl Fn(f64) -> bool,
+    reason: &str,
+) -> Result<(), GlmError> {
+    if y.iter().all(|&v| valid(v)) {
+        Ok(())
+    } else {
+        Err(GlmError::InvalidResponse {
+            family: family.to_string(),
+            reason: reason.to_string(),
+        })
+    }
+}
+
+/// Return y log(y / mu), taken as 0 at y = 0.
+fn y_log_y(y: f64, mu: f64) -> f64 {
+    if y == 0.0 {
+        0.0
+    } else {
+        y * (y / mu).ln()
+    }
+}
+
+/// The normal distribution, with identity, log or inverse link.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Gaussian {
+    link: Link,
+}
+
+impl Gaussian {
+    /// Return the Gaussian family with `link`.
+    ///
+    /// # Errors
+    /// Returns `GlmError::UnsupportedLink` for links other than identity, log and inverse.
+    pub fn new(link: Link) -> Result<Self, GlmError> {
+        let allowed = [Link::Identity, Link::Log, Link::Inverse];
+        Ok(Gaussian {
+            link: check_link("gaussian", link, &allowed)?,
+        })
+    }
+}
+
+impl Default for Gaussian {
+    fn default() -> Self {
+        Gaussian {
+            link: Link::Identity,
+        }
+    }
+}
+
+impl Family for Gaussian {
+    fn name(&self) -> &'static str {
+        "gaussian"
+    }
+
+    fn link(&self) -> Link {
+        self.link
+    }
+
+    fn variance(&self, _mu: f64) -> f64 {
+        1.0
+    }
+
+    fn deviance_residual(&self, y: f64, mu: f64, weight: f64) -> f64 {
+        weight * (y - mu).powi(2)
+    }
+
+    fn aic(&self, y: &[f64], _mu: &[f64], weights: &[f64], deviance: f64) -> f64 {
+        let n = y.len() as f64;
+        n * ((2.0 * PI * deviance / n).ln() + 1.0) + 2.0
+            - weights.iter().map(|w| w.ln()).sum::<f64>()
+    }
+
+    fn initialize(&self, y: &[f64], _weights: &[f64]) -> Result<Vec<f64>, GlmError> {
+        let reason = "cannot find valid starting values: please specify some";
+        match self.link {
+            Link::Log => check_response("gaussian", y, |v| v > 0.0, reason)?,
+            Link::Inverse => check_response("gaussian", y, |v| v != 0.0, reason)?,
+            _ => {}
+        }
+        Ok(y.to_vec())
+    }
+}
+
+/// The binomial distribution of a proportion y out of `weight` trials, with logit, probit,
+/// cloglog or log link.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Binomial {
+    link: Link,
+}
+
+impl Binomial {
+    /// Return the binomial family with `link`.
+    ///
+    /// # Errors
+    /// Returns `GlmError::UnsupportedLink` for links other than logit, probit, cloglog and log.
+    pub fn new(link: Link) -> Result<Self, GlmError> {
+        let allowed = [Link::Logit, Link::Probit, Link::Cloglog, Link::Log];
+        Ok(Binomial {
+            link: check_link("binomial", link, &allowed)?,
+        })
+    }
+}
+
+impl Default for Binomial {
+    fn default() -> Self {
+        Binomial { link: Link::Logit }
+    }
+}
+
+impl Family for Binomial {
+    fn name(&self) -> &'static str {
+        "binomial"
+    }
+
+    fn link(&self) -> Link {
+        self.link
+    }
+
+    fn variance(&self, mu: f64) -> f64 {
+        mu * (1.0 - mu)
+    }
+
+    fn deviance_residual(&self, y: f64, mu: f64, weight: f64) -> f64 {
+        2.0 * weight * (y_log_y(y, mu) + y_log_y(1.0 - y, 1.0 - mu))
+    }
+
+    fn aic(&self, y: &[f64], mu: &[f64], weights: &[f64], _deviance: f64) -> f64 {
+        // The weights are the numbers of trials; each term is a log binomial probability.
+        -2.0 * y
+            .iter()
+            .zip(mu)
+            .zip(weights)
+            .map(|((&y, &mu), &m)| {
+                let trials = m.round();
+                let successes = (m * y).round();
+                let mut log_density = log_gamma(trials + 1.0)
+                    - log_gamma(successes + 1.0)
+                    - log_gamma(trials - successes + 1.0);
+                if successes > 0.0 {
+                    log_density += successes * mu.ln();
+                }
+                if trials > successes {
+                    log_density += (trials - successes) * (1.0 - mu).ln();
+                }
+                log_density
+            })
+            .sum::<f64>()
+    }
+
+    fn initialize(&self, y: &[f64], weights: &[f64]) -> Result<Vec<f64>, GlmError> {
+        check_response(
+            "binomial",
+            y,
+            |v| (0.0..=1.0).contains(&v),
+            "y values must be 0 <= y <= 1",
+        )?;
+        Ok(y.iter()
+            .zip(weights)
+            .map(|(y, w)| (w * y + 0.5) / (w + 1.0))
+            .collect())
+    }
+
+    fn valid_mu(&self, mu: f64) -> bool {
+        mu.is_finite() && mu > 0.0 && mu < 1.0
+    }
+
+    fn fixed_dispersion(&self) -> bool {
+        true
+    }
+
+    fn boundary_warning(&self, mu: &[f64]) -> Option<GlmWarning> {
+        mu.iter()
+            .any(|mu| !(BOUNDARY_EPS..=1.0 - BOUNDARY_EPS).contains(mu))
+            .then_some(GlmWarning::FittedProbabilitiesZeroOrOne)
+    }
+}
+
+/// The Poisson distribution of counts, with log, identity or sqrt link.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Poisson {
+    link: Link,
+}
+
+impl Poisson {
+    /// Return the Poisson family with `link`.
+    ///
+    /// # Errors
+    /// Returns `GlmError::UnsupportedLink` for links other than log, identity and sqrt.
+    pub fn new(link: Link) -> Result<Self, GlmError> {
+        let allowed = [Link::Log, Link::Identity, Link::Sqrt];
+        Ok(Poisson {
+            link: check_link("poisson", link, &allowed)?,
+        })
+    }
+}
+
+impl Default for Poisson {
+    fn default() -> Self {
+        Poisson { link: Link::Log }
+    }
+}
+
+impl Family for Poisson {
+    fn name(&self) -> &'static str {
+        "poisson"
+    }
+
+    fn link(&self) -> Link {
+        self.link
+    }
+
+    fn variance(&self, mu: f64) -> f64 {
+        mu
+    }
+
+    fn deviance_residual(&self, y: f64, mu: f64, weight: f64) -> f64 {
+        2.0 * weight * (y_log_y(y, mu) - (y - mu))
+    }
+
+    fn aic(&self, y: &[f64], mu: &[f64], weights: &[f64], _deviance: f64) -> f64 {
+        -2.0 * y
+            .iter()
+            .zip(mu)
+            .zip(weights)
+            .map(|((&y, &mu), &w)| {
+                let log_mu = if y == 0.0 { 0.0 } else { y * mu.ln() };
+                w * (log_mu - mu - log_gamma(y + 1.0))
+            })
+            .sum::<f64>()
+    }
+
+    fn initialize(&self, y: &[f64], _weights: &[f64]) -> Result<Vec<f64>, GlmError> {
+        check_response(
+            "poisson",
+            y,
+            |v| v >= 0.0,
+            "negative values not allowed for the 'Poisson' family",
+        )?;
+        Ok(y.iter().map(|y| y + 0.1).collect())
+    }
+
+    fn valid_mu(&self, mu: f64) -> bool {
+        mu.is_finite() && mu > 0.0
+    }
+
+    fn fixed_dispersion(&self) -> bool {
+        true
+    }
+
+    fn boundary_warning(&self, mu: &[f64]) -> Option<GlmWarning> {
+        mu.iter()
+            .any(|&mu| mu < BOUNDARY_EPS)
+            .then_some(GlmWarning::FittedRatesZero)
+    }
+}
+
+/// The Gamma distribution, with inverse, identity or log link.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Gamma {
+    link: Link,
+}
+
+impl Gamma {
+    /// Return the Gamma family with `link`.
+    ///
+    /// # Errors
+    /// Returns `GlmError::UnsupportedLink` for links other than inverse, identity and log.
+    pub fn new(link: Link) -> Result<Self, GlmError> {
+        let allowed = [Link::Inverse, Link::Identity, Link::Log];
+        Ok(Gamma {
+            link: check_link("Gamma", link, &allowed)?,
+        })
+    }
+}
+
+impl Default for Gamma {
+    fn default() -> Self {
+        Gamma {
+            link: Link::Inverse,
+        }
+    }
+}
+
+impl Family for Gamma {
+    fn name(&self) -> &'static str {
+        "Gamma"
+    }
+
+    fn link(&self) -> Link {
+        self.link
+    }
+
+    fn variance(&self, mu: f64) -> f64 {
+        mu * mu
+    }
+
+    fn deviance_residual(&self, y: f64, mu: f64, weight: f64) -> f64 {
+        -2.0 * weight * ((y / mu).ln() - (y - mu) / mu)
+    }
+
+    fn aic(&self, y: &[f64], mu: &[f64], weights: &[f64], deviance: f64) -> f64 {
+        let dispersion = deviance / weights.iter().sum::<f64>();
+        let shape = 1.0 / dispersion;
+        -2.0 * y
+            .iter()
+            .zip(mu)
+            .zip(weights)
+            .map(|((&y, &mu), &w)| {
+                let scale = mu * dispersion;
+                w * ((shape - 1.0) * y.ln() - y / scale - log_gamma(shape) - shape * scale.ln())
+            })
+            .sum::<f64>()
+            + 2.0
+    }
+
+    fn initialize(&self, y: &[f64], _weights: &[f64]) -> Result<Vec<f64>, GlmError> {
+        check_response(
+            "Gamma",
+            y,
+            |v| v > 0.0,
+            "non-positive values not allowed for the 'Gamma' family",
+        )?;
+        Ok(y.to_vec())
+    }
+
+    fn valid_mu(&self, mu: f64) -> bool {
+        mu.is_finite() && mu > 0.0
+    }
+}
+
+/// The inverse Gaussian distribution, with 1/mu^2, inverse, identity or log link.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InverseGaussian {
+    link: Link,
+}
+
+impl InverseGaussian {
+    /// Return the inverse Gaussian family with `link`.
+    ///
+    /// # Errors
+    /// Returns `GlmError::UnsupportedLink` for links other than 1/mu^2, inverse, identity and
+    /// log.
+    pub fn new(link: Link) -> Result<Self, GlmError> {
+        let allowed = [
+            Link::InverseSquare,
+            Link::Inverse,
+            Link::Identity,
+            Link::Log,
+        ];
+        Ok(InverseGaussian {
+            link: check_link("inverse.gaussian", link, &allowed)?,
+        })
+    }
+}
+
+impl Default for InverseGaussian {
+    fn default() -> Self {
+        InverseGaussian {
+            link: Link::InverseSquare,
+        }
+    }
+}
+
+impl Family for InverseGaussian {
+    fn name(&self) -> &'static str {
+        "inverse.gaussian"
+    }
+
+    fn link(&self) -> Link {
+        self.link
+    }
+
+    fn variance(&self, mu: f64) -> f64 {
+        mu.powi(3)
+    }
+
+    fn deviance_residual(&self, y: f64, mu: f64, weight: f64) -> f64 {
+        weight * (y - mu).powi(2) / (y * mu * mu)
+    }
+
+    fn aic(&self, y: &[f64], _mu: &[f64], weights: &[f64], deviance: f64) -> f64 {
+        let total: f64 = weights.iter().sum();
+        let dispersion = deviance / total;
+        total * ((dispersion * 2.0 * PI).ln() + 1.0)
+            + 3.0 * y.iter().zip(weights).map(|(y, w)| w * y.ln()).sum::<f64>()
+            + 2.0
+    }
+
+    fn initialize(&self, y: &[f64], _weights: &[f64]) -> Result<Vec<f64>, GlmError> {
+        check_response(
+            "inverse.gaussian",
+            y,
+            |v| v > 0.0,
+            "positive values only are allowed for the 'inverse.gaussian' family",
+        )?;
+        Ok(y.to_vec())
+    }
+}
+
+/// The quasi-binomial family: the binomial mean and variance with an estimated dispersion, for
+/// overdispersed proportions. It has no likelihood, so its AIC is `NaN`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QuasiBinomial {
+    link: Link,
+}
+
+impl QuasiBinomial {
+    /// Return the quasi-binomial family with `link`.
+    ///
+    /// # Errors
+    /// Returns `GlmError::UnsupportedLink` for links other than logit, probit, cloglog and log.
+    pub fn new(link: Link) -> Result<Self, GlmError> {
+        let allowed = [Link::Logit, Link::Probit, Link::Cloglog, Link::Log];
+        Ok(QuasiBinomial {
+            link: check_link("quasibinomial", link, &allowed)?,
+        })
+    }
+
+    fn binomial(&self) -> Binomial {
+        Binomial { link: self.link }
+    }
+}
+
+impl Default for QuasiBinomial {
+    fn default() -> Self {
+        QuasiBinomial { link: Link::Logit }
+    }
+}
+
+impl Family for QuasiBinomial {
+    fn name(&self) -> &'static str {
+        "quasibinomial"
+    }
+
+    fn link(&self) -> Link {
+        self.link
+    }
+
+    fn variance(&self, mu: f64) -> f64 {
+        self.binomial().variance(mu)
+    }
+
+    fn deviance_residual(&self, y: f64, mu: f64, weight: f64) -> f64 {
+        self.binomial().deviance_residual(y, mu, weight)
+    }
+
+    fn aic(&self, _y: &[f64], _mu: &[f64], _weights: &[f64], _deviance: f64) -> f64 {
+        f64::NAN
+    }
+
+    fn initialize(&self, y: &[f64], weights: &[f64]) -> Result<Vec<f64>, GlmError> {
+        check_response(
+            "quasibinomial",
+            y,
+            |v| (0.0..=1.0).contains(&v),
+            "y values must be 0 <= y <= 1",
+        )?;
+        self.binomial().initialize(y, weights)
+    }
+
+    fn valid_mu(&self, mu: f64) -> bool {
+        self.binomial().valid_mu(mu)
+    }
+}
+
+/// The quasi-Poisson family: the Poisson mean and variance with an estimated dispersion, for
+/// overdispersed counts. It has no likelihood, so its AIC is `NaN`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QuasiPoisson {
+    link: Link,
+}
+
+impl QuasiPoisson {
+    /// Return the quasi-Poisson family with `link`.
+    ///
+    /// # Errors
+    /// Returns `GlmError::UnsupportedLink` for links other than log, identity and sqrt.
+    pub fn new(link: Link) -> Result<Self, GlmError> {
+        let allowed = [Link::Log, Link::Identity, Link::Sqrt];
+        Ok(QuasiPoisson {
+            link: check_link("quasipoisson", link, &allowed)?,
+        })
+    }
+
+    fn poisson(&self) -> Poisson {
+        Poisson { link: self.link }
+    }
+}
+
+impl Default for QuasiPoisson {
+    fn default() -> Self {
+        QuasiPoisson { link: Link::Log }
+    }
+}
+
+impl Family for QuasiPoisson {
+    fn name(&self) -> &'static str {
+        "quasipoisson"
+    }
+
+    fn link(&self) -> Link {
+        self.link
+    }
+
+    fn variance(&self, mu: f64) -> f64 {
+        mu
+    }
+
+    fn deviance_residual(&self, y: f64, mu: f64, weight: f64) -> f64 {
+        self.poisson().deviance_residual(y, mu, weight)
+    }
+
+    fn aic(&self, _y: &[f64], _mu: &[f64], _weights: &[f64], _deviance: f64) -> f64 {
+        f64::NAN
+    }
+
+    fn initialize(&self, y: &[f64], _weights: &[f64]) -> Result<Vec<f64>, GlmError> {
+        check_response(
+            "quasipoisson",
+            y,
+            |v| v >= 0.0,
+            "negative values not allowed for the 'quasiPoisson' family",
+        )?;
+        Ok(y.iter().map(|y| y + 0.1).collect())
+    }
+
+    fn valid_mu(&self, mu: f64) -> bool {
+        self.poisson().valid_mu(mu)
+    }
+}
+
+/// The negative binomial distribution of counts with a known shape theta, whose variance
+/// mu + mu^2 / theta grows faster than the mean, with log, sqrt or identity link.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NegativeBinomial {
+    theta: f64,
+    link: Link,
+}
+
+impl NegativeBinomial {
+    /// Return the negative binomial family with shape `theta` and `link`.
+    ///
+    /// # Errors
+    /// Returns `GlmError::InvalidTheta` if theta is not positive and finite, and
+    /// `GlmError::UnsupportedLink` for links other than log, sqrt and identity.
+    pub fn new(theta: f64, link: Link) -> Result<Self, GlmError> {
+        if !(theta > 0.0 && theta.is_finite()) {
+            return Err(GlmError::InvalidTheta { theta });
+        }
+        let allowed = [Link::Log, Link::Sqrt, Link::Identity];
+        Ok(NegativeBinomial {
+            theta,
+            link: check_link("negative.binomial", link, &allowed)?,
+        })
+    }
+
+    /// Return the shape theta.
+    pub fn theta(&self) -> f64 {
+        self.theta
+    }
+}
+
+impl Family for NegativeBinomial {
+    fn name(&self) -> &'static str {
+        "negative.binomial"
+    }
+
+    fn link(&self) -> Link {
+        self.link
+    }
+
+    fn variance(&self, mu: f64) -> f64 {
+        mu + mu * mu / self.theta
+    }
+
+    fn deviance_residual(&self, y: f64, mu: f64, weight: f64) -> f64 {
+        let theta = self.theta;
+        2.0 * weight
+            * (y * (y.max(1.0) / mu).ln() - (y + theta) * ((y + theta) / (mu + theta)).ln())
+    }
+
+    fn aic(&self, y: &[f64], mu: &[f64], weights: &[f64], _deviance: f64) -> f64 {
+        let theta = self.theta;
+        2.0 * y
+            .iter()
+            .zip(mu)
+            .zip(weights)
+            .map(|((&y, &mu), &w)| {
+                w * ((y + theta) * (mu + theta).ln() - y * mu.ln() + log_gamma(y + 1.0)
+                    - theta * theta.ln()
+                    + log_gamma(theta)
+                    - log_gamma(theta + y))
+            })
+            .sum::<f64>()
+    }
+
+    fn initialize(&self, y: &[f64], _weights: &[f64]) -> Result<Vec<f64>, GlmError> {
+        check_response(
+            "negative.binomial",
+            y,
+            |v| v >= 0.0,
+            "negative values not allowed for the negative binomial family",
+        )?;
+        Ok(y.iter()
+            .map(|&y| if y == 0.0 { 1.0 / 6.0 } else { y })
+            .collect())
+    }
+
+    fn valid_mu(&self, mu: f64) -> bool {
+        mu.is_finite() && mu > 0.0
+    }
+
+    fn fixed_dispersion(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_links_invert_and_differentiate() {
+        let links = [
+            (Link::Logit, 0.3),
+            (Link::Probit, 0.3),
+            (Link::Cloglog, 0.3),
+            (Link::Log, 2.5),
+            (Link::Identity, 2.5),
+            (Link::Inverse, 2.5),
+            (Link::Sqrt, 2.5),
+            (Link::InverseSquare, 2.5),
+        ];
+        for (link, mu) in links {
+            let eta = link.link(mu);
+            assert!((link.inverse(eta) - mu).abs() < 1e-12, "{link}");
+            let h = 1e-6;
+            let numeric = (link.inverse(eta + h) - link.inverse(eta - h)) / (2.0 * h);
+            assert!((link.mu_eta(eta) - numeric).abs() < 1e-6, "{link}");
+        }
+        assert_eq!(Link::Logit.inverse(100.0), 1.0 / (1.0 + EPS));
+    }
+
+    #[test]
+    fn test_families_check_links_and_responses() {
+        assert!(matches!(
+            Poisson::new(Link::Logit),
+            Err(GlmError::UnsupportedLink { .. })
+        ));
+        assert!(Binomial::default()
+            .initialize(&[0.0, 1.5], &[1.0; 2])
+            .is_err());
+        assert!(Gamma::default().initialize(&[0.0], &[1.0]).is_err());
+        assert_eq!(Poisson::default().deviance_residual(0.0, 2.0, 1.0), 4.0);
+        let binomial = Binomial::default();
+        assert_eq!(binomial.initialize(&[1.0], &[1.0]).unwrap(), vec![0.75]);
+        // Bernoulli deviance is -2 log-likelihood, so the AIC term matches it.
+        let deviance = binomial.deviance_residual(1.0, 0.8, 1.0);
+        assert!((binomial.aic(&[1.0], &[0.8], &[1.0], deviance) - deviance).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,462 @@
+//! This module contains the Cochrane-Orcutt and Prais-Winsten fitters for regressions with
+//! first-order autoregressive errors, which implement the `FitModel` trait for the
+//! `LinearModelFitter` enum, like `orcutt::cochrane.orcutt` and `prais::prais_winsten`.
+//!
+//! The rows of the data are taken to be in time order. Starting from the least squares fit,
+//! rho is estimated by regressing each residual on the one before it, without an intercept.
+//! The data are then quasi-differenced, y_t - rho y_{t-1} and x_t - rho x_{t-1}, and refitted
+//! by QR decomposition; the residuals y - X b of the refit on the original scale give the next
+//! estimate of rho. Cochrane-Orcutt drops the first observation, while Prais-Winsten keeps it
+//! scaled by sqrt(1 - rho^2). Quasi-differencing turns the intercept column into a column of
+//! 1 - rho, so the coefficients stay on the scale of the original model.
+
+// src/fitters/cochrane_orcutt_fitter.rs
+
+use super::fit::FitModel;
+use super::qr_decomposition_fitter::QrDecompositionFitter;
+use crate::anova::{format_number, format_p_value, write_coefficients};
+use crate::distributions::f_upper_tail;
+use crate::errors::LmFitterError;
+use crate::hypothesis::wald_statistic;
+use crate::least_squares::LeastSquaresFit;
+use crate::types::{Data, RealMatrix};
+use crate::vcov::unscaled_covariance;
+use derive_builder::Builder;
+use std::fmt;
+
+/// An enum representing the treatment of the first observation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Ar1Method {
+    /// Drop the first observation, which has no predecessor.
+    #[default]
+    CochraneOrcutt,
+    /// Keep the first observation, scaled by sqrt(1 - rho^2).
+    PraisWinsten,
+}
+
+/// The result of a Cochrane-Orcutt or Prais-Winsten fit. The coefficient statistics are those
+/// of the least squares fit to the transformed data at the final rho.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ar1Fit {
+    /// The procedure the model was fitted with.
+    pub method: Ar1Method,
+    /// The names of the coefficients.
+    pub names: Vec<String>,
+    /// The coefficients.
+    pub coefficients: Vec<f64>,
+    /// The standard errors of the coefficients.
+    pub std_errors: Vec<f64>,
+    /// The covariance of the coefficients.
+    pub covariance: RealMatrix,
+    /// The estimated autocorrelation of the errors.
+    pub rho: f64,
+    /// The standard error of rho from the regression of each residual on the one before it.
+    pub rho_std_error: f64,
+    /// The residual standard error of the transformed model.
+    pub sigma: f64,
+    /// The residual degrees of freedom of the transformed model.
+    pub df_residual: usize,
+    /// The R-squared of the transformed model, about the mean of the transformed response.
+    pub r_squared: f64,
+    /// The R-squared adjusted for the number of coefficients.
+    pub adj_r_squared: f64,
+    /// The F statistic that all coefficients but the intercept are zero, with its degrees of
+    /// freedom and p-value. `None` for a model without an intercept or with no coefficients
+    /// besides it.
+    pub f_statistic: Option<(f64, usize, usize, f64)>,
+    /// The residuals y - X b on the original scale.
+    pub residuals: Vec<f64>,
+    /// The residuals of the transformed model.
+    pub transformed_residuals: Vec<f64>,
+    /// The Durbin-Watson statistic of the least squares residuals.
+    pub durbin_watson: f64,
+    /// The Durbin-Watson statistic of the residuals of the transformed model.
+    pub transformed_durbin_watson: f64,
+    /// The number of updates of rho.
+    pub iterations: usize,
+    /// `true` if rho settled within `max_iter` updates.
+    pub converged: bool,
+}
+
+impl fmt::Display for Ar1Fit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let method = match self.method {
+            Ar1Method::CochraneOrcutt => "Cochrane-Orcutt",
+            Ar1Method::PraisWinsten => "Prais-Winsten",
+        };
+        writeln!(f, "{} estimation", method)?;
+        writeln!(f)?;
+        writeln!(f, "Coefficients:")?;
+        write_coefficients(
+            f,
+            &self.names,
+            &self.coefficients,
+            &self.std_errors,
+            self.df_residual,
+            &["Estimate", "Std. Error", "t value", "Pr(>|t|)"],
+        )?;
+        writeln!(f)?;
+        writeln!(
+            f,
+            "Residual standard error: {} on {} degrees of freedom",
+            format_number(self.sigma),
+            self.df_residual
+        )?;
+        writeln!(
+            f,
+            "Multiple R-squared: {},\tAdjusted R-squared: {}",
+            format_number(self.r_squared),
+            format_number(self.adj_r_squared)
+        )?;
+        if let Some((statistic, df1, df2, p_value)) = self.f_statistic {
+            writeln!(
+                f,
+                "F-statistic: {} on {} and {} DF,  p-value: {}",
+                format_number(statistic),
+                df1,
+                df2,
+                format_p_value(p_value)
+            )?;
+        }
+        writeln!(f)?;
+        writeln!(
+            f,
+            "Rho: {} (Std. Error {}), {} iterations",
+            format_number(self.rho),
+            format_number(self.rho_std_error),
+            self.iterations
+        )?;
+        writeln!(
+            f,
+            "Durbin-Watson statistic: original {}, transformed {}",
+            format_number(self.durbin_watson),
+            format_number(self.transformed_durbin_watson)
+        )
+    }
+}
+
+#[derive(Debug, Builder)]
+pub struct CochraneOrcuttFitter<'a> {
+    pub data: &'a Data,
+    #[builder(default)]
+    pub method: Ar1Method,
+    /// The maximum number of updates of rho.
+    #[builder(default = "100")]
+    pub max_iter: usize,
+    /// The tolerance on the change in rho between updates.
+    #[builder(default = "1e-8")]
+    pub tol: f64,
+}
+
+impl<'a> CochraneOrcuttFitter<'a> {
+    /// Return a new instance of the `CochraneOrcuttFitter` struct for `method` with the default
+    /// controls.
+    pub fn new(data: &'a Data, method: Ar1Method) -> Self {
+        Self {
+            data,
+            method,
+            max_iter: 100,
+            tol: 1e-8,
+        }
+    }
+
+    /// Return the x matrix from the data struct.
+    pub fn x(&self) -> &RealMatrix {
+        &self.data.x
+    }
+
+    /// Return the y matrix from the data struct.
+    pub fn y(&self) -> &RealMatrix {
+        &self.data.y
+    }
+
+    /// Iterate the estimate of rho and the fit to the transformed data until rho settles.
+    ///
+    /// # Errors
+    /// Returns `LmFitterError::MultipleResponses`, `LmFitterError::PriorWeights`,
+    /// `LmFitterError::TooFewObservations` or `LmFitterError::RankDeficient` for unsuitable
+    /// data, and `LmFitterError::NonStationary` if rho leaves (-1, 1) for Prais-Winsten.
+    pub fn ar1(&self) -> Result<Ar1Fit, LmFitterError> {
+        let (x, y) = (self.data.x(), self.data.y());
+        let (n, p) = (x.n_rows(), x.n_cols());
+        if y.n_cols() != 1 {
+            return Err(LmFitterError::MultipleResponses { found: y.n_cols() });
+        }
+        if self.data.weights().is_some() {
+            return Err(LmFitterError::PriorWeights);
+        }
+        if n < p + 3 {
+            return Err(LmFitterError::TooFewObservations {
+                needed: p + 3,
+                found: n,
+            });
+        }
+
+        let ols = QrDecompositionFitter::new(self.data, None).decompose()?;
+        if ols.rank() < p {
+            return Err(LmFitterError::RankDeficient {
+                rank: ols.rank(),
+                columns: p,
+            });
+        }
+        let ols_residuals: Vec<f64> = ols.residuals.values.column(0).to_vec();
+        let (mut rho, mut rho_std_error) = lag_regression(&ols_residuals);
+        let mut iterations = 0;
+        let mut converged = false;
+        while iterations < self.max_iter {
+            let b = self.transformed_fit(rho)?.1.coefficients;
+            let residuals: Vec<f64> = (0..n)
+                .map(|i| {
+                    y.values[[i, 0]]
+                        - (0..p)
+                            .map(|j| x.values[[i, j]] * b.values[[j, 0]])
+                            .sum::<f64>()
+                })
+                .collect();
+            iterations += 1;
+            let (next, next_std_error) = lag_regression(&residuals);
+            let change = (next - rho).abs();
+            (rho, rho_std_error) = (next, next_std_error);
+            if change <= self.tol {
+                converged = true;
+                break;
+            }
+        }
+        // Refit at the final rho, so that the coefficients, rho and its standard error all
+        // describe the same point.
+        let (transformed, fit) = self.transformed_fit(rho)?;
+
+        let coefficients: Vec<f64> = (0..p).map(|j| fit.coefficients.values[[j, 0]]).collect();
+        let residuals: Vec<f64> = (0..n)
+            .map(|i| {
+                y.values[[i, 0]]
+                    - (0..p)
+                        .map(|j| x.values[[i, j]] * coefficients[j])
+                        .sum::<f64>()
+            })
+            .collect();
+        let transformed_residuals: Vec<f64> = fit.residuals.values.column(0).to_vec();
+        let m = transformed_residuals.len();
+        let df_residual = m - p;
+        let rss: f64 = transformed_residuals.iter().map(|r| r * r).sum();
+        let sigma = (rss / df_residual as f64).sqrt();
+        let mut covariance = unscaled_covariance(&fit.qr);
+        covariance.values.mapv_inplace(|v| v * sigma * sigma);
+        let std_errors = (0..p).map(|j| covariance.values[[j, j]].sqrt()).collect();
+
+        let ty = transformed.y.values.column(0);
+        let mean = ty.sum() / m as f64;
+        let tss: f64 = ty.iter().map(|v| (v - mean).powi(2)).sum();
+        let r_squared = 1.0 - rss / tss;
+        let adj_r_squared = 1.0 - (1.0 - r_squared) * (m - 1) as f64 / df_residual as f64;
+        let terms = self.data.terms();
+        let slopes: Vec<usize> = (0..p).filter(|&j| terms.assign[j] != 0).collect();
+        let f_statistic = if slopes.is_empty() || slopes.len() == p {
+            None
+        } else {
+            let mut l = RealMatrix::with_shape(slopes.len(), p);
+            for (row, &j) in slopes.iter().enumerate() {
+                l.values[[row, j]] = 1.0;
+            }
+            wald_statistic(&l, &coefficients, &covariance, &vec![0.0; slopes.len()]).map(|w| {
+                let statistic = w / slopes.len() as f64;
+                let p_value = f_upper_tail(statistic, slopes.len() as f64, df_residual as f64);
+                (statistic, slopes.len(), df_residual, p_value)
+            })
+        };
+
+        Ok(Ar1Fit {
+            method: self.method,
+            names: self.data.column_names().to_vec(),
+            coefficients,
+            std_errors,
+            covariance,
+            rho,
+            rho_std_error,
+            sigma,
+            df_residual,
+            r_squared,
+            adj_r_squared,
+            f_statistic,
+            residuals,
+            durbin_watson: durbin_watson(&ols_residuals),
+            transformed_durbin_watson: durbin_watson(&transformed_residuals),
+            transformed_residuals,
+            iterations,
+            converged,
+        })
+    }
+
+    /// Return the quasi-differenced data for `rho` and its least squares fit.
+    fn transformed_fit(&self, rho: f64) -> Result<(Data, LeastSquaresFit), LmFitterError> {
+        let transformed = self.transform(rho)?;
+        let fit = QrDecompositionFitter::new(&transformed, None).decompose()?;
+        let p = transformed.x.n_cols();
+        if fit.rank() < p {
+            return Err(LmFitterError::RankDeficient {
+                rank: fit.rank(),
+                columns: p,
+            });
+        }
+        Ok((transformed, fit))
+    }
+
+    /// Return the quasi-differenced data for `rho`.
+    fn transform(&self, rho: f64) -> Result<Data, LmFitterError> {
+        let (x, y) = (self.data.x(), self.data.y());
+        let (n, p) = (x.n_rows(), x.n_cols());
+        let mut rows = Vec::with_capacity(n);
+        if self.method == Ar1Method::PraisWinsten {
+            if rho.abs() >= 1.0 {
+                return Err(LmFitterError::NonStationary { rho });
+            }
+            let scale = (1.0 - rho * rho).sqrt();
+            rows.push(
+                (0..=p)
+                    .map(|j| scale * value(x, y, 0, j))
+                    .collect::<Vec<f64>>(),
+            );
+        }
+        for i in 1..n {
+            rows.push(
+                (0..=p)
+                    .map(|j| value(x, y, i, j) - rho * value(x, y, i - 1, j))
+                    .collect(),
+            );
+        }
+        let m = rows.len();
+        let tx = rows.iter().flat_map(|row| row[..p].to_vec()).collect();
+        let ty = rows.iter().map(|row| row[p]).collect();
+        Ok(Data::new(
+            RealMatrix::from_vec(tx, m, Some(p)),
+            RealMatrix::from_vec(ty, m, None),
+        )
+        .with_column_names(self.data.column_names().to_vec())
+        .with_terms(self.data.terms()))
+    }
+}
+
+impl<'a> FitModel for CochraneOrcuttFitter<'a> {
+    /// Fit the model and return the coefficients as a single column.
+    fn fit(&self) -> Result<RealMatrix, LmFitterError> {
+        let coefficients = self.ar1()?.coefficients;
+        let p = coefficients.len();
+        Ok(RealMatrix::from_vec(coefficients, p, None))
+    }
+
+    fn x(&self) -> &RealMatrix {
+        self.data.x()
+    }
+
+    fn y(&self) -> &RealMatrix {
+        self.data.y()
+    }
+}
+
+/// Return column `j` of x in row `i`, or y for `j` = p.
+fn value(x: &RealMatrix, y: &RealMatrix, i: usize, j: usize) -> f64 {
+    if j < x.n_cols() {
+        x.values[[i, j]]
+    } else {
+        y.values[[i, 0]]
+    }
+}
+
+/// Regress each residual on the one before it without an intercept, returning the slope rho
+/// and its standard error.
+fn lag_regression(residuals: &[f64]) -> (f64, f64) {
+    let n = residuals.len();
+    let cross: f64 = (1..n).map(|t| residuals[t] * residuals[t - 1]).sum();
+    let lagged: f64 = residuals[..n - 1].iter().map(|e| e * e).sum();
+    let rho = cross / lagged;
+    let rss: f64 = (1..n)
+        .map(|t| (residuals[t] - rho * residuals[t - 1]).powi(2))
+        .sum();
+    (rho, (rss / (n - 2) as f64 / lagged).sqrt())
+}
+
+/// Return the Durbin-Watson statistic sum (e_t - e_{t-1})^2 / sum e_t^2.
+fn durbin_watson(residuals: &[f64]) -> f64 {
+    let differences: f64 = residuals
+        .windows(2)
+        .map(|pair| (pair[1] - pair[0]).powi(2))
+        .sum();
+    differences / residuals.iter().map(|e| e * e).sum::<f64>()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fitters::fit::LinearModelFitter;
+
+    /// y = 2 + 0.3 t plus AR(1) errors with rho = 0.7, driven by a deterministic sequence.
+    fn series() -> Data {
+        let n = 60;
+        let x: Vec<f64> = (0..n).flat_map(|i| [1.0, i as f64 / 5.0]).collect();
+        let mut error = 0.0;
+        let y: Vec<f64> = (0..n)
+            .map(|i| {
+                error = 0.7 * error + ((i * i) as f64 * 0.37).sin();
+                2.0 + 0.06 * i as f64 + error
+            })
+            .collect();
+        Data::new(
+            RealMatrix::from_vec(x, n, Some(2)),
+            RealMatrix::from_vec(y, n, None),
+        )
+        .with_column_names(vec!["(Intercept)".to_string(), "t".to_string()])
+    }
+
+    #[test]
+    fn test_converged_fit_is_a_fixed_point() {
+        let data = series();
+        for method in [Ar1Method::CochraneOrcutt, Ar1Method::PraisWinsten] {
+            let fitter = CochraneOrcuttFitter::new(&data, method);
+            let fit = fitter.ar1().unwrap();
+            assert!(fit.converged);
+            assert!(fit.rho > 0.3 && fit.rho < 0.95);
+
+            // Refitting the data transformed at the final rho gives the same coefficients, and
+            // their residuals give back rho.
+            let transformed = fitter.transform(fit.rho).unwrap();
+            let refit = QrDecompositionFitter::new(&transformed, None)
+                .decompose()
+                .unwrap();
+            for j in 0..2 {
+                assert!((refit.coefficients.values[[j, 0]] - fit.coefficients[j]).abs() < 1e-10);
+            }
+            let (rho, rho_std_error) = lag_regression(&fit.residuals);
+            assert!((rho - fit.rho).abs() < 1e-6);
+            assert!((rho_std_error - fit.rho_std_error).abs() < 1e-6);
+            let expected_rows = match method {
+                Ar1Method::CochraneOrcutt => 59,
+                Ar1Method::PraisWinsten => 60,
+            };
+            assert_eq!(fit.transformed_residuals.len(), expected_rows);
+            assert_eq!(fit.df_residual, expected_rows - 2);
+        }
+    }
+
+    #[test]
+    fn test_transformation_removes_serial_correlation() {
+        let data = series();
+        let fitter = CochraneOrcuttFitter::new(&data, Ar1Method::PraisWinsten);
+        let fit = fitter.ar1().unwrap();
+        assert!(fit.durbin_watson < 1.5);
+        assert!((fit.transformed_durbin_watson - 2.0).abs() < (fit.durbin_watson - 2.0).abs());
+        let (statistic, df1, df2, _) = fit.f_statistic.unwrap();
+        let t = fit.coefficients[1] / fit.std_errors[1];
+        assert!((statistic - t * t).abs() < 1e-8);
+        assert_eq!((df1, df2), (1, 58));
+        assert!(fit.to_string().contains("Prais-Winsten estimation"));
+
+        let coefficients = fit.coefficients.clone();
+        let fitted = LinearModelFitter::CochraneOrcutt(fitter).fit().unwrap();
+        assert_eq!(fitted.values[[1, 0]], coefficients[1]);
+        let weighted = data.clone().with_weights(vec![1.0; 60]);
+        assert!(matches!(
+            CochraneOrcuttFitter::new(&weighted, Ar1Method::CochraneOrcutt).ar1(),
+            Err(LmFitterError::PriorWeights)
+        ));
+    }
+}
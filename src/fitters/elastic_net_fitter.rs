@@ -0,0 +1,764 @@
+//! This module contains the lasso and elastic-net fitter, which implements the `FitModel` trait
+//! for the `LinearModelFitter` enum.
+//!
+//! The fitter minimises
+//!
+//! (1 / 2) sum v (y - b0 - x b)^2 + λ sum pf_j (α |b_j| + (1 - α) / 2 b_j^2)
+//!
+//! with normalised weights v (summing to one) by cyclic coordinate descent on the standardised
+//! columns, as in `glmnet`. The penalties are visited in decreasing order along a log-spaced
+//! path, each fit starting from the previous one. The sequential strong rule screens out columns
+//! that are very likely to stay at zero, and the Karush-Kuhn-Tucker conditions are checked on
+//! the screened columns after convergence, so the screening never changes the solution.
+
+// src/fitters/elastic_net_fitter.rs
+
+use super::fit::FitModel;
+use super::standardization::Standardization;
+use crate::anova::format_number;
+use crate::cv::CrossValidation;
+use crate::errors::LmFitterError;
+use crate::types::{Data, RealMatrix};
+use derive_builder::Builder;
+use std::fmt;
+
+/// The number of penalties in the default path.
+const DEFAULT_PATH_LENGTH: usize = 100;
+
+/// The fraction of deviance explained beyond which the default path stops early.
+const MAX_DEVIANCE_RATIO: f64 = 0.999;
+
+/// An enum representing the rules used to choose the penalty from a cross-validation curve.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum LambdaRule {
+    /// The penalty with the lowest cross-validated error, `lambda.min`.
+    Min,
+    /// The largest penalty whose error is within one standard error of the lowest,
+    /// `lambda.1se`.
+    #[default]
+    OneStandardError,
+}
+
+/// The cross-validated error along the path, like the output of `cv.glmnet`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CrossValidationCurve {
+    /// The mean of the fold mean squared errors for each penalty.
+    pub mean: Vec<f64>,
+    /// The standard error of `mean` for each penalty.
+    pub std_error: Vec<f64>,
+    /// The index of the penalty with the lowest error.
+    pub index_min: usize,
+    /// The index of the largest penalty within one standard error of the lowest error.
+    pub index_1se: usize,
+}
+
+/// The elastic-net fits along a path of penalties.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ElasticNetPath {
+    /// The penalties, in decreasing order.
+    pub lambdas: Vec<f64>,
+    /// The coefficients on the original scale, with one column per penalty.
+    pub coefficients: RealMatrix,
+    /// The number of non-zero coefficients of each fit, not counting the intercept.
+    pub df: Vec<usize>,
+    /// The fraction of the null deviance explained by each fit.
+    pub dev_ratio: Vec<f64>,
+    /// Whether coordinate descent converged within `max_iter` passes for each fit.
+    pub converged: Vec<bool>,
+    /// The cross-validated error along the path, if it was computed.
+    pub cv: Option<CrossValidationCurve>,
+    /// The index of the selected penalty: the one chosen by cross-validation, or the smallest.
+    pub selected: usize,
+}
+
+impl ElasticNetPath {
+    /// Return the selected penalty.
+    pub fn lambda(&self) -> f64 {
+        self.lambdas[self.selected]
+    }
+
+    /// Return the penalty with the lowest cross-validated error, if it was computed.
+    pub fn lambda_min(&self) -> Option<f64> {
+        self.cv.as_ref().map(|cv| self.lambdas[cv.index_min])
+    }
+
+    /// Return the largest penalty within one standard error of the lowest cross-validated error,
+    /// if it was computed.
+    pub fn lambda_1se(&self) -> Option<f64> {
+        self.cv.as_ref().map(|cv| self.lambdas[cv.index_1se])
+    }
+
+    /// Return the coefficients of the selected fit as a column vector.
+    pub fn selected_coefficients(&self) -> RealMatrix {
+        let column = self.coefficients.values.column(self.selected).to_vec();
+        RealMatrix::from_vec(column, self.coefficients.n_rows(), None)
+    }
+}
+
+/// Print the path like R's `print.glmnet`.
+impl fmt::Display for ElasticNetPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let rows: Vec<[String; 3]> = (0..self.lambdas.len())
+            .map(|k| {
+                [
+                    self.df[k].to_string(),
+                    format!("{:.2}", 100.0 * self.dev_ratio[k]),
+                    format_number(self.lambdas[k]),
+                ]
+            })
+            .collect();
+        let header = ["Df", "%Dev", "Lambda"];
+        let widths: Vec<usize> = (0..3)
+            .map(|j| {
+                rows.iter()
+                    .map(|r| r[j].len())
+                    .fold(header[j].len(), usize::max)
+            })
+            .collect();
+        let label_width = rows.len().to_string().len();
+        write!(f, "{:label_width$}", "")?;
+        for (name, width) in header.iter().zip(&widths) {
+            write!(f, " {name:>width$}")?;
+        }
+        writeln!(f)?;
+        for (k, row) in rows.iter().enumerate() {
+            write!(f, "{:>label_width$}", k + 1)?;
+            for (cell, width) in row.iter().zip(&widths) {
+                write!(f, " {cell:>width$}")?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Builder)]
+pub struct ElasticNetFitter<'a> {
+    pub data: &'a Data,
+    /// The mixing parameter: 1 for the lasso, 0 for ridge regression.
+    #[builder(default = "1.0")]
+    pub alpha: f64,
+    /// The penalties to fit. If `None`, a log-spaced path is derived from the data.
+    #[builder(default)]
+    pub lambdas: Option<Vec<f64>>,
+    /// The ratio of the smallest to the largest penalty of the default path. If `None`, 1e-4 is
+    /// used when there are more observations than columns and 0.01 otherwise.
+    #[builder(default)]
+    pub lambda_min_ratio: Option<f64>,
+    /// The relative penalty of each column of x. The intercept entry is ignored, a zero factor
+    /// leaves a column unpenalised and an infinite factor excludes it.
+    #[builder(default)]
+    pub penalty_factors: Option<Vec<f64>>,
+    /// The lower bound of each coefficient, which must not be positive.
+    #[builder(default)]
+    pub lower_limits: Option<Vec<f64>>,
+    /// The upper bound of each coefficient, which must not be negative.
+    #[builder(default)]
+    pub upper_limits: Option<Vec<f64>>,
+    /// The cross-validation design used to choose the penalty, if any.
+    #[builder(default)]
+    pub cv: Option<CrossValidation>,
+    #[builder(default)]
+    pub rule: LambdaRule,
+    /// The convergence threshold on the largest squared change of a standardised coefficient.
+    #[builder(default = "1e-10")]
+    pub tol: f64,
+    /// The maximum number of passes over the columns for each penalty.
+    #[builder(default = "100_000")]
+    pub max_iter: usize,
+}
+
+impl<'a> ElasticNetFitter<'a> {
+    /// Return a new instance of the `ElasticNetFitter` struct with the default path, no penalty
+    /// factors or limits, and no cross-validation. Use `ElasticNetFitterBuilder` to set the
+    /// other options.
+    pub fn new(data: &'a Data, alpha: f64) -> Self {
+        Self {
+            data,
+            alpha,
+            lambdas: None,
+            lambda_min_ratio: None,
+            penalty_factors: None,
+            lower_limits: None,
+            upper_limits: None,
+            cv: None,
+            rule: LambdaRule::default(),
+            tol: 1e-10,
+            max_iter: 100_000,
+        }
+    }
+
+    /// Return the x matrix from the data struct.
+    pub fn x(&self) -> &RealMatrix {
+        &self.data.x
+    }
+
+    /// Return the y matrix from the data struct.
+    pub fn y(&self) -> &RealMatrix {
+        &self.data.y
+    }
+
+    /// Fit the path of penalties, choosing one by cross-validation if a design was given.
+    ///
+    /// # Errors
+    /// Returns `LmFitterError::MultipleResponses` if the data has more than one response,
+    /// `LmFitterError::InvalidAlpha`, `LmFitterError::NoPenalties`,
+    /// `LmFitterError::InvalidPenalty`, `LmFitterError::InvalidLimits` or
+    /// `LmFitterError::LengthMismatch` for invalid options, and
+    /// `LmFitterError::CrossValidation` if the folds cannot be drawn.
+    pub fn path(&self) -> Result<ElasticNetPath, LmFitterError> {
+        if !(0.0..=1.0).contains(&self.alpha) {
+            return Err(LmFitterError::InvalidAlpha { alpha: self.alpha });
+        }
+        let problem = Problem::new(self, self.data)?;
+        let (lambdas, early_stop) = match &self.lambdas {
+            Some(lambdas) => {
+                if lambdas.is_empty() {
+                    return Err(LmFitterError::NoPenalties);
+                }
+                if let Some(&lambda) = lambdas.iter().find(|l| !(l.is_finite() && **l >= 0.0)) {
+                    return Err(LmFitterError::InvalidPenalty { lambda });
+                }
+                let mut lambdas = lambdas.clone();
+                lambdas.sort_by(|a, b| b.total_cmp(a));
+                (lambdas, false)
+            }
+            None => (self.default_lambdas(&problem), true),
+        };
+
+        let fits = problem.solve_path(&lambdas, early_stop);
+        let lambdas = lambdas[..fits.len()].to_vec();
+        let mut coefficients = RealMatrix::with_shape(self.data.x().n_cols(), fits.len());
+        for (k, fit) in fits.iter().enumerate() {
+            let b = problem
+                .standardization
+                .original_coefficients(&fit.coefficients);
+            coefficients
+                .values
+                .column_mut(k)
+                .assign(&ndarray::Array1::from(b));
+        }
+
+        let cv = match &self.cv {
+            Some(cv) => Some(self.cross_validate(cv, &lambdas)?),
+            None => None,
+        };
+        let selected = match (&cv, self.rule) {
+            (Some(cv), LambdaRule::Min) => cv.index_min,
+            (Some(cv), LambdaRule::OneStandardError) => cv.index_1se,
+            (None, _) => lambdas.len() - 1,
+        };
+
+        Ok(ElasticNetPath {
+            df: fits
+                .iter()
+                .map(|fit| fit.coefficients.iter().filter(|&&b| b != 0.0).count())
+                .collect(),
+            dev_ratio: fits.iter().map(|fit| fit.dev_ratio).collect(),
+            converged: fits.iter().map(|fit| fit.converged).collect(),
+            lambdas,
+            coefficients,
+            cv,
+            selected,
+        })
+    }
+
+    /// Return the default path, from the smallest penalty that sets every penalised coefficient
+    /// to zero down to `lambda_min_ratio` times that.
+    fn default_lambdas(&self, problem: &Problem) -> Vec<f64> {
+        let largest = problem.lambda_max(self.alpha).max(f64::MIN_POSITIVE);
+        let ratio = self.lambda_min_ratio.unwrap_or_else(|| {
+            if self.data.x().n_rows() > self.data.x().n_cols() {
+                1e-4
+            } else {
+                0.01
+            }
+        });
+        let (start, end) = (largest.ln(), (largest * ratio).ln());
+        (0..DEFAULT_PATH_LENGTH)
+            .map(|k| (start + (end - start) * k as f64 / (DEFAULT_PATH_LENGTH - 1) as f64).exp())
+            .collect()
+    }
+
+    /// Compute the cross-validated mean squared error of every penalty, refitting the path on
+    /// the training folds.
+    fn cross_validate(
+        &self,
+        cv: &CrossValidation,
+        lambdas: &[f64],
+    ) -> Result<CrossValidationCurve, LmFitterError> {
+        let n = self.data.x().n_rows();
+        let assignments = cv
+            .fold_assignments(n)
+            .map_err(|e| LmFitterError::CrossValidation(Box::new(e)))?;
+        let y = self.data.y().values.column(0);
+
+        let mut errors: Vec<Vec<f64>> = Vec::new();
+        for assignment in &assignments {
+            for fold in 0..cv.folds {
+                let (test, train): (Vec<usize>, Vec<usize>) =
+                    (0..n).partition(|&i| assignment[i] == fold);
+                let training = self.data.select_rows(&train);
+                let problem = Problem::new(self, &training)?;
+                let fold_errors = problem
+                    .solve_path(lambdas, false)
+                    .iter()
+                    .map(|fit| {
+                        let b = problem
+                            .standardization
+                            .original_coefficients(&fit.coefficients);
+                        test.iter()
+                            .map(|&i| {
+                                let row = self.data.x().values.row(i);
+                                let prediction: f64 = row.iter().zip(&b).map(|(x, b)| x * b).sum();
+                                (y[i] - prediction).powi(2)
+                            })
+                            .sum::<f64>()
+                            / test.len() as f64
+                    })
+                    .collect();
+                errors.push(fold_errors);
+            }
+        }
+
+        let k = errors.len() as f64;
+        let mean: Vec<f64> = (0..lambdas.len())
+            .map(|l| errors.iter().map(|e| e[l]).sum::<f64>() / k)
+            .collect();
+        let std_error: Vec<f64> = (0..lambdas.len())
+            .map(|l| {
+                let variance =
+                    errors.iter().map(|e| (e[l] - mean[l]).powi(2)).sum::<f64>() / (k - 1.0);
+                (variance / k).sqrt()
+            })
+            .collect();
+        let index_min = (0..lambdas.len())
+            .min_by(|&a, &b| mean[a].total_cmp(&mean[b]))
+            .unwrap_or(0);
+        let threshold = mean[index_min] + std_error[index_min];
+        let index_1se = (0..=index_min)
+            .find(|&l| mean[l] <= threshold)
+            .unwrap_or(index_min);
+
+        Ok(CrossValidationCurve {
+            mean,
+            std_error,
+            index_min,
+            index_1se,
+        })
+    }
+}
+
+impl<'a> FitModel for ElasticNetFitter<'a> {
+    /// Fit the path and return the coefficients for the selected penalty, on the original scale
+    /// of x.
+    fn fit(&self) -> Result<RealMatrix, LmFitterError> {
+        Ok(self.path()?.selected_coefficients())
+    }
+
+    fn x(&self) -> &RealMatrix {
+        self.data.x()
+    }
+
+    fn y(&self) -> &RealMatrix {
+        self.data.y()
+    }
+}
+
+/// The fit for one penalty, on the standardised scale.
+struct Fit {
+    coefficients: Vec<f64>,
+    dev_ratio: f64,
+    converged: bool,
+}
+
+/// The standardised coordinate descent problem.
+struct Problem {
+    standardization: Standardization,
+    /// The standardised columns, one vector per column.
+    z: Vec<Vec<f64>>,
+    /// The centred response.
+    response: Vec<f64>,
+    /// The weights, normalised to sum to one.
+    v: Vec<f64>,
+    alpha: f64,
+    penalty_factors: Vec<f64>,
+    /// The bounds of the standardised coefficients.
+    lower: Vec<f64>,
+    upper: Vec<f64>,
+    tol: f64,
+    max_iter: usize,
+}
+
+impl Problem {
+    fn new(fitter: &ElasticNetFitter, data: &Data) -> Result<Self, LmFitterError> {
+        let standardization = Standardization::new(data)?;
+        let p = data.x().n_cols();
+        let per_column = |name: &str, values: &Option<Vec<f64>>, default: f64| match values {
+            Some(values) if values.len() != p => Err(LmFitterError::LengthMismatch {
+                name: name.to_string(),
+                expected: p,
+                found: values.len(),
+            }),
+            Some(values) => Ok(standardization
+                .columns
+                .iter()
+                .map(|&j| values[j])
+                .collect::<Vec<f64>>()),
+            None => Ok(vec![default; standardization.columns.len()]),
+        };
+
+        // Rescale the finite penalty factors to sum to their number, like glmnet.
+        let mut penalty_factors = per_column("penalty_factors", &fitter.penalty_factors, 1.0)?;
+        if let Some(&factor) = penalty_factors.iter().find(|f| f.is_nan() || **f < 0.0) {
+            return Err(LmFitterError::InvalidPenalty { lambda: factor });
+        }
+        let finite: Vec<f64> = penalty_factors
+            .iter()
+            .copied()
+            .filter(|f| f.is_finite())
+            .collect();
+        let sum: f64 = finite.iter().sum();
+        if sum > 0.0 {
+            let scale = finite.len() as f64 / sum;
+            penalty_factors.iter_mut().for_each(|f| *f *= scale);
+        }
+
+        let lower = per_column("lower_limits", &fitter.lower_limits, f64::NEG_INFINITY)?;
+        let upper = per_column("upper_limits", &fitter.upper_limits, f64::INFINITY)?;
+        if let Some(a) = (0..lower.len()).find(|&a| lower[a] > 0.0 || upper[a] < 0.0) {
+            return Err(LmFitterError::InvalidLimits {
+                column: standardization.columns[a],
+            });
+        }
+        let scales = &standardization.scales;
+        let lower = lower.iter().zip(scales).map(|(l, s)| l * s).collect();
+        let upper = upper.iter().zip(scales).map(|(u, s)| u * s).collect();
+
+        let z_matrix = standardization.x(data.x(), false);
+        let z = (0..z_matrix.n_cols())
+            .map(|a| z_matrix.values.column(a).to_vec())
+            .collect();
+        let response = standardization.y(data.y(), false);
+        let v = standardization
+            .weights
+            .iter()
+            .map(|w| w / standardization.total_weight)
+            .collect();
+
+        Ok(Problem {
+            standardization,
+            z,
+            response,
+            v,
+            alpha: fitter.alpha,
+            penalty_factors,
+            lower,
+            upper,
+            tol: fitter.tol,
+            max_iter: fitter.max_iter,
+        })
+    }
+
+    /// Return sum v z_a r, the gradient of the fit for column `a` at residuals `r`.
+    fn gradient(&self, a: usize, residuals: &[f64]) -> f64 {
+        self.z[a]
+            .iter()
+            .zip(residuals)
+            .zip(&self.v)
+            .map(|((z, r), v)| v * z * r)
+            .sum()
+    }
+
+    /// Return the smallest penalty at which every penalised coefficient is zero. With α = 0 the
+    /// path starts where it would for α = 0.001, as in glmnet.
+    fn lambda_max(&self, alpha: f64) -> f64 {
+        let residuals = self.unpenalised_residuals();
+        (0..self.z.len())
+            .filter(|&a| self.penalty_factors[a] > 0.0 && self.penalty_factors[a].is_finite())
+            .map(|a| {
+                self.gradient(a, &residuals).abs() / (alpha.max(1e-3) * self.penalty_factors[a])
+            })
+            .fold(0.0, f64::max)
+    }
+
+    /// Return the residuals after fitting the unpenalised columns alone.
+    fn unpenalised_residuals(&self) -> Vec<f64> {
+        let mut coefficients = vec![0.0; self.z.len()];
+        let mut residuals = self.response.clone();
+        let free: Vec<usize> = (0..self.z.len())
+            .filter(|&a| self.penalty_factors[a] == 0.0)
+            .collect();
+        self.descend(&free, f64::INFINITY, &mut coefficients, &mut residuals);
+        residuals
+    }
+
+    /// Return the new value of coefficient `a` given its gradient.
+    fn update(&self, a: usize, gradient: f64, coefficient: f64, lambda: f64) -> f64 {
+        let factor = self.penalty_factors[a];
+        if factor.is_infinite() {
+            return 0.0;
+        }
+        let u = gradient + coefficient;
+        let threshold = if factor == 0.0 {
+            0.0
+        } else {
+            lambda * self.alpha * factor
+        };
+        let shrink = if factor == 0.0 {
+            1.0
+        } else {
+            1.0 + lambda * (1.0 - self.alpha) * factor
+        };
+        let value = u.signum() * (u.abs() - threshold).max(0.0) / shrink;
+        value.clamp(self.lower[a], self.upper[a])
+    }
+
+    /// Run coordinate descent over the columns `active` until the largest squared change of a
+    /// coefficient falls below the tolerance. Returns whether it did so within `max_iter`
+    /// passes.
+    fn descend(
+        &self,
+        active: &[usize],
+        lambda: f64,
+        coefficients: &mut [f64],
+        residuals: &mut [f64],
+    ) -> bool {
+        for _ in 0..self.max_iter {
+            let mut largest_change: f64 = 0.0;
+            for &a in active {
+                let old = coefficients[a];
+                let new = self.update(a, self.gradient(a, residuals), old, lambda);
+                if new != old {
+                    for (r, z) in residuals.iter_mut().zip(&self.z[a]) {
+                        *r -= z * (new - old);
+                    }
+                    coefficients[a] = new;
+                    largest_change = largest_change.max((new - old).powi(2));
+                }
+            }
+            if largest_change < self.tol {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Fit every penalty in `lambdas` with warm starts. With `early_stop`, the path ends once
+    /// the fraction of deviance explained exceeds `MAX_DEVIANCE_RATIO`.
+    fn solve_path(&self, lambdas: &[f64], early_stop: bool) -> Vec<Fit> {
+        let m = self.z.len();
+        let null_deviance: f64 = self
+            .response
+            .iter()
+            .zip(&self.v)
+            .map(|(r, v)| v * r * r)
+            .sum();
+        let mut coefficients = vec![0.0; m];
+        let mut residuals = self.response.clone();
+        let mut previous_lambda = self.lambda_max(self.alpha);
+        let mut fits = Vec::new();
+
+        for &lambda in lambdas {
+            // The sequential strong rule keeps a column if its gradient at the previous penalty
+            // exceeds α (2λ - λ_prev) times its penalty factor.
+            let threshold = self.alpha * (2.0 * lambda - previous_lambda);
+            let mut strong: Vec<usize> = (0..m)
+                .filter(|&a| {
+                    coefficients[a] != 0.0
+                        || self.gradient(a, &residuals).abs() >= threshold * self.penalty_factors[a]
+                })
+                .collect();
+
+            let converged = loop {
+                let converged = self.descend(&strong, lambda, &mut coefficients, &mut residuals);
+                let violations: Vec<usize> = (0..m)
+                    .filter(|a| !strong.contains(a))
+                    .filter(|&a| self.update(a, self.gradient(a, &residuals), 0.0, lambda) != 0.0)
+                    .collect();
+                if violations.is_empty() {
+                    break converged;
+                }
+                strong.extend(violations);
+                strong.sort_unstable();
+            };
+
+            let deviance: f64 = residuals.iter().zip(&self.v).map(|(r, v)| v * r * r).sum();
+            let dev_ratio = if null_deviance > 0.0 {
+                1.0 - deviance / null_deviance
+            } else {
+                0.0
+            };
+            fits.push(Fit {
+                coefficients: coefficients.clone(),
+                dev_ratio,
+                converged,
+            });
+            previous_lambda = lambda;
+            if early_stop && dev_ratio > MAX_DEVIANCE_RATIO {
+                break;
+            }
+        }
+        fits
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fitters::fit::LinearModelFitter;
+    use crate::fitters::ridge_fitter::{LambdaSelection, RidgeFitter};
+
+    fn data() -> Data {
+        let n = 30;
+        let x: Vec<f64> = (0..n)
+            .flat_map(|i| {
+                let t = i as f64;
+                [
+                    1.0,
+                    (t * 0.9).sin(),
+                    (t * 1.7).cos(),
+                    ((i * i) % 7) as f64,
+                    t / 10.0,
+                ]
+            })
+            .collect();
+        let y: Vec<f64> = (0..n)
+            .map(|i| {
+                let t = i as f64;
+                1.0 + 3.0 * (t * 0.9).sin() - 2.0 * (t * 1.7).cos() + 0.3 * (t * 2.3).sin()
+            })
+            .collect();
+        Data::new(
+            RealMatrix::from_vec(x, n, Some(5)),
+            RealMatrix::from_vec(y, n, None),
+        )
+        .with_column_names(
+            ["(Intercept)", "a", "b", "c", "d"]
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_alpha_zero_matches_ridge() {
+        let data = data();
+        let lambda = 0.2;
+        let fitter = ElasticNetFitterBuilder::default()
+            .data(&data)
+            .alpha(0.0)
+            .lambdas(Some(vec![lambda]))
+            .build()
+            .unwrap();
+        let elastic_net = fitter.fit().unwrap();
+
+        // The ridge objective is n times larger, so its penalty is n times larger too.
+        let ridge = RidgeFitter::new(&data, Some(vec![30.0 * lambda]), LambdaSelection::Gcv)
+            .fit()
+            .unwrap();
+        for j in 0..5 {
+            assert!((elastic_net.values[[j, 0]] - ridge.values[[j, 0]]).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn test_lasso_satisfies_the_kkt_conditions() {
+        let data = data();
+        let fitter = ElasticNetFitter::new(&data, 1.0);
+        let path = fitter.path().unwrap();
+        assert_eq!(path.df[0], 0);
+        assert!(path.dev_ratio.windows(2).all(|w| w[0] <= w[1] + 1e-12));
+        assert!(path.converged.iter().all(|&c| c));
+
+        let k = 20;
+        let lambda = path.lambdas[k];
+        let problem = Problem::new(&fitter, &data).unwrap();
+        let fits = problem.solve_path(&path.lambdas[..=k], false);
+        let b = &fits[k].coefficients;
+        let mut residuals = problem.response.clone();
+        for (a, column) in problem.z.iter().enumerate() {
+            for (r, z) in residuals.iter_mut().zip(column) {
+                *r -= z * b[a];
+            }
+        }
+        for (a, &b) in b.iter().enumerate() {
+            let gradient = problem.gradient(a, &residuals);
+            if b == 0.0 {
+                assert!(gradient.abs() <= lambda + 1e-6);
+            } else {
+                assert!((gradient - lambda * b.signum()).abs() < 1e-6);
+            }
+        }
+    }
+
+    #[test]
+    fn test_path_reports_fits_that_stop_at_max_iter() {
+        let data = data();
+        let fitter = ElasticNetFitterBuilder::default()
+            .data(&data)
+            .alpha(1.0)
+            .max_iter(1)
+            .build()
+            .unwrap();
+        let path = fitter.path().unwrap();
+        assert_eq!(path.converged.len(), path.lambdas.len());
+        assert!(path.converged.iter().any(|&c| !c));
+    }
+
+    #[test]
+    fn test_penalty_factors_and_limits() {
+        let data = data();
+        let fitter = ElasticNetFitterBuilder::default()
+            .data(&data)
+            .penalty_factors(Some(vec![1.0, 1.0, 1.0, 1.0, 0.0]))
+            .upper_limits(Some(vec![0.0, f64::INFINITY, 0.0, f64::INFINITY, 0.5]))
+            .build()
+            .unwrap();
+        let path = fitter.path().unwrap();
+
+        // The unpenalised column d is in every model; b is held at or below zero and d at or
+        // below 0.5.
+        for k in 0..path.lambdas.len() {
+            assert!(path.coefficients.values[[4, k]] != 0.0);
+            assert!(path.coefficients.values[[4, k]] <= 0.5 + 1e-12);
+            assert!(path.coefficients.values[[2, k]] <= 0.0);
+        }
+        assert!(matches!(
+            ElasticNetFitterBuilder::default()
+                .data(&data)
+                .lower_limits(Some(vec![0.0, 1.0, 0.0, 0.0, 0.0]))
+                .build()
+                .unwrap()
+                .path(),
+            Err(LmFitterError::InvalidLimits { column: 1 })
+        ));
+        assert!(matches!(
+            ElasticNetFitterBuilder::default()
+                .data(&data)
+                .lambdas(Some(vec![]))
+                .build()
+                .unwrap()
+                .path(),
+            Err(LmFitterError::NoPenalties)
+        ));
+    }
+
+    #[test]
+    fn test_cross_validation_chooses_lambda_min_and_1se() {
+        let data = data();
+        let fitter = ElasticNetFitterBuilder::default()
+            .data(&data)
+            .alpha(0.5)
+            .cv(Some(CrossValidation::new(5).unwrap().with_seed(11)))
+            .build()
+            .unwrap();
+        let path = fitter.path().unwrap();
+
+        assert!(path.lambda_1se().unwrap() >= path.lambda_min().unwrap());
+        assert_eq!(path.lambda(), path.lambda_1se().unwrap());
+        let coefficients = LinearModelFitter::ElasticNet(fitter).fit().unwrap();
+        assert_eq!(coefficients, path.selected_coefficients());
+        assert!(path.to_string().starts_with("    Df"));
+    }
+}
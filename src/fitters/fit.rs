@@ -5,8 +5,26 @@
 
 // src/fitters/fit.rs
 
+use super::cochrane_orcutt_fitter::CochraneOrcuttFitter;
+use super::elastic_net_fitter::ElasticNetFitter;
+use super::fixed_effects_fitter::FixedEffectsFitter;
+use super::glm_fitter::GlmFitter;
+use super::glm_nb_fitter::GlmNbFitter;
+use super::gls_fitter::GlsFitter;
+use super::iv_fitter::IvFitter;
+use super::lmer_fitter::LmerFitter;
+use super::lts_fitter::LtsFitter;
+use super::mm_fitter::MmFitter;
+use super::multinomial_fitter::MultinomialFitter;
+use super::nls_fitter::NlsFitter;
+use super::ordinal_fitter::OrdinalFitter;
+use super::panel_fitter::PanelFitter;
 use super::qr_decomposition_fitter::QrDecompositionFitter;
+use super::quantile_regression_fitter::QuantileRegressionFitter;
+use super::ridge_fitter::RidgeFitter;
+use super::rlm_fitter::RlmFitter;
 use crate::errors::LmFitterError;
+use crate::types::Tolerance;
 use crate::RealMatrix;
 
 /// A trait for fitting a linear regression model to a dataset.
@@ -23,6 +41,12 @@ pub trait FitModel {
 
     /// Get the y matrix.
     fn y(&self) -> &RealMatrix;
+
+    /// Get the tolerance used to determine the rank of x. Fitters without a rank tolerance use
+    /// the default.
+    fn tolerance(&self) -> Tolerance {
+        Tolerance::default()
+    }
 }
 
 /// An enum representing the available strategies for fitting a linear model to a dataset.
@@ -30,24 +54,116 @@ pub trait FitModel {
 pub enum LinearModelFitter<'a> {
     /// Fit the linear model using the QR decomposition method.
     QrDecomposition(QrDecompositionFitter<'a>),
+    /// Fit the linear model by ridge regression, choosing the penalty from a path.
+    Ridge(RidgeFitter<'a>),
+    /// Fit the linear model by the lasso or elastic net along a path of penalties.
+    ElasticNet(ElasticNetFitter<'a>),
+    /// Fit the linear model by robust M-estimation with iteratively reweighted least squares.
+    Rlm(RlmFitter<'a>),
+    /// Fit the linear model by least trimmed squares with the FAST-LTS algorithm.
+    Lts(LtsFitter<'a>),
+    /// Fit the linear model by MM-estimation from an S-estimator start.
+    Mm(MmFitter<'a>),
+    /// Fit the linear model at a quantile of the response by minimising the check loss.
+    QuantileRegression(QuantileRegressionFitter<'a>),
+    /// Fit a generalized linear model by iteratively reweighted least squares.
+    Glm(GlmFitter<'a>),
+    /// Fit a negative binomial regression, alternating IRLS for the mean model with theta.
+    GlmNb(GlmNbFitter<'a>),
+    /// Fit a baseline-category multinomial logit model by Newton-Raphson.
+    Multinomial(MultinomialFitter<'a>),
+    /// Fit a cumulative-link ordinal regression model by Newton-Raphson.
+    Ordinal(OrdinalFitter<'a>),
+    /// Fit a linear model with correlated or heteroskedastic errors by generalized least squares.
+    Gls(GlsFitter<'a>),
+    /// Fit a regression with AR(1) errors by the Cochrane-Orcutt or Prais-Winsten procedure.
+    CochraneOrcutt(CochraneOrcuttFitter<'a>),
+    /// Fit a linear mixed-effects model by penalized least squares and REML or ML.
+    Lmer(LmerFitter<'a>),
+    /// Instrumental-variables regression by 2SLS, LIML or GMM.
+    Iv(IvFitter<'a>),
+    /// Least squares with high-dimensional fixed effects absorbed by alternating projections.
+    FixedEffects(FixedEffectsFitter<'a>),
+    /// Panel data models: pooled, within, between, first-difference and random effects.
+    Panel(PanelFitter<'a>),
+    /// Nonlinear least squares by Gauss-Newton, Levenberg-Marquardt or bounded port iterations.
+    Nls(NlsFitter<'a>),
 }
 
 impl<'a> FitModel for LinearModelFitter<'a> {
     fn fit(&self) -> Result<RealMatrix, LmFitterError> {
         match self {
             LinearModelFitter::QrDecomposition(fitter) => fitter.fit(),
+            LinearModelFitter::Ridge(fitter) => fitter.fit(),
+            LinearModelFitter::ElasticNet(fitter) => fitter.fit(),
+            LinearModelFitter::Rlm(fitter) => fitter.fit(),
+            LinearModelFitter::Lts(fitter) => fitter.fit(),
+            LinearModelFitter::Mm(fitter) => fitter.fit(),
+            LinearModelFitter::QuantileRegression(fitter) => fitter.fit(),
+            LinearModelFitter::Glm(fitter) => fitter.fit(),
+            LinearModelFitter::GlmNb(fitter) => fitter.fit(),
+            LinearModelFitter::Multinomial(fitter) => fitter.fit(),
+            LinearModelFitter::Ordinal(fitter) => fitter.fit(),
+            LinearModelFitter::Gls(fitter) => fitter.fit(),
+            LinearModelFitter::CochraneOrcutt(fitter) => fitter.fit(),
+            LinearModelFitter::Lmer(fitter) => fitter.fit(),
+            LinearModelFitter::Iv(fitter) => fitter.fit(),
+            LinearModelFitter::FixedEffects(fitter) => fitter.fit(),
+            LinearModelFitter::Panel(fitter) => fitter.fit(),
+            LinearModelFitter::Nls(fitter) => fitter.fit(),
         }
     }
 
     fn x(&self) -> &RealMatrix {
         match self {
             LinearModelFitter::QrDecomposition(fitter) => fitter.x(),
+            LinearModelFitter::Ridge(fitter) => fitter.x(),
+            LinearModelFitter::ElasticNet(fitter) => fitter.x(),
+            LinearModelFitter::Rlm(fitter) => fitter.x(),
+            LinearModelFitter::Lts(fitter) => fitter.x(),
+            LinearModelFitter::Mm(fitter) => fitter.x(),
+            LinearModelFitter::QuantileRegression(fitter) => fitter.x(),
+            LinearModelFitter::Glm(fitter) => fitter.x(),
+            LinearModelFitter::GlmNb(fitter) => fitter.x(),
+            LinearModelFitter::Multinomial(fitter) => fitter.x(),
+            LinearModelFitter::Ordinal(fitter) => fitter.x(),
+            LinearModelFitter::Gls(fitter) => fitter.x(),
+            LinearModelFitter::CochraneOrcutt(fitter) => fitter.x(),
+            LinearModelFitter::Lmer(fitter) => fitter.x(),
+            LinearModelFitter::Iv(fitter) => fitter.x(),
+            LinearModelFitter::FixedEffects(fitter) => fitter.x(),
+            LinearModelFitter::Panel(fitter) => fitter.x(),
+            LinearModelFitter::Nls(fitter) => fitter.x(),
         }
     }
 
     fn y(&self) -> &RealMatrix {
         match self {
             LinearModelFitter::QrDecomposition(fitter) => fitter.y(),
+            LinearModelFitter::Ridge(fitter) => fitter.y(),
+            LinearModelFitter::ElasticNet(fitter) => fitter.y(),
+            LinearModelFitter::Rlm(fitter) => fitter.y(),
+            LinearModelFitter::Lts(fitter) => fitter.y(),
+            LinearModelFitter::Mm(fitter) => fitter.y(),
+            LinearModelFitter::QuantileRegression(fitter) => fitter.y(),
+            LinearModelFitter::Glm(fitter) => fitter.y(),
+            LinearModelFitter::GlmNb(fitter) => fitter.y(),
+            LinearModelFitter::Multinomial(fitter) => fitter.y(),
+            LinearModelFitter::Ordinal(fitter) => fitter.y(),
+            LinearModelFitter::Gls(fitter) => fitter.y(),
+            LinearModelFitter::CochraneOrcutt(fitter) => fitter.y(),
+            LinearModelFitter::Lmer(fitter) => fitter.y(),
+            LinearModelFitter::Iv(fitter) => fitter.y(),
+            LinearModelFitter::FixedEffects(fitter) => fitter.y(),
+            LinearModelFitter::Panel(fitter) => fitter.y(),
+            LinearModelFitter::Nls(fitter) => fitter.y(),
+        }
+    }
+
+    fn tolerance(&self) -> Tolerance {
+        match self {
+            LinearModelFitter::QrDecomposition(fitter) => fitter.tolerance(),
+            _ => Tolerance::default(),
         }
     }
 }
@@ -0,0 +1,655 @@
+//! This module contains the fixed-effects fitter, which absorbs one or more high-cardinality
+//! factors the way `fixest::feols` and `reghdfe` do, and implements the `FitModel` trait for
+//! the `LinearModelFitter` enum.
+//!
+//! The fixed effects are never expanded into dummy columns. Instead y and each column of x are
+//! demeaned by alternating projections: the means of each factor's levels are subtracted in
+//! turn until no level mean is left, which converges to the residuals of the regression on all
+//! the dummies. The demeaned y is then regressed on the demeaned x by QR decomposition, which
+//! gives the coefficients and residuals of the full model (Frisch-Waugh-Lovell). Columns of x
+//! that the fixed effects absorb, such as the intercept, come out as aliased.
+//!
+//! The fixed effects use up one degree of freedom per level, less the redundant ones: the
+//! first factor counts all its levels, the second loses one level per connected component of
+//! the graph linking the levels of the two factors that share an observation, which is exact,
+//! and each later factor adds nothing if an earlier factor is nested in it, loses the levels of
+//! the largest earlier factor it is nested in, or otherwise loses one level for the shared
+//! constant, which may undercount redundancies.
+
+// src/fitters/fixed_effects_fitter.rs
+
+use super::fit::FitModel;
+use crate::anova::{format_number, write_coefficients};
+use crate::errors::LmFitterError;
+use crate::least_squares::dqrls;
+use crate::mixed::spec::GroupingFactor;
+use crate::terms::INTERCEPT_LABEL;
+use crate::types::{Data, RealMatrix, Tolerance};
+use crate::vcov::unscaled_covariance;
+use derive_builder::Builder;
+use std::collections::HashMap;
+use std::fmt;
+
+/// The estimated fixed effects of one factor.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FixedEffects {
+    /// The name of the factor.
+    pub name: String,
+    /// The level codes, in order of first appearance.
+    pub levels: Vec<usize>,
+    /// The effect of each level.
+    pub values: Vec<f64>,
+}
+
+/// The result of a fit with absorbed fixed effects.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FixedEffectsFit {
+    /// The names of the coefficients: the columns of x other than the intercept.
+    pub names: Vec<String>,
+    /// The coefficients, `NaN` for columns the fixed effects absorb.
+    pub coefficients: Vec<f64>,
+    /// The standard errors of the coefficients.
+    pub std_errors: Vec<f64>,
+    /// The covariance of the coefficients.
+    pub covariance: RealMatrix,
+    /// The residual standard error.
+    pub sigma: f64,
+    /// The residual degrees of freedom, net of the coefficients and the fixed effects.
+    pub df_residual: usize,
+    /// The number of levels of each factor.
+    pub levels: Vec<usize>,
+    /// The degrees of freedom used by the fixed effects, net of redundant levels.
+    pub fixed_effect_df: usize,
+    /// The R-squared of the full model, about the mean of y.
+    pub r_squared: f64,
+    /// The R-squared of the demeaned model, the share of the variation within the fixed
+    /// effects that x explains.
+    pub within_r_squared: f64,
+    /// The residuals.
+    pub residuals: Vec<f64>,
+    /// The largest number of alternating-projection sweeps any column needed.
+    pub iterations: usize,
+    /// `true` if every column was demeaned to within the tolerance.
+    pub converged: bool,
+    /// The absorbed factors, for recovering the fixed effects.
+    absorbed: Absorbed,
+    /// The sum of the fixed effects of each observation, y - X b - e.
+    effect_sums: Vec<f64>,
+    tol: f64,
+    max_iter: usize,
+}
+
+impl FixedEffectsFit {
+    /// Return the estimated fixed effects of each factor, solved from the sum of the fixed
+    /// effects of each observation by alternating projections. Only sums of effects across
+    /// factors are identified: the first level of every factor after the first is set to zero,
+    /// and within a set of levels not connected to the rest, effects shift freely between
+    /// factors.
+    pub fn fixed_effects(&self) -> Vec<FixedEffects> {
+        let absorbed = &self.absorbed;
+        let mut values: Vec<Vec<f64>> =
+            absorbed.counts.iter().map(|c| vec![0.0; c.len()]).collect();
+        let scale = self
+            .effect_sums
+            .iter()
+            .fold(1.0_f64, |acc, v| acc.max(v.abs()));
+        let mut remainder = self.effect_sums.clone();
+        for _ in 0..self.max_iter {
+            let mut change = 0.0_f64;
+            for (k, codes) in absorbed.codes.iter().enumerate() {
+                let means = absorbed.means(k, &remainder);
+                for (i, &code) in codes.iter().enumerate() {
+                    remainder[i] -= means[code];
+                }
+                for (value, mean) in values[k].iter_mut().zip(&means) {
+                    *value += mean;
+                    change = change.max(mean.abs());
+                }
+            }
+            if change <= self.tol * scale {
+                break;
+            }
+        }
+
+        for k in 1..values.len() {
+            let shift = values[k][0];
+            values[k].iter_mut().for_each(|value| *value -= shift);
+            values[0].iter_mut().for_each(|value| *value += shift);
+        }
+        absorbed
+            .names
+            .iter()
+            .zip(&absorbed.levels)
+            .zip(values)
+            .map(|((name, levels), values)| FixedEffects {
+                name: name.clone(),
+                levels: levels.clone(),
+                values,
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for FixedEffectsFit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let factors: Vec<String> = self
+            .absorbed
+            .names
+            .iter()
+            .zip(&self.levels)
+            .map(|(name, levels)| format!("{} ({} levels)", name, levels))
+            .collect();
+        writeln!(f, "OLS estimation, fixed effects: {}", factors.join(", "))?;
+        writeln!(f)?;
+        writeln!(f, "Coefficients:")?;
+        write_coefficients(
+            f,
+            &self.names,
+            &self.coefficients,
+            &self.std_errors,
+            self.df_residual,
+            &["Estimate", "Std. Error", "t value", "Pr(>|t|)"],
+        )?;
+        writeln!(f)?;
+        writeln!(
+            f,
+            "Residual standard error: {} on {} degrees of freedom ({} absorbed by fixed effects)",
+            format_number(self.sigma),
+            self.df_residual,
+            self.fixed_effect_df
+        )?;
+        writeln!(
+            f,
+            "R-squared: {},\tWithin R-squared: {}",
+            format_number(self.r_squared),
+            format_number(self.within_r_squared)
+        )?;
+        if !self.converged {
+            writeln!(
+                f,
+                "Demeaning did not converge in {} sweeps",
+                self.iterations
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Builder)]
+pub struct FixedEffectsFitter<'a> {
+    pub data: &'a Data,
+    /// The factors to absorb.
+    pub factors: Vec<GroupingFactor>,
+    /// The maximum number of alternating-projection sweeps per column.
+    #[builder(default = "10_000")]
+    pub max_iter: usize,
+    /// The sweeps stop once no level mean exceeds `tol` times the largest absolute value of the
+    /// column.
+    #[builder(default = "1e-10")]
+    pub tol: f64,
+}
+
+impl<'a> FixedEffectsFitter<'a> {
+    /// Return a new instance of the `FixedEffectsFitter` struct with the default controls.
+    pub fn new(data: &'a Data, factors: Vec<GroupingFactor>) -> Self {
+        Self {
+            data,
+            factors,
+            max_iter: 10_000,
+            tol: 1e-10,
+        }
+    }
+
+    /// Return the x matrix from the data struct.
+    pub fn x(&self) -> &RealMatrix {
+        &self.data.x
+    }
+
+    /// Return the y matrix from the data struct.
+    pub fn y(&self) -> &RealMatrix {
+        &self.data.y
+    }
+
+    /// Fit the model with the factors absorbed.
+    ///
+    /// # Errors
+    /// Returns `LmFitterError::MultipleResponses` if y has more than one column,
+    /// `LmFitterError::PriorWeights` if the data are weighted, `LmFitterError::NoFixedEffects`
+    /// if there are no factors, `LmFitterError::ObservationMismatch` if a factor does not have
+    /// one level per observation, and `LmFitterError::TooFewObservations` if no degrees of
+    /// freedom are left.
+    pub fn feols(&self) -> Result<FixedEffectsFit, LmFitterError> {
+        let (x, y) = (self.x(), self.y());
+        if y.n_cols() != 1 {
+            return Err(LmFitterError::MultipleResponses { found: y.n_cols() });
+        }
+        if self.data.weights.is_some() {
+            return Err(LmFitterError::PriorWeights);
+        }
+        if self.factors.is_empty() {
+            return Err(LmFitterError::NoFixedEffects);
+        }
+        let n = x.n_rows();
+        for factor in &self.factors {
+            if factor.levels.len() != n {
+                return Err(LmFitterError::ObservationMismatch {
+                    name: format!("level of factor {}", factor.name),
+                    expected: n,
+                    found: factor.levels.len(),
+                });
+            }
+        }
+        let absorbed = Absorbed::new(&self.factors);
+        let fixed_effect_df = absorbed.degrees_of_freedom();
+        let Demeaned {
+            x: x_within,
+            y: y_within,
+            columns: kept,
+            iterations,
+            converged,
+        } = self.absorb(&absorbed);
+        let names = self.data.column_names();
+        let p = kept.len();
+        let y: Vec<f64> = y.values.column(0).to_vec();
+
+        let (coefficients, residuals, covariance, rank) = if p == 0 {
+            (
+                Vec::new(),
+                y_within.clone(),
+                RealMatrix::with_shape(0, 0),
+                0,
+            )
+        } else {
+            let y_matrix = RealMatrix::from_vec(y_within.clone(), n, None);
+            let fit = dqrls(&x_within, &y_matrix, &Tolerance::default())?;
+            (
+                fit.coefficients.values.column(0).to_vec(),
+                fit.residuals.values.column(0).to_vec(),
+                unscaled_covariance(&fit.qr),
+                fit.rank(),
+            )
+        };
+        let used = rank + fixed_effect_df;
+        if n <= used {
+            return Err(LmFitterError::TooFewObservations {
+                needed: used + 1,
+                found: n,
+            });
+        }
+        let df_residual = n - used;
+        let rss: f64 = residuals.iter().map(|e| e * e).sum();
+        let sigma2 = rss / df_residual as f64;
+        let covariance = RealMatrix::new(&covariance.values * sigma2);
+        let std_errors = (0..p).map(|j| covariance.values[[j, j]].sqrt()).collect();
+
+        let mean = y.iter().sum::<f64>() / n as f64;
+        let tss: f64 = y.iter().map(|v| (v - mean).powi(2)).sum();
+        let within_tss: f64 = y_within.iter().map(|v| v * v).sum();
+        let effect_sums = (0..n)
+            .map(|i| {
+                let fitted: f64 = kept
+                    .iter()
+                    .zip(&coefficients)
+                    .filter(|(_, b)| !b.is_nan())
+                    .map(|(&j, b)| x.values[[i, j]] * b)
+                    .sum();
+                y[i] - fitted - residuals[i]
+            })
+            .collect();
+
+        Ok(FixedEffectsFit {
+            names: kept.iter().map(|&j| names[j].clone()).collect(),
+            coefficients,
+            std_errors,
+            covariance,
+            sigma: sigma2.sqrt(),
+            df_residual,
+            levels: absorbed.counts.iter().map(Vec::len).collect(),
+            fixed_effect_df,
+            r_squared: 1.0 - rss / tss,
+            within_r_squared: 1.0 - rss / within_tss,
+            residuals,
+            iterations,
+            converged,
+            absorbed,
+            effect_sums,
+            tol: self.tol,
+            max_iter: self.max_iter,
+        })
+    }
+
+    /// Demean y and every column of x but the intercept by the absorbed factors.
+    pub(crate) fn absorb(&self, absorbed: &Absorbed) -> Demeaned {
+        let (x, y) = (self.x(), self.y());
+        let names = self.data.column_names();
+        let columns: Vec<usize> = (0..x.n_cols())
+            .filter(|&j| names[j] != INTERCEPT_LABEL)
+            .collect();
+        let (y, mut iterations, mut converged) =
+            self.demean(absorbed, &y.values.column(0).to_vec());
+        let mut demeaned = RealMatrix::with_shape(x.n_rows(), columns.len());
+        for (target, &j) in columns.iter().enumerate() {
+            let original = x.values.column(j).to_vec();
+            let (mut column, sweeps, done) = self.demean(absorbed, &original);
+            iterations = iterations.max(sweeps);
+            converged &= done;
+            // A column the fixed effects absorb is left as noise of the order of `tol`, which
+            // the QR tolerance, relative to the column itself, would not flag as aliased.
+            let norm = |values: &[f64]| values.iter().map(|v| v * v).sum::<f64>().sqrt();
+            if norm(&column) <= self.tol.sqrt() * norm(&original) {
+                column.iter_mut().for_each(|value| *value = 0.0);
+            }
+            for (i, value) in column.into_iter().enumerate() {
+                demeaned.values[[i, target]] = value;
+            }
+        }
+        Demeaned {
+            x: demeaned,
+            y,
+            columns,
+            iterations,
+            converged,
+        }
+    }
+
+    /// Sweep the level means of each factor out of `values` in turn until none is left, and
+    /// return the result with the number of sweeps and whether it converged.
+    fn demean(&self, absorbed: &Absorbed, values: &[f64]) -> (Vec<f64>, usize, bool) {
+        let mut values = values.to_vec();
+        let scale = values.iter().fold(1.0_f64, |acc, v| acc.max(v.abs()));
+        for sweep in 1..=self.max_iter {
+            let mut change = 0.0_f64;
+            for (k, codes) in absorbed.codes.iter().enumerate() {
+                let means = absorbed.means(k, &values);
+                change = means.iter().fold(change, |acc, m| acc.max(m.abs()));
+                for (value, &code) in values.iter_mut().zip(codes) {
+                    *value -= means[code];
+                }
+            }
+            if change <= self.tol * scale {
+                return (values, sweep, true);
+            }
+        }
+        (values, self.max_iter, false)
+    }
+}
+
+impl<'a> FitModel for FixedEffectsFitter<'a> {
+    /// Fit the model and return the coefficients of the columns of x other than the intercept
+    /// as a single column.
+    fn fit(&self) -> Result<RealMatrix, LmFitterError> {
+        let coefficients = self.feols()?.coefficients;
+        let p = coefficients.len();
+        Ok(RealMatrix::from_vec(coefficients, p, None))
+    }
+
+    fn x(&self) -> &RealMatrix {
+        self.data.x()
+    }
+
+    fn y(&self) -> &RealMatrix {
+        self.data.y()
+    }
+}
+
+/// The data with the fixed effects absorbed.
+pub(crate) struct Demeaned {
+    /// The demeaned columns of x other than the intercept.
+    pub(crate) x: RealMatrix,
+    /// The demeaned y.
+    pub(crate) y: Vec<f64>,
+    /// The columns of the original x that are kept.
+    pub(crate) columns: Vec<usize>,
+    /// The largest number of sweeps any column needed.
+    pub(crate) iterations: usize,
+    /// `true` if every column was demeaned to within the tolerance.
+    pub(crate) converged: bool,
+}
+
+/// The absorbed factors, with their levels renumbered 0, 1, ... in order of first appearance.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Absorbed {
+    names: Vec<String>,
+    /// The renumbered level of each observation, for each factor.
+    pub(crate) codes: Vec<Vec<usize>>,
+    /// The original level codes, for each factor.
+    pub(crate) levels: Vec<Vec<usize>>,
+    /// The number of observations at each level, for each factor.
+    pub(crate) counts: Vec<Vec<usize>>,
+}
+
+impl Absorbed {
+    pub(crate) fn new(factors: &[GroupingFactor]) -> Self {
+        let mut absorbed = Absorbed {
+            names: Vec::new(),
+            codes: Vec::new(),
+            levels: Vec::new(),
+            counts: Vec::new(),
+        };
+        for factor in factors {
+            let mut renumbered: HashMap<usize, usize> = HashMap::new();
+            let mut levels = Vec::new();
+            let mut counts = Vec::new();
+            let codes = factor
+                .levels
+                .iter()
+                .map(|&level| {
+                    let code = *renumbered.entry(level).or_insert_with(|| {
+                        levels.push(level);
+                        counts.push(0);
+                        levels.len() - 1
+                    });
+                    counts[code] += 1;
+                    code
+                })
+                .collect();
+            absorbed.names.push(factor.name.clone());
+            absorbed.codes.push(codes);
+            absorbed.levels.push(levels);
+            absorbed.counts.push(counts);
+        }
+        absorbed
+    }
+
+    /// Return the mean of `values` at each level of factor `k`.
+    pub(crate) fn means(&self, k: usize, values: &[f64]) -> Vec<f64> {
+        let mut sums = vec![0.0; self.counts[k].len()];
+        for (&code, value) in self.codes[k].iter().zip(values) {
+            sums[code] += value;
+        }
+        sums.iter()
+            .zip(&self.counts[k])
+            .map(|(sum, &count)| sum / count as f64)
+            .collect()
+    }
+
+    /// Return the number of levels net of redundancies, as described in the module docs.
+    pub(crate) fn degrees_of_freedom(&self) -> usize {
+        let mut df = self.counts[0].len();
+        for k in 1..self.codes.len() {
+            let levels = self.counts[k].len();
+            let redundant = if k == 1 {
+                self.connected_components(0, 1)
+            } else if (0..k).any(|j| self.nested(j, k)) {
+                levels
+            } else {
+                (0..k)
+                    .filter(|&j| self.nested(k, j))
+                    .map(|j| self.counts[j].len())
+                    .max()
+                    .unwrap_or(1)
+            };
+            df += levels - redundant.min(levels);
+        }
+        df
+    }
+
+    /// Return `true` if every level of factor `inner` falls within a single level of `outer`.
+    fn nested(&self, inner: usize, outer: usize) -> bool {
+        let mut parent = vec![None; self.counts[inner].len()];
+        self.codes[inner]
+            .iter()
+            .zip(&self.codes[outer])
+            .all(|(&a, &b)| *parent[a].get_or_insert(b) == b)
+    }
+
+    /// Return the number of connected components of the graph whose nodes are the levels of
+    /// factors `a` and `b`, with an edge for each observation.
+    fn connected_components(&self, a: usize, b: usize) -> usize {
+        let offset = self.counts[a].len();
+        let mut parent: Vec<usize> = (0..offset + self.counts[b].len()).collect();
+        fn root(parent: &mut [usize], mut node: usize) -> usize {
+            while parent[node] != node {
+                parent[node] = parent[parent[node]];
+                node = parent[node];
+            }
+            node
+        }
+        let mut components = parent.len();
+        for (&i, &j) in self.codes[a].iter().zip(&self.codes[b]) {
+            let (ri, rj) = (root(&mut parent, i), root(&mut parent, offset + j));
+            if ri != rj {
+                parent[ri] = rj;
+                components -= 1;
+            }
+        }
+        components
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Return x and the dummy columns of `factors`, for the least squares fit the fixed effects
+    /// stand in for.
+    fn with_dummies(x: &RealMatrix, factors: &[GroupingFactor]) -> RealMatrix {
+        let mut columns: Vec<Vec<f64>> = (0..x.n_cols())
+            .map(|j| x.values.column(j).to_vec())
+            .collect();
+        for factor in factors {
+            let mut levels = factor.levels.clone();
+            levels.sort_unstable();
+            levels.dedup();
+            for level in levels {
+                columns.push(
+                    factor
+                        .levels
+                        .iter()
+                        .map(|&l| f64::from(l == level))
+                        .collect(),
+                );
+            }
+        }
+        let (n, p) = (x.n_rows(), columns.len());
+        let mut full = RealMatrix::with_shape(n, p);
+        for (j, column) in columns.iter().enumerate() {
+            for (i, &value) in column.iter().enumerate() {
+                full.values[[i, j]] = value;
+            }
+        }
+        full
+    }
+
+    #[test]
+    fn test_absorbed_fit_matches_the_dummy_regression() {
+        let n = 60;
+        let customers: Vec<usize> = (0..n).map(|i| 1000 + (i * 7) % 9).collect();
+        let weeks: Vec<usize> = (0..n).map(|i| (i / 2) % 5).collect();
+        let mut x = RealMatrix::with_shape(n, 4);
+        let mut y = RealMatrix::with_shape(n, 1);
+        for i in 0..n {
+            let t = i as f64;
+            let (x1, x2) = ((t * 0.9).sin(), (t * t * 0.13).cos());
+            // x3 is constant within customers, so the fixed effects absorb it.
+            let x3 = (customers[i] % 4) as f64;
+            x.values[[i, 0]] = 1.0;
+            x.values[[i, 1]] = x1;
+            x.values[[i, 2]] = x2;
+            x.values[[i, 3]] = x3;
+            let effects = 0.3 * (customers[i] - 1000) as f64 - 0.5 * weeks[i] as f64;
+            y.values[[i, 0]] = 2.0 + 1.5 * x1 - 0.7 * x2 + effects + 0.2 * (t * 2.3).sin();
+        }
+        let names = ["(Intercept)", "x1", "x2", "x3"].map(String::from).to_vec();
+        let data = Data::new(x.clone(), y.clone()).with_column_names(names);
+        let factors = vec![
+            GroupingFactor::new("customer", customers),
+            GroupingFactor::new("week", weeks),
+        ];
+        let fit = FixedEffectsFitter::new(&data, factors.clone())
+            .feols()
+            .unwrap();
+        assert!(fit.converged);
+        assert_eq!(fit.names, vec!["x1", "x2", "x3"]);
+        assert_eq!(fit.levels, vec![9, 5]);
+        assert_eq!(fit.fixed_effect_df, 13);
+        assert!(fit.coefficients[2].is_nan());
+
+        let dummies = with_dummies(&x, &factors);
+        let expected = dqrls(&dummies, &y, &Tolerance::default()).unwrap();
+        assert_eq!(fit.df_residual, n - expected.rank());
+        let rss: f64 = expected.residuals.values.iter().map(|e| e * e).sum();
+        let covariance = unscaled_covariance(&expected.qr);
+        for j in 0..2 {
+            let b = expected.coefficients.values[[j + 1, 0]];
+            let se = (covariance.values[[j + 1, j + 1]] * rss / fit.df_residual as f64).sqrt();
+            assert!((fit.coefficients[j] - b).abs() < 1e-8);
+            assert!((fit.std_errors[j] - se).abs() < 1e-8);
+        }
+
+        // The recovered fixed effects rebuild the fitted values of the dummy regression.
+        let effects = fit.fixed_effects();
+        assert_eq!(effects[0].levels[..3], [1000, 1007, 1005]);
+        assert_eq!(effects[1].values[0], 0.0);
+        let fitted = expected.fitted_values(&y);
+        for i in 0..n {
+            let level = |k: usize| {
+                let position = effects[k]
+                    .levels
+                    .iter()
+                    .position(|&l| l == factors[k].levels[i])
+                    .unwrap();
+                effects[k].values[position]
+            };
+            let value = fit.coefficients[0] * x.values[[i, 1]]
+                + fit.coefficients[1] * x.values[[i, 2]]
+                + level(0)
+                + level(1);
+            assert!((value - fitted.values[[i, 0]]).abs() < 1e-7);
+        }
+    }
+
+    #[test]
+    fn test_degrees_of_freedom_for_nested_and_disconnected_factors() {
+        // Customers 0-2 are only seen in weeks 0-2 and customers 3-5 in weeks 3-5, so the two
+        // factors split into two connected sets, and customers are nested in regions.
+        let n = 36;
+        let customers: Vec<usize> = (0..n).map(|i| i % 6).collect();
+        let weeks: Vec<usize> = (0..n).map(|i| 3 * (i % 6 / 3) + (i / 6) % 3).collect();
+        let regions: Vec<usize> = customers.iter().map(|c| c / 2).collect();
+        let mut x = RealMatrix::with_shape(n, 1);
+        let mut y = RealMatrix::with_shape(n, 1);
+        for i in 0..n {
+            x.values[[i, 0]] = (i as f64 * 1.7).sin();
+            y.values[[i, 0]] = x.values[[i, 0]] + (i as f64 * 0.61).cos();
+        }
+        let data = Data::new(x.clone(), y.clone());
+        let factors = vec![
+            GroupingFactor::new("customer", customers),
+            GroupingFactor::new("week", weeks),
+            GroupingFactor::new("region", regions),
+        ];
+        let fit = FixedEffectsFitter::new(&data, factors.clone())
+            .feols()
+            .unwrap();
+        assert_eq!(fit.fixed_effect_df, 6 + 6 - 2);
+        let expected = dqrls(&with_dummies(&x, &factors), &y, &Tolerance::default()).unwrap();
+        assert_eq!(fit.df_residual, n - expected.rank());
+        assert!((fit.coefficients[0] - expected.coefficients.values[[0, 0]]).abs() < 1e-8);
+
+        assert!(matches!(
+            FixedEffectsFitter::new(&data, Vec::new()).feols(),
+            Err(LmFitterError::NoFixedEffects)
+        ));
+    }
+}
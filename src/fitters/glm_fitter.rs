@@ -0,0 +1,1076 @@
+//! This module contains the generalized linear model fitter, which implements the `FitModel`
+//! trait for the `LinearModelFitter` enum, following R's `glm.fit`.
+//!
+//! The coefficients are found by iteratively reweighted least squares. Each iteration forms the
+//! working response z = eta - offset + (y - mu) / mu'(eta) and the working weights
+//! w mu'(eta)^2 / V(mu), and solves the weighted least squares problem with the same QR solver
+//! as `lm`, so aliased columns are detected the same way and get `NaN` coefficients. The
+//! iterations stop when the relative change in the deviance falls below `epsilon`. When an
+//! update gives an infinite deviance or leaves the domain of the family, the step is halved
+//! towards the previous coefficients, and the fit is flagged as having hit the boundary.
+//!
+//! A fit is summarised like R's `summary.glm`: the dispersion is fixed at 1 for the binomial
+//! and Poisson families and otherwise estimated by Pearson's chi-squared over the residual
+//! degrees of freedom, giving z or t tests of the coefficients. Profile-likelihood confidence
+//! intervals follow `MASS::confint.glm`, refitting the model with each coefficient held fixed
+//! in the offset.
+
+// src/fitters/glm_fitter.rs
+
+use super::fit::FitModel;
+use super::rlm_fitter::weighted_fit;
+use crate::anova::{format_number, format_p_value, write_table};
+use crate::distributions::{normal_cdf, normal_quantile, t_upper_tail};
+use crate::errors::{GlmError, LmFitterError};
+use crate::family::{Family, Link};
+use crate::least_squares::QrDecomposition;
+use crate::types::{Data, RealMatrix};
+use crate::vcov::unscaled_covariance;
+use derive_builder::Builder;
+use ndarray::Array1;
+use std::fmt;
+use std::sync::Arc;
+
+pub use crate::family::GlmWarning;
+
+/// A test of a step's linear predictor, means and deviance, for step-halving.
+type StepCheck<'f> = &'f dyn Fn(&[f64], &[f64], f64) -> bool;
+
+/// The maximum number of times the step away from the estimate is doubled when bracketing a
+/// profile-likelihood confidence limit.
+const MAX_DOUBLINGS: usize = 30;
+
+/// The convergence settings of the IRLS iterations, like R's `glm.control`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GlmControl {
+    /// The tolerance on the relative change in the deviance.
+    pub epsilon: f64,
+    /// The maximum number of iterations.
+    pub max_iter: usize,
+}
+
+impl Default for GlmControl {
+    fn default() -> Self {
+        GlmControl {
+            epsilon: 1e-8,
+            max_iter: 25,
+        }
+    }
+}
+
+/// The result of a generalized linear model fit.
+#[derive(Debug, Clone)]
+pub struct GlmFit<'a> {
+    /// The family the model was fitted with.
+    pub family: Arc<dyn Family + Send + Sync>,
+    /// The data the model was fitted to.
+    pub data: &'a Data,
+    /// The names of the coefficients.
+    pub names: Vec<String>,
+    /// The coefficients, as a single column, `NaN` for aliased columns.
+    pub coefficients: RealMatrix,
+    /// The fitted means mu.
+    pub fitted_values: Vec<f64>,
+    /// The linear predictors eta, including the offset.
+    pub linear_predictors: Vec<f64>,
+    /// The working residuals (y - mu) / mu'(eta).
+    pub residuals: Vec<f64>,
+    /// The working weights of the final iteration.
+    pub working_weights: Vec<f64>,
+    /// The prior weights.
+    pub prior_weights: Vec<f64>,
+    /// The offset.
+    pub offset: Vec<f64>,
+    /// The response.
+    pub y: Vec<f64>,
+    /// The QR decomposition of the model matrix scaled by the square roots of the working
+    /// weights of the final iteration.
+    pub qr: QrDecomposition,
+    /// The residual deviance.
+    pub deviance: f64,
+    /// The deviance of the model with only the intercept (if any) and the offset.
+    pub null_deviance: f64,
+    /// The AIC, counting the coefficients but not an estimated dispersion.
+    pub aic: f64,
+    /// The number of non-aliased coefficients.
+    pub rank: usize,
+    /// The residual degrees of freedom.
+    pub df_residual: usize,
+    /// The degrees of freedom of the null model.
+    pub df_null: usize,
+    /// `true` if the model has an intercept.
+    pub intercept: bool,
+    /// The number of IRLS iterations.
+    pub iterations: usize,
+    /// `true` if the deviance converged.
+    pub converged: bool,
+    /// `true` if a step was halved to stay inside the domain of the family.
+    pub boundary: bool,
+    /// The warnings R would print for this fit.
+    pub warnings: Vec<GlmWarning>,
+    /// The convergence settings the model was fitted with, reused when it is refitted.
+    pub control: GlmControl,
+}
+
+/// An enum representing the kinds of residuals of a generalized linear model, like the `type`
+/// argument of R's `residuals.glm`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ResidualType {
+    /// sign(y - mu) sqrt(w d(y, mu)), whose squares sum to the deviance.
+    #[default]
+    Deviance,
+    /// (y - mu) sqrt(w / V(mu)), whose squares sum to Pearson's chi-squared.
+    Pearson,
+    /// The working residuals (y - mu) / mu'(eta) of the final iteration.
+    Working,
+    /// The residuals y - mu on the scale of the response.
+    Response,
+}
+
+/// The summary of a generalized linear model fit, like R's `summary.glm`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GlmSummary {
+    /// The name of the family.
+    pub family: String,
+    /// The link function.
+    pub link: Link,
+    /// The names of the coefficients.
+    pub names: Vec<String>,
+    /// The coefficients, `NaN` for aliased columns.
+    pub coefficients: Vec<f64>,
+    /// The standard errors of the coefficients.
+    pub std_errors: Vec<f64>,
+    /// The z or t statistics b / se.
+    pub statistics: Vec<f64>,
+    /// The two-sided p-values of the statistics.
+    pub p_values: Vec<f64>,
+    /// The dispersion the covariance is scaled by.
+    pub dispersion: f64,
+    /// `true` if the dispersion was estimated, so that the statistics are compared with the t
+    /// distribution on the residual degrees of freedom rather than the standard normal.
+    pub estimated_dispersion: bool,
+    /// The covariance of the coefficients, phi (X'WX)^-1. Rows and columns of aliased
+    /// coefficients are `NaN`.
+    pub covariance: RealMatrix,
+    /// The residual deviance.
+    pub deviance: f64,
+    /// The null deviance.
+    pub null_deviance: f64,
+    /// The residual degrees of freedom.
+    pub df_residual: usize,
+    /// The degrees of freedom of the null model.
+    pub df_null: usize,
+    /// The AIC, `NaN` for the quasi-families.
+    pub aic: f64,
+    /// The number of IRLS iterations.
+    pub iterations: usize,
+}
+
+impl fmt::Display for GlmSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Family: {}, link: {}", self.family, self.link)?;
+        writeln!(f)?;
+        writeln!(f, "Coefficients:")?;
+        let header = if self.estimated_dispersion {
+            ["Estimate", "Std. Error", "t value", "Pr(>|t|)"]
+        } else {
+            ["Estimate", "Std. Error", "z value", "Pr(>|z|)"]
+        };
+        let labels: Vec<&str> = self.names.iter().map(String::as_str).collect();
+        let cells: Vec<Vec<String>> = (0..self.names.len())
+            .map(|j| {
+                if self.coefficients[j].is_nan() {
+                    vec!["NA".to_string(); 4]
+                } else {
+                    vec![
+                        format_number(self.coefficients[j]),
+                        format_number(self.std_errors[j]),
+                        format_number(self.statistics[j]),
+                        format_p_value(self.p_values[j]),
+                    ]
+                }
+            })
+            .collect();
+        write_table(f, &labels, &header, &cells)?;
+        writeln!(f)?;
+        writeln!(
+            f,
+            "(Dispersion parameter for {} family taken to be {})",
+            self.family,
+            format_number(self.dispersion)
+        )?;
+        writeln!(f)?;
+        writeln!(
+            f,
+            "    Null deviance: {}  on {}  degrees of freedom",
+            format_number(self.null_deviance),
+            self.df_null
+        )?;
+        writeln!(
+            f,
+            "Residual deviance: {}  on {}  degrees of freedom",
+            format_number(self.deviance),
+            self.df_residual
+        )?;
+        if self.aic.is_nan() {
+            writeln!(f, "AIC: NA")?;
+        } else {
+            writeln!(f, "AIC: {}", format_number(self.aic))?;
+        }
+        writeln!(f)?;
+        writeln!(
+            f,
+            "Number of Fisher Scoring iterations: {}",
+            self.iterations
+        )
+    }
+}
+
+impl<'a> GlmFit<'a> {
+    /// Return the residuals of type `kind`.
+    pub fn residuals_of(&self, kind: ResidualType) -> Vec<f64> {
+        let n = self.y.len();
+        match kind {
+            ResidualType::Deviance => (0..n)
+                .map(|i| {
+                    let (y, mu) = (self.y[i], self.fitted_values[i]);
+                    let contribution = self.family.deviance_residual(y, mu, self.prior_weights[i]);
+                    (y - mu).signum() * contribution.max(0.0).sqrt()
+                })
+                .collect(),
+            ResidualType::Pearson => (0..n)
+                .map(|i| {
+                    let (y, mu) = (self.y[i], self.fitted_values[i]);
+                    (y - mu) * (self.prior_weights[i] / self.family.variance(mu)).sqrt()
+                })
+                .collect(),
+            ResidualType::Working => self.residuals.clone(),
+            ResidualType::Response => (0..n).map(|i| self.y[i] - self.fitted_values[i]).collect(),
+        }
+    }
+
+    /// Return the dispersion: 1 for families with a fixed dispersion, and otherwise Pearson's
+    /// chi-squared over the residual degrees of freedom, `NaN` when there are none left.
+    pub fn dispersion(&self) -> f64 {
+        if self.family.fixed_dispersion() {
+            return 1.0;
+        }
+        if self.df_residual == 0 {
+            return f64::NAN;
+        }
+        let pearson: f64 = self
+            .working_weights
+            .iter()
+            .zip(&self.residuals)
+            .filter(|(&w, _)| w > 0.0)
+            .map(|(w, r)| w * r * r)
+            .sum();
+        pearson / self.df_residual as f64
+    }
+
+    /// Return the summary of the fit, with Wald tests of the coefficients.
+    pub fn summary(&self) -> GlmSummary {
+        let dispersion = self.dispersion();
+        let estimated_dispersion = !self.family.fixed_dispersion();
+        let mut covariance = unscaled_covariance(&self.qr);
+        covariance.values.mapv_inplace(|v| v * dispersion);
+
+        let p = self.names.len();
+        let coefficients: Vec<f64> = self.coefficients.values.column(0).to_vec();
+        let std_errors: Vec<f64> = (0..p).map(|j| covariance.values[[j, j]].sqrt()).collect();
+        let statistics: Vec<f64> = (0..p).map(|j| coefficients[j] / std_errors[j]).collect();
+        let p_values = statistics
+            .iter()
+            .map(|statistic| {
+                if estimated_dispersion {
+                    2.0 * t_upper_tail(statistic.abs(), self.df_residual as f64)
+                } else {
+                    2.0 * normal_cdf(-statistic.abs())
+                }
+            })
+            .collect();
+
+        GlmSummary {
+            family: self.family.name().to_string(),
+            link: self.family.link(),
+            names: self.names.clone(),
+            coefficients,
+            std_errors,
+            statistics,
+            p_values,
+            dispersion,
+            estimated_dispersion,
+            covariance,
+            deviance: self.deviance,
+            null_deviance: self.null_deviance,
+            df_residual: self.df_residual,
+            df_null: self.df_null,
+            aic: self.aic,
+            iterations: self.iterations,
+        }
+    }
+
+    /// Return profile-likelihood confidence intervals for the coefficients at `level`.
+    ///
+    /// Each limit is where the signed root of the scaled deviance increase, refitting the other
+    /// coefficients with the coefficient held fixed, reaches the normal quantile, as in
+    /// `MASS::confint.glm`. A limit is `NaN` when the profile never reaches the quantile, as
+    /// for a coefficient that diverges under separation, and both limits are `NaN` for aliased
+    /// coefficients.
+    ///
+    /// # Errors
+    /// Returns `GlmError::InvalidLevel` if `level` is not in (0, 1).
+    pub fn confint(&self, level: f64) -> Result<Vec<(f64, f64)>, GlmError> {
+        if !(level > 0.0 && level < 1.0) {
+            return Err(GlmError::InvalidLevel { level });
+        }
+        let summary = self.summary();
+        let cutoff = normal_quantile((1.0 + level) / 2.0);
+        let x = self.data.x();
+        let p = self.names.len();
+        let retained: Vec<usize> = (0..p)
+            .filter(|&j| !summary.coefficients[j].is_nan())
+            .collect();
+
+        let intervals = (0..p)
+            .map(|j| {
+                let estimate = summary.coefficients[j];
+                if estimate.is_nan() {
+                    return (f64::NAN, f64::NAN);
+                }
+                let others: Vec<usize> = retained.iter().copied().filter(|&k| k != j).collect();
+                let signed_root = |b: f64| -> Option<f64> {
+                    let offset: Vec<f64> = (0..self.y.len())
+                        .map(|i| self.offset[i] + x.values[[i, j]] * b)
+                        .collect();
+                    let (deviance, _) = self.refit(&others, &offset).ok()?;
+                    let z = ((deviance - self.deviance).max(0.0) / summary.dispersion).sqrt();
+                    Some(if b < estimate { -z } else { z })
+                };
+                let step = summary.std_errors[j];
+                (
+                    profile_limit(&signed_root, estimate, -step, cutoff),
+                    profile_limit(&signed_root, estimate, step, cutoff),
+                )
+            })
+            .collect();
+        Ok(intervals)
+    }
+
+    /// Return the deviance and the rank of the model refitted with only the columns `columns`
+    /// of x and the offset `offset`, starting the IRLS iterations from the fitted linear
+    /// predictors.
+    pub(crate) fn refit(
+        &self,
+        columns: &[usize],
+        offset: &[f64],
+    ) -> Result<(f64, usize), GlmError> {
+        let n = self.y.len();
+        if columns.is_empty() {
+            let link = self.family.link();
+            let deviance = (0..n)
+                .map(|i| {
+                    let mu = link.inverse(offset[i]);
+                    self.family
+                        .deviance_residual(self.y[i], mu, self.prior_weights[i])
+                })
+                .sum();
+            return Ok((deviance, 0));
+        }
+        let mut x = RealMatrix::with_shape(n, columns.len());
+        for (b, &j) in columns.iter().enumerate() {
+            x.values
+                .column_mut(b)
+                .assign(&self.data.x().values.column(j));
+        }
+        let eta = self.linear_predictors.clone();
+        let refit = irls(
+            &x,
+            &self.y,
+            &self.prior_weights,
+            offset,
+            self.family.as_ref(),
+            eta,
+            None,
+            &self.control,
+        )?;
+        Ok((refit.deviance, refit.qr.rank))
+    }
+}
+
+impl fmt::Display for GlmFit<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Family: {}, link: {}",
+            self.family.name(),
+            self.family.link()
+        )?;
+        writeln!(f)?;
+        writeln!(f, "Coefficients:")?;
+        let labels: Vec<&str> = self.names.iter().map(String::as_str).collect();
+        let cells: Vec<Vec<String>> = self
+            .coefficients
+            .values
+            .column(0)
+            .iter()
+            .map(|&b| {
+                vec![if b.is_nan() {
+                    "NA".to_string()
+                } else {
+                    format_number(b)
+                }]
+            })
+            .collect();
+        write_table(f, &labels, &["Estimate"], &cells)?;
+        writeln!(f)?;
+        writeln!(
+            f,
+            "Degrees of Freedom: {} Total (i.e. Null);  {} Residual",
+            self.df_null, self.df_residual
+        )?;
+        writeln!(
+            f,
+            "Null Deviance:\t    {}",
+            format_number(self.null_deviance)
+        )?;
+        writeln!(
+            f,
+            "Residual Deviance: {} \tAIC: {}",
+            format_number(self.deviance),
+            if self.aic.is_nan() {
+                "NA".to_string()
+            } else {
+                format_number(self.aic)
+            }
+        )?;
+        for warning in &self.warnings {
+            writeln!(f, "Warning: {warning}")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Builder)]
+#[builder(pattern = "owned")]
+pub struct GlmFitter<'a> {
+    pub data: &'a Data,
+    pub family: Arc<dyn Family + Send + Sync>,
+    /// A known component of the linear predictor, one entry per observation.
+    #[builder(default)]
+    pub offset: Option<Vec<f64>>,
+    /// Starting coefficients, one per column of x.
+    #[builder(default)]
+    pub start: Option<Vec<f64>>,
+    #[builder(default)]
+    pub control: GlmControl,
+}
+
+/// The state of the IRLS iterations at convergence.
+pub(crate) struct Irls {
+    pub(crate) coefficients: RealMatrix,
+    pub(crate) eta: Vec<f64>,
+    pub(crate) mu: Vec<f64>,
+    pub(crate) working_weights: Vec<f64>,
+    pub(crate) qr: QrDecomposition,
+    pub(crate) deviance: f64,
+    pub(crate) iterations: usize,
+    pub(crate) converged: bool,
+    pub(crate) boundary: bool,
+}
+
+impl<'a> GlmFitter<'a> {
+    /// Return a new instance of the `GlmFitter` struct for `family`, without offset or starting
+    /// values and with the default control.
+    pub fn new(data: &'a Data, family: impl Family + Send + Sync + 'static) -> Self {
+        Self {
+            data,
+            family: Arc::new(family),
+            offset: None,
+            start: None,
+            control: GlmControl::default(),
+        }
+    }
+
+    /// Return the x matrix from the data struct.
+    pub fn x(&self) -> &RealMatrix {
+        &self.data.x
+    }
+
+    /// Return the y matrix from the data struct.
+    pub fn y(&self) -> &RealMatrix {
+        &self.data.y
+    }
+
+    /// Fit the model by IRLS.
+    ///
+    /// # Errors
+    /// Returns `GlmError::InvalidResponse` if the response is outside the support of the family,
+    /// `GlmError::LengthMismatch` if the offset or the starting values have the wrong length,
+    /// `GlmError::NoValidCoefficients` or `GlmError::StepHalvingFailed` if the iterations leave
+    /// the domain of the family and cannot be brought back, and `GlmError::MultipleResponses`
+    /// for more than one response.
+    pub fn glm(&self) -> Result<GlmFit<'a>, GlmError> {
+        let (x, data_y) = (self.data.x(), self.data.y());
+        if data_y.n_cols() != 1 {
+            return Err(GlmError::MultipleResponses {
+                found: data_y.n_cols(),
+            });
+        }
+        let (n, p) = (x.n_rows(), x.n_cols());
+        let family = self.family.as_ref();
+        let y: Vec<f64> = data_y.values.column(0).to_vec();
+        let weights: Vec<f64> = self
+            .data
+            .weights()
+            .map_or_else(|| vec![1.0; n], |weights| weights.to_vec());
+        let offset = match &self.offset {
+            Some(offset) => {
+                check_length("offset", "observation", n, offset.len())?;
+                offset.clone()
+            }
+            None => vec![0.0; n],
+        };
+
+        let mustart = family.initialize(&y, &weights)?;
+        let start = match &self.start {
+            Some(start) => {
+                check_length("start", "column of x", p, start.len())?;
+                Some(Array1::from(start.clone()))
+            }
+            None => None,
+        };
+        let eta = match &start {
+            Some(start) => {
+                let fitted = x.values.dot(start);
+                (0..n).map(|i| fitted[i] + offset[i]).collect()
+            }
+            None => mustart.iter().map(|&mu| family.link().link(mu)).collect(),
+        };
+        let result = irls(x, &y, &weights, &offset, family, eta, start, &self.control)?;
+
+        let intercept = self.data.terms().has_intercept();
+        let null_deviance = if intercept && offset.iter().any(|&o| o != 0.0) {
+            let ones = RealMatrix::from_vec(vec![1.0; n], n, Some(1));
+            let eta = mustart.iter().map(|&mu| family.link().link(mu)).collect();
+            irls(
+                &ones,
+                &y,
+                &weights,
+                &offset,
+                family,
+                eta,
+                None,
+                &self.control,
+            )?
+            .deviance
+        } else {
+            let total: f64 = weights.iter().sum();
+            let weighted_mean = y.iter().zip(&weights).map(|(y, w)| w * y).sum::<f64>() / total;
+            (0..n)
+                .map(|i| {
+                    let mu = if intercept {
+                        weighted_mean
+                    } else {
+                        family.link().inverse(offset[i])
+                    };
+                    family.deviance_residual(y[i], mu, weights[i])
+                })
+                .sum()
+        };
+
+        let mut warnings = Vec::new();
+        if !result.converged {
+            warnings.push(GlmWarning::NotConverged);
+        }
+        if result.boundary {
+            warnings.push(GlmWarning::Boundary);
+        }
+        warnings.extend(family.boundary_warning(&result.mu));
+
+        let link = family.link();
+        let residuals = (0..n)
+            .map(|i| (y[i] - result.mu[i]) / link.mu_eta(result.eta[i]))
+            .collect();
+        let rank = result.qr.rank;
+        let aic = family.aic(&y, &result.mu, &weights, result.deviance) + 2.0 * rank as f64;
+        Ok(GlmFit {
+            family: Arc::clone(&self.family),
+            data: self.data,
+            names: self.data.column_names().to_vec(),
+            coefficients: result.coefficients,
+            fitted_values: result.mu,
+            linear_predictors: result.eta,
+            residuals,
+            working_weights: result.working_weights,
+            prior_weights: weights,
+            offset,
+            y,
+            qr: result.qr,
+            deviance: result.deviance,
+            null_deviance,
+            aic,
+            rank,
+            df_residual: n - rank,
+            df_null: n - usize::from(intercept),
+            intercept,
+            iterations: result.iterations,
+            converged: result.converged,
+            boundary: result.boundary,
+            warnings,
+            control: self.control,
+        })
+    }
+}
+
+impl<'a> FitModel for GlmFitter<'a> {
+    /// Fit the model by IRLS and return its coefficients.
+    fn fit(&self) -> Result<RealMatrix, LmFitterError> {
+        match self.glm() {
+            Ok(fit) => Ok(fit.coefficients),
+            Err(GlmError::Fit(error)) => Err(error),
+            Err(error) => Err(LmFitterError::Glm(Box::new(error))),
+        }
+    }
+
+    fn x(&self) -> &RealMatrix {
+        self.data.x()
+    }
+
+    fn y(&self) -> &RealMatrix {
+        self.data.y()
+    }
+}
+
+fn check_length(name: &str, per: &str, expected: usize, found: usize) -> Result<(), GlmError> {
+    if expected == found {
+        Ok(())
+    } else {
+        Err(GlmError::LengthMismatch {
+            name: name.to_string(),
+            per: per.to_string(),
+            expected,
+            found,
+        })
+    }
+}
+
+/// Return the point where the profile `signed_root` reaches `cutoff` in absolute value on the
+/// side of `estimate` given by the sign of `step`. The step is doubled until the limit is
+/// bracketed, and the bracket is then bisected. Returns `NaN` if the limit cannot be bracketed
+/// or a refit fails.
+fn profile_limit(
+    signed_root: &dyn Fn(f64) -> Option<f64>,
+    estimate: f64,
+    step: f64,
+    cutoff: f64,
+) -> f64 {
+    if !step.is_finite() || step == 0.0 {
+        return f64::NAN;
+    }
+    let (mut inner, mut outer) = (estimate, estimate + step);
+    let mut bracketed = false;
+    for doubling in 0..MAX_DOUBLINGS {
+        match signed_root(outer) {
+            Some(z) if z.abs() >= cutoff => {
+                bracketed = true;
+                break;
+            }
+            Some(_) => {
+                inner = outer;
+                outer = estimate + step * 2f64.powi(doubling as i32 + 1);
+            }
+            None => return f64::NAN,
+        }
+    }
+    if !bracketed {
+        return f64::NAN;
+    }
+    while (outer - inner).abs() > 1e-8 * (1.0 + inner.abs()) {
+        let middle = (inner + outer) / 2.0;
+        match signed_root(middle) {
+            Some(z) if z.abs() >= cutoff => outer = middle,
+            Some(_) => inner = middle,
+            None => return f64::NAN,
+        }
+    }
+    (inner + outer) / 2.0
+}
+
+/// Run the IRLS iterations of `glm.fit` from the linear predictor `eta`. If `start` gives the
+/// coefficients behind `eta`, the first step can be halved back towards them, as R does with
+/// `coefold = start`.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn irls(
+    x: &RealMatrix,
+    y: &[f64],
+    weights: &[f64],
+    offset: &[f64],
+    family: &dyn Family,
+    eta: Vec<f64>,
+    start: Option<Array1<f64>>,
+    control: &GlmControl,
+) -> Result<Irls, GlmError> {
+    let n = x.n_rows();
+    let link = family.link();
+    let predict = |coefficients: &Array1<f64>| -> (Vec<f64>, Vec<f64>, f64) {
+        let fitted = x.values.dot(coefficients);
+        let eta: Vec<f64> = (0..n).map(|i| fitted[i] + offset[i]).collect();
+        let mu: Vec<f64> = eta.iter().map(|&e| link.inverse(e)).collect();
+        let deviance = (0..n)
+            .map(|i| family.deviance_residual(y[i], mu[i], weights[i]))
+            .sum();
+        (eta, mu, deviance)
+    };
+    let valid = |eta: &[f64], mu: &[f64]| {
+        eta.iter().all(|&e| link.valid_eta(e)) && mu.iter().all(|&m| family.valid_mu(m))
+    };
+
+    let mut eta = eta;
+    let mut mu: Vec<f64> = eta.iter().map(|&e| link.inverse(e)).collect();
+    if !valid(&eta, &mu) {
+        return Err(GlmError::InvalidResponse {
+            family: family.name().to_string(),
+            reason: "cannot find valid starting values: please specify some".to_string(),
+        });
+    }
+    let mut deviance_old: f64 = (0..n)
+        .map(|i| family.deviance_residual(y[i], mu[i], weights[i]))
+        .sum();
+    let mut coefficients_old = start;
+    let mut boundary = false;
+    let mut converged = false;
+    let mut iterations = 0;
+    let mut last = None;
+
+    while iterations < control.max_iter {
+        iterations += 1;
+        let mut z = RealMatrix::with_shape(n, 1);
+        let mut working_weights = vec![0.0; n];
+        for i in 0..n {
+            let derivative = link.mu_eta(eta[i]);
+            z.values[[i, 0]] = eta[i] - offset[i] + (y[i] - mu[i]) / derivative;
+            if weights[i] > 0.0 && derivative != 0.0 {
+                working_weights[i] = weights[i] * derivative.powi(2) / family.variance(mu[i]);
+            }
+        }
+        let (fit, _) = weighted_fit(x, &z, &working_weights)?;
+        if fit.coefficients.values.iter().any(|b| b.is_infinite()) {
+            return Err(GlmError::NoValidCoefficients);
+        }
+        let mut coefficients =
+            fit.coefficients
+                .values
+                .column(0)
+                .mapv(|b| if b.is_nan() { 0.0 } else { b });
+        let (mut eta_new, mut mu_new, mut deviance) = predict(&coefficients);
+
+        // Halve the step until the deviance is finite and eta and mu are valid.
+        let checks: [(&str, StepCheck); 2] = [
+            ("non-finite deviance", &|_, _, deviance| {
+                deviance.is_finite()
+            }),
+            ("invalid eta/mu", &|eta, mu, _| valid(eta, mu)),
+        ];
+        for (stage, ok) in checks {
+            if ok(&eta_new, &mu_new, deviance) {
+                continue;
+            }
+            let Some(old) = &coefficients_old else {
+                return Err(GlmError::NoValidCoefficients);
+            };
+            let mut halvings = 0;
+            while !ok(&eta_new, &mu_new, deviance) {
+                halvings += 1;
+                if halvings > control.max_iter {
+                    return Err(GlmError::StepHalvingFailed {
+                        stage: stage.to_string(),
+                    });
+                }
+                coefficients = (&coefficients + old) / 2.0;
+                (eta_new, mu_new, deviance) = predict(&coefficients);
+            }
+            boundary = true;
+        }
+
+        let mut estimates = fit.coefficients.clone();
+        for (j, b) in coefficients.iter().enumerate() {
+            if !estimates.values[[j, 0]].is_nan() {
+                estimates.values[[j, 0]] = *b;
+            }
+        }
+        (eta, mu) = (eta_new, mu_new);
+        let change = (deviance - deviance_old).abs() / (deviance.abs() + 0.1);
+        last = Some((estimates, working_weights, fit.qr, deviance));
+        if change < control.epsilon {
+            converged = true;
+            break;
+        }
+        deviance_old = deviance;
+        coefficients_old = Some(coefficients);
+    }
+
+    let (coefficients, working_weights, qr, deviance) =
+        last.ok_or(GlmError::NoValidCoefficients)?;
+    Ok(Irls {
+        coefficients,
+        eta,
+        mu,
+        working_weights,
+        qr,
+        deviance,
+        iterations,
+        converged,
+        boundary,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::family::{Binomial, Gamma, Gaussian, InverseGaussian, Link, Poisson, QuasiPoisson};
+    use crate::fitters::fit::LinearModelFitter;
+    use crate::fitters::qr_decomposition_fitter::QrDecompositionFitter;
+
+    fn design(n: usize) -> RealMatrix {
+        let x: Vec<f64> = (0..n).flat_map(|i| [1.0, i as f64 / 10.0]).collect();
+        RealMatrix::from_vec(x, n, Some(2))
+    }
+
+    fn data(y: Vec<f64>) -> Data {
+        let n = y.len();
+        Data::new(design(n), RealMatrix::from_vec(y, n, None))
+            .with_column_names(vec!["(Intercept)".to_string(), "t".to_string()])
+    }
+
+    /// Return X'(y - mu), which is zero at the maximum likelihood fit for canonical links.
+    fn score(fit: &GlmFit<'_>) -> Vec<f64> {
+        let x = design(fit.y.len());
+        (0..2)
+            .map(|j| {
+                (0..fit.y.len())
+                    .map(|i| x.values[[i, j]] * (fit.y[i] - fit.fitted_values[i]))
+                    .sum()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_gaussian_identity_is_least_squares() {
+        let y: Vec<f64> = (0..20)
+            .map(|i| 1.0 + 0.3 * i as f64 + (i as f64).sin())
+            .collect();
+        let data = data(y);
+        let fitter = GlmFitter::new(&data, Gaussian::default());
+        let fit = fitter.glm().unwrap();
+        let ols = QrDecompositionFitter::new(&data, None).fit().unwrap();
+        assert!((&fit.coefficients.values - &ols.values)
+            .iter()
+            .all(|d| d.abs() < 1e-10));
+        let rss: f64 = fit.residuals.iter().map(|r| r * r).sum();
+        assert!((fit.deviance - rss).abs() < 1e-10);
+        let expected_aic = 20.0 * ((2.0 * std::f64::consts::PI * rss / 20.0).ln() + 1.0) + 6.0;
+        assert!((fit.aic - expected_aic).abs() < 1e-10);
+        assert!(fit.converged && fit.warnings.is_empty() && fit.df_null == 19);
+        let expected = fit.coefficients.clone();
+        let coefficients = LinearModelFitter::Glm(fitter).fit().unwrap();
+        assert_eq!(coefficients, expected);
+    }
+
+    #[test]
+    fn test_canonical_fits_solve_the_score_equations() {
+        let counts: Vec<f64> = (0..30)
+            .map(|i| {
+                ((0.5 + 0.08 * i as f64).exp() + (i as f64 * 1.3).sin())
+                    .round()
+                    .max(0.0)
+            })
+            .collect();
+        let poisson = data(counts);
+        let fitter = GlmFitter::new(&poisson, Poisson::default());
+        let fit = fitter.glm().unwrap();
+        assert!(fit.converged && fit.deviance < fit.null_deviance);
+        assert!(score(&fit).iter().all(|s| s.abs() < 1e-6));
+        assert!((fit.coefficients.values[[1, 0]] - 0.8).abs() < 0.1);
+
+        let outcomes: Vec<f64> = (0..40)
+            .map(|i| ((i * 7) % 10 < i / 4) as u8 as f64)
+            .collect();
+        let binary = data(outcomes);
+        let fitter = GlmFitter::new(&binary, Binomial::default());
+        let fit = fitter.glm().unwrap();
+        assert!(fit.converged && fit.warnings.is_empty());
+        assert!(score(&fit).iter().all(|s| s.abs() < 1e-6));
+        assert!(fit.coefficients.values[[1, 0]] > 0.0);
+        let probit = GlmFitter::new(&binary, Binomial::new(Link::Probit).unwrap());
+        let cloglog = GlmFitter::new(&binary, Binomial::new(Link::Cloglog).unwrap());
+        assert!(probit.glm().unwrap().converged && cloglog.glm().unwrap().converged);
+    }
+
+    #[test]
+    fn test_positive_families_and_offsets_recover_exact_means() {
+        let means: Vec<f64> = (0..25).map(|i| (1.0 + 0.05 * i as f64).exp()).collect();
+        let data_gamma = data(means.clone());
+        for fitter in [
+            GlmFitter::new(&data_gamma, Gamma::new(Link::Log).unwrap()),
+            GlmFitter::new(&data_gamma, InverseGaussian::new(Link::Log).unwrap()),
+        ] {
+            let fit = fitter.glm().unwrap();
+            assert!(fit.converged && fit.deviance < 1e-12);
+            assert!((fit.coefficients.values[[1, 0]] - 0.5).abs() < 1e-8);
+        }
+        assert!(
+            GlmFitter::new(&data_gamma, Gamma::default())
+                .glm()
+                .unwrap()
+                .converged
+        );
+
+        let exposure: Vec<f64> = (0..25).map(|i| 1.0 + (i % 4) as f64).collect();
+        let rates: Vec<f64> = (0..25)
+            .map(|i| exposure[i] * (0.3 + 0.05 * i as f64).exp())
+            .collect();
+        let data_rates = data(rates);
+        let fitter = GlmFitterBuilder::default()
+            .data(&data_rates)
+            .family(Arc::new(Poisson::default()))
+            .offset(Some(exposure.iter().map(|e| e.ln()).collect()))
+            .build()
+            .unwrap();
+        let fit = fitter.glm().unwrap();
+        assert!((fit.coefficients.values[[0, 0]] - 0.3).abs() < 1e-8);
+        assert!(fit.deviance < 1e-12 && fit.null_deviance > 1.0);
+    }
+
+    #[test]
+    fn test_summary_tests_and_residuals() {
+        let counts: Vec<f64> = (0..30)
+            .map(|i| ((0.5 + 0.08 * i as f64).exp() + 3.0 * (i as f64 * 1.3).sin()).round())
+            .collect();
+        let data = data(counts);
+        let fitter = GlmFitter::new(&data, Poisson::default());
+        let fit = fitter.glm().unwrap();
+        let summary = fit.summary();
+        assert!(!summary.estimated_dispersion && summary.dispersion == 1.0);
+        let unscaled = unscaled_covariance(&fit.qr);
+        for j in 0..2 {
+            assert!((summary.std_errors[j].powi(2) - unscaled.values[[j, j]]).abs() < 1e-12);
+            let z = summary.coefficients[j] / summary.std_errors[j];
+            assert!((summary.p_values[j] - 2.0 * normal_cdf(-z.abs())).abs() < 1e-12);
+        }
+        assert!(summary.to_string().contains("Pr(>|z|)"));
+
+        let deviance: f64 = fit
+            .residuals_of(ResidualType::Deviance)
+            .iter()
+            .map(|r| r * r)
+            .sum();
+        assert!((deviance - fit.deviance).abs() < 1e-8);
+        let pearson: f64 = fit
+            .residuals_of(ResidualType::Pearson)
+            .iter()
+            .map(|r| r * r)
+            .sum();
+        let response = fit.residuals_of(ResidualType::Response);
+        assert!((response[3] - (fit.y[3] - fit.fitted_values[3])).abs() < 1e-12);
+
+        // The quasi-Poisson fit shares the coefficients, with standard errors inflated by the
+        // root of Pearson's dispersion and t tests.
+        let fitter = GlmFitter::new(&data, QuasiPoisson::default());
+        let quasi = fitter.glm().unwrap();
+        let quasi_summary = quasi.summary();
+        assert!(quasi_summary.estimated_dispersion && quasi.aic.is_nan());
+        // Like R, the dispersion uses the working weights of the last iteration, which lag the
+        // fitted means by one step.
+        assert!((quasi_summary.dispersion - pearson / 28.0).abs() < 1e-6);
+        assert!(quasi_summary.dispersion > 1.0);
+        for j in 0..2 {
+            assert!((quasi_summary.coefficients[j] - summary.coefficients[j]).abs() < 1e-10);
+            let inflated = summary.std_errors[j] * quasi_summary.dispersion.sqrt();
+            assert!((quasi_summary.std_errors[j] - inflated).abs() < 1e-8);
+            let t = quasi_summary.statistics[j].abs();
+            assert!((quasi_summary.p_values[j] - 2.0 * t_upper_tail(t, 28.0)).abs() < 1e-12);
+        }
+        assert!(quasi_summary.to_string().contains("AIC: NA"));
+    }
+
+    #[test]
+    fn test_profile_intervals() {
+        // The Gaussian profile is exactly quadratic, so the limits are b +- z se.
+        let y: Vec<f64> = (0..20)
+            .map(|i| 1.0 + 0.3 * i as f64 + (i as f64).sin())
+            .collect();
+        let gaussian = data(y);
+        let fitter = GlmFitter::new(&gaussian, Gaussian::default());
+        let fit = fitter.glm().unwrap();
+        let summary = fit.summary();
+        let z = normal_quantile(0.975);
+        for (j, (lower, upper)) in fit.confint(0.95).unwrap().into_iter().enumerate() {
+            let (b, se) = (summary.coefficients[j], summary.std_errors[j]);
+            assert!((lower - (b - z * se)).abs() < 1e-6 && (upper - (b + z * se)).abs() < 1e-6);
+        }
+        assert!(matches!(
+            fit.confint(1.0),
+            Err(GlmError::InvalidLevel { .. })
+        ));
+
+        let outcomes: Vec<f64> = (0..40)
+            .map(|i| ((i * 7) % 10 < i / 4) as u8 as f64)
+            .collect();
+        let binary = data(outcomes);
+        let fitter = GlmFitter::new(&binary, Binomial::default());
+        let fit = fitter.glm().unwrap();
+        let (lower, upper) = fit.confint(0.95).unwrap()[1];
+        let (b, se) = (fit.summary().coefficients[1], fit.summary().std_errors[1]);
+        assert!(lower < b && b < upper);
+        assert!((lower - (b - z * se)).abs() < 0.5 * se);
+        assert!((upper - lower - 2.0 * z * se).abs() > 1e-3);
+
+        // Under separation the slope is unbounded above.
+        let separated = data((0..20).map(|i| (i >= 10) as u8 as f64).collect());
+        let fitter = GlmFitter::new(&separated, Binomial::default());
+        let fit = fitter.glm().unwrap();
+        assert!(fit.confint(0.95).unwrap()[1].1.is_nan());
+    }
+
+    #[test]
+    fn test_start_values_bound_the_first_step() {
+        // An identity-link Poisson fit whose first unrestricted step goes below zero: from the
+        // starting values, the step is halved back towards them instead of failing.
+        let counts = data(vec![
+            0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 1.0, 3.0, 6.0, 12.0,
+        ]);
+        let family = Poisson::new(Link::Identity).unwrap();
+        let mut fitter = GlmFitter::new(&counts, family);
+        fitter.start = Some(vec![0.5, 0.1]);
+        let fit = fitter.glm().unwrap();
+        assert!(fit.boundary && fit.warnings.contains(&GlmWarning::Boundary));
+        assert!(fit.fitted_values.iter().all(|&mu| mu > 0.0));
+
+        fitter.start = None;
+        assert!(matches!(fitter.glm(), Err(GlmError::NoValidCoefficients)));
+    }
+
+    #[test]
+    fn test_fitters_and_fits_can_be_shared_between_threads() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<GlmFitter<'_>>();
+        assert_send_sync::<GlmFit<'_>>();
+        assert_send_sync::<LinearModelFitter<'_>>();
+    }
+
+    #[test]
+    fn test_separation_is_reported() {
+        let outcomes: Vec<f64> = (0..20).map(|i| (i >= 10) as u8 as f64).collect();
+        let separated = data(outcomes);
+        let fitter = GlmFitter::new(&separated, Binomial::default());
+        let fit = fitter.glm().unwrap();
+        assert!(fit
+            .warnings
+            .contains(&GlmWarning::FittedProbabilitiesZeroOrOne));
+        assert!(matches!(
+            GlmFitter::new(&data(vec![-1.0, 2.0, 3.0]), Poisson::default()).glm(),
+            Err(GlmError::InvalidResponse { .. })
+        ));
+    }
+}
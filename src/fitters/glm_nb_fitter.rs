@@ -0,0 +1,449 @@
+//! This module contains the negative binomial regression fitter, which implements the
+//! `FitModel` trait for the `LinearModelFitter` enum, following `MASS::glm.nb`.
+//!
+//! The counts are first fitted as Poisson, and theta is estimated by maximum likelihood from
+//! the fitted means with Newton's method on the score of theta. The fitter then alternates an
+//! IRLS fit of the mean model with the negative binomial family at the current theta and a new
+//! estimate of theta, until both the log-likelihood and theta settle. The Poisson fit is kept
+//! for the likelihood-ratio test of overdispersion.
+
+// src/fitters/glm_nb_fitter.rs
+
+use super::fit::FitModel;
+use super::glm_fitter::{GlmControl, GlmFit, GlmFitter, GlmSummary};
+use crate::anova::{format_number, format_p_value};
+use crate::distributions::{chi_squared_upper_tail, digamma, log_gamma, trigamma};
+use crate::errors::{GlmError, LmFitterError};
+use crate::family::{Family, Link, NegativeBinomial, Poisson};
+use crate::types::{Data, RealMatrix};
+use derive_builder::Builder;
+use std::fmt;
+use std::sync::Arc;
+
+/// An enum representing the warnings `glm.nb` and `theta.ml` can raise about a fit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NbWarning {
+    /// Newton's method for theta did not converge within the iteration limit.
+    ThetaIterationLimit,
+    /// The estimate of theta was negative and was truncated at zero.
+    ThetaTruncated,
+    /// The alternation between the mean model and theta did not converge.
+    AlternationLimit,
+}
+
+impl fmt::Display for NbWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            NbWarning::ThetaIterationLimit => "theta.ml: iteration limit reached",
+            NbWarning::ThetaTruncated => "theta.ml: estimate truncated at zero",
+            NbWarning::AlternationLimit => "glm.nb: alternation limit reached",
+        })
+    }
+}
+
+/// The likelihood-ratio test of the Poisson model against the negative binomial model.
+///
+/// The Poisson model is the limit of the negative binomial as theta goes to infinity, on the
+/// boundary of the parameter space, so the statistic is compared with an equal mixture of a
+/// point mass at zero and a chi-squared with one degree of freedom, like `pscl::odTest`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OverdispersionTest {
+    /// The log-likelihood of the Poisson model.
+    pub poisson_log_lik: f64,
+    /// The log-likelihood of the negative binomial model.
+    pub log_lik: f64,
+    /// Twice the difference of the log-likelihoods.
+    pub statistic: f64,
+    /// Half the upper tail probability of the statistic under a chi-squared with one degree of
+    /// freedom.
+    pub p_value: f64,
+}
+
+impl fmt::Display for OverdispersionTest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Likelihood ratio test of H0: Poisson, as restricted NB model"
+        )?;
+        writeln!(
+            f,
+            "Chi-Square Test Statistic = {} p-value = {}",
+            format_number(self.statistic),
+            format_p_value(self.p_value)
+        )
+    }
+}
+
+/// The result of a negative binomial regression fit.
+#[derive(Debug, Clone)]
+pub struct GlmNbFit<'a> {
+    /// The fit of the mean model at the final theta. Its AIC counts theta as a parameter.
+    pub glm: GlmFit<'a>,
+    /// The maximum likelihood estimate of theta.
+    pub theta: f64,
+    /// The standard error of theta, from the observed information.
+    pub theta_se: f64,
+    /// Twice the maximized log-likelihood.
+    pub two_log_lik: f64,
+    /// The log-likelihood of the Poisson fit the iterations started from.
+    pub poisson_log_lik: f64,
+    /// The number of alternations between the mean model and theta.
+    pub iterations: usize,
+    /// `true` if the alternations converged.
+    pub converged: bool,
+    /// The warnings R would print about theta and the alternations.
+    pub warnings: Vec<NbWarning>,
+}
+
+impl GlmNbFit<'_> {
+    /// Return the summary of the mean model, with z tests since the dispersion is fixed at 1
+    /// given theta.
+    pub fn summary(&self) -> GlmSummary {
+        self.glm.summary()
+    }
+
+    /// Return the likelihood-ratio test of the Poisson model against this fit.
+    pub fn overdispersion_test(&self) -> OverdispersionTest {
+        let log_lik = self.two_log_lik / 2.0;
+        let statistic = (2.0 * (log_lik - self.poisson_log_lik)).max(0.0);
+        let p_value = 0.5 * chi_squared_upper_tail(statistic, 1.0);
+        OverdispersionTest {
+            poisson_log_lik: self.poisson_log_lik,
+            log_lik,
+            statistic,
+            p_value,
+        }
+    }
+}
+
+impl fmt::Display for GlmNbFit<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.glm)?;
+        writeln!(f)?;
+        writeln!(f, "              Theta:  {}", format_number(self.theta))?;
+        writeln!(f, "          Std. Err.:  {}", format_number(self.theta_se))?;
+        writeln!(
+            f,
+            " 2 x log-likelihood:  {}",
+            format_number(self.two_log_lik)
+        )?;
+        for warning in &self.warnings {
+            writeln!(f, "Warning: {warning}")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Builder)]
+pub struct GlmNbFitter<'a> {
+    pub data: &'a Data,
+    /// The link of the mean model: log, sqrt or identity.
+    #[builder(default = "Link::Log")]
+    pub link: Link,
+    /// A known component of the linear predictor, one entry per observation.
+    #[builder(default)]
+    pub offset: Option<Vec<f64>>,
+    /// A starting value of theta for the first fit of the mean model, which is otherwise a
+    /// Poisson fit.
+    #[builder(default)]
+    pub init_theta: Option<f64>,
+    /// The convergence settings of both the IRLS fits and the alternations.
+    #[builder(default)]
+    pub control: GlmControl,
+}
+
+/// The estimate of theta from `theta_ml`.
+struct ThetaEstimate {
+    theta: f64,
+    se: f64,
+    warnings: Vec<NbWarning>,
+}
+
+impl<'a> GlmNbFitter<'a> {
+    /// Return a new instance of the `GlmNbFitter` struct with the log link and the defaults of
+    /// `glm.nb`.
+    pub fn new(data: &'a Data) -> Self {
+        Self {
+            data,
+            link: Link::Log,
+            offset: None,
+            init_theta: None,
+            control: GlmControl::default(),
+        }
+    }
+
+    /// Return the x matrix from the data struct.
+    pub fn x(&self) -> &RealMatrix {
+        &self.data.x
+    }
+
+    /// Return the y matrix from the data struct.
+    pub fn y(&self) -> &RealMatrix {
+        &self.data.y
+    }
+
+    /// Fit the negative binomial model, alternating the mean model and theta.
+    ///
+    /// # Errors
+    /// Returns `GlmError::UnsupportedLink` for links other than log, sqrt and identity,
+    /// `GlmError::InvalidTheta` if `init_theta` or an estimate of theta is not positive, and
+    /// the errors of `GlmFitter::glm` for the fits of the mean model.
+    pub fn glm_nb(&self) -> Result<GlmNbFit<'a>, GlmError> {
+        let poisson = self.mean_model(Arc::new(Poisson::new(self.link)?), None)?;
+        let poisson_log_lik = -0.5 * (poisson.aic - 2.0 * poisson.rank as f64);
+        let mut fit = match self.init_theta {
+            Some(theta) => {
+                let family = NegativeBinomial::new(theta, self.link)?;
+                self.mean_model(Arc::new(family), None)?
+            }
+            None => poisson,
+        };
+
+        let (y, weights) = (fit.y.clone(), fit.prior_weights.clone());
+        let mut estimate = theta_ml(&y, &fit.fitted_values, &weights, self.control.max_iter);
+        let mut log_lik = nb_log_lik(estimate.theta, &fit.fitted_values, &y, &weights);
+        let d1 = (2.0 * fit.df_residual.max(1) as f64).sqrt();
+        let (mut previous, mut change) = (log_lik + 2.0 * d1, 1.0);
+        let settled = |previous: f64, log_lik: f64, change: f64| {
+            (previous - log_lik).abs() / d1 + change.abs() <= self.control.epsilon
+        };
+        let mut iterations = 0;
+        while iterations < self.control.max_iter && !settled(previous, log_lik, change) {
+            iterations += 1;
+            let family = NegativeBinomial::new(estimate.theta, self.link)?;
+            let start = fit
+                .coefficients
+                .values
+                .column(0)
+                .mapv(|b| if b.is_nan() { 0.0 } else { b })
+                .to_vec();
+            fit = self.mean_model(Arc::new(family), Some(start))?;
+            let theta = estimate.theta;
+            estimate = theta_ml(&y, &fit.fitted_values, &weights, self.control.max_iter);
+            change = theta - estimate.theta;
+            previous = log_lik;
+            log_lik = nb_log_lik(estimate.theta, &fit.fitted_values, &y, &weights);
+        }
+        let converged = settled(previous, log_lik, change);
+        let two_log_lik = 2.0 * log_lik;
+        fit.aic = -two_log_lik + 2.0 * fit.rank as f64 + 2.0;
+
+        let mut warnings = estimate.warnings;
+        if !converged {
+            warnings.push(NbWarning::AlternationLimit);
+        }
+        Ok(GlmNbFit {
+            glm: fit,
+            theta: estimate.theta,
+            theta_se: estimate.se,
+            two_log_lik,
+            poisson_log_lik,
+            iterations,
+            converged,
+            warnings,
+        })
+    }
+
+    /// Fit the mean model with `family`, from `start` if given.
+    fn mean_model(
+        &self,
+        family: Arc<dyn Family + Send + Sync>,
+        start: Option<Vec<f64>>,
+    ) -> Result<GlmFit<'a>, GlmError> {
+        let fitter = GlmFitter {
+            data: self.data,
+            family,
+            offset: self.offset.clone(),
+            start,
+            control: self.control,
+        };
+        fitter.glm()
+    }
+}
+
+impl<'a> FitModel for GlmNbFitter<'a> {
+    /// Fit the negative binomial model and return the coefficients of the mean model.
+    fn fit(&self) -> Result<RealMatrix, LmFitterError> {
+        match self.glm_nb() {
+            Ok(fit) => Ok(fit.glm.coefficients),
+            Err(GlmError::Fit(error)) => Err(error),
+            Err(error) => Err(LmFitterError::Glm(Box::new(error))),
+        }
+    }
+
+    fn x(&self) -> &RealMatrix {
+        self.data.x()
+    }
+
+    fn y(&self) -> &RealMatrix {
+        self.data.y()
+    }
+}
+
+/// Return the negative binomial log-likelihood of the counts `y` with means `mu`.
+fn nb_log_lik(theta: f64, mu: &[f64], y: &[f64], weights: &[f64]) -> f64 {
+    (0..y.len())
+        .map(|i| {
+            let (y, mu) = (y[i], mu[i]);
+            let zero = if y == 0.0 { 1.0 } else { 0.0 };
+            weights[i]
+                * (log_gamma(theta + y) - log_gamma(theta) - log_gamma(y + 1.0)
+                    + theta * theta.ln()
+                    + y * (mu + zero).ln()
+                    - (theta + y) * (theta + mu).ln())
+        })
+        .sum()
+}
+
+/// Estimate theta by maximum likelihood for known means, like `MASS::theta.ml`: Newton's
+/// method on the score, started from the method-of-moments estimate, with the standard error
+/// from the information at the last step.
+fn theta_ml(y: &[f64], mu: &[f64], weights: &[f64], limit: usize) -> ThetaEstimate {
+    let eps = f64::EPSILON.powf(0.25);
+    let n: f64 = weights.iter().sum();
+    let moments: f64 = (0..y.len())
+        .map(|i| weights[i] * (y[i] / mu[i] - 1.0).powi(2))
+        .sum();
+    let score = |theta: f64| -> f64 {
+        (0..y.len())
+            .map(|i| {
+                weights[i]
+                    * (digamma(theta + y[i]) - digamma(theta) + theta.ln() + 1.0
+                        - (theta + mu[i]).ln()
+                        - (y[i] + theta) / (mu[i] + theta))
+            })
+            .sum()
+    };
+    let info = |theta: f64| -> f64 {
+        (0..y.len())
+            .map(|i| {
+                weights[i]
+                    * (-trigamma(theta + y[i]) + trigamma(theta) - 1.0 / theta
+                        + 2.0 / (mu[i] + theta)
+                        - (y[i] + theta) / (mu[i] + theta).powi(2))
+            })
+            .sum()
+    };
+
+    let mut theta = n / moments;
+    let mut information = f64::NAN;
+    let mut step: f64 = 1.0;
+    let mut iterations = 0;
+    while iterations < limit && step.abs() > eps {
+        iterations += 1;
+        theta = theta.abs();
+        information = info(theta);
+        step = score(theta) / information;
+        theta += step;
+    }
+
+    let mut warnings = Vec::new();
+    if theta < 0.0 {
+        theta = 0.0;
+        warnings.push(NbWarning::ThetaTruncated);
+    }
+    if step.abs() > eps {
+        warnings.push(NbWarning::ThetaIterationLimit);
+    }
+    ThetaEstimate {
+        theta,
+        se: (1.0 / information).sqrt(),
+        warnings,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fitters::fit::LinearModelFitter;
+
+    /// Counts with means exp(1 + 0.5 t) drawn from a negative binomial with theta = 2 by
+    /// inverting its distribution function at fixed quantiles.
+    fn overdispersed() -> Data {
+        let n = 60;
+        let theta: f64 = 2.0;
+        let mut x = Vec::with_capacity(2 * n);
+        let mut y = Vec::with_capacity(n);
+        for i in 0..n {
+            let t = (i % 10) as f64 / 5.0;
+            let mu = (1.0 + 0.5 * t).exp();
+            let u = ((i * 37) % n) as f64 / n as f64 + 0.5 / n as f64;
+            let p = theta / (theta + mu);
+            let (mut k, mut probability) = (0.0, p.powf(theta));
+            let mut cumulative = probability;
+            while cumulative < u {
+                probability *= (k + theta) / (k + 1.0) * (1.0 - p);
+                k += 1.0;
+                cumulative += probability;
+            }
+            x.extend([1.0, t]);
+            y.push(k);
+        }
+        Data::new(
+            RealMatrix::from_vec(x, n, Some(2)),
+            RealMatrix::from_vec(y, n, None),
+        )
+        .with_column_names(vec!["(Intercept)".to_string(), "t".to_string()])
+    }
+
+    #[test]
+    fn test_theta_maximizes_the_likelihood() {
+        let data = overdispersed();
+        let fitter = GlmNbFitter::new(&data);
+        let fit = fitter.glm_nb().unwrap();
+        assert!(fit.converged && fit.warnings.is_empty());
+        assert!(fit.theta > 0.8 && fit.theta < 5.0, "{}", fit.theta);
+        assert!(fit.theta_se > 0.0 && fit.theta_se < fit.theta);
+        assert!((fit.glm.coefficients.values[[1, 0]] - 0.5).abs() < 0.3);
+
+        // The log-likelihood is maximal in theta at the fitted means.
+        let (y, w) = (&fit.glm.y, &fit.glm.prior_weights);
+        let best = nb_log_lik(fit.theta, &fit.glm.fitted_values, y, w);
+        assert!((2.0 * best - fit.two_log_lik).abs() < 1e-8);
+        for theta in [fit.theta * 0.9, fit.theta * 1.1] {
+            assert!(nb_log_lik(theta, &fit.glm.fitted_values, y, w) < best);
+        }
+        let expected_aic = -fit.two_log_lik + 2.0 * 2.0 + 2.0;
+        assert!((fit.glm.aic - expected_aic).abs() < 1e-10);
+        assert_eq!(fit.summary().dispersion, 1.0);
+
+        let test = fit.overdispersion_test();
+        assert!(test.statistic > 10.0 && test.p_value < 0.001);
+
+        let coefficients = fit.glm.coefficients.clone();
+        assert_eq!(
+            LinearModelFitter::GlmNb(fitter).fit().unwrap(),
+            coefficients
+        );
+    }
+
+    #[test]
+    fn test_theta_ml_recovers_a_known_theta() {
+        // With the means known, theta.ml solves the score equation for theta.
+        let data = overdispersed();
+        let y: Vec<f64> = data.y().values.column(0).to_vec();
+        let mu: Vec<f64> = (0..y.len())
+            .map(|i| (1.0 + 0.5 * data.x().values[[i, 1]]).exp())
+            .collect();
+        let weights = vec![1.0; y.len()];
+        let estimate = theta_ml(&y, &mu, &weights, 25);
+        assert!(estimate.warnings.is_empty());
+        assert!((estimate.theta - 2.0).abs() < 1.0);
+
+        // A limit that allows exactly the steps Newton's method needs does not warn.
+        let steps = (1..25)
+            .find(|&limit| theta_ml(&y, &mu, &weights, limit).warnings.is_empty())
+            .unwrap();
+        assert_eq!(theta_ml(&y, &mu, &weights, steps).theta, estimate.theta);
+        assert_eq!(
+            theta_ml(&y, &mu, &weights, steps - 1).warnings,
+            vec![NbWarning::ThetaIterationLimit]
+        );
+        let pi_squared = std::f64::consts::PI.powi(2);
+        assert!((trigamma(1.0) - pi_squared / 6.0).abs() < 1e-12);
+        assert!(matches!(
+            NegativeBinomial::new(-1.0, Link::Log),
+            Err(GlmError::InvalidTheta { .. })
+        ));
+    }
+}
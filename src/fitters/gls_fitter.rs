@@ -0,0 +1,1000 @@
+//! This module contains the generalized least squares fitter, which implements the `FitModel`
+//! trait for the `LinearModelFitter` enum, like `nlme::gls`.
+//!
+//! The errors have covariance sigma^2 Lambda, where Lambda is block diagonal over groups of
+//! observations. Within a group, Lambda = S C S, with C a correlation matrix of the chosen
+//! structure, indexed by the position of each observation within its group in data order, and
+//! S a diagonal matrix of standard deviation multipliers from a variance function. Given the
+//! parameters of C and S, each group is whitened and the coefficients and sigma are profiled out
+//! by the QR solver. Autoregressive and ARMA errors are whitened in O(n) by the innovations
+//! algorithm, which gives the one-step prediction errors of the process; compound symmetry and
+//! unstructured correlations use the Cholesky factor of the group block. The profiled ML or REML
+//! log-likelihood is then maximised over the remaining parameters by Nelder-Mead, on an
+//! unconstrained scale that keeps every correlation matrix positive definite.
+
+// src/fitters/gls_fitter.rs
+
+use super::fit::FitModel;
+use crate::anova::{format_number, write_coefficients, write_table};
+use crate::errors::LmFitterError;
+use crate::least_squares::{dqrls, LeastSquaresFit};
+use crate::optim::{nelder_mead, NelderMeadControl};
+use crate::types::{Data, RealMatrix, Tolerance};
+use crate::vcov::unscaled_covariance;
+use derive_builder::Builder;
+use std::f64::consts::PI;
+use std::fmt;
+
+/// An enum representing the within-group correlation structures of the errors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Correlation {
+    /// Uncorrelated errors.
+    #[default]
+    Independent,
+    /// A first-order autoregressive process, cor(e_i, e_j) = phi^|i - j|, like `corAR1`.
+    Ar1,
+    /// An ARMA(p, q) process, like `corARMA`. Its parameters are the autoregressive
+    /// coefficients followed by the moving average coefficients.
+    Arma { p: usize, q: usize },
+    /// A common correlation rho between any two errors of a group, like `corCompSymm`.
+    CompoundSymmetry,
+    /// A general correlation matrix between the positions within a group, like `corSymm`.
+    Unstructured,
+}
+
+impl Correlation {
+    /// Return the name of the structure.
+    fn name(&self) -> String {
+        match self {
+            Correlation::Independent => "corIdent".to_string(),
+            Correlation::Ar1 => "AR(1)".to_string(),
+            Correlation::Arma { p, q } => format!("ARMA({},{})", p, q),
+            Correlation::CompoundSymmetry => "Compound symmetry".to_string(),
+            Correlation::Unstructured => "General correlation".to_string(),
+        }
+    }
+
+    /// Return the names of the parameters for groups of at most `size` observations.
+    fn parameter_names(&self, size: usize) -> Vec<String> {
+        match self {
+            Correlation::Independent => vec![],
+            Correlation::Ar1 => vec!["Phi".to_string()],
+            Correlation::Arma { p, q } => (1..=*p)
+                .map(|i| format!("Phi{}", i))
+                .chain((1..=*q).map(|j| format!("Theta{}", j)))
+                .collect(),
+            Correlation::CompoundSymmetry => vec!["Rho".to_string()],
+            Correlation::Unstructured => (1..size)
+                .flat_map(|i| (0..i).map(move |j| format!("cor({},{})", j + 1, i + 1)))
+                .collect(),
+        }
+    }
+
+    /// Map unconstrained values to the parameters of the structure. Autoregressive and moving
+    /// average coefficients come from partial autocorrelations tanh(u) by the Durbin-Levinson
+    /// recursion, which keeps the process stationary and invertible. The unstructured
+    /// correlations are built from a Cholesky factor whose rows have unit length.
+    fn natural(&self, u: &[f64], size: usize) -> Vec<f64> {
+        match self {
+            Correlation::Independent => vec![],
+            Correlation::Ar1 => vec![u[0].tanh()],
+            Correlation::Arma { p, .. } => {
+                let mut parameters = durbin_levinson(&u[..*p]);
+                let ma = durbin_levinson(&u[*p..]);
+                parameters.extend(ma.iter().map(|c| -c));
+                parameters
+            }
+            Correlation::CompoundSymmetry => {
+                let lower = -1.0 / (size.max(2) - 1) as f64;
+                vec![lower + (1.0 - lower) / (1.0 + (-u[0]).exp())]
+            }
+            Correlation::Unstructured => {
+                let mut factor = RealMatrix::with_shape(size, size);
+                factor.values[[0, 0]] = 1.0;
+                let mut next = 0;
+                for i in 1..size {
+                    let mut remaining: f64 = 1.0;
+                    for j in 0..i {
+                        factor.values[[i, j]] = u[next].tanh() * remaining.sqrt();
+                        remaining -= factor.values[[i, j]].powi(2);
+                        next += 1;
+                    }
+                    factor.values[[i, i]] = remaining.sqrt();
+                }
+                let correlation = factor.dot(&factor.transpose());
+                (1..size)
+                    .flat_map(|i| (0..i).map(move |j| (i, j)))
+                    .map(|(i, j)| correlation.values[[i, j]])
+                    .collect()
+            }
+        }
+    }
+
+    /// Return the whitening transform of groups of at most `size` observations with the given
+    /// parameters, or `None` if they do not give a positive definite correlation matrix.
+    fn whitener(&self, parameters: &[f64], size: usize) -> Option<Whitener> {
+        let innovations = match self {
+            Correlation::Independent => Innovations::new(&[], &[], size)?,
+            Correlation::Ar1 => Innovations::new(parameters, &[], size)?,
+            Correlation::Arma { p, .. } => {
+                Innovations::new(&parameters[..*p], &parameters[*p..], size)?
+            }
+            Correlation::CompoundSymmetry | Correlation::Unstructured => {
+                let factor = self.matrix(parameters, size).cholesky()?;
+                return Some(Whitener::Cholesky(factor));
+            }
+        };
+        Some(Whitener::Innovations(innovations))
+    }
+
+    /// Return the `size` x `size` correlation matrix of a group with compound symmetry or an
+    /// unstructured correlation.
+    fn matrix(&self, parameters: &[f64], size: usize) -> RealMatrix {
+        let mut matrix = RealMatrix::with_shape(size, size);
+        let mut next = 0;
+        for i in 0..size {
+            matrix.values[[i, i]] = 1.0;
+            for j in 0..i {
+                let value = match self {
+                    Correlation::CompoundSymmetry => parameters[0],
+                    Correlation::Unstructured => {
+                        next += 1;
+                        parameters[next - 1]
+                    }
+                    _ => 0.0,
+                };
+                matrix.values[[i, j]] = value;
+                matrix.values[[j, i]] = value;
+            }
+        }
+        matrix
+    }
+}
+
+/// The map from the errors of a group to uncorrelated errors with unit variance, L^-1 e for the
+/// Cholesky factor L of the group's correlation matrix.
+enum Whitener {
+    /// The Cholesky factor of the correlation matrix of the largest group. The correlation of a
+    /// smaller group is a leading block, whose factor is the leading block of this one.
+    Cholesky(RealMatrix),
+    /// The one-step predictions of an ARMA process.
+    Innovations(Innovations),
+}
+
+impl Whitener {
+    /// Return the whitened values of a group.
+    fn whiten(&self, values: &[f64]) -> Vec<f64> {
+        match self {
+            Whitener::Cholesky(factor) => {
+                let mut whitened: Vec<f64> = Vec::with_capacity(values.len());
+                for (a, value) in values.iter().enumerate() {
+                    let known: f64 = (0..a).map(|b| factor.values[[a, b]] * whitened[b]).sum();
+                    whitened.push((value - known) / factor.values[[a, a]]);
+                }
+                whitened
+            }
+            Whitener::Innovations(innovations) => innovations.whiten(values),
+        }
+    }
+
+    /// Return the log-determinant of the correlation matrix of a group of `k` observations.
+    fn log_det(&self, k: usize) -> f64 {
+        match self {
+            Whitener::Cholesky(factor) => (0..k).map(|a| 2.0 * factor.values[[a, a]].ln()).sum(),
+            Whitener::Innovations(innovations) => {
+                innovations.variances[..k].iter().map(|v| v.ln()).sum()
+            }
+        }
+    }
+}
+
+/// The innovations algorithm of Brockwell and Davis for the ARMA process
+/// e_t = sum phi_i e_{t-i} + a_t + sum theta_j a_{t-j}. Beyond the first m = max(p, q)
+/// observations, the prediction of e_t needs only the previous p values and q prediction
+/// errors, so a group of k observations is whitened in O(k (p + q)) after O(k q^2) setup.
+struct Innovations {
+    phi: Vec<f64>,
+    /// The order m = max(p, q).
+    order: usize,
+    /// The coefficients of the previous prediction errors e_{t-1} - ê_{t-1}, e_{t-2} - ê_{t-2},
+    /// ... in the prediction of observation t.
+    coefficients: Vec<Vec<f64>>,
+    /// The prediction error variances, relative to the variance of the process.
+    variances: Vec<f64>,
+}
+
+impl Innovations {
+    /// Run the recursion for groups of at most `size` observations. Returns `None` if the
+    /// process has no finite autocovariances or a prediction error variance is not positive.
+    fn new(phi: &[f64], theta: &[f64], size: usize) -> Option<Self> {
+        let (p, q) = (phi.len(), theta.len());
+        let m = p.max(q);
+        let gamma = arma_autocovariances(phi, theta, 2 * m + 1)?;
+        let theta_at = |j: usize| match j {
+            0 => 1.0,
+            j if j <= q => theta[j - 1],
+            _ => 0.0,
+        };
+        // The covariances of the transformed process, e_t for t < m and phi(B) e_t after.
+        let kappa = |a: usize, b: usize| -> f64 {
+            let (low, high) = (a.min(b), a.max(b));
+            let h = high - low;
+            if high < m {
+                gamma[h]
+            } else if low < m {
+                gamma[h]
+                    - (1..=p)
+                        .map(|r| phi[r - 1] * gamma[r.abs_diff(h)])
+                        .sum::<f64>()
+            } else if h <= q {
+                (0..=q - h).map(|r| theta_at(r) * theta_at(r + h)).sum()
+            } else {
+                0.0
+            }
+        };
+
+        let mut coefficients: Vec<Vec<f64>> = Vec::with_capacity(size);
+        let mut variances: Vec<f64> = Vec::with_capacity(size);
+        for t in 0..size {
+            // Only the errors of observations first..t enter the prediction of observation t.
+            let first = if t >= m { t.saturating_sub(q) } else { 0 };
+            let mut current = vec![0.0; t - first];
+            for k in first..t {
+                let earlier = &coefficients[k];
+                let known: f64 = (first..k)
+                    .filter(|&j| k - j <= earlier.len())
+                    .map(|j| earlier[k - j - 1] * current[t - j - 1] * variances[j])
+                    .sum();
+                current[t - k - 1] = (kappa(t, k) - known) / variances[k];
+            }
+            let variance = kappa(t, t)
+                - (first..t)
+                    .map(|j| current[t - j - 1].powi(2) * variances[j])
+                    .sum::<f64>();
+            if variance.is_nan() || variance <= 0.0 {
+                return None;
+            }
+            coefficients.push(current);
+            variances.push(variance);
+        }
+        variances.iter_mut().for_each(|v| *v /= gamma[0]);
+        Some(Innovations {
+            phi: phi.to_vec(),
+            order: m,
+            coefficients,
+            variances,
+        })
+    }
+
+    /// Return the prediction errors of the values, each divided by its standard deviation.
+    fn whiten(&self, values: &[f64]) -> Vec<f64> {
+        let mut errors: Vec<f64> = Vec::with_capacity(values.len());
+        for (t, value) in values.iter().enumerate() {
+            let mut prediction: f64 = self.coefficients[t]
+                .iter()
+                .enumerate()
+                .map(|(j, c)| c * errors[t - j - 1])
+                .sum();
+            if t >= self.order {
+                prediction += (1..=self.phi.len())
+                    .map(|r| self.phi[r - 1] * values[t - r])
+                    .sum::<f64>();
+            }
+            errors.push(value - prediction);
+        }
+        errors
+            .iter()
+            .zip(&self.variances)
+            .map(|(e, v)| e / v.sqrt())
+            .collect()
+    }
+}
+
+/// An enum representing the variance functions of the errors, which scale the standard deviation
+/// of each observation.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum VarianceFunction {
+    /// A common variance.
+    #[default]
+    Constant,
+    /// A separate standard deviation per stratum, like `varIdent`. Strata are coded 0, 1, ...
+    /// per observation, and the multiplier of stratum 0 is fixed at one.
+    Ident { strata: Vec<usize> },
+    /// A standard deviation proportional to |v|^delta for a covariate v, like `varPower`.
+    Power { covariate: Vec<f64> },
+}
+
+impl VarianceFunction {
+    /// Return the name of the variance function.
+    fn name(&self) -> &'static str {
+        match self {
+            VarianceFunction::Constant => "Constant variance",
+            VarianceFunction::Ident { .. } => "Different standard deviations per stratum",
+            VarianceFunction::Power { .. } => "Power of variance covariate",
+        }
+    }
+
+    /// Return the names of the parameters.
+    fn parameter_names(&self) -> Vec<String> {
+        match self {
+            VarianceFunction::Constant => vec![],
+            VarianceFunction::Ident { strata } => (1..=strata.iter().copied().max().unwrap_or(0))
+                .map(|s| s.to_string())
+                .collect(),
+            VarianceFunction::Power { .. } => vec!["power".to_string()],
+        }
+    }
+
+    /// Map unconstrained values to the parameters: positive multipliers for `Ident`, and the
+    /// power itself for `Power`.
+    fn natural(&self, u: &[f64]) -> Vec<f64> {
+        match self {
+            VarianceFunction::Ident { .. } => u.iter().map(|v| v.exp()).collect(),
+            _ => u.to_vec(),
+        }
+    }
+
+    /// Return the standard deviation multiplier of observation `i`.
+    fn scale(&self, parameters: &[f64], i: usize) -> f64 {
+        match self {
+            VarianceFunction::Constant => 1.0,
+            VarianceFunction::Ident { strata } => match strata[i] {
+                0 => 1.0,
+                s => parameters[s - 1],
+            },
+            VarianceFunction::Power { covariate } => covariate[i].abs().powf(parameters[0]),
+        }
+    }
+}
+
+/// An enum representing the criteria the variance and correlation parameters are estimated by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GlsMethod {
+    /// Restricted maximum likelihood.
+    #[default]
+    Reml,
+    /// Maximum likelihood.
+    Ml,
+}
+
+/// The result of a generalized least squares fit.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GlsFit {
+    /// The criterion the fit maximised.
+    pub method: GlsMethod,
+    /// The names of the coefficients.
+    pub names: Vec<String>,
+    /// The coefficients.
+    pub coefficients: Vec<f64>,
+    /// The standard errors of the coefficients.
+    pub std_errors: Vec<f64>,
+    /// The covariance of the coefficients, sigma^2 (X' Lambda^-1 X)^-1.
+    pub covariance: RealMatrix,
+    /// The residual standard error.
+    pub sigma: f64,
+    /// The correlation structure.
+    pub correlation: Correlation,
+    /// The names of the correlation parameters.
+    pub correlation_names: Vec<String>,
+    /// The correlation parameters, estimated or known.
+    pub correlation_parameters: Vec<f64>,
+    /// `true` if the correlation parameters were estimated rather than known.
+    pub correlation_estimated: bool,
+    /// The variance function.
+    pub variance: VarianceFunction,
+    /// The names of the variance parameters.
+    pub variance_names: Vec<String>,
+    /// The estimated variance parameters.
+    pub variance_parameters: Vec<f64>,
+    /// The fitted values X b.
+    pub fitted_values: Vec<f64>,
+    /// The residuals y - X b.
+    pub residuals: Vec<f64>,
+    /// The whitened residuals divided by sigma, which are uncorrelated with unit variance under
+    /// the model.
+    pub normalized_residuals: Vec<f64>,
+    /// The maximised ML or REML log-likelihood.
+    pub log_lik: f64,
+    /// The AIC, counting the coefficients, the estimated parameters and sigma.
+    pub aic: f64,
+    /// The BIC, with the number of observations less the coefficients for REML.
+    pub bic: f64,
+    /// The residual degrees of freedom, n - p.
+    pub df_residual: usize,
+    /// The number of evaluations of the log-likelihood.
+    pub evaluations: usize,
+    /// `true` if the optimisation of the parameters converged.
+    pub converged: bool,
+}
+
+impl fmt::Display for GlsFit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let method = match self.method {
+            GlsMethod::Reml => "REML",
+            GlsMethod::Ml => "maximum likelihood",
+        };
+        writeln!(f, "Generalized least squares fit by {}", method)?;
+        write_table(
+            f,
+            &[""],
+            &["AIC", "BIC", "logLik"],
+            &[vec![
+                format_number(self.aic),
+                format_number(self.bic),
+                format_number(self.log_lik),
+            ]],
+        )?;
+        writeln!(f)?;
+        if self.correlation != Correlation::Independent {
+            writeln!(f, "Correlation Structure: {}", self.correlation.name())?;
+            let estimate = if self.correlation_estimated {
+                "Parameter estimate(s):"
+            } else {
+                "Known parameter(s):"
+            };
+            writeln!(f, " {}", estimate)?;
+            let labels: Vec<&str> = self.correlation_names.iter().map(String::as_str).collect();
+            let cells: Vec<Vec<String>> = self
+                .correlation_parameters
+                .iter()
+                .map(|&v| vec![format_number(v)])
+                .collect();
+            write_table(f, &labels, &["Value"], &cells)?;
+        }
+        if self.variance != VarianceFunction::Constant {
+            writeln!(f, "Variance function:")?;
+            writeln!(f, " Structure: {}", self.variance.name())?;
+            writeln!(f, " Parameter estimates:")?;
+            let labels: Vec<&str> = self.variance_names.iter().map(String::as_str).collect();
+            let cells: Vec<Vec<String>> = self
+                .variance_parameters
+                .iter()
+                .map(|&v| vec![format_number(v)])
+                .collect();
+            write_table(f, &labels, &["Value"], &cells)?;
+        }
+        writeln!(f)?;
+        writeln!(f, "Coefficients:")?;
+        write_coefficients(
+            f,
+            &self.names,
+            &self.coefficients,
+            &self.std_errors,
+            self.df_residual,
+            &["Value", "Std.Error", "t-value", "p-value"],
+        )?;
+        writeln!(f)?;
+        writeln!(f, "Residual standard error: {}", format_number(self.sigma))?;
+        writeln!(
+            f,
+            "Degrees of freedom: {} total; {} residual",
+            self.residuals.len(),
+            self.df_residual
+        )
+    }
+}
+
+#[derive(Debug, Builder)]
+pub struct GlsFitter<'a> {
+    pub data: &'a Data,
+    #[builder(default)]
+    pub correlation: Correlation,
+    #[builder(default)]
+    pub variance: VarianceFunction,
+    /// The group of each observation. Observations in different groups are uncorrelated; by
+    /// default all observations form one group, as for a single time series.
+    #[builder(default)]
+    pub groups: Option<Vec<usize>>,
+    /// Known values of the correlation parameters, which are then held fixed.
+    #[builder(default)]
+    pub known_correlation: Option<Vec<f64>>,
+    #[builder(default)]
+    pub method: GlsMethod,
+    #[builder(default)]
+    pub control: NelderMeadControl,
+}
+
+impl<'a> GlsFitter<'a> {
+    /// Return a new instance of the `GlsFitter` struct for one group with the `correlation`
+    /// structure, a constant variance and REML estimation.
+    pub fn new(data: &'a Data, correlation: Correlation) -> Self {
+        Self {
+            data,
+            correlation,
+            variance: VarianceFunction::Constant,
+            groups: None,
+            known_correlation: None,
+            method: GlsMethod::Reml,
+            control: NelderMeadControl::default(),
+        }
+    }
+
+    /// Return the x matrix from the data struct.
+    pub fn x(&self) -> &RealMatrix {
+        &self.data.x
+    }
+
+    /// Return the y matrix from the data struct.
+    pub fn y(&self) -> &RealMatrix {
+        &self.data.y
+    }
+
+    /// Fit the model, estimating the variance function and any unknown correlation parameters.
+    ///
+    /// # Errors
+    /// Returns `LmFitterError::MultipleResponses`, `LmFitterError::PriorWeights` or
+    /// `LmFitterError::RankDeficient` for unsuitable data,
+    /// `LmFitterError::ObservationMismatch` for groups, strata or covariates of the wrong
+    /// length, `LmFitterError::InvalidParameters` for the wrong number of known correlation
+    /// parameters, and `LmFitterError::NotPositiveDefinite` if the known parameters or the
+    /// starting values do not give a valid covariance matrix.
+    pub fn gls(&self) -> Result<GlsFit, LmFitterError> {
+        let (x, y) = (self.data.x(), self.data.y());
+        let (n, p) = (x.n_rows(), x.n_cols());
+        if y.n_cols() != 1 {
+            return Err(LmFitterError::MultipleResponses { found: y.n_cols() });
+        }
+        if self.data.weights().is_some() {
+            return Err(LmFitterError::PriorWeights);
+        }
+        let rank = dqrls(x, y, &Tolerance::default())?.rank();
+        if rank < p {
+            return Err(LmFitterError::RankDeficient { rank, columns: p });
+        }
+        let groups = self.group_indices(n)?;
+        match &self.variance {
+            VarianceFunction::Ident { strata } if strata.len() != n => {
+                return Err(observation_mismatch("stratum", n, strata.len()))
+            }
+            VarianceFunction::Power { covariate } if covariate.len() != n => {
+                return Err(observation_mismatch(
+                    "variance covariate",
+                    n,
+                    covariate.len(),
+                ))
+            }
+            _ => {}
+        }
+        let size = groups.iter().map(Vec::len).max().unwrap_or(0);
+        let correlation_names = self.correlation.parameter_names(size);
+        let variance_names = self.variance.parameter_names();
+        if let Some(known) = &self.known_correlation {
+            if known.len() != correlation_names.len() {
+                return Err(LmFitterError::InvalidParameters {
+                    structure: self.correlation.name(),
+                    expected: correlation_names.len(),
+                    found: known.len(),
+                });
+            }
+        }
+
+        // The unconstrained parameters are the unknown correlation parameters followed by the
+        // variance parameters.
+        let n_correlation = match self.known_correlation {
+            Some(_) => 0,
+            None => correlation_names.len(),
+        };
+        let parameters = |u: &[f64]| -> (Vec<f64>, Vec<f64>) {
+            let correlation = match &self.known_correlation {
+                Some(known) => known.clone(),
+                None => self.correlation.natural(&u[..n_correlation], size),
+            };
+            (correlation, self.variance.natural(&u[n_correlation..]))
+        };
+        let profile = |u: &[f64]| -> Option<Profile> {
+            let (correlation, variance) = parameters(u);
+            self.profile(&groups, size, &correlation, &variance)
+        };
+        let start = vec![0.0; n_correlation + variance_names.len()];
+        if profile(&start).is_none() {
+            return Err(LmFitterError::NotPositiveDefinite {
+                structure: self.correlation.name(),
+            });
+        }
+        let objective = |u: &[f64]| profile(u).map_or(f64::INFINITY, |profile| -profile.log_lik);
+        let minimum = nelder_mead(&objective, &start, &self.control);
+        let (correlation_parameters, variance_parameters) = parameters(&minimum.point);
+        let profile = profile(&minimum.point).ok_or(LmFitterError::NotPositiveDefinite {
+            structure: self.correlation.name(),
+        })?;
+
+        let coefficients: Vec<f64> = (0..p)
+            .map(|j| profile.fit.coefficients.values[[j, 0]])
+            .collect();
+        let fitted_values: Vec<f64> = (0..n)
+            .map(|i| (0..p).map(|j| x.values[[i, j]] * coefficients[j]).sum())
+            .collect();
+        let residuals: Vec<f64> = (0..n)
+            .map(|i| y.values[[i, 0]] - fitted_values[i])
+            .collect();
+        let sigma = (profile.rss / profile.df as f64).sqrt();
+        let mut normalized_residuals = vec![0.0; n];
+        for (position, &i) in groups.iter().flatten().enumerate() {
+            normalized_residuals[i] = profile.fit.residuals.values[[position, 0]] / sigma;
+        }
+        let mut covariance = unscaled_covariance(&profile.fit.qr);
+        covariance.values.mapv_inplace(|v| v * sigma * sigma);
+        let std_errors = (0..p).map(|j| covariance.values[[j, j]].sqrt()).collect();
+
+        let n_parameters = p + n_correlation + variance_names.len() + 1;
+        let log_lik = profile.log_lik;
+        Ok(GlsFit {
+            method: self.method,
+            names: self.data.column_names().to_vec(),
+            coefficients,
+            std_errors,
+            covariance,
+            sigma,
+            correlation: self.correlation,
+            correlation_names,
+            correlation_parameters,
+            correlation_estimated: self.known_correlation.is_none(),
+            variance: self.variance.clone(),
+            variance_names,
+            variance_parameters,
+            fitted_values,
+            residuals,
+            normalized_residuals,
+            log_lik,
+            aic: -2.0 * log_lik + 2.0 * n_parameters as f64,
+            bic: -2.0 * log_lik + n_parameters as f64 * (profile.df as f64).ln(),
+            df_residual: n - p,
+            evaluations: minimum.evaluations,
+            converged: minimum.converged,
+        })
+    }
+
+    /// Return the observations of each group in data order, with groups in order of first
+    /// appearance.
+    fn group_indices(&self, n: usize) -> Result<Vec<Vec<usize>>, LmFitterError> {
+        let Some(labels) = &self.groups else {
+            return Ok(vec![(0..n).collect()]);
+        };
+        if labels.len() != n {
+            return Err(observation_mismatch("group", n, labels.len()));
+        }
+        let mut seen: Vec<usize> = Vec::new();
+        let mut groups: Vec<Vec<usize>> = Vec::new();
+        for (i, label) in labels.iter().enumerate() {
+            match seen.iter().position(|l| l == label) {
+                Some(g) => groups[g].push(i),
+                None => {
+                    seen.push(*label);
+                    groups.push(vec![i]);
+                }
+            }
+        }
+        Ok(groups)
+    }
+
+    /// Whiten the data for the given parameters and profile out the coefficients and sigma.
+    /// Returns `None` if a block of Lambda is not positive definite.
+    fn profile(
+        &self,
+        groups: &[Vec<usize>],
+        size: usize,
+        correlation: &[f64],
+        variance: &[f64],
+    ) -> Option<Profile> {
+        let (x, y) = (self.data.x(), self.data.y());
+        let (n, p) = (x.n_rows(), x.n_cols());
+        let whitener = self.correlation.whitener(correlation, size)?;
+
+        // Rows of the whitened data follow the groups. With Lambda = S C S, each column of a
+        // group is divided by the scales and then whitened for C.
+        let mut xw = RealMatrix::with_shape(n, p);
+        let mut yw = RealMatrix::with_shape(n, 1);
+        let mut log_det = 0.0;
+        let mut row = 0;
+        for group in groups {
+            let k = group.len();
+            let scales: Vec<f64> = group
+                .iter()
+                .map(|&i| self.variance.scale(variance, i))
+                .collect();
+            if scales.iter().any(|&s| !(s > 0.0 && s.is_finite())) {
+                return None;
+            }
+            log_det += whitener.log_det(k) + 2.0 * scales.iter().map(|s| s.ln()).sum::<f64>();
+            for j in 0..=p {
+                let values: Vec<f64> = group
+                    .iter()
+                    .zip(&scales)
+                    .map(|(&i, s)| {
+                        if j < p {
+                            x.values[[i, j]] / s
+                        } else {
+                            y.values[[i, 0]] / s
+                        }
+                    })
+                    .collect();
+                for (a, value) in whitener.whiten(&values).into_iter().enumerate() {
+                    if j < p {
+                        xw.values[[row + a, j]] = value;
+                    } else {
+                        yw.values[[row + a, 0]] = value;
+                    }
+                }
+            }
+            row += k;
+        }
+
+        let fit = dqrls(&xw, &yw, &Tolerance::default()).ok()?;
+        if fit.rank() < p {
+            return None;
+        }
+        let rss: f64 = fit.residuals.values.iter().map(|r| r * r).sum();
+        let (df, log_det_r) = match self.method {
+            GlsMethod::Ml => (n, 0.0),
+            GlsMethod::Reml => {
+                let r = fit.qr.r();
+                (n - p, (0..p).map(|j| r.values[[j, j]].abs().ln()).sum())
+            }
+        };
+        let m = df as f64;
+        let log_lik =
+            -m / 2.0 * ((2.0 * PI).ln() + 1.0 + (rss / m).ln()) - log_det / 2.0 - log_det_r;
+        log_lik.is_finite().then_some(Profile {
+            fit,
+            rss,
+            df,
+            log_lik,
+        })
+    }
+}
+
+impl<'a> FitModel for GlsFitter<'a> {
+    /// Fit the model and return the coefficients as a single column.
+    fn fit(&self) -> Result<RealMatrix, LmFitterError> {
+        let coefficients = self.gls()?.coefficients;
+        let p = coefficients.len();
+        Ok(RealMatrix::from_vec(coefficients, p, None))
+    }
+
+    fn x(&self) -> &RealMatrix {
+        self.data.x()
+    }
+
+    fn y(&self) -> &RealMatrix {
+        self.data.y()
+    }
+}
+
+/// The whitened least squares fit at one set of parameters.
+struct Profile {
+    fit: LeastSquaresFit,
+    rss: f64,
+    /// The divisor of the residual sum of squares for sigma^2: n for ML and n - p for REML.
+    df: usize,
+    log_lik: f64,
+}
+
+fn observation_mismatch(name: &str, expected: usize, found: usize) -> LmFitterError {
+    LmFitterError::ObservationMismatch {
+        name: name.to_string(),
+        expected,
+        found,
+    }
+}
+
+/// Return the coefficients of the autoregression whose partial autocorrelations are tanh(u),
+/// by the Durbin-Levinson recursion.
+fn durbin_levinson(u: &[f64]) -> Vec<f64> {
+    let mut coefficients: Vec<f64> = Vec::with_capacity(u.len());
+    for value in u {
+        let partial = value.tanh();
+        let previous = coefficients.clone();
+        let k = previous.len();
+        for j in 0..k {
+            coefficients[j] = previous[j] - partial * previous[k - 1 - j];
+        }
+        coefficients.push(partial);
+    }
+    coefficients
+}
+
+/// Return the autocovariances at lags 0 to `lags` - 1 of the ARMA process
+/// e_t = sum phi_i e_{t-i} + a_t + sum theta_j a_{t-j} with unit innovation variance, as in R's
+/// `ARMAacf`. The autocovariances up to lag r = max(p, q + 1) solve a linear system, and later
+/// lags follow the autoregressive recursion. Returns `None` if the system is singular or the
+/// variance is not positive.
+fn arma_autocovariances(phi: &[f64], theta: &[f64], lags: usize) -> Option<Vec<f64>> {
+    let (p, q) = (phi.len(), theta.len());
+    let r = p.max(q + 1);
+    let theta_at = |j: usize| match j {
+        0 => 1.0,
+        j if j <= q => theta[j - 1],
+        _ => 0.0,
+    };
+    // The weights psi_j of the moving average representation, up to lag q.
+    let mut psi = vec![1.0];
+    for j in 1..=q {
+        let ar: f64 = (1..=j.min(p)).map(|i| phi[i - 1] * psi[j - i]).sum();
+        psi.push(theta_at(j) + ar);
+    }
+
+    // gamma(k) - sum phi_i gamma(|k - i|) = sum_{j >= k} theta_j psi_{j - k}, for k = 0..r.
+    let mut system = RealMatrix::with_shape(r + 1, r + 1);
+    let mut rhs = vec![0.0; r + 1];
+    for k in 0..=r {
+        system.values[[k, k]] += 1.0;
+        for i in 1..=p {
+            system.values[[k, k.abs_diff(i)]] -= phi[i - 1];
+        }
+        rhs[k] = (k..=q).map(|j| theta_at(j) * psi[j - k]).sum();
+    }
+    let inverse = system.inverse()?;
+    let mut gamma: Vec<f64> = (0..=r)
+        .map(|a| (0..=r).map(|b| inverse.values[[a, b]] * rhs[b]).sum())
+        .collect();
+    for k in (r + 1)..lags {
+        let next = (1..=p).map(|i| phi[i - 1] * gamma[k - i]).sum();
+        gamma.push(next);
+    }
+    gamma.truncate(lags);
+    (gamma[0] > 0.0).then_some(gamma)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fitters::fit::LinearModelFitter;
+    use crate::fitters::qr_decomposition_fitter::QrDecompositionFitter;
+
+    /// y = 1 + 0.5 t plus AR(1) errors with phi = 0.6, driven by a deterministic sequence.
+    fn series(n: usize) -> Data {
+        let x: Vec<f64> = (0..n).flat_map(|i| [1.0, i as f64 / 10.0]).collect();
+        let mut error = 0.0;
+        let y: Vec<f64> = (0..n)
+            .map(|i| {
+                let shock = ((i * i) as f64 * 0.37).sin() + ((i * 13) % 5) as f64 / 5.0 - 0.4;
+                error = 0.6 * error + shock;
+                1.0 + 0.05 * i as f64 + error
+            })
+            .collect();
+        Data::new(
+            RealMatrix::from_vec(x, n, Some(2)),
+            RealMatrix::from_vec(y, n, None),
+        )
+        .with_column_names(vec!["(Intercept)".to_string(), "t".to_string()])
+    }
+
+    #[test]
+    fn test_known_correlation_matches_the_gls_formula() {
+        let data = series(40);
+        let phi = 0.5;
+        let fitter = GlsFitterBuilder::default()
+            .data(&data)
+            .correlation(Correlation::Ar1)
+            .known_correlation(Some(vec![phi]))
+            .build()
+            .unwrap();
+        let fit = fitter.gls().unwrap();
+
+        // b = (X' C^-1 X)^-1 X' C^-1 y with C_ij = phi^|i - j|.
+        let n = 40;
+        let mut c = RealMatrix::with_shape(n, n);
+        for i in 0..n {
+            for j in 0..n {
+                c.values[[i, j]] = phi.powi(i.abs_diff(j) as i32);
+            }
+        }
+        let c_inverse = c.inverse().unwrap();
+        let xt = data.x.transpose();
+        let xtcx = xt.dot(&c_inverse).dot(&data.x).inverse().unwrap();
+        let b = xtcx.dot(&xt).dot(&c_inverse).dot(&data.y);
+        let residuals = data.y.minus(&data.x.dot(&b));
+        let rss = residuals.transpose().dot(&c_inverse).dot(&residuals).values[[0, 0]];
+        let sigma2 = rss / (n - 2) as f64;
+        for j in 0..2 {
+            assert!((fit.coefficients[j] - b.values[[j, 0]]).abs() < 1e-8);
+            let se = (sigma2 * xtcx.values[[j, j]]).sqrt();
+            assert!((fit.std_errors[j] - se).abs() < 1e-8);
+        }
+        assert!(!fit.correlation_estimated);
+
+        // Independent errors give the least squares fit and its residual standard error.
+        let fitter = GlsFitter::new(&data, Correlation::Independent);
+        let independent = fitter.gls().unwrap();
+        let ols = QrDecompositionFitter::new(&data, None).decompose().unwrap();
+        let rss: f64 = ols.residuals.values.iter().map(|r| r * r).sum();
+        assert!((independent.sigma - (rss / 38.0).sqrt()).abs() < 1e-10);
+        let coefficient = ols.coefficients.values[[1, 0]];
+        let fitted = LinearModelFitter::Gls(fitter).fit().unwrap();
+        assert!((fitted.values[[1, 0]] - coefficient).abs() < 1e-10);
+    }
+
+    #[test]
+    fn test_innovations_match_the_cholesky_factor() {
+        // ARMA(2, 1), whose prediction uses both the autoregression and earlier errors.
+        let (phi, theta) = ([0.5, -0.3], [0.4]);
+        let size = 12;
+        let gamma = arma_autocovariances(&phi, &theta, size).unwrap();
+        let mut correlation = RealMatrix::with_shape(size, size);
+        for i in 0..size {
+            for j in 0..size {
+                correlation.values[[i, j]] = gamma[i.abs_diff(j)] / gamma[0];
+            }
+        }
+        let dense = Whitener::Cholesky(correlation.cholesky().unwrap());
+        let innovations = Whitener::Innovations(Innovations::new(&phi, &theta, size).unwrap());
+        let values: Vec<f64> = (0..size).map(|i| (i as f64 * 0.7).sin() + 0.1).collect();
+        for k in [1, 3, size] {
+            let expected = dense.whiten(&values[..k]);
+            let whitened = innovations.whiten(&values[..k]);
+            for (a, b) in whitened.iter().zip(&expected) {
+                assert!((a - b).abs() < 1e-10);
+            }
+            assert!((innovations.log_det(k) - dense.log_det(k)).abs() < 1e-10);
+        }
+        assert!(Innovations::new(&[1.0], &[], size).is_none());
+    }
+
+    #[test]
+    fn test_estimated_structures_maximise_the_likelihood() {
+        let data = series(80);
+        let fit = GlsFitter::new(&data, Correlation::Ar1).gls().unwrap();
+        assert!(fit.converged);
+        let phi = fit.correlation_parameters[0];
+        assert!(phi > 0.3 && phi < 0.9);
+        for shift in [-0.02, 0.02] {
+            let fitter = GlsFitterBuilder::default()
+                .data(&data)
+                .correlation(Correlation::Ar1)
+                .known_correlation(Some(vec![phi + shift]))
+                .build()
+                .unwrap();
+            assert!(fitter.gls().unwrap().log_lik < fit.log_lik);
+        }
+        assert!(fit.to_string().contains("Correlation Structure: AR(1)"));
+
+        // ARMA(1, 0) is the same model as AR(1).
+        let arma = GlsFitter::new(&data, Correlation::Arma { p: 1, q: 0 })
+            .gls()
+            .unwrap();
+        assert!((arma.log_lik - fit.log_lik).abs() < 1e-6);
+        assert!((arma.correlation_parameters[0] - phi).abs() < 1e-3);
+        let arma = GlsFitter::new(&data, Correlation::Arma { p: 1, q: 1 })
+            .gls()
+            .unwrap();
+        assert!(arma.log_lik >= fit.log_lik - 1e-6);
+
+        // Groups of four with a common correlation, and a larger spread in the second stratum.
+        let groups: Vec<usize> = (0..80).map(|i| i / 4).collect();
+        let strata: Vec<usize> = (0..80).map(|i| usize::from(i >= 40)).collect();
+        let fitter = GlsFitterBuilder::default()
+            .data(&data)
+            .correlation(Correlation::CompoundSymmetry)
+            .variance(VarianceFunction::Ident { strata })
+            .groups(Some(groups.clone()))
+            .method(GlsMethod::Ml)
+            .build()
+            .unwrap();
+        let fit = fitter.gls().unwrap();
+        assert!(fit.correlation_parameters[0] > -1.0 / 3.0);
+        assert_eq!(fit.variance_parameters.len(), 1);
+        assert_eq!(fit.aic, -2.0 * fit.log_lik + 2.0 * 5.0);
+
+        // The unstructured correlation nests compound symmetry.
+        let fitter = GlsFitter {
+            groups: Some(groups.clone()),
+            ..GlsFitter::new(&data, Correlation::CompoundSymmetry)
+        };
+        let symmetric = fitter.gls().unwrap();
+        let fitter = GlsFitter {
+            groups: Some(groups),
+            ..GlsFitter::new(&data, Correlation::Unstructured)
+        };
+        let unstructured = fitter.gls().unwrap();
+        assert_eq!(unstructured.correlation_parameters.len(), 6);
+        assert!(unstructured.log_lik >= symmetric.log_lik - 1e-6);
+        let fitter = GlsFitter {
+            groups: Some(vec![0; 3]),
+            ..GlsFitter::new(&data, Correlation::Ar1)
+        };
+        assert!(matches!(
+            fitter.gls(),
+            Err(LmFitterError::ObservationMismatch { expected: 80, .. })
+        ));
+    }
+}
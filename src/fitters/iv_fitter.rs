@@ -0,0 +1,868 @@
+//! This module contains the instrumental-variables fitter, which implements the `FitModel` trait
+//! for the `LinearModelFitter` enum, like `AER::ivreg` with the diagnostics of `ivreg2`.
+//!
+//! Every column of x is a regressor. The endogenous columns are instrumented by the exogenous
+//! columns of x together with a matrix of excluded instruments, so that the full instrument
+//! matrix is Z = [X1, Z2]. The repository has no formula language, so `IvFitter::parse` reads
+//! the `ivreg`-style specification `y ~ x1 + d | x1 + z1 + z2` against the column names of x
+//! and the names of the instruments: the regressors before the bar must be the columns of x,
+//! and those not repeated after it are endogenous.
+//!
+//! Two-stage least squares and LIML are k-class estimators,
+//! b = [X'(I - k M_Z) X]^-1 X'(I - k M_Z) y, with k = 1 for 2SLS and k the smallest root of
+//! |W'M_X1 W - k W'M_Z W| = 0 for LIML, where W = [y, X2]. Their classical covariance is
+//! s^2 [X'(I - k M_Z) X]^-1 with s^2 from the structural residuals y - X b, not from the
+//! second-stage regression on the fitted values. Two-step GMM reweights the moments Z'u by the
+//! inverse of sum u_i^2 z_i z_i' at the 2SLS residuals, which is efficient under
+//! heteroskedasticity.
+
+// src/fitters/iv_fitter.rs
+
+use super::fit::FitModel;
+use crate::anova::{format_number, format_p_value, write_coefficients, write_table};
+use crate::errors::{IvError, LmFitterError};
+use crate::hypothesis::DiagnosticTest;
+use crate::least_squares::dqrls;
+use crate::terms::INTERCEPT_LABEL;
+use crate::types::{Data, RealMatrix, Tolerance};
+use derive_builder::Builder;
+use std::fmt;
+
+/// An enum representing the estimator of an instrumental-variables regression.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IvEstimator {
+    /// Two-stage least squares.
+    #[default]
+    TwoStageLeastSquares,
+    /// Limited-information maximum likelihood.
+    Liml,
+    /// Two-step efficient GMM with a heteroskedasticity-robust weight matrix.
+    Gmm,
+}
+
+/// The first-stage regression of one endogenous regressor on the instruments.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FirstStage {
+    /// The name of the endogenous regressor.
+    pub name: String,
+    /// The F test that the coefficients of the excluded instruments are zero.
+    pub test: DiagnosticTest,
+    /// The share of the variation left by the exogenous regressors that the excluded
+    /// instruments explain.
+    pub partial_r_squared: f64,
+}
+
+/// The result of an instrumental-variables fit.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IvFit {
+    /// The estimator the model was fitted with.
+    pub estimator: IvEstimator,
+    /// `true` if the standard errors and tests are heteroskedasticity-robust (HC0).
+    pub robust: bool,
+    /// The names of the coefficients.
+    pub names: Vec<String>,
+    /// The coefficients.
+    pub coefficients: Vec<f64>,
+    /// The standard errors of the coefficients.
+    pub std_errors: Vec<f64>,
+    /// The covariance of the coefficients.
+    pub covariance: RealMatrix,
+    /// The residual standard error, from the structural residuals.
+    pub sigma: f64,
+    /// The residual degrees of freedom.
+    pub df_residual: usize,
+    /// The structural residuals y - X b.
+    pub residuals: Vec<f64>,
+    /// The k of the k-class estimator: 1 for 2SLS and the LIML root for LIML. `None` for GMM.
+    pub kappa: Option<f64>,
+    /// The first-stage regression of each endogenous regressor.
+    pub first_stage: Vec<FirstStage>,
+    /// The Cragg-Donald Wald F statistic for weak instruments, the smallest eigenvalue of the
+    /// first-stage F matrix. It equals the first-stage F with one endogenous regressor.
+    pub cragg_donald: f64,
+    /// The Kleibergen-Paap rk Wald test that the first-stage coefficients of the excluded
+    /// instruments have less than full rank, with robust (HC0) first-stage errors.
+    pub kleibergen_paap: DiagnosticTest,
+    /// The Kleibergen-Paap rk Wald F statistic for weak instruments, the robust analogue of
+    /// the Cragg-Donald statistic.
+    pub kleibergen_paap_f: f64,
+    /// The Wu-Hausman test that the endogenous regressors are exogenous, from the regression of
+    /// y on x and the first-stage residuals.
+    pub wu_hausman: DiagnosticTest,
+    /// The Sargan test of the overidentifying restrictions, or the Hansen J test for GMM and
+    /// robust fits. `None` for an exactly identified model.
+    pub overidentification: Option<DiagnosticTest>,
+}
+
+impl IvFit {
+    /// Return the name of the overidentification test, "Sargan" or "Hansen J".
+    pub fn overidentification_name(&self) -> &'static str {
+        if self.estimator == IvEstimator::Gmm || self.robust {
+            "Hansen J"
+        } else {
+            "Sargan"
+        }
+    }
+}
+
+impl fmt::Display for IvFit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let estimator = match self.estimator {
+            IvEstimator::TwoStageLeastSquares => "2SLS",
+            IvEstimator::Liml => "LIML",
+            IvEstimator::Gmm => "two-step GMM",
+        };
+        writeln!(f, "Instrumental-variables regression by {}", estimator)?;
+        if self.robust || self.estimator == IvEstimator::Gmm {
+            writeln!(f, "Heteroskedasticity-robust standard errors")?;
+        }
+        writeln!(f)?;
+        writeln!(f, "Coefficients:")?;
+        write_coefficients(
+            f,
+            &self.names,
+            &self.coefficients,
+            &self.std_errors,
+            self.df_residual,
+            &["Estimate", "Std. Error", "t value", "Pr(>|t|)"],
+        )?;
+        writeln!(f)?;
+
+        writeln!(f, "Diagnostic tests:")?;
+        let mut labels: Vec<String> = self
+            .first_stage
+            .iter()
+            .map(|stage| format!("Weak instruments ({})", stage.name))
+            .collect();
+        let mut tests: Vec<DiagnosticTest> = self.first_stage.iter().map(|s| s.test).collect();
+        labels.push("Kleibergen-Paap rk".to_string());
+        tests.push(self.kleibergen_paap);
+        labels.push("Wu-Hausman".to_string());
+        tests.push(self.wu_hausman);
+        if let Some(test) = self.overidentification {
+            labels.push(self.overidentification_name().to_string());
+            tests.push(test);
+        }
+        let labels: Vec<&str> = labels.iter().map(String::as_str).collect();
+        let cells: Vec<Vec<String>> = tests
+            .iter()
+            .map(|test| {
+                vec![
+                    test.df1.to_string(),
+                    test.df2.map_or("-".to_string(), |df| df.to_string()),
+                    format_number(test.statistic),
+                    format_p_value(test.p_value),
+                ]
+            })
+            .collect();
+        write_table(f, &labels, &["df1", "df2", "statistic", "p-value"], &cells)?;
+        writeln!(f)?;
+        writeln!(
+            f,
+            "Cragg-Donald Wald F: {},\tKleibergen-Paap rk Wald F: {}",
+            format_number(self.cragg_donald),
+            format_number(self.kleibergen_paap_f)
+        )?;
+        writeln!(
+            f,
+            "Residual standard error: {} on {} degrees of freedom",
+            format_number(self.sigma),
+            self.df_residual
+        )?;
+        if let Some(kappa) = self.kappa.filter(|_| self.estimator == IvEstimator::Liml) {
+            writeln!(f, "LIML kappa: {}", format_number(kappa))?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Builder)]
+pub struct IvFitter<'a> {
+    pub data: &'a Data,
+    /// The excluded instruments, one column per instrument.
+    pub instruments: RealMatrix,
+    /// The names of the excluded instruments.
+    pub instrument_names: Vec<String>,
+    /// The columns of x that are endogenous.
+    pub endogenous: Vec<usize>,
+    #[builder(default)]
+    pub estimator: IvEstimator,
+    /// Use heteroskedasticity-robust (HC0) standard errors and tests.
+    #[builder(default)]
+    pub robust: bool,
+}
+
+impl<'a> IvFitter<'a> {
+    /// Return a new instance of the `IvFitter` struct, fitted by 2SLS with classical standard
+    /// errors. The instruments are named `z1`, `z2`, ...
+    pub fn new(data: &'a Data, instruments: RealMatrix, endogenous: Vec<usize>) -> Self {
+        let instrument_names = (1..=instruments.n_cols())
+            .map(|j| format!("z{j}"))
+            .collect();
+        Self {
+            data,
+            instruments,
+            instrument_names,
+            endogenous,
+            estimator: IvEstimator::TwoStageLeastSquares,
+            robust: false,
+        }
+    }
+
+    /// Return a new instance of the `IvFitter` struct from a specification such as
+    /// `"y ~ x1 + d | x1 + z1 + z2"`. The response before `~` is optional and ignored. The
+    /// regressors before the bar must be the columns of x other than the intercept, which is
+    /// always exogenous; after the bar come the exogenous columns of x and the excluded
+    /// instruments, named by `instrument_names`, to use.
+    ///
+    /// # Errors
+    /// Returns `IvError::InvalidFormula` if the specification does not have one bar,
+    /// `IvError::UnknownName` for a name that is not a column of x or an instrument, and
+    /// `IvError::MissingRegressor` if a column of x is not among the regressors.
+    pub fn parse(
+        data: &'a Data,
+        instruments: &RealMatrix,
+        instrument_names: &[String],
+        spec: &str,
+    ) -> Result<Self, IvError> {
+        let invalid = || IvError::InvalidFormula {
+            spec: spec.to_string(),
+        };
+        let unknown = |name: &str| IvError::UnknownName {
+            name: name.to_string(),
+            spec: spec.to_string(),
+        };
+        let formula = spec.split_once('~').map_or(spec, |(_, rhs)| rhs);
+        let (regressors, instrumented) = formula.split_once('|').ok_or_else(invalid)?;
+        if instrumented.contains('|') {
+            return Err(invalid());
+        }
+        let names = |side: &'_ str| -> Result<Vec<String>, IvError> {
+            side.split('+')
+                .map(str::trim)
+                .filter(|name| *name != "1")
+                .map(|name| match name {
+                    "" => Err(invalid()),
+                    name => Ok(name.to_string()),
+                })
+                .collect()
+        };
+
+        let column_names = data.column_names();
+        let regressors = names(regressors)?;
+        if let Some(name) = regressors
+            .iter()
+            .find(|name| !column_names.contains(name) || *name == INTERCEPT_LABEL)
+        {
+            return Err(unknown(name));
+        }
+        if let Some(name) = column_names
+            .iter()
+            .find(|name| *name != INTERCEPT_LABEL && !regressors.contains(name))
+        {
+            return Err(IvError::MissingRegressor {
+                name: name.clone(),
+                spec: spec.to_string(),
+            });
+        }
+
+        let mut exogenous = Vec::new();
+        let mut selected = Vec::new();
+        for name in names(instrumented)? {
+            if regressors.contains(&name) {
+                exogenous.push(name);
+            } else if let Some(k) = instrument_names.iter().position(|z| *z == name) {
+                selected.push(k);
+            } else {
+                return Err(unknown(&name));
+            }
+        }
+        let endogenous = (0..column_names.len())
+            .filter(|&j| {
+                column_names[j] != INTERCEPT_LABEL && !exogenous.contains(&column_names[j])
+            })
+            .collect();
+
+        let mut excluded = RealMatrix::with_shape(instruments.n_rows(), selected.len());
+        for (target, &k) in selected.iter().enumerate() {
+            excluded
+                .values
+                .column_mut(target)
+                .assign(&instruments.values.column(k));
+        }
+        Ok(Self {
+            data,
+            instruments: excluded,
+            instrument_names: selected
+                .iter()
+                .map(|&k| instrument_names[k].clone())
+                .collect(),
+            endogenous,
+            estimator: IvEstimator::TwoStageLeastSquares,
+            robust: false,
+        })
+    }
+
+    /// Return the x matrix from the data struct.
+    pub fn x(&self) -> &RealMatrix {
+        &self.data.x
+    }
+
+    /// Return the y matrix from the data struct.
+    pub fn y(&self) -> &RealMatrix {
+        &self.data.y
+    }
+
+    /// Fit the model and compute the weak-instrument, endogeneity and overidentification
+    /// diagnostics.
+    ///
+    /// # Errors
+    /// Returns `IvError::Fit` wrapping `LmFitterError::MultipleResponses` if y has more than one
+    /// column or `LmFitterError::PriorWeights` if the data are weighted,
+    /// `IvError::DimensionMismatch` if the instruments do not have one row per observation,
+    /// `IvError::NoEndogenous` or `IvError::InvalidEndogenous` for an invalid set of endogenous
+    /// columns, `IvError::Underidentified` if there are fewer excluded instruments than
+    /// endogenous regressors, and `IvError::Singular` if the regressors or instruments are
+    /// collinear.
+    pub fn iv(&self) -> Result<IvFit, IvError> {
+        let (x, y) = (self.x(), self.y());
+        if y.n_cols() != 1 {
+            return Err(LmFitterError::MultipleResponses { found: y.n_cols() }.into());
+        }
+        if self.data.weights.is_some() {
+            return Err(LmFitterError::PriorWeights.into());
+        }
+        let (n, p) = (x.n_rows(), x.n_cols());
+        if self.instruments.n_rows() != n {
+            return Err(IvError::DimensionMismatch {
+                expected: n,
+                found: self.instruments.n_rows(),
+            });
+        }
+        if self.endogenous.is_empty() {
+            return Err(IvError::NoEndogenous);
+        }
+        let names = self.data.column_names();
+        for (k, &j) in self.endogenous.iter().enumerate() {
+            if j >= p || names[j] == INTERCEPT_LABEL || self.endogenous[..k].contains(&j) {
+                return Err(IvError::InvalidEndogenous { column: j });
+            }
+        }
+        let (m, l) = (self.endogenous.len(), self.instruments.n_cols());
+        if l < m {
+            return Err(IvError::Underidentified {
+                endogenous: m,
+                instruments: l,
+            });
+        }
+
+        let exogenous: Vec<usize> = (0..p).filter(|j| !self.endogenous.contains(j)).collect();
+        let x1 = select_columns(x, &exogenous);
+        let x2 = select_columns(x, &self.endogenous);
+        let z = hstack(&x1, &self.instruments);
+        let kz = z.n_cols();
+        if n <= kz || n <= p + m {
+            return Err(IvError::Singular);
+        }
+        check_full_rank(x)?;
+        check_full_rank(&z)?;
+
+        // The k-class estimate, which is also the first step of GMM.
+        let mz_x = residualize(&z, x)?;
+        let kappa = match self.estimator {
+            IvEstimator::Liml => liml_kappa(y, &x1, &x2, &z)?,
+            _ => 1.0,
+        };
+        let xk = x.minus(&scale(&mz_x, kappa));
+        let bread = xk.transpose().dot(x).inverse().ok_or(IvError::Singular)?;
+        let mut coefficients = bread.dot(&xk.transpose().dot(y));
+        let mut residuals = y.minus(&x.dot(&coefficients));
+
+        let mut gmm = None;
+        if self.estimator == IvEstimator::Gmm || (self.robust && l > m) {
+            gmm = Some(two_step_gmm(x, y, &z, &residuals)?);
+        }
+        let covariance = match (&gmm, self.estimator) {
+            (Some(step), IvEstimator::Gmm) => {
+                coefficients = step.coefficients.clone();
+                residuals = step.residuals.clone();
+                step.covariance.clone()
+            }
+            _ if self.robust => bread.dot(&meat(&xk, &residuals)).dot(&bread.transpose()),
+            _ => scale(&bread, sum_of_squares(&residuals) / (n - p) as f64),
+        };
+        let df_residual = n - p;
+        let sigma = (sum_of_squares(&residuals) / df_residual as f64).sqrt();
+
+        let overidentification = if l > m {
+            let statistic = match &gmm {
+                Some(step) => step.j_statistic,
+                None => {
+                    let rss = sum_of_squares(&residuals);
+                    let unexplained = sum_of_squares(&residualize(&z, &residuals)?);
+                    n as f64 * (rss - unexplained) / rss
+                }
+            };
+            Some(DiagnosticTest::chi_squared(statistic, l - m))
+        } else {
+            None
+        };
+
+        // The first-stage regressions of each endogenous regressor on all the instruments.
+        let x2_partial = residualize(&x1, &x2)?;
+        let mut first_stage = Vec::with_capacity(m);
+        for (k, &j) in self.endogenous.iter().enumerate() {
+            let column = select_columns(&x2, &[k]);
+            let unexplained = sum_of_squares(&residualize(&z, &column)?);
+            let partial = sum_of_squares(&select_columns(&x2_partial, &[k]));
+            first_stage.push(FirstStage {
+                name: names[j].clone(),
+                test: exclusion_test(&z, &column, l, self.robust)?,
+                partial_r_squared: (partial - unexplained) / partial,
+            });
+        }
+
+        // Cragg-Donald: the smallest eigenvalue of the first-stage F matrix
+        // S^-1/2 X2~' P X2~ S^-1/2 / L, with S the first-stage residual covariance.
+        let v = residualize(&z, &x2)?;
+        let vv = v.transpose().dot(&v);
+        let explained = x2_partial.transpose().dot(&x2_partial).minus(&vv);
+        let root = scale(&vv, 1.0 / (n - kz) as f64)
+            .cholesky()
+            .and_then(|lower| lower.inverse())
+            .ok_or(IvError::Singular)?;
+        let normalized = root.dot(&explained).dot(&root.transpose());
+        let cragg_donald = smallest_eigenvalue(&normalized)? / l as f64;
+
+        let z2_partial = residualize(&x1, &self.instruments)?;
+        let kleibergen_paap = kleibergen_paap(&x2_partial, &z2_partial)?;
+        let kleibergen_paap_f = kleibergen_paap.statistic * (n - kz) as f64 / (n * l) as f64;
+
+        // Wu-Hausman: the regression of y on x and the first-stage residuals.
+        let augmented = hstack(x, &v);
+        let wu_hausman = exclusion_test(&augmented, y, m, self.robust)?;
+
+        let coefficients: Vec<f64> = coefficients.values.column(0).to_vec();
+        let std_errors = (0..p).map(|j| covariance.values[[j, j]].sqrt()).collect();
+        Ok(IvFit {
+            estimator: self.estimator,
+            robust: self.robust,
+            names: names.to_vec(),
+            coefficients,
+            std_errors,
+            covariance,
+            sigma,
+            df_residual,
+            residuals: residuals.values.column(0).to_vec(),
+            kappa: (self.estimator != IvEstimator::Gmm).then_some(kappa),
+            first_stage,
+            cragg_donald,
+            kleibergen_paap,
+            kleibergen_paap_f,
+            wu_hausman,
+            overidentification,
+        })
+    }
+}
+
+impl<'a> FitModel for IvFitter<'a> {
+    /// Fit the model and return the coefficients as a single column.
+    fn fit(&self) -> Result<RealMatrix, LmFitterError> {
+        let coefficients = self
+            .iv()
+            .map_err(|error| match error {
+                IvError::Fit(error) => error,
+                error => LmFitterError::Iv(Box::new(error)),
+            })?
+            .coefficients;
+        let p = coefficients.len();
+        Ok(RealMatrix::from_vec(coefficients, p, None))
+    }
+
+    fn x(&self) -> &RealMatrix {
+        self.data.x()
+    }
+
+    fn y(&self) -> &RealMatrix {
+        self.data.y()
+    }
+}
+
+/// The second step of two-step GMM.
+struct GmmStep {
+    coefficients: RealMatrix,
+    covariance: RealMatrix,
+    residuals: RealMatrix,
+    j_statistic: f64,
+}
+
+/// Refit by GMM with the weight matrix (sum u_i^2 z_i z_i')^-1 at the first-step residuals
+/// `u`, and compute Hansen's J from the moments at the new estimate.
+fn two_step_gmm(
+    x: &RealMatrix,
+    y: &RealMatrix,
+    z: &RealMatrix,
+    u: &RealMatrix,
+) -> Result<GmmStep, IvError> {
+    let weight = meat(z, u).inverse().ok_or(IvError::Singular)?;
+    let xz = x.transpose().dot(z);
+    let covariance = xz
+        .dot(&weight)
+        .dot(&xz.transpose())
+        .inverse()
+        .ok_or(IvError::Singular)?;
+    let coefficients = covariance.dot(&xz).dot(&weight).dot(&z.transpose().dot(y));
+    let residuals = y.minus(&x.dot(&coefficients));
+    let moments = z.transpose().dot(&residuals);
+    let j_statistic = moments.transpose().dot(&weight).dot(&moments).values[[0, 0]];
+    Ok(GmmStep {
+        coefficients,
+        covariance,
+        residuals,
+        j_statistic,
+    })
+}
+
+/// Return the LIML k, the smallest eigenvalue of (W'M_Z W)^-1 W'M_X1 W with W = [y, X2].
+fn liml_kappa(
+    y: &RealMatrix,
+    x1: &RealMatrix,
+    x2: &RealMatrix,
+    z: &RealMatrix,
+) -> Result<f64, IvError> {
+    let w = hstack(y, x2);
+    let exogenous = residualize(x1, &w)?;
+    let all = residualize(z, &w)?;
+    let root = all
+        .transpose()
+        .dot(&all)
+        .cholesky()
+        .and_then(|lower| lower.inverse())
+        .ok_or(IvError::Singular)?;
+    let ratio = root
+        .dot(&exogenous.transpose().dot(&exogenous))
+        .dot(&root.transpose());
+    smallest_eigenvalue(&ratio)
+}
+
+/// Return the Kleibergen-Paap rk Wald test that the coefficients Pi of the regression of the
+/// partialled endogenous regressors `x2` on the partialled instruments `z2` have rank m - 1.
+///
+/// Pi is normalized as Theta = G Pi F with G'G = Z2'Z2 / n and F'F = (V'V / n)^-1, V the
+/// first-stage residuals. With Theta = U S V', the statistic is the robust Wald statistic of
+/// A' Theta B', where A and B span the singular vectors of the smallest singular value and the
+/// null space, rotated as in Kleibergen and Paap (2006).
+fn kleibergen_paap(x2: &RealMatrix, z2: &RealMatrix) -> Result<DiagnosticTest, IvError> {
+    let (n, m, l) = (x2.n_rows(), x2.n_cols(), z2.n_cols());
+    let zz_inverse = z2.transpose().dot(z2).inverse().ok_or(IvError::Singular)?;
+    let pi = zz_inverse.dot(&z2.transpose().dot(x2));
+    let v = x2.minus(&z2.dot(&pi));
+
+    // The robust covariance of vec(Pi), stacking its columns.
+    let mut scores = RealMatrix::with_shape(m * l, m * l);
+    for i in 0..n {
+        let score: Vec<f64> = (0..m)
+            .flat_map(|k| (0..l).map(move |j| (k, j)))
+            .map(|(k, j)| v.values[[i, k]] * z2.values[[i, j]])
+            .collect();
+        for (a, sa) in score.iter().enumerate() {
+            for (b, sb) in score.iter().enumerate() {
+                scores.values[[a, b]] += sa * sb;
+            }
+        }
+    }
+    let block = kronecker(&identity(m), &zz_inverse);
+    let pi_covariance = block.dot(&scores).dot(&block);
+
+    let g = symmetric_power(&scale(&z2.transpose().dot(z2), 1.0 / n as f64), 0.5)?;
+    let f = symmetric_power(&scale(&v.transpose().dot(&v), 1.0 / n as f64), -0.5)?;
+    let theta = g.dot(&pi).dot(&f);
+    let transform = kronecker(&f, &g);
+    let theta_covariance = transform.dot(&pi_covariance).dot(&transform.transpose());
+
+    let q = m - 1;
+    let (_, u) = theta
+        .dot(&theta.transpose())
+        .symmetric_eigen()
+        .ok_or(IvError::Singular)?;
+    let u2 = select_columns(&u, &(q..l).collect::<Vec<_>>());
+    let u22 = select_rows(&u2, &(q..l).collect::<Vec<_>>());
+    let a = u2
+        .dot(&u22.inverse().ok_or(IvError::Singular)?)
+        .dot(&symmetric_power(&u22.dot(&u22.transpose()), 0.5)?);
+    let (_, vectors) = theta
+        .transpose()
+        .dot(&theta)
+        .symmetric_eigen()
+        .ok_or(IvError::Singular)?;
+    let sign = vectors.values[[q, q]].signum();
+    let b = scale(&select_columns(&vectors, &[q]), sign).transpose();
+
+    let lambda = a.transpose().dot(&theta).dot(&b.transpose());
+    let k = kronecker(&b, &a.transpose());
+    let omega = k.dot(&theta_covariance).dot(&k.transpose());
+    let statistic = lambda
+        .transpose()
+        .dot(&omega.inverse().ok_or(IvError::Singular)?)
+        .dot(&lambda)
+        .values[[0, 0]];
+    Ok(DiagnosticTest::chi_squared(statistic, l - q))
+}
+
+/// Return the F test that the last `tested` coefficients of the regression of `y` on `x` are
+/// zero, as a Wald statistic divided by `tested`, with classical or robust (HC0) covariance.
+fn exclusion_test(
+    x: &RealMatrix,
+    y: &RealMatrix,
+    tested: usize,
+    robust: bool,
+) -> Result<DiagnosticTest, IvError> {
+    let (n, p) = (x.n_rows(), x.n_cols());
+    let bread = x.transpose().dot(x).inverse().ok_or(IvError::Singular)?;
+    let coefficients = bread.dot(&x.transpose().dot(y));
+    let residuals = y.minus(&x.dot(&coefficients));
+    let covariance = if robust {
+        bread.dot(&meat(x, &residuals)).dot(&bread)
+    } else {
+        scale(&bread, sum_of_squares(&residuals) / (n - p) as f64)
+    };
+    let rows: Vec<usize> = (p - tested..p).collect();
+    let gamma = select_rows(&coefficients, &rows);
+    let sub = select_rows(&select_columns(&covariance, &rows), &rows);
+    let wald = gamma
+        .transpose()
+        .dot(&sub.inverse().ok_or(IvError::Singular)?)
+        .dot(&gamma)
+        .values[[0, 0]];
+    Ok(DiagnosticTest::f(wald / tested as f64, tested, n - p))
+}
+
+/// Return the residuals of the least squares regression of each column of `w` on `z`.
+fn residualize(z: &RealMatrix, w: &RealMatrix) -> Result<RealMatrix, IvError> {
+    if z.n_cols() == 0 {
+        return Ok(w.clone());
+    }
+    let fit = dqrls(z, w, &Tolerance::default()).map_err(|_| IvError::Singular)?;
+    if fit.rank() < z.n_cols() {
+        return Err(IvError::Singular);
+    }
+    Ok(fit.residuals)
+}
+
+fn check_full_rank(x: &RealMatrix) -> Result<(), IvError> {
+    residualize(x, &RealMatrix::with_shape(x.n_rows(), 1)).map(|_| ())
+}
+
+/// Return sum u_i^2 x_i x_i', the middle of the HC0 sandwich.
+fn meat(x: &RealMatrix, u: &RealMatrix) -> RealMatrix {
+    let mut weighted = x.clone();
+    for (mut row, &ui) in weighted
+        .values
+        .rows_mut()
+        .into_iter()
+        .zip(u.values.column(0))
+    {
+        row *= ui;
+    }
+    weighted.transpose().dot(&weighted)
+}
+
+/// Return a^power for a symmetric positive definite matrix `a`.
+fn symmetric_power(a: &RealMatrix, power: f64) -> Result<RealMatrix, IvError> {
+    let (values, vectors) = a.symmetric_eigen().ok_or(IvError::Singular)?;
+    if values.iter().any(|&value| value <= 0.0) {
+        return Err(IvError::Singular);
+    }
+    let mut scaled = vectors.clone();
+    for (mut column, value) in scaled.values.columns_mut().into_iter().zip(values) {
+        column *= value.powf(power);
+    }
+    Ok(scaled.dot(&vectors.transpose()))
+}
+
+fn smallest_eigenvalue(a: &RealMatrix) -> Result<f64, IvError> {
+    let (values, _) = a.symmetric_eigen().ok_or(IvError::Singular)?;
+    values.last().copied().ok_or(IvError::Singular)
+}
+
+fn kronecker(a: &RealMatrix, b: &RealMatrix) -> RealMatrix {
+    let (ra, ca, rb, cb) = (a.n_rows(), a.n_cols(), b.n_rows(), b.n_cols());
+    let mut product = RealMatrix::with_shape(ra * rb, ca * cb);
+    for ((i, j), &aij) in a.values.indexed_iter() {
+        for ((k, l), &bkl) in b.values.indexed_iter() {
+            product.values[[i * rb + k, j * cb + l]] = aij * bkl;
+        }
+    }
+    product
+}
+
+fn identity(n: usize) -> RealMatrix {
+    RealMatrix::new(ndarray::Array2::eye(n))
+}
+
+fn scale(a: &RealMatrix, factor: f64) -> RealMatrix {
+    RealMatrix::new(&a.values * factor)
+}
+
+fn sum_of_squares(u: &RealMatrix) -> f64 {
+    u.values.iter().map(|value| value * value).sum()
+}
+
+fn select_columns(a: &RealMatrix, columns: &[usize]) -> RealMatrix {
+    RealMatrix::new(a.values.select(ndarray::Axis(1), columns))
+}
+
+fn select_rows(a: &RealMatrix, rows: &[usize]) -> RealMatrix {
+    RealMatrix::new(a.values.select(ndarray::Axis(0), rows))
+}
+
+fn hstack(a: &RealMatrix, b: &RealMatrix) -> RealMatrix {
+    RealMatrix::new(
+        ndarray::concatenate(ndarray::Axis(1), &[a.values.view(), b.values.view()]).unwrap(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Simulated data with an endogenous regressor `d` driven by instruments and by the
+    /// structural error.
+    fn simulated(n: usize) -> (Data, RealMatrix, Vec<String>) {
+        let mut x = RealMatrix::with_shape(n, 3);
+        let mut y = RealMatrix::with_shape(n, 1);
+        let mut z = RealMatrix::with_shape(n, 3);
+        for i in 0..n {
+            let t = i as f64;
+            let (z1, z2, z3) = ((t * 1.3).sin(), (t * t * 0.11).cos(), (t * 0.7 + 1.0).sin());
+            let error = ((t * t) * 0.37).sin();
+            let w = (t * 0.23).cos();
+            let d = 0.9 * z1 + 0.5 * z2 + 0.3 * z3 + 0.6 * error + 0.4 * (t * 2.9).sin();
+            x.values[[i, 0]] = 1.0;
+            x.values[[i, 1]] = w;
+            x.values[[i, 2]] = d;
+            y.values[[i, 0]] = 1.0 + 2.0 * w - 1.5 * d + error;
+            z.values[[i, 0]] = z1;
+            z.values[[i, 1]] = z2;
+            z.values[[i, 2]] = z3 + 0.2 * z1 * z2;
+        }
+        let names = ["(Intercept)", "w", "d"].map(String::from).to_vec();
+        let instrument_names = ["z1", "z2", "z3"].map(String::from).to_vec();
+        (
+            Data::new(x, y).with_column_names(names),
+            z,
+            instrument_names,
+        )
+    }
+
+    #[test]
+    fn test_exactly_identified_estimators_agree_with_the_iv_formula() {
+        let (data, z, names) = simulated(80);
+        let fitter = IvFitter::parse(&data, &z, &names, "y ~ w + d | w + z1").unwrap();
+        assert_eq!(fitter.endogenous, vec![2]);
+        let fit = fitter.iv().unwrap();
+
+        // b = (Z'X)^-1 Z'y, with covariance s^2 (X'P_Z X)^-1 from the structural residuals.
+        let full = hstack(&select_columns(&data.x, &[0, 1]), &fitter.instruments);
+        let zx = full.transpose().dot(&data.x);
+        let b = zx.inverse().unwrap().dot(&full.transpose().dot(&data.y));
+        let u = data.y.minus(&data.x.dot(&b));
+        let s2 = sum_of_squares(&u) / 77.0;
+        let projected = data.x.minus(&residualize(&full, &data.x).unwrap());
+        let covariance = scale(
+            &projected.transpose().dot(&projected).inverse().unwrap(),
+            s2,
+        );
+        for j in 0..3 {
+            assert!((fit.coefficients[j] - b.values[[j, 0]]).abs() < 1e-9);
+            assert!((fit.covariance.values[[j, j]] - covariance.values[[j, j]]).abs() < 1e-9);
+        }
+        assert!((fit.coefficients[2] + 1.5).abs() < 0.5);
+        assert!(fit.overidentification.is_none());
+
+        // With one endogenous regressor Cragg-Donald is the first-stage F, and Kleibergen-Paap
+        // is the robust Wald statistic of the excluded instrument.
+        assert!((fit.cragg_donald - fit.first_stage[0].test.statistic).abs() < 1e-8);
+        let robust = exclusion_test(&full, &select_columns(&data.x, &[2]), 1, true).unwrap();
+        assert!((fit.kleibergen_paap.statistic - robust.statistic).abs() < 1e-8);
+
+        for estimator in [IvEstimator::Liml, IvEstimator::Gmm] {
+            let other = IvFitter {
+                estimator,
+                ..IvFitter::parse(&data, &z, &names, "w + d | w + z1").unwrap()
+            };
+            let other = other.iv().unwrap();
+            for j in 0..3 {
+                assert!((other.coefficients[j] - fit.coefficients[j]).abs() < 1e-8);
+            }
+        }
+    }
+
+    #[test]
+    fn test_overidentified_diagnostics() {
+        let (data, z, names) = simulated(120);
+        let spec = "y ~ w + d | w + z1 + z2 + z3";
+        let fit = IvFitter::parse(&data, &z, &names, spec)
+            .unwrap()
+            .iv()
+            .unwrap();
+        let sargan = fit.overidentification.unwrap();
+        assert_eq!(sargan.df1, 2);
+        assert_eq!(fit.overidentification_name(), "Sargan");
+        assert!(fit.wu_hausman.p_value < 0.05);
+        assert!(fit.first_stage[0].test.statistic > 10.0);
+
+        // The first-stage F from the restricted and unrestricted residual sums of squares.
+        let d = select_columns(&data.x, &[2]);
+        let exogenous = select_columns(&data.x, &[0, 1]);
+        let rss_r = sum_of_squares(&residualize(&exogenous, &d).unwrap());
+        let rss_u = sum_of_squares(&residualize(&hstack(&exogenous, &z), &d).unwrap());
+        let f = ((rss_r - rss_u) / 3.0) / (rss_u / 115.0);
+        assert!((fit.first_stage[0].test.statistic - f).abs() < 1e-8);
+
+        let liml = IvFitterBuilder::default()
+            .data(&data)
+            .instruments(z.clone())
+            .instrument_names(names.clone())
+            .endogenous(vec![2])
+            .estimator(IvEstimator::Liml)
+            .build()
+            .unwrap()
+            .iv()
+            .unwrap();
+        assert!(liml.kappa.unwrap() >= 1.0);
+        let gmm = IvFitter {
+            estimator: IvEstimator::Gmm,
+            ..IvFitter::new(&data, z.clone(), vec![2])
+        }
+        .iv()
+        .unwrap();
+        assert_eq!(gmm.overidentification_name(), "Hansen J");
+        assert!(gmm.overidentification.unwrap().statistic >= 0.0);
+        assert!((gmm.coefficients[2] - fit.coefficients[2]).abs() < 0.2);
+
+        assert!(matches!(
+            IvFitter::parse(&data, &z, &names, "w | w + z1"),
+            Err(IvError::MissingRegressor { .. })
+        ));
+        assert!(matches!(
+            IvFitter::parse(&data, &z, &names, "w + d | w + z9"),
+            Err(IvError::UnknownName { .. })
+        ));
+        // Two endogenous regressors test the rank of a 3 x 2 first-stage matrix.
+        let two = IvFitter::parse(&data, &z, &names, "w + d | z1 + z2 + z3").unwrap();
+        let two = two.iv().unwrap();
+        assert_eq!(two.kleibergen_paap.df1, 2);
+        assert!(two.kleibergen_paap.statistic > 0.0 && two.cragg_donald > 0.0);
+        assert!(two.to_string().contains("Weak instruments (w)"));
+
+        let both = IvFitter::parse(&data, &z, &names, "w + d | z1").unwrap();
+        assert!(matches!(
+            both.iv(),
+            Err(IvError::Underidentified {
+                endogenous: 2,
+                instruments: 1
+            })
+        ));
+    }
+}
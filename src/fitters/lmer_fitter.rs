@@ -0,0 +1,759 @@
+//! This module contains the linear mixed-effects model fitter, which implements the `FitModel`
+//! trait for the `LinearModelFitter` enum, following `lme4::lmer`.
+//!
+//! The model is y = X b + Z Lambda u + e, with spherical random effects u ~ N(0, sigma^2 I) and
+//! e ~ N(0, sigma^2 I). Lambda is block diagonal, with one lower triangular template per
+//! random-effects term repeated for each of its groups; theta holds the entries of the
+//! templates. For a given theta, the penalized least squares problem in u and b is solved with
+//! the sparse Cholesky factor L of Lambda' Z' Z Lambda + I and a dense Cholesky factor of the
+//! fixed-effects block, which gives the profiled deviance
+//!
+//! * ML: log |L|^2 + n (1 + log(2 pi r^2 / n)),
+//! * REML: log |L|^2 + log |R_X|^2 + (n - p) (1 + log(2 pi r^2 / (n - p))),
+//!
+//! where r^2 is the penalized residual sum of squares. The deviance is minimised over theta by
+//! Nelder-Mead, starting from identity templates. Random effects are ordered by decreasing
+//! number of groups, and the sparse factorization permutes them by minimum degree, so that
+//! nested and crossed groupings factor with little fill.
+
+// src/fitters/lmer_fitter.rs
+
+use super::fit::FitModel;
+use crate::anova::{format_number, write_table};
+use crate::distributions::normal_quantile;
+use crate::errors::{LmFitterError, MixedModelError};
+use crate::least_squares::dqrls;
+use crate::mixed::sparse::{SparseCholesky, SparseSymmetric};
+use crate::mixed::spec::{GroupingFactor, RandomTerm};
+use crate::optim::{nelder_mead, NelderMeadControl};
+use crate::types::{Data, RealMatrix, Tolerance};
+use derive_builder::Builder;
+use std::collections::HashMap;
+use std::f64::consts::PI;
+use std::fmt;
+
+/// An enum representing the criteria a mixed model can be fitted by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LmerMethod {
+    /// Restricted maximum likelihood.
+    #[default]
+    Reml,
+    /// Maximum likelihood.
+    Ml,
+}
+
+/// The estimated covariance of the random effects of one term.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VarianceComponent {
+    /// The name of the grouping.
+    pub group: String,
+    /// The names of the random coefficients.
+    pub names: Vec<String>,
+    /// The covariance matrix of the random coefficients of a group.
+    pub covariance: RealMatrix,
+}
+
+impl VarianceComponent {
+    /// Return the standard deviations of the random coefficients.
+    pub fn std_devs(&self) -> Vec<f64> {
+        (0..self.names.len())
+            .map(|j| self.covariance.values[[j, j]].sqrt())
+            .collect()
+    }
+
+    /// Return the correlation between random coefficients `i` and `j`.
+    pub fn correlation(&self, i: usize, j: usize) -> f64 {
+        let sd = self.std_devs();
+        self.covariance.values[[i, j]] / (sd[i] * sd[j])
+    }
+}
+
+/// The conditional modes (BLUPs) of the random effects of one term.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RandomEffects {
+    /// The name of the grouping.
+    pub group: String,
+    /// The names of the random coefficients.
+    pub names: Vec<String>,
+    /// The levels of the grouping factors of each group, in order of first appearance.
+    pub levels: Vec<Vec<usize>>,
+    /// The random coefficients, one row per group.
+    pub values: RealMatrix,
+}
+
+/// The result of a linear mixed-effects model fit.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LmerFit {
+    /// The criterion the fit minimised.
+    pub method: LmerMethod,
+    /// The names of the fixed effects.
+    pub names: Vec<String>,
+    /// The fixed effects.
+    pub coefficients: Vec<f64>,
+    /// The standard errors of the fixed effects.
+    pub std_errors: Vec<f64>,
+    /// The covariance of the fixed effects.
+    pub covariance: RealMatrix,
+    /// The residual standard deviation.
+    pub sigma: f64,
+    /// The covariance of the random effects of each term, in the order of the terms.
+    pub variance_components: Vec<VarianceComponent>,
+    /// The conditional modes of the random effects of each term, in the order of the terms.
+    pub random_effects: Vec<RandomEffects>,
+    /// The entries of the relative covariance templates, column by column for each term.
+    pub theta: Vec<f64>,
+    /// The REML criterion or the ML deviance at the optimum.
+    pub deviance: f64,
+    /// The REML or ML log-likelihood, minus half the deviance.
+    pub log_lik: f64,
+    /// The AIC, counting the fixed effects, theta and sigma.
+    pub aic: f64,
+    /// The BIC.
+    pub bic: f64,
+    /// The fitted values X b + Z Lambda u.
+    pub fitted_values: Vec<f64>,
+    /// The residuals y minus the fitted values.
+    pub residuals: Vec<f64>,
+    /// The number of evaluations of the deviance.
+    pub evaluations: usize,
+    /// `true` if the optimisation converged.
+    pub converged: bool,
+}
+
+impl LmerFit {
+    /// Return Wald confidence intervals for the fixed effects at `level`, from the normal
+    /// distribution, like `confint(fit, method = "Wald")`.
+    ///
+    /// # Errors
+    /// Returns `MixedModelError::InvalidLevel` if `level` is not in (0, 1).
+    pub fn confint(&self, level: f64) -> Result<Vec<(f64, f64)>, MixedModelError> {
+        if !(level > 0.0 && level < 1.0) {
+            return Err(MixedModelError::InvalidLevel { level });
+        }
+        let z = normal_quantile(0.5 + level / 2.0);
+        Ok(self
+            .coefficients
+            .iter()
+            .zip(&self.std_errors)
+            .map(|(b, se)| (b - z * se, b + z * se))
+            .collect())
+    }
+}
+
+impl fmt::Display for LmerFit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.method {
+            LmerMethod::Reml => {
+                writeln!(f, "Linear mixed model fit by REML")?;
+                writeln!(
+                    f,
+                    "REML criterion at convergence: {}",
+                    format_number(self.deviance)
+                )?;
+            }
+            LmerMethod::Ml => {
+                writeln!(f, "Linear mixed model fit by maximum likelihood")?;
+                write_table(
+                    f,
+                    &[""],
+                    &["AIC", "BIC", "logLik", "deviance"],
+                    &[vec![
+                        format_number(self.aic),
+                        format_number(self.bic),
+                        format_number(self.log_lik),
+                        format_number(self.deviance),
+                    ]],
+                )?;
+            }
+        }
+        writeln!(f)?;
+        writeln!(f, "Random effects:")?;
+        let mut labels = Vec::new();
+        let mut cells = Vec::new();
+        for component in &self.variance_components {
+            let sd = component.std_devs();
+            for (i, name) in component.names.iter().enumerate() {
+                labels.push(if i == 0 { component.group.as_str() } else { "" });
+                let correlations: Vec<String> = (0..i)
+                    .map(|j| format!("{:.2}", component.correlation(i, j)))
+                    .collect();
+                cells.push(vec![
+                    name.clone(),
+                    format_number(sd[i] * sd[i]),
+                    format_number(sd[i]),
+                    correlations.join(" "),
+                ]);
+            }
+        }
+        labels.push("Residual");
+        cells.push(vec![
+            String::new(),
+            format_number(self.sigma * self.sigma),
+            format_number(self.sigma),
+            String::new(),
+        ]);
+        write_table(
+            f,
+            &labels,
+            &["Name", "Variance", "Std.Dev.", "Corr"],
+            &cells,
+        )?;
+        let groups: Vec<String> = self
+            .random_effects
+            .iter()
+            .map(|effects| format!("{}, {}", effects.group, effects.levels.len()))
+            .collect();
+        writeln!(
+            f,
+            "Number of obs: {}, groups: {}",
+            self.residuals.len(),
+            groups.join("; ")
+        )?;
+        writeln!(f)?;
+        writeln!(f, "Fixed effects:")?;
+        let labels: Vec<&str> = self.names.iter().map(String::as_str).collect();
+        let cells: Vec<Vec<String>> = (0..self.names.len())
+            .map(|j| {
+                vec![
+                    format_number(self.coefficients[j]),
+                    format_number(self.std_errors[j]),
+                    format_number(self.coefficients[j] / self.std_errors[j]),
+                ]
+            })
+            .collect();
+        write_table(f, &labels, &["Estimate", "Std. Error", "t value"], &cells)
+    }
+}
+
+#[derive(Debug, Builder)]
+pub struct LmerFitter<'a> {
+    pub data: &'a Data,
+    /// The random-effects terms, which can be parsed with `RandomTerm::parse`.
+    pub terms: Vec<RandomTerm>,
+    /// The grouping factors the terms refer to.
+    pub factors: Vec<GroupingFactor>,
+    #[builder(default)]
+    pub method: LmerMethod,
+    #[builder(default)]
+    pub control: NelderMeadControl,
+}
+
+impl<'a> LmerFitter<'a> {
+    /// Return a new instance of the `LmerFitter` struct, fitted by REML with the default
+    /// controls.
+    pub fn new(data: &'a Data, terms: Vec<RandomTerm>, factors: Vec<GroupingFactor>) -> Self {
+        Self {
+            data,
+            terms,
+            factors,
+            method: LmerMethod::Reml,
+            control: NelderMeadControl::default(),
+        }
+    }
+
+    /// Return the x matrix from the data struct.
+    pub fn x(&self) -> &RealMatrix {
+        &self.data.x
+    }
+
+    /// Return the y matrix from the data struct.
+    pub fn y(&self) -> &RealMatrix {
+        &self.data.y
+    }
+
+    /// Fit the model by minimising the profiled REML or ML deviance over theta.
+    ///
+    /// # Errors
+    /// Returns `MixedModelError::NoRandomEffects` without terms,
+    /// `MixedModelError::LevelMismatch` for grouping factors of the wrong length,
+    /// `MixedModelError::TooManyRandomEffects` if a term has as many random effects as there
+    /// are observations, `MixedModelError::Singular` if the system cannot be factored at the
+    /// starting values, and `MixedModelError::Fit` wrapping `LmFitterError::MultipleResponses`,
+    /// `LmFitterError::PriorWeights` or `LmFitterError::RankDeficient` for unsuitable data.
+    pub fn lmer(&self) -> Result<LmerFit, MixedModelError> {
+        let (x, y) = (self.data.x(), self.data.y());
+        let (n, p) = (x.n_rows(), x.n_cols());
+        if y.n_cols() != 1 {
+            return Err(LmFitterError::MultipleResponses { found: y.n_cols() }.into());
+        }
+        if self.data.weights().is_some() {
+            return Err(LmFitterError::PriorWeights.into());
+        }
+        let rank = dqrls(x, y, &Tolerance::default())
+            .map_err(LmFitterError::from)?
+            .rank();
+        if rank < p {
+            return Err(LmFitterError::RankDeficient { rank, columns: p }.into());
+        }
+        if self.terms.is_empty() {
+            return Err(MixedModelError::NoRandomEffects);
+        }
+        for factor in &self.factors {
+            if factor.levels.len() != n {
+                return Err(MixedModelError::LevelMismatch {
+                    name: factor.name.clone(),
+                    expected: n,
+                    found: factor.levels.len(),
+                });
+            }
+        }
+        let blocks = self.blocks()?;
+
+        let n_theta: usize = blocks.iter().map(|b| b.size * (b.size + 1) / 2).sum();
+        let mut start = Vec::with_capacity(n_theta);
+        for block in &blocks {
+            for j in 0..block.size {
+                start.extend((j..block.size).map(|i| if i == j { 1.0 } else { 0.0 }));
+            }
+        }
+        if self.solve(&blocks, &start).is_none() {
+            return Err(MixedModelError::Singular);
+        }
+        let objective = |theta: &[f64]| {
+            self.solve(&blocks, theta)
+                .map_or(f64::INFINITY, |s| s.deviance)
+        };
+        let minimum = nelder_mead(&objective, &start, &self.control);
+
+        // Only Lambda Lambda' is identified, so flip columns of the templates to make their
+        // diagonals non-negative, like the bounds lme4 places on theta.
+        let mut theta = minimum.point.clone();
+        for block in &blocks {
+            let mut template = block.template(&theta);
+            for j in 0..block.size {
+                if template.values[[j, j]] < 0.0 {
+                    for i in j..block.size {
+                        template.values[[i, j]] = -template.values[[i, j]];
+                    }
+                }
+            }
+            let mut next = block.theta_offset;
+            for j in 0..block.size {
+                for i in j..block.size {
+                    theta[next] = template.values[[i, j]];
+                    next += 1;
+                }
+            }
+        }
+        let solution = self
+            .solve(&blocks, &theta)
+            .ok_or(MixedModelError::Singular)?;
+
+        let sigma = solution.sigma;
+        let mut covariance = solution.rx_inverse;
+        covariance.values.mapv_inplace(|v| v * sigma * sigma);
+        let std_errors = (0..p).map(|j| covariance.values[[j, j]].sqrt()).collect();
+        let column_names = self.data.column_names();
+        let mut variance_components = Vec::with_capacity(blocks.len());
+        let mut random_effects = Vec::with_capacity(blocks.len());
+        for (term, block) in self.terms.iter().zip(&blocks) {
+            let template = block.template(&theta);
+            let mut component = template.dot(&template.transpose());
+            component.values.mapv_inplace(|v| v * sigma * sigma);
+            let group = term.group_name(&self.factors);
+            let names = term.names(column_names);
+            let mut values = RealMatrix::with_shape(block.levels.len(), block.size);
+            for level in 0..block.levels.len() {
+                let u = &solution.u[block.offset + level * block.size..][..block.size];
+                for r in 0..block.size {
+                    values.values[[level, r]] = (0..block.size)
+                        .map(|c| template.values[[r, c]] * u[c])
+                        .sum();
+                }
+            }
+            variance_components.push(VarianceComponent {
+                group: group.clone(),
+                names: names.clone(),
+                covariance: component,
+            });
+            random_effects.push(RandomEffects {
+                group,
+                names,
+                levels: block.levels.clone(),
+                values,
+            });
+        }
+
+        let df = (p + n_theta + 1) as f64;
+        let deviance = solution.deviance;
+        Ok(LmerFit {
+            method: self.method,
+            names: column_names.to_vec(),
+            coefficients: solution.beta,
+            std_errors,
+            covariance,
+            sigma,
+            variance_components,
+            random_effects,
+            theta,
+            deviance,
+            log_lik: -deviance / 2.0,
+            aic: deviance + 2.0 * df,
+            bic: deviance + (n as f64).ln() * df,
+            residuals: (0..n)
+                .map(|i| y.values[[i, 0]] - solution.fitted_values[i])
+                .collect(),
+            fitted_values: solution.fitted_values,
+            evaluations: minimum.evaluations,
+            converged: minimum.converged,
+        })
+    }
+
+    /// Return the random-effects structure of each term, with the random effects of the terms
+    /// with the most groups placed first.
+    fn blocks(&self) -> Result<Vec<Block>, MixedModelError> {
+        let n = self.data.x().n_rows();
+        let mut blocks = Vec::with_capacity(self.terms.len());
+        let mut theta_offset = 0;
+        for term in &self.terms {
+            let mut codes: HashMap<Vec<usize>, usize> = HashMap::new();
+            let mut levels = Vec::new();
+            let group_of: Vec<usize> = (0..n)
+                .map(|i| {
+                    let key: Vec<usize> = term
+                        .factors
+                        .iter()
+                        .map(|&k| self.factors[k].levels[i])
+                        .collect();
+                    *codes.entry(key.clone()).or_insert_with(|| {
+                        levels.push(key);
+                        levels.len() - 1
+                    })
+                })
+                .collect();
+            let size = term.size();
+            if levels.len() * size >= n {
+                return Err(MixedModelError::TooManyRandomEffects {
+                    group: term.group_name(&self.factors),
+                    effects: levels.len() * size,
+                    observations: n,
+                });
+            }
+            blocks.push(Block {
+                intercept: term.intercept,
+                columns: term.columns.clone(),
+                size,
+                offset: 0,
+                group_of,
+                levels,
+                theta_offset,
+            });
+            theta_offset += size * (size + 1) / 2;
+        }
+        let mut order: Vec<usize> = (0..blocks.len()).collect();
+        order.sort_by_key(|&b| std::cmp::Reverse(blocks[b].levels.len()));
+        let mut offset = 0;
+        for b in order {
+            blocks[b].offset = offset;
+            offset += blocks[b].levels.len() * blocks[b].size;
+        }
+        Ok(blocks)
+    }
+
+    /// Solve the penalized least squares problem at `theta` and return the profiled deviance,
+    /// or `None` if a factorization fails.
+    fn solve(&self, blocks: &[Block], theta: &[f64]) -> Option<Solution> {
+        let (x, y) = (self.data.x(), self.data.y());
+        let (n, p) = (x.n_rows(), x.n_cols());
+        let q: usize = blocks.iter().map(|b| b.levels.len() * b.size).sum();
+        let templates: Vec<RealMatrix> = blocks.iter().map(|b| b.template(theta)).collect();
+
+        // The nonzeros of each row of Z Lambda, and the cross products with X and y.
+        let mut rows: Vec<Vec<(usize, f64)>> = Vec::with_capacity(n);
+        let mut system = SparseSymmetric::new(q);
+        let mut zty = vec![0.0; q];
+        let mut ztx = RealMatrix::with_shape(q, p);
+        for i in 0..n {
+            let mut row = Vec::new();
+            for (block, template) in blocks.iter().zip(&templates) {
+                let z: Vec<f64> = block.covariates(x, i);
+                let base = block.offset + block.group_of[i] * block.size;
+                for r in 0..block.size {
+                    let value: f64 = (0..block.size)
+                        .map(|c| z[c] * template.values[[c, r]])
+                        .sum();
+                    row.push((base + r, value));
+                }
+            }
+            for (a, &(ia, va)) in row.iter().enumerate() {
+                for &(ib, vb) in &row[..=a] {
+                    system.add(ia, ib, va * vb);
+                }
+                zty[ia] += va * y.values[[i, 0]];
+                for j in 0..p {
+                    ztx.values[[ia, j]] += va * x.values[[i, j]];
+                }
+            }
+            rows.push(row);
+        }
+        for k in 0..q {
+            system.add(k, k, 1.0);
+        }
+        let factor = SparseCholesky::factor(&system)?;
+
+        // R_ZX = L^-1 Lambda' Z' X, and R_X R_X' = X'X - R_ZX' R_ZX.
+        let mut rzx = RealMatrix::with_shape(q, p);
+        for j in 0..p {
+            let mut column: Vec<f64> = (0..q).map(|k| ztx.values[[k, j]]).collect();
+            factor.solve_lower(&mut column);
+            for (k, value) in column.into_iter().enumerate() {
+                rzx.values[[k, j]] = value;
+            }
+        }
+        let mut cu = zty;
+        factor.solve_lower(&mut cu);
+        let xtx = x.transpose().dot(x);
+        let rxtrx = xtx.minus(&rzx.transpose().dot(&rzx));
+        let rx = rxtrx.cholesky()?;
+        let rx_inverse = rxtrx.inverse()?;
+        let rhs: Vec<f64> = (0..p)
+            .map(|j| {
+                (0..n)
+                    .map(|i| x.values[[i, j]] * y.values[[i, 0]])
+                    .sum::<f64>()
+                    - (0..q).map(|k| rzx.values[[k, j]] * cu[k]).sum::<f64>()
+            })
+            .collect();
+        let beta: Vec<f64> = (0..p)
+            .map(|a| (0..p).map(|b| rx_inverse.values[[a, b]] * rhs[b]).sum())
+            .collect();
+        let mut u: Vec<f64> = (0..q)
+            .map(|k| cu[k] - (0..p).map(|j| rzx.values[[k, j]] * beta[j]).sum::<f64>())
+            .collect();
+        factor.solve_upper(&mut u);
+
+        let fitted_values: Vec<f64> = (0..n)
+            .map(|i| {
+                (0..p).map(|j| x.values[[i, j]] * beta[j]).sum::<f64>()
+                    + rows[i].iter().map(|&(k, v)| v * u[k]).sum::<f64>()
+            })
+            .collect();
+        let penalized_rss: f64 = (0..n)
+            .map(|i| (y.values[[i, 0]] - fitted_values[i]).powi(2))
+            .sum::<f64>()
+            + u.iter().map(|v| v * v).sum::<f64>();
+        let log_det_l = factor.log_determinant();
+        let (df, log_det_rx) = match self.method {
+            LmerMethod::Ml => (n, 0.0),
+            LmerMethod::Reml => (n - p, (0..p).map(|j| 2.0 * rx.values[[j, j]].ln()).sum()),
+        };
+        let m = df as f64;
+        let deviance = log_det_l + log_det_rx + m * (1.0 + (2.0 * PI * penalized_rss / m).ln());
+        deviance.is_finite().then(|| Solution {
+            deviance,
+            beta,
+            u,
+            sigma: (penalized_rss / m).sqrt(),
+            rx_inverse,
+            fitted_values,
+        })
+    }
+}
+
+impl<'a> FitModel for LmerFitter<'a> {
+    /// Fit the model and return the fixed effects as a single column.
+    fn fit(&self) -> Result<RealMatrix, LmFitterError> {
+        let coefficients = self
+            .lmer()
+            .map_err(|error| match error {
+                MixedModelError::Fit(error) => error,
+                error => LmFitterError::Mixed(Box::new(error)),
+            })?
+            .coefficients;
+        let p = coefficients.len();
+        Ok(RealMatrix::from_vec(coefficients, p, None))
+    }
+
+    fn x(&self) -> &RealMatrix {
+        self.data.x()
+    }
+
+    fn y(&self) -> &RealMatrix {
+        self.data.y()
+    }
+}
+
+/// The random effects of one term within the vector u.
+struct Block {
+    intercept: bool,
+    columns: Vec<usize>,
+    /// The number of random coefficients per group.
+    size: usize,
+    /// The position of the first random effect of the term in u.
+    offset: usize,
+    /// The group of each observation.
+    group_of: Vec<usize>,
+    /// The levels of the grouping factors of each group.
+    levels: Vec<Vec<usize>>,
+    /// The position of the first template entry of the term in theta.
+    theta_offset: usize,
+}
+
+impl Block {
+    /// Return the lower triangular template of the term, filled column by column from theta.
+    fn template(&self, theta: &[f64]) -> RealMatrix {
+        let mut template = RealMatrix::with_shape(self.size, self.size);
+        let mut next = self.theta_offset;
+        for j in 0..self.size {
+            for i in j..self.size {
+                template.values[[i, j]] = theta[next];
+                next += 1;
+            }
+        }
+        template
+    }
+
+    /// Return the random-effects covariates of observation `i`.
+    fn covariates(&self, x: &RealMatrix, i: usize) -> Vec<f64> {
+        let intercept = self.intercept.then_some(1.0);
+        intercept
+            .into_iter()
+            .chain(self.columns.iter().map(|&j| x.values[[i, j]]))
+            .collect()
+    }
+}
+
+/// The solution of the penalized least squares problem at one theta.
+struct Solution {
+    deviance: f64,
+    beta: Vec<f64>,
+    u: Vec<f64>,
+    sigma: f64,
+    /// (R_X R_X')^-1, the unscaled covariance of the fixed effects.
+    rx_inverse: RealMatrix,
+    fitted_values: Vec<f64>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fitters::fit::LinearModelFitter;
+    use crate::fitters::gls_fitter::{Correlation, GlsFitter, GlsMethod};
+
+    /// A balanced one-way layout: six groups of five, with group effects and noise.
+    fn one_way() -> (Data, Vec<GroupingFactor>) {
+        let (a, m) = (6, 5);
+        let n = a * m;
+        let y: Vec<f64> = (0..n)
+            .map(|i| {
+                let (g, j) = (i / m, i % m);
+                10.0 + 1.5 * ((g * 7 % 6) as f64 - 2.5)
+                    + ((i * i) as f64 * 0.37).sin()
+                    + 0.2 * j as f64
+            })
+            .collect();
+        let data = Data::new(
+            RealMatrix::from_vec(vec![1.0; n], n, Some(1)),
+            RealMatrix::from_vec(y, n, None),
+        )
+        .with_column_names(vec!["(Intercept)".to_string()]);
+        let factors = vec![GroupingFactor::new("g", (0..n).map(|i| i / m).collect())];
+        (data, factors)
+    }
+
+    #[test]
+    fn test_one_way_reml_matches_the_anova_estimators() {
+        let (data, factors) = one_way();
+        let terms = RandomTerm::parse("(1 | g)", data.column_names(), &factors).unwrap();
+        let fitter = LmerFitter::new(&data, terms, factors);
+        let fit = fitter.lmer().unwrap();
+        assert!(fit.converged);
+
+        let (a, m) = (6, 5);
+        let y = data.y.values.column(0);
+        let mean = y.sum() / 30.0;
+        let means: Vec<f64> = (0..a)
+            .map(|g| (0..m).map(|j| y[g * m + j]).sum::<f64>() / m as f64)
+            .collect();
+        let within: f64 = (0..30).map(|i| (y[i] - means[i / m]).powi(2)).sum::<f64>() / 24.0;
+        let between: f64 = means.iter().map(|v| (v - mean).powi(2)).sum::<f64>() * m as f64 / 5.0;
+        let group_variance = (between - within) / m as f64;
+        assert!(group_variance > 0.0);
+        assert!((fit.sigma.powi(2) - within).abs() < 1e-4 * within);
+        let component = &fit.variance_components[0];
+        assert!(
+            (component.covariance.values[[0, 0]] - group_variance).abs() < 1e-4 * group_variance
+        );
+        assert!((fit.coefficients[0] - mean).abs() < 1e-8);
+        assert!((fit.std_errors[0] - (between / 30.0).sqrt()).abs() < 1e-4);
+        let (lower, upper) = fit.confint(0.95).unwrap()[0];
+        assert!((upper - lower - 2.0 * 1.959964 * fit.std_errors[0]).abs() < 1e-5);
+
+        // The BLUPs shrink the group means towards the grand mean.
+        let shrinkage = group_variance / (group_variance + within / m as f64);
+        let blups = &fit.random_effects[0];
+        assert_eq!(blups.levels.len(), 6);
+        for (g, group_mean) in means.iter().enumerate() {
+            let expected = shrinkage * (group_mean - mean);
+            assert!((blups.values.values[[g, 0]] - expected).abs() < 1e-4);
+        }
+        assert!(fit.to_string().contains("REML criterion at convergence"));
+        let coefficient = fit.coefficients[0];
+        let fitted = LinearModelFitter::Lmer(fitter).fit().unwrap();
+        assert_eq!(fitted.values[[0, 0]], coefficient);
+    }
+
+    #[test]
+    fn test_random_intercepts_match_compound_symmetry() {
+        // Twelve sites of six with a fixed slope on t; the random intercept model is the
+        // compound symmetry model with a positive correlation.
+        let n = 72;
+        let x: Vec<f64> = (0..n).flat_map(|i| [1.0, (i % 6) as f64]).collect();
+        let y: Vec<f64> = (0..n)
+            .map(|i| {
+                let site = i / 6;
+                2.0 + 0.5 * (i % 6) as f64
+                    + ((site * 5 % 12) as f64 - 5.5) / 3.0
+                    + 0.3 * (site % 3) as f64 * (i % 6) as f64
+                    + ((i * i) as f64 * 0.37).sin()
+            })
+            .collect();
+        let data = Data::new(
+            RealMatrix::from_vec(x, n, Some(2)),
+            RealMatrix::from_vec(y, n, None),
+        )
+        .with_column_names(vec!["(Intercept)".to_string(), "t".to_string()]);
+        let sites: Vec<usize> = (0..n).map(|i| i / 6).collect();
+        let factors = vec![
+            GroupingFactor::new("site", sites.clone()),
+            GroupingFactor::new("half", (0..n).map(|i| i % 6 / 3).collect()),
+        ];
+
+        let terms = RandomTerm::parse("(1 | site)", data.column_names(), &factors).unwrap();
+        let fitter = LmerFitter {
+            method: LmerMethod::Ml,
+            ..LmerFitter::new(&data, terms, factors.clone())
+        };
+        let fit = fitter.lmer().unwrap();
+        let gls = GlsFitter {
+            groups: Some(sites),
+            method: GlsMethod::Ml,
+            ..GlsFitter::new(&data, Correlation::CompoundSymmetry)
+        }
+        .gls()
+        .unwrap();
+        assert!(gls.correlation_parameters[0] > 0.0);
+        assert!((fit.log_lik - gls.log_lik).abs() < 1e-5);
+        for j in 0..2 {
+            assert!((fit.coefficients[j] - gls.coefficients[j]).abs() < 1e-4);
+        }
+
+        // Correlated random slopes, with halves of each site nested within the sites.
+        let terms = RandomTerm::parse(
+            "(1 + t | site) + (1 | site:half)",
+            data.column_names(),
+            &factors,
+        )
+        .unwrap();
+        let fit = LmerFitter::new(&data, terms, factors).lmer().unwrap();
+        assert!(fit.converged);
+        assert_eq!(fit.theta.len(), 3 + 1);
+        assert_eq!(fit.random_effects[1].group, "site:half");
+        assert_eq!(fit.random_effects[1].levels.len(), 24);
+        let slopes = &fit.variance_components[0];
+        assert_eq!(slopes.names, vec!["(Intercept)", "t"]);
+        assert!(slopes.correlation(1, 0).abs() <= 1.0 + 1e-12);
+        assert!(slopes.covariance.values[[1, 1]] > 0.0);
+        assert!(fit.to_string().contains("site:half"));
+    }
+}
@@ -0,0 +1,509 @@
+//! This module contains the least trimmed squares fitter, which implements the `FitModel` trait
+//! for the `LinearModelFitter` enum, with the coverage, consistency factors and reweighting of
+//! R's `robustbase::ltsReg`.
+//!
+//! LTS minimises the sum of the `h` smallest squared residuals, which tolerates up to n - h
+//! outliers. The search is FAST-LTS: random elemental subsets give starting fits, and each is
+//! improved by C-steps, which refit least squares to the `h` observations with the smallest
+//! residuals and can only lower the objective. The best few starts are concentrated on the full
+//! data until the objective stops decreasing. The raw fit is then reweighted: observations with
+//! standardised residuals beyond the 0.9875 quantile of the normal (about 2.24) get weight zero
+//! and least squares on the rest gives the final coefficients and standard errors.
+//!
+//! With 600 or more observations the starts are found on nested subsets, so that they do not
+//! cost C-steps over all n observations. A random subsample of up to 1500 observations is split
+//! into up to five disjoint groups of 300; the starts are shared among the groups and take their
+//! two C-steps within their group. The best ten of each group then take two C-steps on the
+//! merged subsample, and the best ten of those are concentrated on the full data.
+
+// src/fitters/lts_fitter.rs
+
+use super::fit::FitModel;
+use super::rlm_fitter::weighted_fit;
+use crate::distributions::{normal_density, normal_quantile};
+use crate::errors::LmFitterError;
+use crate::types::{Data, RealMatrix};
+use crate::vcov::unscaled_covariance;
+use derive_builder::Builder;
+use rand::rngs::StdRng;
+use rand::seq::index::sample;
+use rand::{Rng, SeedableRng};
+
+/// The number of best starts that are concentrated to convergence.
+const N_BEST: usize = 10;
+
+/// The maximum number of C-steps taken from a single start.
+const MAX_C_STEPS: usize = 100;
+
+/// The size of the groups of a large data set, which has at least two of them.
+const GROUP_SIZE: usize = 300;
+
+/// The largest number of groups a large data set is split into.
+const MAX_GROUPS: usize = 5;
+
+/// The result of a least trimmed squares fit.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LtsFit {
+    /// The reweighted least squares coefficients, `NaN` for aliased columns.
+    pub coefficients: RealMatrix,
+    /// The standard errors of the reweighted coefficients.
+    pub std_errors: Vec<f64>,
+    /// The consistency-corrected scale of the reweighted fit.
+    pub scale: f64,
+    /// The residuals y - X b of the reweighted fit.
+    pub residuals: Vec<f64>,
+    /// The reweighting weights, 1 for retained observations and 0 for flagged outliers.
+    pub weights: Vec<f64>,
+    /// The raw LTS coefficients.
+    pub raw_coefficients: RealMatrix,
+    /// The consistency-corrected scale of the raw fit.
+    pub raw_scale: f64,
+    /// The sum of the `h` smallest squared residuals of the raw fit.
+    pub objective: f64,
+    /// The `h` observations of the best subset, in increasing order.
+    pub subset: Vec<usize>,
+    /// The number of observations whose residuals are trimmed into the objective.
+    pub h: usize,
+}
+
+#[derive(Debug, Builder)]
+pub struct LtsFitter<'a> {
+    pub data: &'a Data,
+    /// The fraction of observations kept, between 0.5 (highest breakdown) and 1 (least squares).
+    #[builder(default = "0.5")]
+    pub alpha: f64,
+    /// The number of random elemental subsets drawn as starts.
+    #[builder(default = "500")]
+    pub n_samples: usize,
+    /// The seed of the generator used to draw the subsets.
+    #[builder(default = "0")]
+    pub seed: u64,
+}
+
+/// A candidate LTS fit, with its objective and residuals.
+struct Candidate {
+    objective: f64,
+    coefficients: RealMatrix,
+    residuals: Vec<f64>,
+}
+
+impl<'a> LtsFitter<'a> {
+    /// Return a new instance of the `LtsFitter` struct with 500 starts and seed 0.
+    pub fn new(data: &'a Data, alpha: f64) -> Self {
+        Self {
+            data,
+            alpha,
+            n_samples: 500,
+            seed: 0,
+        }
+    }
+
+    /// Return the x matrix from the data struct.
+    pub fn x(&self) -> &RealMatrix {
+        &self.data.x
+    }
+
+    /// Return the y matrix from the data struct.
+    pub fn y(&self) -> &RealMatrix {
+        &self.data.y
+    }
+
+    /// Return the number of observations kept for `n` observations and rank `p`, the
+    /// `h.alpha.n` of `robustbase`.
+    pub fn coverage(&self, n: usize, p: usize) -> usize {
+        let half = (n + p).div_ceil(2);
+        let h = (2 * half) as f64 - n as f64 + 2.0 * (n - half) as f64 * self.alpha;
+        (h.floor() as usize).min(n)
+    }
+
+    /// Run the C-step algorithm and the reweighting step.
+    ///
+    /// # Errors
+    /// Returns `LmFitterError::InvalidCoverage` if alpha is outside [0.5, 1],
+    /// `LmFitterError::NoSubsets` if `n_samples` is zero,
+    /// `LmFitterError::PriorWeights` if the data carries weights,
+    /// `LmFitterError::TooFewObservations` if there are no more observations than the rank,
+    /// before or after reweighting, and `LmFitterError::MultipleResponses` for more than one
+    /// response.
+    pub fn lts(&self) -> Result<LtsFit, LmFitterError> {
+        let (x, y) = (self.data.x(), self.data.y());
+        check_robust_data(self.data)?;
+        if !(0.5..=1.0).contains(&self.alpha) {
+            return Err(LmFitterError::InvalidCoverage { alpha: self.alpha });
+        }
+        if self.n_samples == 0 {
+            return Err(LmFitterError::NoSubsets);
+        }
+        let n = x.n_rows();
+        let (full, _) = weighted_fit(x, y, &vec![1.0; n])?;
+        let rank = full.qr.rank;
+        let h = self.coverage(n, rank);
+
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        let starts = if n >= 2 * GROUP_SIZE {
+            self.nested_starts(x, y, rank, &mut rng)?
+                .into_iter()
+                .map(|candidate| evaluate(x, y, candidate.coefficients, h))
+                .collect()
+        } else {
+            starts(x, y, rank, h, self.n_samples, &mut rng)?
+        };
+
+        let mut best: Option<Candidate> = None;
+        for mut candidate in starts {
+            for _ in 0..MAX_C_STEPS {
+                let next = c_step(x, y, &candidate, h)?;
+                let improved = next.objective < candidate.objective;
+                candidate = next;
+                if !improved {
+                    break;
+                }
+            }
+            if best
+                .as_ref()
+                .is_none_or(|b| candidate.objective < b.objective)
+            {
+                best = Some(candidate);
+            }
+        }
+        let raw = best.expect("at least one start is drawn");
+
+        let raw_scale = (raw.objective / h as f64).sqrt() * consistency(h as f64 / n as f64);
+        let cutoff = normal_quantile(0.9875);
+        let weights: Vec<f64> = raw
+            .residuals
+            .iter()
+            .map(|r| {
+                if r.abs() <= cutoff * raw_scale {
+                    1.0
+                } else {
+                    0.0
+                }
+            })
+            .collect();
+        let retained: f64 = weights.iter().sum();
+        let (fit, residuals) = weighted_fit(x, y, &weights)?;
+        if retained <= fit.qr.rank as f64 {
+            return Err(LmFitterError::TooFewObservations {
+                needed: fit.qr.rank + 1,
+                found: retained as usize,
+            });
+        }
+        let rss: f64 = residuals.iter().zip(&weights).map(|(r, w)| w * r * r).sum();
+        let scale =
+            (rss / (retained - fit.qr.rank as f64)).sqrt() * consistency(retained / n as f64);
+        let covariance = unscaled_covariance(&fit.qr);
+        let std_errors = (0..x.n_cols())
+            .map(|j| scale * covariance.values[[j, j]].sqrt())
+            .collect();
+
+        Ok(LtsFit {
+            coefficients: fit.coefficients,
+            std_errors,
+            scale,
+            residuals,
+            weights,
+            subset: smallest(&raw.residuals, h),
+            raw_coefficients: raw.coefficients,
+            raw_scale,
+            objective: raw.objective,
+            h,
+        })
+    }
+
+    /// Return the best starts found on the nested subsets of a large data set, after their
+    /// C-steps on the merged subsample.
+    fn nested_starts(
+        &self,
+        x: &RealMatrix,
+        y: &RealMatrix,
+        rank: usize,
+        rng: &mut StdRng,
+    ) -> Result<Vec<Candidate>, LmFitterError> {
+        let n = x.n_rows();
+        let groups = (n / GROUP_SIZE).min(MAX_GROUPS);
+        let merged = sample(rng, n, groups * GROUP_SIZE).into_vec();
+        let mut candidates = Vec::new();
+        for rows in merged.chunks(GROUP_SIZE) {
+            let (x_group, y_group) = (select_rows(x, rows), select_rows(y, rows));
+            let h = self.coverage(rows.len(), rank);
+            let n_samples = self.n_samples.div_ceil(groups);
+            candidates.extend(starts(&x_group, &y_group, rank, h, n_samples, rng)?);
+        }
+
+        let (x_merged, y_merged) = (select_rows(x, &merged), select_rows(y, &merged));
+        let h = self.coverage(merged.len(), rank);
+        let mut starts = Vec::with_capacity(candidates.len());
+        for candidate in candidates {
+            let mut candidate = evaluate(&x_merged, &y_merged, candidate.coefficients, h);
+            for _ in 0..2 {
+                candidate = c_step(&x_merged, &y_merged, &candidate, h)?;
+            }
+            starts.push(candidate);
+        }
+        Ok(best_starts(starts))
+    }
+}
+
+impl<'a> FitModel for LtsFitter<'a> {
+    /// Fit LTS and return the reweighted coefficients.
+    fn fit(&self) -> Result<RealMatrix, LmFitterError> {
+        Ok(self.lts()?.coefficients)
+    }
+
+    fn x(&self) -> &RealMatrix {
+        self.data.x()
+    }
+
+    fn y(&self) -> &RealMatrix {
+        self.data.y()
+    }
+}
+
+/// Check that the data has a single response, no prior weights and more observations than
+/// columns, as the high-breakdown fitters need.
+pub(crate) fn check_robust_data(data: &Data) -> Result<(), LmFitterError> {
+    if data.y().n_cols() != 1 {
+        return Err(LmFitterError::MultipleResponses {
+            found: data.y().n_cols(),
+        });
+    }
+    if data.weights().is_some() {
+        return Err(LmFitterError::PriorWeights);
+    }
+    if data.x().n_rows() <= data.x().n_cols() {
+        return Err(LmFitterError::TooFewObservations {
+            needed: data.x().n_cols() + 1,
+            found: data.x().n_rows(),
+        });
+    }
+    Ok(())
+}
+
+/// Fit least squares exactly through a random elemental subset of `rank` observations, adding
+/// further random observations while the subset is singular. Returns the coefficients and the
+/// residuals of all observations.
+pub(crate) fn elemental_fit(
+    x: &RealMatrix,
+    y: &RealMatrix,
+    rank: usize,
+    rng: &mut StdRng,
+) -> Result<(RealMatrix, Vec<f64>), LmFitterError> {
+    let n = x.n_rows();
+    let mut weights = vec![0.0; n];
+    for i in sample(rng, n, rank).into_iter() {
+        weights[i] = 1.0;
+    }
+    loop {
+        let (fit, residuals) = weighted_fit(x, y, &weights)?;
+        let size = weights.iter().filter(|&&w| w > 0.0).count();
+        if fit.qr.rank == rank || size == n {
+            return Ok((fit.coefficients, residuals));
+        }
+        let unused: Vec<usize> = (0..n).filter(|&i| weights[i] == 0.0).collect();
+        weights[unused[rng.gen_range(0..unused.len())]] = 1.0;
+    }
+}
+
+/// Draw `n_samples` elemental starts, improve each by two C-steps and return the best.
+fn starts(
+    x: &RealMatrix,
+    y: &RealMatrix,
+    rank: usize,
+    h: usize,
+    n_samples: usize,
+    rng: &mut StdRng,
+) -> Result<Vec<Candidate>, LmFitterError> {
+    let mut starts = Vec::with_capacity(n_samples);
+    for _ in 0..n_samples {
+        let (coefficients, residuals) = elemental_fit(x, y, rank, rng)?;
+        let mut candidate = Candidate {
+            objective: trimmed_sum(&residuals, h),
+            coefficients,
+            residuals,
+        };
+        for _ in 0..2 {
+            candidate = c_step(x, y, &candidate, h)?;
+        }
+        starts.push(candidate);
+    }
+    Ok(best_starts(starts))
+}
+
+/// Keep the `N_BEST` starts with the smallest objective.
+fn best_starts(mut starts: Vec<Candidate>) -> Vec<Candidate> {
+    starts.sort_by(|a, b| a.objective.total_cmp(&b.objective));
+    starts.truncate(N_BEST);
+    starts
+}
+
+/// Return the candidate with `coefficients` on the observations of `x` and `y`, treating
+/// aliased coefficients as zero.
+fn evaluate(x: &RealMatrix, y: &RealMatrix, coefficients: RealMatrix, h: usize) -> Candidate {
+    let estimable = coefficients
+        .values
+        .mapv(|b| if b.is_nan() { 0.0 } else { b });
+    let fitted = x.values.dot(&estimable);
+    let residuals: Vec<f64> = (0..x.n_rows())
+        .map(|i| y.values[[i, 0]] - fitted[[i, 0]])
+        .collect();
+    Candidate {
+        objective: trimmed_sum(&residuals, h),
+        coefficients,
+        residuals,
+    }
+}
+
+/// Return the rows `rows` of `a`.
+fn select_rows(a: &RealMatrix, rows: &[usize]) -> RealMatrix {
+    RealMatrix::new(a.values.select(ndarray::Axis(0), rows))
+}
+
+/// Refit least squares to the `h` observations with the smallest absolute residuals.
+fn c_step(
+    x: &RealMatrix,
+    y: &RealMatrix,
+    candidate: &Candidate,
+    h: usize,
+) -> Result<Candidate, LmFitterError> {
+    let mut weights = vec![0.0; x.n_rows()];
+    for i in smallest(&candidate.residuals, h) {
+        weights[i] = 1.0;
+    }
+    let (fit, residuals) = weighted_fit(x, y, &weights)?;
+    Ok(Candidate {
+        objective: trimmed_sum(&residuals, h),
+        coefficients: fit.coefficients,
+        residuals,
+    })
+}
+
+/// Return the indices of the `h` smallest absolute residuals, in increasing index order.
+fn smallest(residuals: &[f64], h: usize) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..residuals.len()).collect();
+    order.sort_by(|&a, &b| residuals[a].abs().total_cmp(&residuals[b].abs()));
+    order.truncate(h);
+    order.sort_unstable();
+    order
+}
+
+/// Return the sum of the `h` smallest squared residuals.
+fn trimmed_sum(residuals: &[f64], h: usize) -> f64 {
+    let mut squares: Vec<f64> = residuals.iter().map(|r| r * r).collect();
+    squares.sort_by(f64::total_cmp);
+    squares[..h].iter().sum()
+}
+
+/// Return the factor that makes the root mean of the smallest fraction `coverage` of squared
+/// normal residuals consistent for the standard deviation.
+fn consistency(coverage: f64) -> f64 {
+    if coverage >= 1.0 {
+        return 1.0;
+    }
+    let q = normal_quantile((1.0 + coverage) / 2.0);
+    1.0 / (1.0 - 2.0 * q * normal_density(q) / coverage).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fitters::fit::LinearModelFitter;
+    use crate::fitters::qr_decomposition_fitter::QrDecompositionFitter;
+
+    /// y = 1 + 2 t plus small noise, with 40% of the observations replaced by a distant cluster.
+    fn heavily_contaminated() -> Data {
+        let n = 40;
+        let x: Vec<f64> = (0..n)
+            .flat_map(|i| {
+                [
+                    1.0,
+                    if i < 24 {
+                        i as f64
+                    } else {
+                        30.0 + 0.1 * i as f64
+                    },
+                ]
+            })
+            .collect();
+        let y: Vec<f64> = (0..n)
+            .map(|i| {
+                let t = i as f64;
+                if i < 24 {
+                    1.0 + 2.0 * t + 0.5 * (t * 1.7).sin()
+                } else {
+                    -20.0 + (t * 0.9).cos()
+                }
+            })
+            .collect();
+        Data::new(
+            RealMatrix::from_vec(x, n, Some(2)),
+            RealMatrix::from_vec(y, n, None),
+        )
+    }
+
+    #[test]
+    fn test_lts_ignores_forty_percent_contamination() {
+        let data = heavily_contaminated();
+        let ols = QrDecompositionFitter::new(&data, None).fit().unwrap();
+        assert!(ols.values[[1, 0]] < 0.0);
+
+        let fitter = LtsFitter::new(&data, 0.5);
+        let fit = fitter.lts().unwrap();
+        assert_eq!(fit.h, 21);
+        assert!((fit.coefficients.values[[1, 0]] - 2.0).abs() < 0.05);
+        assert!((fit.raw_coefficients.values[[1, 0]] - 2.0).abs() < 0.1);
+        assert!(fit.weights[..24].iter().all(|&w| w == 1.0));
+        assert!(fit.weights[24..].iter().all(|&w| w == 0.0));
+        assert!(fit.scale < 1.0 && fit.std_errors.iter().all(|s| s.is_finite()));
+
+        assert_eq!(fitter.lts().unwrap(), fit);
+        let coefficients = LinearModelFitter::Lts(fitter).fit().unwrap();
+        assert_eq!(coefficients, fit.coefficients);
+    }
+
+    #[test]
+    fn test_large_data_are_searched_on_nested_subsets() {
+        // 700 observations split into two groups of 300, with 30% placed on a distant line.
+        let n = 700;
+        let x: Vec<f64> = (0..n).flat_map(|i| [1.0, (i % 100) as f64]).collect();
+        let y: Vec<f64> = (0..n)
+            .map(|i| {
+                let (t, u) = ((i % 100) as f64, i as f64);
+                if i % 10 < 7 {
+                    1.0 + 2.0 * t + 0.5 * (u * 1.7).sin()
+                } else {
+                    300.0 - 3.0 * t + (u * 0.9).cos()
+                }
+            })
+            .collect();
+        let data = Data::new(
+            RealMatrix::from_vec(x, n, Some(2)),
+            RealMatrix::from_vec(y, n, None),
+        );
+        let fitter = LtsFitter {
+            n_samples: 50,
+            ..LtsFitter::new(&data, 0.5)
+        };
+        let fit = fitter.lts().unwrap();
+        assert!((fit.coefficients.values[[1, 0]] - 2.0).abs() < 0.01);
+        assert_eq!(fit.weights.iter().sum::<f64>(), 490.0);
+        assert_eq!(fitter.lts().unwrap(), fit);
+    }
+
+    #[test]
+    fn test_consistency_and_coverage() {
+        assert_eq!(consistency(1.0), 1.0);
+        assert!(consistency(0.5) > 2.0);
+        let data = heavily_contaminated();
+        assert_eq!(LtsFitter::new(&data, 1.0).coverage(40, 2), 40);
+        assert!(matches!(
+            LtsFitter::new(&data, 0.3).lts(),
+            Err(LmFitterError::InvalidCoverage { .. })
+        ));
+        let no_subsets = LtsFitter {
+            n_samples: 0,
+            ..LtsFitter::new(&data, 0.5)
+        };
+        assert!(matches!(no_subsets.lts(), Err(LmFitterError::NoSubsets)));
+    }
+}
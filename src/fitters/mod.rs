@@ -1,4 +1,22 @@
 // src/fitters/mod.rs
 
+pub mod cochrane_orcutt_fitter;
+pub mod elastic_net_fitter;
 pub mod fit;
+pub mod fixed_effects_fitter;
+pub mod glm_fitter;
+pub mod glm_nb_fitter;
+pub mod gls_fitter;
+pub mod iv_fitter;
+pub mod lmer_fitter;
+pub mod lts_fitter;
+pub mod mm_fitter;
+pub mod multinomial_fitter;
+pub mod nls_fitter;
+pub mod ordinal_fitter;
+pub mod panel_fitter;
 pub mod qr_decomposition_fitter;
+pub mod quantile_regression_fitter;
+pub mod ridge_fitter;
+pub mod rlm_fitter;
+pub(crate) mod standardization;
@@ -0,0 +1,435 @@
+//! This module contains the baseline-category multinomial logit fitter, which implements the
+//! `FitModel` trait for the `LinearModelFitter` enum, like `nnet::multinom`.
+//!
+//! The response is a single column of category codes 0, 1, ..., K - 1. Category 0 is the
+//! baseline, and each other category k has its own coefficients b_k, with
+//! P(y = k) = exp(x'b_k) / (1 + sum_l exp(x'b_l)). The log-likelihood is maximised by
+//! Newton-Raphson with the analytic Hessian, halving steps that do not increase it, and the
+//! standard errors come from the inverse of the observed information at the maximum.
+
+// src/fitters/multinomial_fitter.rs
+
+use super::fit::FitModel;
+use crate::anova::{format_number, write_table};
+use crate::errors::LmFitterError;
+use crate::types::{Data, RealMatrix};
+use derive_builder::Builder;
+use std::fmt;
+
+/// The maximum number of times a Newton step is halved.
+const MAX_HALVINGS: usize = 30;
+
+/// The result of a baseline-category multinomial logit fit.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MultinomialFit {
+    /// The names of the columns of x.
+    pub names: Vec<String>,
+    /// The number of categories K.
+    pub n_categories: usize,
+    /// The coefficients, one column per non-baseline category 1, ..., K - 1.
+    pub coefficients: RealMatrix,
+    /// The standard errors of the coefficients, in the same layout.
+    pub std_errors: RealMatrix,
+    /// The covariance of the coefficients, stacked category by category.
+    pub covariance: RealMatrix,
+    /// The fitted probabilities of each category, one row per observation.
+    pub probabilities: RealMatrix,
+    /// The residual deviance, -2 times the maximised log-likelihood.
+    pub deviance: f64,
+    /// The AIC, the deviance plus twice the number of coefficients.
+    pub aic: f64,
+    /// The number of Newton iterations.
+    pub iterations: usize,
+    /// `true` if the log-likelihood converged within `max_iter` iterations.
+    pub converged: bool,
+}
+
+impl MultinomialFit {
+    /// Return the predicted probabilities of each category for the rows of `x`, which must have
+    /// the same columns as the x the model was fitted to.
+    pub fn predict(&self, x: &RealMatrix) -> RealMatrix {
+        let eta = x.dot(&self.coefficients);
+        let mut probabilities = RealMatrix::with_shape(x.n_rows(), self.n_categories);
+        for i in 0..x.n_rows() {
+            let row: Vec<f64> = eta.values.row(i).to_vec();
+            for (k, probability) in softmax(&row).into_iter().enumerate() {
+                probabilities.values[[i, k]] = probability;
+            }
+        }
+        probabilities
+    }
+}
+
+impl fmt::Display for MultinomialFit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let header: Vec<&str> = self.names.iter().map(String::as_str).collect();
+        let labels: Vec<String> = (1..self.n_categories).map(|k| k.to_string()).collect();
+        let labels: Vec<&str> = labels.iter().map(String::as_str).collect();
+        let cells = |matrix: &RealMatrix| -> Vec<Vec<String>> {
+            (0..self.n_categories - 1)
+                .map(|k| {
+                    matrix
+                        .values
+                        .column(k)
+                        .iter()
+                        .map(|&v| format_number(v))
+                        .collect()
+                })
+                .collect()
+        };
+        writeln!(f, "Coefficients:")?;
+        write_table(f, &labels, &header, &cells(&self.coefficients))?;
+        writeln!(f)?;
+        writeln!(f, "Std. Errors:")?;
+        write_table(f, &labels, &header, &cells(&self.std_errors))?;
+        writeln!(f)?;
+        writeln!(f, "Residual Deviance: {}", format_number(self.deviance))?;
+        writeln!(f, "AIC: {}", format_number(self.aic))
+    }
+}
+
+#[derive(Debug, Builder)]
+pub struct MultinomialFitter<'a> {
+    pub data: &'a Data,
+    /// The maximum number of Newton iterations.
+    #[builder(default = "100")]
+    pub max_iter: usize,
+    /// The tolerance on the relative change in the log-likelihood.
+    #[builder(default = "1e-10")]
+    pub tol: f64,
+}
+
+/// The state of the Newton-Raphson iterations at convergence.
+pub(crate) struct Newton {
+    pub(crate) theta: Vec<f64>,
+    pub(crate) log_lik: f64,
+    pub(crate) information: RealMatrix,
+    pub(crate) iterations: usize,
+    pub(crate) converged: bool,
+}
+
+/// The log-likelihood, its gradient and the observed information at a parameter, or `None` if
+/// the parameter is outside the domain of the model.
+pub(crate) type Evaluation = Option<(f64, Vec<f64>, RealMatrix)>;
+
+impl<'a> MultinomialFitter<'a> {
+    /// Return a new instance of the `MultinomialFitter` struct with the default controls.
+    pub fn new(data: &'a Data) -> Self {
+        Self {
+            data,
+            max_iter: 100,
+            tol: 1e-10,
+        }
+    }
+
+    /// Return the x matrix from the data struct.
+    pub fn x(&self) -> &RealMatrix {
+        &self.data.x
+    }
+
+    /// Return the y matrix from the data struct.
+    pub fn y(&self) -> &RealMatrix {
+        &self.data.y
+    }
+
+    /// Fit the multinomial logit model by Newton-Raphson.
+    ///
+    /// # Errors
+    /// Returns `LmFitterError::InvalidCategory`, `LmFitterError::TooFewCategories` or
+    /// `LmFitterError::EmptyCategory` if the response is not a set of category codes with every
+    /// category observed, `LmFitterError::SingularHessian` if the information is singular, and
+    /// `LmFitterError::MultipleResponses` for more than one response.
+    pub fn multinom(&self) -> Result<MultinomialFit, LmFitterError> {
+        let (categories, n_categories) = categories(self.data, 2)?;
+        let x = self.data.x();
+        let (n, p) = (x.n_rows(), x.n_cols());
+        let weights: Vec<f64> = self
+            .data
+            .weights()
+            .map_or_else(|| vec![1.0; n], |weights| weights.to_vec());
+        let m = n_categories - 1;
+
+        let evaluate = |theta: &[f64]| -> Evaluation {
+            let mut log_lik = 0.0;
+            let mut gradient = vec![0.0; m * p];
+            let mut information = RealMatrix::with_shape(m * p, m * p);
+            for i in 0..n {
+                let row = x.values.row(i);
+                let eta: Vec<f64> = (0..m)
+                    .map(|k| (0..p).map(|j| row[j] * theta[k * p + j]).sum())
+                    .collect();
+                let probabilities = softmax(&eta);
+                let w = weights[i];
+                log_lik += w * probabilities[categories[i]].ln();
+                for k in 0..m {
+                    let residual = f64::from(categories[i] == k + 1) - probabilities[k + 1];
+                    for j in 0..p {
+                        gradient[k * p + j] += w * row[j] * residual;
+                    }
+                    for l in 0..m {
+                        let delta = f64::from(k == l);
+                        let factor = w * probabilities[k + 1] * (delta - probabilities[l + 1]);
+                        for a in 0..p {
+                            for b in 0..p {
+                                information.values[[k * p + a, l * p + b]] +=
+                                    factor * row[a] * row[b];
+                            }
+                        }
+                    }
+                }
+            }
+            log_lik
+                .is_finite()
+                .then_some((log_lik, gradient, information))
+        };
+        let newton = newton(vec![0.0; m * p], &evaluate, self.max_iter, self.tol)?;
+        let covariance = newton
+            .information
+            .inverse()
+            .ok_or(LmFitterError::SingularHessian)?;
+
+        let mut coefficients = RealMatrix::with_shape(p, m);
+        let mut std_errors = RealMatrix::with_shape(p, m);
+        for k in 0..m {
+            for j in 0..p {
+                coefficients.values[[j, k]] = newton.theta[k * p + j];
+                std_errors.values[[j, k]] = covariance.values[[k * p + j, k * p + j]].sqrt();
+            }
+        }
+        let mut fit = MultinomialFit {
+            names: self.data.column_names().to_vec(),
+            n_categories,
+            coefficients,
+            std_errors,
+            covariance,
+            probabilities: RealMatrix::with_shape(0, 0),
+            deviance: -2.0 * newton.log_lik,
+            aic: -2.0 * newton.log_lik + 2.0 * (m * p) as f64,
+            iterations: newton.iterations,
+            converged: newton.converged,
+        };
+        fit.probabilities = fit.predict(x);
+        Ok(fit)
+    }
+}
+
+impl<'a> FitModel for MultinomialFitter<'a> {
+    /// Fit the multinomial logit model and return its coefficients, one column per
+    /// non-baseline category.
+    fn fit(&self) -> Result<RealMatrix, LmFitterError> {
+        Ok(self.multinom()?.coefficients)
+    }
+
+    fn x(&self) -> &RealMatrix {
+        self.data.x()
+    }
+
+    fn y(&self) -> &RealMatrix {
+        self.data.y()
+    }
+}
+
+/// Return the category of each observation and the number of categories, checking that the
+/// response is a single column of codes 0, 1, ..., K - 1 with at least `needed` categories,
+/// each of them observed.
+pub(crate) fn categories(data: &Data, needed: usize) -> Result<(Vec<usize>, usize), LmFitterError> {
+    let y = data.y();
+    if y.n_cols() != 1 {
+        return Err(LmFitterError::MultipleResponses { found: y.n_cols() });
+    }
+    let mut categories = Vec::with_capacity(y.n_rows());
+    for (row, &value) in y.values.column(0).iter().enumerate() {
+        if !(value >= 0.0 && value.fract() == 0.0 && value.is_finite()) {
+            return Err(LmFitterError::InvalidCategory { row, value });
+        }
+        categories.push(value as usize);
+    }
+    let n_categories = categories.iter().max().map_or(0, |&k| k + 1);
+    if n_categories < needed {
+        return Err(LmFitterError::TooFewCategories {
+            needed,
+            found: n_categories,
+        });
+    }
+    if let Some(category) = (0..n_categories).find(|k| !categories.contains(k)) {
+        return Err(LmFitterError::EmptyCategory { category });
+    }
+    Ok((categories, n_categories))
+}
+
+/// Maximise a log-likelihood by Newton-Raphson from `start`, halving steps that leave the
+/// domain or decrease the log-likelihood, until its relative change falls below `tol`. If no
+/// halved step is accepted, the fit is reported as converged only when the Newton decrement is
+/// below `tol` relative to the log-likelihood.
+pub(crate) fn newton(
+    start: Vec<f64>,
+    evaluate: &dyn Fn(&[f64]) -> Evaluation,
+    max_iter: usize,
+    tol: f64,
+) -> Result<Newton, LmFitterError> {
+    let mut theta = start;
+    let (mut log_lik, mut gradient, mut information) =
+        evaluate(&theta).ok_or(LmFitterError::SingularHessian)?;
+    let mut iterations = 0;
+    let mut converged = false;
+    while iterations < max_iter {
+        iterations += 1;
+        let inverse = information
+            .inverse()
+            .ok_or(LmFitterError::SingularHessian)?;
+        let step: Vec<f64> = (0..theta.len())
+            .map(|a| {
+                (0..theta.len())
+                    .map(|b| inverse.values[[a, b]] * gradient[b])
+                    .sum()
+            })
+            .collect();
+
+        let mut scale = 1.0;
+        let mut accepted = None;
+        for _ in 0..=MAX_HALVINGS {
+            let candidate: Vec<f64> = theta
+                .iter()
+                .zip(&step)
+                .map(|(t, s)| t + scale * s)
+                .collect();
+            match evaluate(&candidate) {
+                Some(evaluation) if evaluation.0 >= log_lik - 1e-12 * log_lik.abs() => {
+                    accepted = Some((candidate, evaluation));
+                    break;
+                }
+                _ => scale /= 2.0,
+            }
+        }
+        let Some((candidate, (next, next_gradient, next_information))) = accepted else {
+            // No step improves the log-likelihood. That is the maximum to machine precision
+            // only if the gradient, measured in the metric of the inverse information (the
+            // Newton decrement g' I^-1 g), is negligible; otherwise the iterations stalled.
+            let decrement: f64 = step.iter().zip(&gradient).map(|(s, g)| s * g).sum();
+            converged = decrement.abs() / (log_lik.abs() + 0.1) < tol;
+            break;
+        };
+        let change = (next - log_lik).abs() / (next.abs() + 0.1);
+        (theta, log_lik, gradient, information) =
+            (candidate, next, next_gradient, next_information);
+        if change < tol {
+            converged = true;
+            break;
+        }
+    }
+    Ok(Newton {
+        theta,
+        log_lik,
+        information,
+        iterations,
+        converged,
+    })
+}
+
+/// Return the category probabilities for the linear predictors `eta` of the non-baseline
+/// categories, with the baseline's predictor fixed at zero.
+fn softmax(eta: &[f64]) -> Vec<f64> {
+    let largest = eta.iter().fold(0.0_f64, |acc, &e| acc.max(e));
+    let mut probabilities: Vec<f64> = std::iter::once(-largest)
+        .chain(eta.iter().map(|e| e - largest))
+        .map(f64::exp)
+        .collect();
+    let total: f64 = probabilities.iter().sum();
+    probabilities.iter_mut().for_each(|p| *p /= total);
+    probabilities
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::family::Binomial;
+    use crate::fitters::fit::LinearModelFitter;
+    use crate::fitters::glm_fitter::GlmFitter;
+
+    fn data(n: usize, n_categories: usize) -> Data {
+        let x: Vec<f64> = (0..n).flat_map(|i| [1.0, (i % 13) as f64 / 4.0]).collect();
+        let y: Vec<f64> = (0..n)
+            .map(|i| (((i % 13) / 3 + i * 7 / 5) % n_categories) as f64)
+            .collect();
+        Data::new(
+            RealMatrix::from_vec(x, n, Some(2)),
+            RealMatrix::from_vec(y, n, None),
+        )
+        .with_column_names(vec!["(Intercept)".to_string(), "t".to_string()])
+    }
+
+    #[test]
+    fn test_two_categories_match_logistic_regression() {
+        let data = data(60, 2);
+        let fit = MultinomialFitter::new(&data).multinom().unwrap();
+        let fitter = GlmFitter::new(&data, Binomial::default());
+        let logistic = fitter.glm().unwrap();
+        let summary = logistic.summary();
+        for j in 0..2 {
+            let b = fit.coefficients.values[[j, 0]];
+            assert!((b - summary.coefficients[j]).abs() < 1e-6);
+            assert!((fit.std_errors.values[[j, 0]] - summary.std_errors[j]).abs() < 1e-6);
+        }
+        assert!((fit.deviance - logistic.deviance).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_newton_reports_a_stalled_search() {
+        // The log-likelihood -(t - 1)^2 is defined only at its starting point, so no step is
+        // ever accepted even though the gradient there is large.
+        let evaluate = |theta: &[f64]| -> Evaluation {
+            let t = theta[0];
+            (t == 0.0).then(|| {
+                let information = RealMatrix::from_vec(vec![2.0], 1, Some(1));
+                (-(t - 1.0).powi(2), vec![-2.0 * (t - 1.0)], information)
+            })
+        };
+        let stalled = newton(vec![0.0], &evaluate, 100, 1e-10).unwrap();
+        assert!(!stalled.converged && stalled.iterations == 1);
+
+        let everywhere = |theta: &[f64]| -> Evaluation {
+            let t = theta[0];
+            let information = RealMatrix::from_vec(vec![2.0], 1, Some(1));
+            Some((-(t - 1.0).powi(2), vec![-2.0 * (t - 1.0)], information))
+        };
+        let maximum = newton(vec![0.0], &everywhere, 100, 1e-10).unwrap();
+        assert!(maximum.converged && (maximum.theta[0] - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_fitted_probabilities_solve_the_score_equations() {
+        let data = data(90, 3);
+        let fitter = MultinomialFitter::new(&data);
+        let fit = fitter.multinom().unwrap();
+        assert!(fit.converged);
+        assert_eq!(fit.coefficients.shape(), &[2, 2]);
+        // At the maximum, the expected counts of each category match the observed counts,
+        // overall and weighted by t.
+        for k in 0..3 {
+            let observed: f64 = (0..90)
+                .map(|i| f64::from(data.y().values[[i, 0]] == k as f64))
+                .sum();
+            let expected: f64 = fit.probabilities.values.column(k).sum();
+            assert!((observed - expected).abs() < 1e-6);
+        }
+        assert!(fit
+            .probabilities
+            .values
+            .rows()
+            .into_iter()
+            .all(|row| (row.sum() - 1.0).abs() < 1e-12));
+        assert!((fit.aic - fit.deviance - 8.0).abs() < 1e-12);
+
+        let coefficients = fit.coefficients.clone();
+        assert_eq!(
+            LinearModelFitter::Multinomial(fitter).fit().unwrap(),
+            coefficients
+        );
+        let mut invalid = data.clone();
+        invalid.y.values[[0, 0]] = 0.5;
+        assert!(matches!(
+            MultinomialFitter::new(&invalid).multinom(),
+            Err(LmFitterError::InvalidCategory { row: 0, .. })
+        ));
+    }
+}
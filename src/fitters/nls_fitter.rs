@@ -0,0 +1,701 @@
+//! This module contains the nonlinear least squares fitter, which implements the `FitModel`
+//! trait for the `LinearModelFitter` enum, like R's `nls`.
+//!
+//! The model is a Rust closure giving the mean of y at every row of x for a vector of
+//! parameters, with an optional closure for its Jacobian; without one, the Jacobian is taken by
+//! central differences. Each iteration linearizes the model at the current parameters and
+//! solves the linear least squares problem J d = r for the increment d with `dqrls`.
+//!
+//! * Gauss-Newton takes the full increment, halving it until the residual sum of squares falls,
+//!   and stops with an error once the step factor drops below `min_factor`, like `nls`.
+//! * Levenberg-Marquardt solves the damped problem [J; sqrt(lambda) D] d = [r; 0], with D the
+//!   column norms of J, dividing lambda by ten after a successful step and multiplying it by ten
+//!   after a failed one.
+//! * Port keeps the parameters within bounds. It is not the PORT library's `nl2sol`, but a
+//!   projected Gauss-Newton method: parameters at a bound whose increment points outside are
+//!   held fixed for the iteration, and trial points are projected onto the bounds.
+//!
+//! Convergence is tested with the relative-offset criterion of `nls`: the length of the
+//! projection of the residuals onto the columns of J, relative to the residual standard error.
+//! Prior weights multiply the squared residuals.
+
+// src/fitters/nls_fitter.rs
+
+use super::fit::FitModel;
+use crate::anova::{format_number, write_coefficients};
+use crate::errors::{LmFitterError, NlsError};
+use crate::least_squares::dqrls;
+use crate::types::{Data, RealMatrix, Tolerance};
+use crate::vcov::unscaled_covariance;
+use derive_builder::Builder;
+use std::fmt;
+
+/// The mean of y at each row of x, given the parameters.
+pub type ModelFunction<'a> = &'a (dyn Fn(&RealMatrix, &[f64]) -> Vec<f64> + Sync);
+
+/// The n x k derivative of the model with respect to the parameters.
+pub type JacobianFunction<'a> = &'a (dyn Fn(&RealMatrix, &[f64]) -> RealMatrix + Sync);
+
+/// A nonlinear model, with its Jacobian if it is known.
+#[derive(Clone, Copy)]
+pub struct NlsModel<'a> {
+    /// The mean of y at each row of x.
+    pub function: ModelFunction<'a>,
+    /// The Jacobian of `function`, or `None` for central differences.
+    pub jacobian: Option<JacobianFunction<'a>>,
+}
+
+impl<'a> NlsModel<'a> {
+    /// Create a new `NlsModel` struct whose Jacobian is taken by central differences.
+    pub fn new(function: ModelFunction<'a>) -> Self {
+        NlsModel {
+            function,
+            jacobian: None,
+        }
+    }
+
+    /// Return the same model with an analytic Jacobian.
+    pub fn with_jacobian(mut self, jacobian: JacobianFunction<'a>) -> Self {
+        self.jacobian = Some(jacobian);
+        self
+    }
+}
+
+impl fmt::Debug for NlsModel<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NlsModel")
+            .field("analytic_jacobian", &self.jacobian.is_some())
+            .finish_non_exhaustive()
+    }
+}
+
+/// An enum representing the algorithms of the nonlinear least squares fitter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NlsAlgorithm {
+    /// Gauss-Newton with step halving.
+    #[default]
+    GaussNewton,
+    /// Levenberg-Marquardt with Marquardt's scaling.
+    LevenbergMarquardt,
+    /// Projected Gauss-Newton within the bounds `lower` and `upper`.
+    Port,
+}
+
+/// The controls of the nonlinear least squares fitter, with the defaults of `nls.control`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NlsControl {
+    /// The maximum number of iterations.
+    pub max_iter: usize,
+    /// The relative-offset convergence tolerance.
+    pub tol: f64,
+    /// The smallest Gauss-Newton step factor before giving up.
+    pub min_factor: f64,
+}
+
+impl Default for NlsControl {
+    fn default() -> Self {
+        Self {
+            max_iter: 50,
+            tol: 1e-5,
+            min_factor: 1.0 / 1024.0,
+        }
+    }
+}
+
+/// The result of a nonlinear least squares fit.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NlsFit {
+    /// The algorithm the model was fitted with.
+    pub algorithm: NlsAlgorithm,
+    /// The names of the parameters.
+    pub names: Vec<String>,
+    /// The parameter estimates.
+    pub parameters: Vec<f64>,
+    /// The standard errors of the parameters.
+    pub std_errors: Vec<f64>,
+    /// The covariance of the parameters, sigma^2 (J'J)^-1 at the estimates.
+    pub covariance: RealMatrix,
+    /// The residual standard error.
+    pub sigma: f64,
+    /// The residual degrees of freedom.
+    pub df_residual: usize,
+    /// The (weighted) residual sum of squares.
+    pub rss: f64,
+    /// The fitted values.
+    pub fitted_values: Vec<f64>,
+    /// The residuals y - f(x, parameters).
+    pub residuals: Vec<f64>,
+    /// The number of iterations.
+    pub iterations: usize,
+    /// `true` if the relative offset fell below the tolerance within `max_iter` iterations.
+    pub converged: bool,
+    /// The relative offset at the last iteration.
+    pub achieved_tolerance: f64,
+}
+
+impl fmt::Display for NlsFit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let algorithm = match self.algorithm {
+            NlsAlgorithm::GaussNewton => "Gauss-Newton",
+            NlsAlgorithm::LevenbergMarquardt => "Levenberg-Marquardt",
+            NlsAlgorithm::Port => "port",
+        };
+        writeln!(f, "Nonlinear regression model ({})", algorithm)?;
+        writeln!(f)?;
+        writeln!(f, "Parameters:")?;
+        write_coefficients(
+            f,
+            &self.names,
+            &self.parameters,
+            &self.std_errors,
+            self.df_residual,
+            &["Estimate", "Std. Error", "t value", "Pr(>|t|)"],
+        )?;
+        writeln!(f)?;
+        writeln!(
+            f,
+            "Residual standard error: {} on {} degrees of freedom",
+            format_number(self.sigma),
+            self.df_residual
+        )?;
+        writeln!(f)?;
+        if self.converged {
+            writeln!(
+                f,
+                "Number of iterations to convergence: {}",
+                self.iterations
+            )?;
+        } else {
+            writeln!(f, "No convergence in {} iterations", self.iterations)?;
+        }
+        writeln!(
+            f,
+            "Achieved convergence tolerance: {}",
+            format_number(self.achieved_tolerance)
+        )
+    }
+}
+
+#[derive(Debug, Builder)]
+pub struct NlsFitter<'a> {
+    pub data: &'a Data,
+    pub model: NlsModel<'a>,
+    /// The starting values of the parameters.
+    pub start: Vec<f64>,
+    /// The names of the parameters, `b1`, `b2`, ... if empty.
+    #[builder(default)]
+    pub names: Vec<String>,
+    #[builder(default)]
+    pub algorithm: NlsAlgorithm,
+    /// The lower bounds of the parameters, used by the port algorithm only.
+    #[builder(default)]
+    pub lower: Option<Vec<f64>>,
+    /// The upper bounds of the parameters, used by the port algorithm only.
+    #[builder(default)]
+    pub upper: Option<Vec<f64>>,
+    #[builder(default)]
+    pub control: NlsControl,
+}
+
+impl<'a> NlsFitter<'a> {
+    /// Return a new instance of the `NlsFitter` struct, fitted by Gauss-Newton with the default
+    /// controls.
+    pub fn new(data: &'a Data, model: NlsModel<'a>, start: Vec<f64>) -> Self {
+        Self {
+            data,
+            model,
+            start,
+            names: Vec::new(),
+            algorithm: NlsAlgorithm::GaussNewton,
+            lower: None,
+            upper: None,
+            control: NlsControl::default(),
+        }
+    }
+
+    /// Return the x matrix from the data struct.
+    pub fn x(&self) -> &RealMatrix {
+        &self.data.x
+    }
+
+    /// Return the y matrix from the data struct.
+    pub fn y(&self) -> &RealMatrix {
+        &self.data.y
+    }
+
+    /// Fit the model.
+    ///
+    /// # Errors
+    /// Returns `NlsError::Fit` wrapping `LmFitterError::MultipleResponses` if y has more than
+    /// one column, `NlsError::LengthMismatch` if the names or bounds do not have one entry per
+    /// parameter, `NlsError::TooFewObservations` if there are no more observations than
+    /// parameters, `NlsError::InfeasibleStart` for a port start outside the bounds,
+    /// `NlsError::ModelLength`, `NlsError::JacobianShape` or `NlsError::NonFinite` for a model
+    /// that does not evaluate properly, `NlsError::SingularGradient` if the Jacobian loses rank,
+    /// and `NlsError::NoProgress` if no step reduces the residual sum of squares.
+    pub fn nls(&self) -> Result<NlsFit, NlsError> {
+        let y = self.y();
+        if y.n_cols() != 1 {
+            return Err(LmFitterError::MultipleResponses { found: y.n_cols() }.into());
+        }
+        let (n, k) = (y.n_rows(), self.start.len());
+        let names = self.parameter_names()?;
+        let (lower, upper) = self.bounds()?;
+        if n <= k {
+            return Err(NlsError::TooFewObservations {
+                needed: k + 1,
+                found: n,
+            });
+        }
+        for j in 0..k {
+            if self.start[j] < lower[j] || self.start[j] > upper[j] {
+                return Err(NlsError::InfeasibleStart {
+                    parameter: names[j].clone(),
+                });
+            }
+        }
+
+        let control = &self.control;
+        let mut theta = self.start.clone();
+        let mut residuals = self.residuals(&theta)?;
+        if residuals.iter().any(|r| !r.is_finite()) {
+            return Err(NlsError::NonFinite);
+        }
+        let mut rss = sum_of_squares(&residuals);
+        let (mut iterations, mut converged) = (0, false);
+        let mut achieved_tolerance;
+        let (mut factor, mut lambda) = (1.0_f64, 1e-3_f64);
+        loop {
+            let jacobian = self.jacobian(&theta, &lower, &upper)?;
+            if jacobian.values.iter().any(|v| !v.is_finite()) {
+                return Err(NlsError::NonFinite);
+            }
+            let (increment, offset) = if self.algorithm == NlsAlgorithm::Port {
+                bounded_step(&jacobian, &residuals, &theta, &lower, &upper, iterations)?
+            } else {
+                let free: Vec<usize> = (0..k).collect();
+                gauss_newton_step(&jacobian, &residuals, &free, iterations)?
+            };
+            achieved_tolerance = offset;
+            if offset < control.tol || rss == 0.0 {
+                converged = true;
+                break;
+            }
+            if iterations == control.max_iter {
+                break;
+            }
+            iterations += 1;
+
+            // Find a step that reduces the residual sum of squares.
+            loop {
+                let trial: Vec<f64> = match self.algorithm {
+                    NlsAlgorithm::LevenbergMarquardt => {
+                        let damped = marquardt_step(&jacobian, &residuals, lambda, iterations)?;
+                        theta.iter().zip(&damped).map(|(t, d)| t + d).collect()
+                    }
+                    _ => (0..k)
+                        .map(|j| (theta[j] + factor * increment[j]).clamp(lower[j], upper[j]))
+                        .collect(),
+                };
+                let trial_residuals = self.residuals(&trial)?;
+                let trial_rss = sum_of_squares(&trial_residuals);
+                if trial_rss.is_finite() && trial_rss < rss {
+                    theta = trial;
+                    residuals = trial_residuals;
+                    rss = trial_rss;
+                    factor = (2.0 * factor).min(1.0);
+                    lambda /= 10.0;
+                    break;
+                }
+                factor /= 2.0;
+                lambda *= 10.0;
+                let stalled = match self.algorithm {
+                    NlsAlgorithm::LevenbergMarquardt => lambda > 1e16,
+                    _ => factor < control.min_factor,
+                };
+                if stalled {
+                    return Err(NlsError::NoProgress {
+                        iteration: iterations,
+                    });
+                }
+            }
+        }
+
+        // The covariance comes from the QR decomposition of the Jacobian at the estimates.
+        let jacobian = self.jacobian(&theta, &lower, &upper)?;
+        let r = RealMatrix::from_vec(residuals.clone(), n, None);
+        let qr = dqrls(&jacobian, &r, &Tolerance::default())
+            .map_err(LmFitterError::from)?
+            .qr;
+        let df_residual = n - k;
+        let sigma2 = rss / df_residual as f64;
+        let covariance = RealMatrix::new(unscaled_covariance(&qr).values * sigma2);
+        let fitted_values = (self.model.function)(self.x(), &theta);
+        Ok(NlsFit {
+            algorithm: self.algorithm,
+            names,
+            std_errors: (0..k).map(|j| covariance.values[[j, j]].sqrt()).collect(),
+            parameters: theta,
+            covariance,
+            sigma: sigma2.sqrt(),
+            df_residual,
+            rss,
+            residuals: y
+                .values
+                .column(0)
+                .iter()
+                .zip(&fitted_values)
+                .map(|(y, f)| y - f)
+                .collect(),
+            fitted_values,
+            iterations,
+            converged,
+            achieved_tolerance,
+        })
+    }
+
+    /// Return the names of the parameters.
+    fn parameter_names(&self) -> Result<Vec<String>, NlsError> {
+        let k = self.start.len();
+        if self.names.is_empty() {
+            return Ok((1..=k).map(|j| format!("b{j}")).collect());
+        }
+        if self.names.len() != k {
+            return Err(NlsError::LengthMismatch {
+                name: "name".to_string(),
+                expected: k,
+                found: self.names.len(),
+            });
+        }
+        Ok(self.names.clone())
+    }
+
+    /// Return the lower and upper bounds, which are infinite unless the algorithm is port.
+    fn bounds(&self) -> Result<(Vec<f64>, Vec<f64>), NlsError> {
+        let k = self.start.len();
+        let bound = |values: &Option<Vec<f64>>, name: &str, infinity: f64| match values {
+            Some(values) if self.algorithm == NlsAlgorithm::Port => {
+                if values.len() != k {
+                    return Err(NlsError::LengthMismatch {
+                        name: name.to_string(),
+                        expected: k,
+                        found: values.len(),
+                    });
+                }
+                Ok(values.clone())
+            }
+            _ => Ok(vec![infinity; k]),
+        };
+        Ok((
+            bound(&self.lower, "lower bound", f64::NEG_INFINITY)?,
+            bound(&self.upper, "upper bound", f64::INFINITY)?,
+        ))
+    }
+
+    /// Return sqrt(w) (y - f(x, theta)), the residuals of the least squares problem.
+    fn residuals(&self, theta: &[f64]) -> Result<Vec<f64>, NlsError> {
+        let y = self.y();
+        let fitted = (self.model.function)(self.x(), theta);
+        if fitted.len() != y.n_rows() {
+            return Err(NlsError::ModelLength {
+                expected: y.n_rows(),
+                found: fitted.len(),
+            });
+        }
+        Ok(y.values
+            .column(0)
+            .iter()
+            .zip(fitted)
+            .enumerate()
+            .map(|(i, (y, f))| self.root_weight(i) * (y - f))
+            .collect())
+    }
+
+    /// Return sqrt(w) times the Jacobian of the model, analytic or by central differences. A
+    /// difference that would cross a bound is taken on one side only.
+    fn jacobian(
+        &self,
+        theta: &[f64],
+        lower: &[f64],
+        upper: &[f64],
+    ) -> Result<RealMatrix, NlsError> {
+        let (n, k) = (self.y().n_rows(), theta.len());
+        let mut jacobian = match self.model.jacobian {
+            Some(jacobian) => {
+                let jacobian = jacobian(self.x(), theta);
+                if jacobian.n_rows() != n || jacobian.n_cols() != k {
+                    return Err(NlsError::JacobianShape {
+                        rows: n,
+                        columns: k,
+                        found_rows: jacobian.n_rows(),
+                        found_columns: jacobian.n_cols(),
+                    });
+                }
+                jacobian
+            }
+            None => {
+                let mut jacobian = RealMatrix::with_shape(n, k);
+                for j in 0..k {
+                    let h = f64::EPSILON.cbrt() * theta[j].abs().max(1.0);
+                    let (mut above, mut below) = (theta.to_vec(), theta.to_vec());
+                    above[j] = (theta[j] + h).min(upper[j]);
+                    below[j] = (theta[j] - h).max(lower[j]);
+                    let (f_above, f_below) = (
+                        (self.model.function)(self.x(), &above),
+                        (self.model.function)(self.x(), &below),
+                    );
+                    for found in [f_above.len(), f_below.len()] {
+                        if found != n {
+                            return Err(NlsError::ModelLength { expected: n, found });
+                        }
+                    }
+                    let width = above[j] - below[j];
+                    for i in 0..n {
+                        jacobian.values[[i, j]] = (f_above[i] - f_below[i]) / width;
+                    }
+                }
+                jacobian
+            }
+        };
+        for (i, mut row) in jacobian.values.rows_mut().into_iter().enumerate() {
+            row *= self.root_weight(i);
+        }
+        Ok(jacobian)
+    }
+
+    fn root_weight(&self, i: usize) -> f64 {
+        self.data.weights().map_or(1.0, |w| w[i].sqrt())
+    }
+}
+
+impl<'a> FitModel for NlsFitter<'a> {
+    /// Fit the model and return the parameters as a single column.
+    fn fit(&self) -> Result<RealMatrix, LmFitterError> {
+        let parameters = self
+            .nls()
+            .map_err(|error| match error {
+                NlsError::Fit(error) => error,
+                error => LmFitterError::Nls(Box::new(error)),
+            })?
+            .parameters;
+        let k = parameters.len();
+        Ok(RealMatrix::from_vec(parameters, k, None))
+    }
+
+    fn x(&self) -> &RealMatrix {
+        self.data.x()
+    }
+
+    fn y(&self) -> &RealMatrix {
+        self.data.y()
+    }
+}
+
+/// Return the Gauss-Newton increment of the parameters in `free`, zero for the others, with the
+/// relative offset sqrt(|Q'r|^2 / p) / sqrt(|r - Q Q'r|^2 / (n - p)) over the free columns.
+fn gauss_newton_step(
+    jacobian: &RealMatrix,
+    residuals: &[f64],
+    free: &[usize],
+    iteration: usize,
+) -> Result<(Vec<f64>, f64), NlsError> {
+    let (n, k, p) = (jacobian.n_rows(), jacobian.n_cols(), free.len());
+    let mut increment = vec![0.0; k];
+    if p == 0 {
+        return Ok((increment, 0.0));
+    }
+    let columns = RealMatrix::new(jacobian.values.select(ndarray::Axis(1), free));
+    let r = RealMatrix::from_vec(residuals.to_vec(), n, None);
+    let fit = dqrls(&columns, &r, &Tolerance::default()).map_err(LmFitterError::from)?;
+    if fit.rank() < p {
+        return Err(NlsError::SingularGradient { iteration });
+    }
+    for (position, &j) in free.iter().enumerate() {
+        increment[j] = fit.coefficients.values[[position, 0]];
+    }
+    let rss = sum_of_squares(residuals);
+    let unexplained = sum_of_squares(fit.residuals.values.as_slice().unwrap_or(&[]));
+    let offset =
+        ((rss - unexplained).max(0.0) / p as f64).sqrt() / (unexplained / (n - p) as f64).sqrt();
+    Ok((increment, offset))
+}
+
+/// Return the Gauss-Newton increment with the parameters at a bound held fixed when their
+/// increment points outside it.
+fn bounded_step(
+    jacobian: &RealMatrix,
+    residuals: &[f64],
+    theta: &[f64],
+    lower: &[f64],
+    upper: &[f64],
+    iteration: usize,
+) -> Result<(Vec<f64>, f64), NlsError> {
+    let mut free: Vec<usize> = (0..theta.len()).collect();
+    loop {
+        let (increment, offset) = gauss_newton_step(jacobian, residuals, &free, iteration)?;
+        let blocked = |&j: &usize| {
+            (theta[j] <= lower[j] && increment[j] < 0.0)
+                || (theta[j] >= upper[j] && increment[j] > 0.0)
+        };
+        if !free.iter().any(blocked) {
+            return Ok((increment, offset));
+        }
+        free.retain(|j| !blocked(j));
+    }
+}
+
+/// Return the Levenberg-Marquardt increment, the least squares solution of
+/// [J; sqrt(lambda) D] d = [r; 0] with D the column norms of J.
+fn marquardt_step(
+    jacobian: &RealMatrix,
+    residuals: &[f64],
+    lambda: f64,
+    iteration: usize,
+) -> Result<Vec<f64>, NlsError> {
+    let (n, k) = (jacobian.n_rows(), jacobian.n_cols());
+    let mut augmented = RealMatrix::with_shape(n + k, k);
+    augmented
+        .values
+        .slice_mut(ndarray::s![..n, ..])
+        .assign(&jacobian.values);
+    for j in 0..k {
+        let norm = jacobian
+            .values
+            .column(j)
+            .iter()
+            .map(|v| v * v)
+            .sum::<f64>()
+            .sqrt();
+        augmented.values[[n + j, j]] = lambda.sqrt() * if norm > 0.0 { norm } else { 1.0 };
+    }
+    let mut r = RealMatrix::with_shape(n + k, 1);
+    for (i, &value) in residuals.iter().enumerate() {
+        r.values[[i, 0]] = value;
+    }
+    let fit = dqrls(&augmented, &r, &Tolerance::default()).map_err(LmFitterError::from)?;
+    if fit.rank() < k {
+        return Err(NlsError::SingularGradient { iteration });
+    }
+    Ok(fit.coefficients.values.column(0).to_vec())
+}
+
+fn sum_of_squares(values: &[f64]) -> f64 {
+    values.iter().map(|v| v * v).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The treated half of R's `Puromycin` data: substrate concentration and reaction rate.
+    fn puromycin() -> Data {
+        let conc = vec![
+            0.02, 0.02, 0.06, 0.06, 0.11, 0.11, 0.22, 0.22, 0.56, 0.56, 1.10, 1.10,
+        ];
+        let rate = vec![
+            76.0, 47.0, 97.0, 107.0, 123.0, 139.0, 159.0, 152.0, 191.0, 201.0, 207.0, 200.0,
+        ];
+        Data::new(
+            RealMatrix::from_vec(conc, 12, None),
+            RealMatrix::from_vec(rate, 12, None),
+        )
+    }
+
+    /// The Michaelis-Menten model Vm conc / (K + conc).
+    fn michaelis_menten(x: &RealMatrix, theta: &[f64]) -> Vec<f64> {
+        x.values
+            .column(0)
+            .iter()
+            .map(|c| theta[0] * c / (theta[1] + c))
+            .collect()
+    }
+
+    fn michaelis_menten_jacobian(x: &RealMatrix, theta: &[f64]) -> RealMatrix {
+        let n = x.n_rows();
+        let mut jacobian = RealMatrix::with_shape(n, 2);
+        for (i, c) in x.values.column(0).iter().enumerate() {
+            jacobian.values[[i, 0]] = c / (theta[1] + c);
+            jacobian.values[[i, 1]] = -theta[0] * c / (theta[1] + c).powi(2);
+        }
+        jacobian
+    }
+
+    #[test]
+    fn test_michaelis_menten_matches_r() {
+        let data = puromycin();
+        let model = NlsModel::new(&michaelis_menten).with_jacobian(&michaelis_menten_jacobian);
+        let mut fitter = NlsFitter::new(&data, model, vec![200.0, 0.05]);
+        fitter.names = vec!["Vm".to_string(), "K".to_string()];
+        let fit = fitter.nls().unwrap();
+        assert!(fit.converged);
+        assert_eq!(fit.df_residual, 10);
+        assert!((fit.parameters[0] - 212.68).abs() < 0.01);
+        assert!((fit.parameters[1] - 0.06412).abs() < 1e-5);
+        assert!((fit.std_errors[0] - 6.947).abs() < 1e-3);
+        assert!((fit.std_errors[1] - 0.008281).abs() < 1e-6);
+        assert!((fit.sigma - 10.93).abs() < 0.01);
+        assert!(fit.to_string().contains("Vm"));
+
+        // Levenberg-Marquardt with finite differences reaches the same estimates.
+        let lm = NlsFitterBuilder::default()
+            .data(&data)
+            .model(NlsModel::new(&michaelis_menten))
+            .start(vec![200.0, 0.05])
+            .algorithm(NlsAlgorithm::LevenbergMarquardt)
+            .build()
+            .unwrap()
+            .nls()
+            .unwrap();
+        assert!(lm.converged);
+        for j in 0..2 {
+            let relative = |a: f64, b: f64| ((a - b) / b).abs();
+            assert!(relative(lm.parameters[j], fit.parameters[j]) < 1e-5);
+            assert!(relative(lm.std_errors[j], fit.std_errors[j]) < 1e-4);
+        }
+    }
+
+    #[test]
+    fn test_port_bounds_and_singular_gradient() {
+        let data = puromycin();
+        let fit = NlsFitterBuilder::default()
+            .data(&data)
+            .model(NlsModel::new(&michaelis_menten))
+            .start(vec![200.0, 0.05])
+            .algorithm(NlsAlgorithm::Port)
+            .lower(Some(vec![0.0, 0.0]))
+            .upper(Some(vec![f64::INFINITY, 0.05]))
+            .build()
+            .unwrap()
+            .nls()
+            .unwrap();
+        assert!(fit.converged);
+        assert_eq!(fit.parameters[1], 0.05);
+
+        // With K at its bound, Vm is the linear least squares slope on conc / (K + conc).
+        let g: Vec<f64> = data
+            .x
+            .values
+            .column(0)
+            .iter()
+            .map(|c| c / (0.05 + c))
+            .collect();
+        let vm = g
+            .iter()
+            .zip(data.y.values.column(0))
+            .map(|(g, y)| g * y)
+            .sum::<f64>()
+            / g.iter().map(|g| g * g).sum::<f64>();
+        assert!((fit.parameters[0] - vm).abs() < 1e-3);
+
+        // Vm and a scale factor on it cannot be told apart.
+        let overparameterized =
+            |x: &RealMatrix, theta: &[f64]| michaelis_menten(x, &[theta[0] * theta[2], theta[1]]);
+        let result = NlsFitter::new(
+            &data,
+            NlsModel::new(&overparameterized),
+            vec![200.0, 0.05, 1.0],
+        )
+        .nls();
+        assert!(matches!(result, Err(NlsError::SingularGradient { .. })));
+    }
+}
@@ -0,0 +1,602 @@
+//! This module contains the cumulative-link ordinal regression fitter, which implements the
+//! `FitModel` trait for the `LinearModelFitter` enum, like `MASS::polr`.
+//!
+//! The response is a single column of ordered category codes 0, 1, ..., K - 1, and
+//! P(y <= k) = F(zeta_k - x'b) for increasing cutpoints zeta_0 < ... < zeta_{K-2}, where F is
+//! the logistic or the normal distribution function. The cutpoints take the place of an
+//! intercept, so intercept columns of x are dropped. The log-likelihood is maximised jointly in
+//! b and the cutpoints by Newton-Raphson with the analytic Hessian.
+//!
+//! The proportional odds assumption, that one b serves every cutpoint, is checked with Brant's
+//! test: separate binary logistic regressions of y > k are fitted for each k, and a Wald test
+//! compares their slopes using the covariance of the stacked estimates.
+
+// src/fitters/ordinal_fitter.rs
+
+use super::fit::FitModel;
+use super::glm_fitter::{irls, GlmControl};
+use super::multinomial_fitter::{categories, newton, Evaluation};
+use crate::anova::{format_number, format_p_value, write_table};
+use crate::distributions::{chi_squared_upper_tail, normal_cdf, normal_density, normal_quantile};
+use crate::errors::{GlmError, LmFitterError};
+use crate::family::{Binomial, Family};
+use crate::hypothesis::wald_statistic;
+use crate::types::{Data, RealMatrix};
+use derive_builder::Builder;
+use std::fmt;
+
+/// An enum representing the distribution functions of the cumulative link.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OrdinalLink {
+    /// The logistic distribution, for the proportional odds model.
+    #[default]
+    Logistic,
+    /// The standard normal distribution, for the ordered probit model.
+    Probit,
+}
+
+impl OrdinalLink {
+    /// Return F(z).
+    fn cdf(&self, z: f64) -> f64 {
+        match self {
+            OrdinalLink::Logistic => 1.0 / (1.0 + (-z).exp()),
+            OrdinalLink::Probit => normal_cdf(z),
+        }
+    }
+
+    /// Return the density f(z).
+    fn density(&self, z: f64) -> f64 {
+        match self {
+            OrdinalLink::Logistic => {
+                let p = self.cdf(z);
+                p * (1.0 - p)
+            }
+            OrdinalLink::Probit => normal_density(z),
+        }
+    }
+
+    /// Return the derivative of the density f'(z).
+    fn density_derivative(&self, z: f64) -> f64 {
+        match self {
+            OrdinalLink::Logistic => {
+                let p = self.cdf(z);
+                p * (1.0 - p) * (1.0 - 2.0 * p)
+            }
+            OrdinalLink::Probit => -z * normal_density(z),
+        }
+    }
+
+    /// Return F^-1(p).
+    fn quantile(&self, p: f64) -> f64 {
+        match self {
+            OrdinalLink::Logistic => (p / (1.0 - p)).ln(),
+            OrdinalLink::Probit => normal_quantile(p),
+        }
+    }
+}
+
+/// The result of a cumulative-link ordinal regression fit.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrdinalFit {
+    /// The link the model was fitted with.
+    pub link: OrdinalLink,
+    /// The columns of x in the linear predictor, which exclude intercept columns.
+    pub columns: Vec<usize>,
+    /// The names of the coefficients.
+    pub names: Vec<String>,
+    /// The coefficients b.
+    pub coefficients: Vec<f64>,
+    /// The standard errors of the coefficients.
+    pub std_errors: Vec<f64>,
+    /// The names of the cutpoints, `0|1`, `1|2`, ...
+    pub zeta_names: Vec<String>,
+    /// The cutpoints zeta.
+    pub zeta: Vec<f64>,
+    /// The standard errors of the cutpoints.
+    pub zeta_std_errors: Vec<f64>,
+    /// The covariance of the coefficients followed by the cutpoints.
+    pub covariance: RealMatrix,
+    /// The fitted probabilities of each category, one row per observation.
+    pub probabilities: RealMatrix,
+    /// The residual deviance, -2 times the maximised log-likelihood.
+    pub deviance: f64,
+    /// The AIC, the deviance plus twice the number of coefficients and cutpoints.
+    pub aic: f64,
+    /// The number of Newton iterations.
+    pub iterations: usize,
+    /// `true` if the log-likelihood converged within `max_iter` iterations.
+    pub converged: bool,
+}
+
+impl OrdinalFit {
+    /// Return the predicted probabilities of each category for the rows of `x`, which must have
+    /// the same columns as the x the model was fitted to.
+    pub fn predict(&self, x: &RealMatrix) -> RealMatrix {
+        let n_categories = self.zeta.len() + 1;
+        let mut probabilities = RealMatrix::with_shape(x.n_rows(), n_categories);
+        for i in 0..x.n_rows() {
+            let eta: f64 = self
+                .columns
+                .iter()
+                .zip(&self.coefficients)
+                .map(|(&j, b)| x.values[[i, j]] * b)
+                .sum();
+            let mut below = 0.0;
+            for k in 0..n_categories {
+                let cumulative = self.zeta.get(k).map_or(1.0, |z| self.link.cdf(z - eta));
+                probabilities.values[[i, k]] = cumulative - below;
+                below = cumulative;
+            }
+        }
+        probabilities
+    }
+}
+
+impl fmt::Display for OrdinalFit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let header = ["Value", "Std. Error", "t value"];
+        let rows = |values: &[f64], std_errors: &[f64]| -> Vec<Vec<String>> {
+            values
+                .iter()
+                .zip(std_errors)
+                .map(|(&v, &se)| vec![format_number(v), format_number(se), format_number(v / se)])
+                .collect()
+        };
+        writeln!(f, "Coefficients:")?;
+        let labels: Vec<&str> = self.names.iter().map(String::as_str).collect();
+        write_table(
+            f,
+            &labels,
+            &header,
+            &rows(&self.coefficients, &self.std_errors),
+        )?;
+        writeln!(f)?;
+        writeln!(f, "Intercepts:")?;
+        let labels: Vec<&str> = self.zeta_names.iter().map(String::as_str).collect();
+        write_table(
+            f,
+            &labels,
+            &header,
+            &rows(&self.zeta, &self.zeta_std_errors),
+        )?;
+        writeln!(f)?;
+        writeln!(f, "Residual Deviance: {}", format_number(self.deviance))?;
+        writeln!(f, "AIC: {}", format_number(self.aic))
+    }
+}
+
+/// One row of Brant's test.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BrantRow {
+    /// `Omnibus` for the joint test, otherwise the name of a coefficient.
+    pub term: String,
+    /// The Wald statistic.
+    pub statistic: f64,
+    /// The degrees of freedom of the statistic.
+    pub df: usize,
+    /// The upper tail probability of the statistic under the chi-squared distribution.
+    pub p_value: f64,
+}
+
+/// Brant's test of the proportional odds assumption, like `brant::brant`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BrantTest {
+    /// The joint test of all coefficients, followed by one test per coefficient.
+    pub rows: Vec<BrantRow>,
+}
+
+impl fmt::Display for BrantTest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let cells: Vec<Vec<String>> = self
+            .rows
+            .iter()
+            .map(|row| {
+                vec![
+                    format_number(row.statistic),
+                    row.df.to_string(),
+                    format_p_value(row.p_value),
+                ]
+            })
+            .collect();
+        let labels: Vec<&str> = self.rows.iter().map(|row| row.term.as_str()).collect();
+        write_table(f, &labels, &["X2", "df", "probability"], &cells)?;
+        writeln!(f)?;
+        writeln!(f, "H0: Parallel Regression Assumption holds")
+    }
+}
+
+#[derive(Debug, Builder)]
+pub struct OrdinalFitter<'a> {
+    pub data: &'a Data,
+    #[builder(default)]
+    pub link: OrdinalLink,
+    /// The maximum number of Newton iterations.
+    #[builder(default = "100")]
+    pub max_iter: usize,
+    /// The tolerance on the relative change in the log-likelihood.
+    #[builder(default = "1e-10")]
+    pub tol: f64,
+}
+
+impl<'a> OrdinalFitter<'a> {
+    /// Return a new instance of the `OrdinalFitter` struct for `link` with the default controls.
+    pub fn new(data: &'a Data, link: OrdinalLink) -> Self {
+        Self {
+            data,
+            link,
+            max_iter: 100,
+            tol: 1e-10,
+        }
+    }
+
+    /// Return the x matrix from the data struct.
+    pub fn x(&self) -> &RealMatrix {
+        &self.data.x
+    }
+
+    /// Return the y matrix from the data struct.
+    pub fn y(&self) -> &RealMatrix {
+        &self.data.y
+    }
+
+    /// Fit the cumulative-link model by Newton-Raphson.
+    ///
+    /// # Errors
+    /// Returns `LmFitterError::InvalidCategory`, `LmFitterError::TooFewCategories` or
+    /// `LmFitterError::EmptyCategory` if the response is not a set of category codes with every
+    /// category observed, `LmFitterError::SingularHessian` if the information is singular, and
+    /// `LmFitterError::MultipleResponses` for more than one response.
+    pub fn polr(&self) -> Result<OrdinalFit, LmFitterError> {
+        let (categories, n_categories) = categories(self.data, 2)?;
+        let x = self.data.x();
+        let n = x.n_rows();
+        let terms = self.data.terms();
+        let columns: Vec<usize> = (0..x.n_cols()).filter(|&j| terms.assign[j] != 0).collect();
+        let weights: Vec<f64> = self
+            .data
+            .weights()
+            .map_or_else(|| vec![1.0; n], |weights| weights.to_vec());
+        let (p, m) = (columns.len(), n_categories - 1);
+        let link = self.link;
+
+        // theta holds b followed by the cutpoints. Each observation depends on theta through
+        // the upper and lower arguments zeta_y - x'b and zeta_{y-1} - x'b.
+        let evaluate = |theta: &[f64]| -> Evaluation {
+            if theta[p..].windows(2).any(|pair| pair[1] <= pair[0]) {
+                return None;
+            }
+            let mut log_lik = 0.0;
+            let mut gradient = vec![0.0; p + m];
+            let mut information = RealMatrix::with_shape(p + m, p + m);
+            for i in 0..n {
+                let eta: f64 = (0..p).map(|a| x.values[[i, columns[a]]] * theta[a]).sum();
+                let y = categories[i];
+                // (density, derivative of the density, cutpoint index, sign) of each bound.
+                let mut bounds = Vec::with_capacity(2);
+                let mut probability = 0.0;
+                if y < m {
+                    let z = theta[p + y] - eta;
+                    probability += link.cdf(z);
+                    bounds.push((link.density(z), link.density_derivative(z), y, 1.0));
+                } else {
+                    probability += 1.0;
+                }
+                if y > 0 {
+                    let z = theta[p + y - 1] - eta;
+                    probability -= link.cdf(z);
+                    bounds.push((link.density(z), link.density_derivative(z), y - 1, -1.0));
+                }
+                if probability <= 0.0 {
+                    return None;
+                }
+                let w = weights[i];
+                log_lik += w * probability.ln();
+
+                // The derivative of each bound's argument is e_zeta - x.
+                let direction = |cutpoint: usize| -> Vec<f64> {
+                    let mut d: Vec<f64> = (0..p).map(|a| -x.values[[i, columns[a]]]).collect();
+                    d.resize(p + m, 0.0);
+                    d[p + cutpoint] = 1.0;
+                    d
+                };
+                let mut score = vec![0.0; p + m];
+                let mut curvature = RealMatrix::with_shape(p + m, p + m);
+                for &(density, slope, cutpoint, sign) in &bounds {
+                    let d = direction(cutpoint);
+                    for a in 0..p + m {
+                        score[a] += sign * density * d[a] / probability;
+                        for b in 0..p + m {
+                            curvature.values[[a, b]] += sign * slope * d[a] * d[b] / probability;
+                        }
+                    }
+                }
+                for a in 0..p + m {
+                    gradient[a] += w * score[a];
+                    for b in 0..p + m {
+                        information.values[[a, b]] -=
+                            w * (curvature.values[[a, b]] - score[a] * score[b]);
+                    }
+                }
+            }
+            log_lik
+                .is_finite()
+                .then_some((log_lik, gradient, information))
+        };
+
+        // Start from b = 0 with the cutpoints at the quantiles of the cumulative proportions.
+        let total: f64 = weights.iter().sum();
+        let mut start = vec![0.0; p];
+        let mut cumulative = 0.0;
+        for k in 0..m {
+            cumulative += (0..n)
+                .filter(|&i| categories[i] == k)
+                .map(|i| weights[i])
+                .sum::<f64>();
+            start.push(link.quantile(cumulative / total));
+        }
+        let newton = newton(start, &evaluate, self.max_iter, self.tol)?;
+        let covariance = newton
+            .information
+            .inverse()
+            .ok_or(LmFitterError::SingularHessian)?;
+        let std_errors: Vec<f64> = (0..p + m)
+            .map(|a| covariance.values[[a, a]].sqrt())
+            .collect();
+
+        let names = self.data.column_names();
+        let mut fit = OrdinalFit {
+            link,
+            names: columns.iter().map(|&j| names[j].clone()).collect(),
+            columns,
+            coefficients: newton.theta[..p].to_vec(),
+            std_errors: std_errors[..p].to_vec(),
+            zeta_names: (0..m).map(|k| format!("{}|{}", k, k + 1)).collect(),
+            zeta: newton.theta[p..].to_vec(),
+            zeta_std_errors: std_errors[p..].to_vec(),
+            covariance,
+            probabilities: RealMatrix::with_shape(0, 0),
+            deviance: -2.0 * newton.log_lik,
+            aic: -2.0 * newton.log_lik + 2.0 * (p + m) as f64,
+            iterations: newton.iterations,
+            converged: newton.converged,
+        };
+        fit.probabilities = fit.predict(x);
+        Ok(fit)
+    }
+
+    /// Return Brant's test of the proportional odds assumption: a joint test that the slopes of
+    /// the binary logistic regressions of y > k agree for every k, and one test per coefficient.
+    /// The test compares logistic regressions whatever the link of the fitter.
+    ///
+    /// # Errors
+    /// Returns `LmFitterError::TooFewCategories` for fewer than three categories, and the errors
+    /// of the binary fits, wrapped in `LmFitterError::Glm`.
+    pub fn brant(&self) -> Result<BrantTest, LmFitterError> {
+        let (categories, n_categories) = categories(self.data, 3)?;
+        let x = self.data.x();
+        let n = x.n_rows();
+        let terms = self.data.terms();
+        let columns: Vec<usize> = (0..x.n_cols()).filter(|&j| terms.assign[j] != 0).collect();
+        let weights: Vec<f64> = self
+            .data
+            .weights()
+            .map_or_else(|| vec![1.0; n], |weights| weights.to_vec());
+        let (p, m) = (columns.len(), n_categories - 1);
+
+        // The design of the binary fits has an intercept in column 0.
+        let mut design = RealMatrix::with_shape(n, p + 1);
+        for i in 0..n {
+            design.values[[i, 0]] = 1.0;
+            for (a, &j) in columns.iter().enumerate() {
+                design.values[[i, a + 1]] = x.values[[i, j]];
+            }
+        }
+        let family = Binomial::default();
+        let offset = vec![0.0; n];
+        let mut slopes = Vec::with_capacity(m * p);
+        let mut fitted = Vec::with_capacity(m);
+        for k in 0..m {
+            let above: Vec<f64> = categories.iter().map(|&y| f64::from(y > k)).collect();
+            let eta = family
+                .initialize(&above, &weights)
+                .map_err(glm_error)?
+                .iter()
+                .map(|&mu| family.link().link(mu))
+                .collect();
+            let binary = irls(
+                &design,
+                &above,
+                &weights,
+                &offset,
+                &family,
+                eta,
+                None,
+                &GlmControl::default(),
+            )
+            .map_err(glm_error)?;
+            slopes.extend((1..=p).map(|a| binary.coefficients.values[[a, 0]]));
+            fitted.push(binary.mu);
+        }
+
+        // The covariance of the binary fits k and l is A_k^-1 X'W_kl X A_l^-1, where
+        // W_kl = w pi_l (1 - pi_k) for k <= l, since y > l implies y > k.
+        let cross = |k: usize, l: usize| -> RealMatrix {
+            let mut product = RealMatrix::with_shape(p + 1, p + 1);
+            for i in 0..n {
+                let weight = weights[i] * fitted[l][i] * (1.0 - fitted[k][i]);
+                for a in 0..=p {
+                    for b in 0..=p {
+                        product.values[[a, b]] +=
+                            weight * design.values[[i, a]] * design.values[[i, b]];
+                    }
+                }
+            }
+            product
+        };
+        let mut inverses = Vec::with_capacity(m);
+        for k in 0..m {
+            inverses.push(
+                cross(k, k)
+                    .inverse()
+                    .ok_or(LmFitterError::SingularHessian)?,
+            );
+        }
+        let mut covariance = RealMatrix::with_shape(m * p, m * p);
+        for k in 0..m {
+            for l in k..m {
+                let block = inverses[k].dot(&cross(k, l)).dot(&inverses[l]);
+                for a in 0..p {
+                    for b in 0..p {
+                        covariance.values[[k * p + a, l * p + b]] = block.values[[a + 1, b + 1]];
+                        covariance.values[[l * p + b, k * p + a]] = block.values[[a + 1, b + 1]];
+                    }
+                }
+            }
+        }
+
+        // Contrast the slopes of each later fit with those of the first.
+        let test = |coefficients: &[usize]| -> Option<BrantRow> {
+            let df = (m - 1) * coefficients.len();
+            let mut l = RealMatrix::with_shape(df, m * p);
+            for k in 1..m {
+                for (c, &a) in coefficients.iter().enumerate() {
+                    let row = (k - 1) * coefficients.len() + c;
+                    l.values[[row, a]] = 1.0;
+                    l.values[[row, k * p + a]] = -1.0;
+                }
+            }
+            let statistic = wald_statistic(&l, &slopes, &covariance, &vec![0.0; df])?;
+            Some(BrantRow {
+                term: String::new(),
+                statistic,
+                df,
+                p_value: chi_squared_upper_tail(statistic, df as f64),
+            })
+        };
+        let names = self.data.column_names();
+        let all: Vec<usize> = (0..p).collect();
+        let mut rows = vec![BrantRow {
+            term: "Omnibus".to_string(),
+            ..test(&all).ok_or(LmFitterError::SingularHessian)?
+        }];
+        for (a, &j) in columns.iter().enumerate() {
+            rows.push(BrantRow {
+                term: names[j].clone(),
+                ..test(&[a]).ok_or(LmFitterError::SingularHessian)?
+            });
+        }
+        Ok(BrantTest { rows })
+    }
+}
+
+impl<'a> FitModel for OrdinalFitter<'a> {
+    /// Fit the cumulative-link model and return the coefficients b as a single column.
+    fn fit(&self) -> Result<RealMatrix, LmFitterError> {
+        let coefficients = self.polr()?.coefficients;
+        let p = coefficients.len();
+        Ok(RealMatrix::from_vec(coefficients, p, None))
+    }
+
+    fn x(&self) -> &RealMatrix {
+        self.data.x()
+    }
+
+    fn y(&self) -> &RealMatrix {
+        self.data.y()
+    }
+}
+
+/// Wrap an error of a binary fit of Brant's test.
+fn glm_error(error: GlmError) -> LmFitterError {
+    match error {
+        GlmError::Fit(error) => error,
+        error => LmFitterError::Glm(Box::new(error)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::family::Link;
+    use crate::fitters::fit::LinearModelFitter;
+    use crate::fitters::glm_fitter::GlmFitter;
+
+    /// Ordered responses from a latent variable 1.2 t plus a deterministic noise.
+    fn ordered_data(n_categories: usize, cuts: &[f64]) -> Data {
+        let n = 120;
+        let x: Vec<f64> = (0..n).flat_map(|i| [1.0, (i % 10) as f64 / 5.0]).collect();
+        let y: Vec<f64> = (0..n)
+            .map(|i| {
+                let t = (i % 10) as f64 / 5.0;
+                let noise = 1.5 * ((i as f64) * 2.39).sin() + ((i * 17) % 7) as f64 / 7.0 - 0.5;
+                let latent = 1.2 * t + noise;
+                cuts.iter()
+                    .filter(|&&c| latent > c)
+                    .count()
+                    .min(n_categories - 1) as f64
+            })
+            .collect();
+        Data::new(
+            RealMatrix::from_vec(x, n, Some(2)),
+            RealMatrix::from_vec(y, n, None),
+        )
+        .with_column_names(vec!["(Intercept)".to_string(), "t".to_string()])
+    }
+
+    #[test]
+    fn test_two_categories_match_binary_regression() {
+        let data = ordered_data(2, &[1.0]);
+        for (link, glm_link) in [
+            (OrdinalLink::Logistic, Link::Logit),
+            (OrdinalLink::Probit, Link::Probit),
+        ] {
+            let fit = OrdinalFitter::new(&data, link).polr().unwrap();
+            let fitter = GlmFitter::new(&data, Binomial::new(glm_link).unwrap());
+            let binary = fitter.glm().unwrap().summary();
+            // P(y = 0) = F(zeta - x'b), so the binary fit of y = 1 has intercept -zeta and
+            // slope b.
+            assert!((fit.zeta[0] + binary.coefficients[0]).abs() < 1e-6);
+            assert!((fit.coefficients[0] - binary.coefficients[1]).abs() < 1e-6);
+            // Under the canonical logit link the observed and expected information agree, so
+            // the standard errors match too; the probit GLM reports expected information.
+            if link == OrdinalLink::Logistic {
+                assert!((fit.std_errors[0] - binary.std_errors[1]).abs() < 1e-5);
+                assert!((fit.zeta_std_errors[0] - binary.std_errors[0]).abs() < 1e-5);
+            }
+        }
+    }
+
+    #[test]
+    fn test_proportional_odds_fit_and_brant_test() {
+        let data = ordered_data(4, &[0.0, 1.0, 2.0]);
+        let fitter = OrdinalFitter::new(&data, OrdinalLink::Logistic);
+        let fit = fitter.polr().unwrap();
+        assert!(fit.converged);
+        assert_eq!(fit.names, vec!["t".to_string()]);
+        assert!(fit.zeta.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(fit.coefficients[0] > 0.5);
+        assert!(fit
+            .probabilities
+            .values
+            .rows()
+            .into_iter()
+            .all(|row| (row.sum() - 1.0).abs() < 1e-12));
+        assert!((fit.aic - fit.deviance - 8.0).abs() < 1e-12);
+
+        let brant = fitter.brant().unwrap();
+        assert_eq!(brant.rows.len(), 2);
+        assert_eq!((brant.rows[0].df, brant.rows[1].df), (2, 2));
+        // With a single coefficient, the omnibus test is the test of that coefficient.
+        assert!((brant.rows[0].statistic - brant.rows[1].statistic).abs() < 1e-8);
+        assert!(brant.rows[0].p_value > 0.0 && brant.rows[0].p_value <= 1.0);
+        assert!(brant.to_string().contains("Omnibus"));
+
+        let coefficients = fit.coefficients.clone();
+        let fitted = LinearModelFitter::Ordinal(fitter).fit().unwrap();
+        assert_eq!(fitted.values[[0, 0]], coefficients[0]);
+        assert!(matches!(
+            OrdinalFitter::new(&ordered_data(2, &[1.0]), OrdinalLink::Logistic).brant(),
+            Err(LmFitterError::TooFewCategories { needed: 3, .. })
+        ));
+    }
+}
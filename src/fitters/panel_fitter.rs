@@ -0,0 +1,667 @@
+//! This module contains the panel data fitter, which implements the `FitModel` trait for the
+//! `LinearModelFitter` enum, like `plm::plm`. Each estimator transforms y and x and fits the
+//! transformed data by least squares, so the covariance comes from the `vcov` module: classical,
+//! heteroskedasticity-robust, or clustered by entity or time period.
+//!
+//! * Pooled OLS fits the data as they are.
+//! * The within (fixed effects) estimator absorbs the entities as `FixedEffectsFitter` does,
+//!   subtracting each entity's means. The intercept and any other column constant within
+//!   entities drop out, and the classical covariance is scaled to the n - N - k residual
+//!   degrees of freedom that the N entity means leave.
+//! * The between estimator fits the entity means, one row per entity.
+//! * The first-difference estimator fits y_t - y_{t-1} on x_t - x_{t-1} for the observations
+//!   whose entity has a row in the period before. The intercept column is kept as a column of
+//!   ones, a linear trend in levels, as in `plm`.
+//! * The random effects estimator of Swamy and Arora takes the idiosyncratic variance from the
+//!   within fit and the entity variance from the between fit, sigma_u^2 = sigma_b^2 -
+//!   sigma_e^2 / T with T the harmonic mean of the entity sizes, and fits the quasi-demeaned
+//!   data y_it - theta_i ybar_i, with theta_i = 1 - sqrt(sigma_e^2 / (T_i sigma_u^2 + sigma_e^2)).
+//!   The transformed errors have variance sigma_e^2, which scales the classical covariance, as
+//!   in Stata's `xtreg, re`; this keeps the Hausman test positive when the effects are
+//!   correlated with the regressors.
+
+// src/fitters/panel_fitter.rs
+
+use super::fit::FitModel;
+use super::fixed_effects_fitter::{Absorbed, FixedEffectsFitter};
+use crate::anova::{format_number, write_coefficients, write_table};
+use crate::errors::{LmFitterError, PanelError};
+use crate::least_squares::dqrls;
+use crate::mixed::spec::GroupingFactor;
+use crate::panel::PanelIndex;
+use crate::terms::INTERCEPT_LABEL;
+use crate::types::{Data, RealMatrix, Tolerance};
+use crate::vcov::{cluster_vcov, unscaled_covariance, vcov, CovarianceType};
+use derive_builder::Builder;
+use std::fmt;
+
+/// An enum representing the estimators of a panel data model.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PanelEstimator {
+    /// Least squares on the data as they are.
+    #[default]
+    Pooled,
+    /// Least squares on the deviations from the entity means (fixed effects).
+    Within,
+    /// Least squares on the entity means.
+    Between,
+    /// Least squares on the differences between consecutive periods.
+    FirstDifference,
+    /// Swamy-Arora random effects, least squares on the quasi-demeaned data.
+    RandomEffects,
+}
+
+/// An enum representing the grouping of clustered standard errors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClusterBy {
+    /// Cluster the observations of each entity.
+    Entity,
+    /// Cluster the observations of each time period.
+    Time,
+}
+
+/// The result of a panel data fit.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PanelFit {
+    /// The estimator the model was fitted with.
+    pub estimator: PanelEstimator,
+    /// The covariance estimator of the standard errors, unless they are clustered.
+    pub covariance_type: CovarianceType,
+    /// The grouping of clustered standard errors, if any.
+    pub cluster: Option<ClusterBy>,
+    /// The names of the coefficients.
+    pub names: Vec<String>,
+    /// The coefficients, `NaN` for aliased columns.
+    pub coefficients: Vec<f64>,
+    /// The standard errors of the coefficients.
+    pub std_errors: Vec<f64>,
+    /// The covariance of the coefficients.
+    pub covariance: RealMatrix,
+    /// The residual standard error of the transformed model.
+    pub sigma: f64,
+    /// The residual degrees of freedom.
+    pub df_residual: usize,
+    /// The R-squared of the transformed model, about the mean of the transformed response.
+    pub r_squared: f64,
+    /// The residuals of the transformed model.
+    pub residuals: Vec<f64>,
+    /// The entity and time of each residual. The between estimator has one residual per
+    /// entity, at time 0.
+    pub index: PanelIndex,
+    /// The number of entities.
+    pub n_entities: usize,
+    /// The number of observations before the transformation.
+    pub n_observations: usize,
+    /// The variance of the idiosyncratic errors, for random effects.
+    pub idiosyncratic_variance: Option<f64>,
+    /// The variance of the entity effects, for random effects.
+    pub individual_variance: Option<f64>,
+    /// The quasi-demeaning weight theta of each entity, for random effects.
+    pub theta: Option<Vec<f64>>,
+}
+
+impl fmt::Display for PanelFit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let estimator = match self.estimator {
+            PanelEstimator::Pooled => "Pooling",
+            PanelEstimator::Within => "Within (fixed effects)",
+            PanelEstimator::Between => "Between",
+            PanelEstimator::FirstDifference => "First-difference",
+            PanelEstimator::RandomEffects => "Random effects (Swamy-Arora)",
+        };
+        writeln!(f, "{} model", estimator)?;
+        writeln!(
+            f,
+            "Panel: {} entities, {} observations",
+            self.n_entities, self.n_observations
+        )?;
+        match self.cluster {
+            Some(ClusterBy::Entity) => writeln!(f, "Standard errors clustered by entity")?,
+            Some(ClusterBy::Time) => writeln!(f, "Standard errors clustered by time")?,
+            None if self.covariance_type != CovarianceType::Classical => {
+                writeln!(f, "Standard errors: {:?}", self.covariance_type)?
+            }
+            None => {}
+        }
+        if let (Some(idiosyncratic), Some(individual)) =
+            (self.idiosyncratic_variance, self.individual_variance)
+        {
+            writeln!(f)?;
+            writeln!(f, "Variance components:")?;
+            let total = idiosyncratic + individual;
+            let cells = [idiosyncratic, individual].map(|variance| {
+                vec![
+                    format_number(variance),
+                    format_number(variance.sqrt()),
+                    format_number(variance / total),
+                ]
+            });
+            write_table(
+                f,
+                &["idiosyncratic", "individual"],
+                &["var", "std.dev", "share"],
+                &cells,
+            )?;
+            if let Some(theta) = &self.theta {
+                let mean = theta.iter().sum::<f64>() / theta.len() as f64;
+                writeln!(f, "theta: {} (mean)", format_number(mean))?;
+            }
+        }
+        writeln!(f)?;
+        writeln!(f, "Coefficients:")?;
+        write_coefficients(
+            f,
+            &self.names,
+            &self.coefficients,
+            &self.std_errors,
+            self.df_residual,
+            &["Estimate", "Std. Error", "t value", "Pr(>|t|)"],
+        )?;
+        writeln!(f)?;
+        writeln!(
+            f,
+            "Residual standard error: {} on {} degrees of freedom",
+            format_number(self.sigma),
+            self.df_residual
+        )?;
+        writeln!(f, "R-squared: {}", format_number(self.r_squared))
+    }
+}
+
+#[derive(Debug, Builder)]
+pub struct PanelFitter<'a> {
+    pub data: &'a Data,
+    /// The entity and time of each row of the data.
+    pub index: PanelIndex,
+    #[builder(default)]
+    pub estimator: PanelEstimator,
+    /// The covariance estimator of the standard errors, unless they are clustered.
+    #[builder(default)]
+    pub covariance: CovarianceType,
+    /// Cluster the standard errors by entity or time, in place of `covariance`. The between
+    /// estimator clusters by entity either way, which gives HC1 standard errors.
+    #[builder(default)]
+    pub cluster: Option<ClusterBy>,
+}
+
+/// The data after an estimator's transformation.
+struct Transformed {
+    x: RealMatrix,
+    y: Vec<f64>,
+    /// The columns of the original x that are kept.
+    columns: Vec<usize>,
+    index: PanelIndex,
+    /// The degrees of freedom used up by the transformation.
+    absorbed: usize,
+    /// The error variance of the transformed data, if it is known from the transformation.
+    variance: Option<f64>,
+}
+
+/// The Swamy-Arora variance components of a random effects fit.
+struct Components {
+    idiosyncratic: f64,
+    individual: f64,
+    /// The quasi-demeaning weight of each entity.
+    theta: Vec<f64>,
+}
+
+/// A least squares fit to transformed data.
+struct Regression {
+    coefficients: Vec<f64>,
+    covariance: RealMatrix,
+    residuals: Vec<f64>,
+    df_residual: usize,
+    r_squared: f64,
+}
+
+impl<'a> PanelFitter<'a> {
+    /// Return a new instance of the `PanelFitter` struct with the given estimator and classical
+    /// standard errors.
+    pub fn new(data: &'a Data, index: PanelIndex, estimator: PanelEstimator) -> Self {
+        Self {
+            data,
+            index,
+            estimator,
+            covariance: CovarianceType::Classical,
+            cluster: None,
+        }
+    }
+
+    /// Return the x matrix from the data struct.
+    pub fn x(&self) -> &RealMatrix {
+        &self.data.x
+    }
+
+    /// Return the y matrix from the data struct.
+    pub fn y(&self) -> &RealMatrix {
+        &self.data.y
+    }
+
+    /// Fit the model with the chosen estimator.
+    ///
+    /// # Errors
+    /// Returns `PanelError::Fit` wrapping `LmFitterError::MultipleResponses` if y has more than
+    /// one column, `LmFitterError::PriorWeights` if the data are weighted, or
+    /// `LmFitterError::TooFewClusters` if the standard errors are clustered by a single group,
+    /// `PanelError::IndexMismatch` or `PanelError::DuplicateObservation` for an invalid index,
+    /// and `PanelError::TooFewObservations` if the transformed data leave no residual degrees
+    /// of freedom.
+    pub fn panel(&self) -> Result<PanelFit, PanelError> {
+        let (x, y) = (self.x(), self.y());
+        if y.n_cols() != 1 {
+            return Err(LmFitterError::MultipleResponses { found: y.n_cols() }.into());
+        }
+        if self.data.weights.is_some() {
+            return Err(LmFitterError::PriorWeights.into());
+        }
+        let n = x.n_rows();
+        self.index.validate(n)?;
+        let entities = self.entities();
+        let n_entities = entities.counts[0].len();
+
+        let mut components = None;
+        let transformed = match self.estimator {
+            PanelEstimator::Pooled => Transformed {
+                x: x.clone(),
+                y: y.values.column(0).to_vec(),
+                columns: (0..x.n_cols()).collect(),
+                index: self.index.clone(),
+                absorbed: 0,
+                variance: None,
+            },
+            PanelEstimator::Within => self.within(&entities),
+            PanelEstimator::Between => self.between(&entities),
+            PanelEstimator::FirstDifference => self.first_difference(),
+            PanelEstimator::RandomEffects => {
+                let (transformed, variances) = self.random_effects(&entities)?;
+                components = Some(variances);
+                transformed
+            }
+        };
+
+        let clusters: Vec<usize> = match (self.cluster, self.estimator) {
+            (Some(ClusterBy::Time), estimator) if estimator != PanelEstimator::Between => {
+                transformed.index.times.clone()
+            }
+            _ => transformed.index.entities.clone(),
+        };
+        let fit = self.regress(&transformed, &clusters)?;
+        let names = self.data.column_names();
+        let (idiosyncratic_variance, individual_variance, theta) = match components {
+            Some(components) => (
+                Some(components.idiosyncratic),
+                Some(components.individual),
+                Some(components.theta),
+            ),
+            None => (None, None, None),
+        };
+        let p = transformed.columns.len();
+        let sigma =
+            (fit.residuals.iter().map(|e| e * e).sum::<f64>() / fit.df_residual as f64).sqrt();
+        Ok(PanelFit {
+            estimator: self.estimator,
+            covariance_type: self.covariance,
+            cluster: self.cluster,
+            names: transformed
+                .columns
+                .iter()
+                .map(|&j| names[j].clone())
+                .collect(),
+            coefficients: fit.coefficients,
+            std_errors: (0..p)
+                .map(|j| fit.covariance.values[[j, j]].sqrt())
+                .collect(),
+            covariance: fit.covariance,
+            sigma,
+            df_residual: fit.df_residual,
+            r_squared: fit.r_squared,
+            residuals: fit.residuals,
+            index: transformed.index,
+            n_entities,
+            n_observations: n,
+            idiosyncratic_variance,
+            individual_variance,
+            theta,
+        })
+    }
+
+    /// Return the entities as an absorbed factor, with the entity codes of the observations.
+    fn entities(&self) -> Absorbed {
+        Absorbed::new(&[self.entity_factor()])
+    }
+
+    fn entity_factor(&self) -> GroupingFactor {
+        GroupingFactor::new("entity", self.index.entities.clone())
+    }
+
+    /// Absorb the entities from every column but the intercept.
+    fn within(&self, entities: &Absorbed) -> Transformed {
+        let fitter = FixedEffectsFitter::new(self.data, vec![self.entity_factor()]);
+        let demeaned = fitter.absorb(entities);
+        Transformed {
+            x: demeaned.x,
+            y: demeaned.y,
+            columns: demeaned.columns,
+            index: self.index.clone(),
+            absorbed: entities.degrees_of_freedom(),
+            variance: None,
+        }
+    }
+
+    /// Average every column within entities.
+    fn between(&self, entities: &Absorbed) -> Transformed {
+        let (x, y) = (self.x(), self.y());
+        let n_entities = entities.counts[0].len();
+        let mut means = RealMatrix::with_shape(n_entities, x.n_cols());
+        for j in 0..x.n_cols() {
+            let column = entities.means(0, &x.values.column(j).to_vec());
+            for (e, value) in column.into_iter().enumerate() {
+                means.values[[e, j]] = value;
+            }
+        }
+        Transformed {
+            x: means,
+            y: entities.means(0, &y.values.column(0).to_vec()),
+            columns: (0..x.n_cols()).collect(),
+            index: PanelIndex::new(entities.levels[0].clone(), vec![0; n_entities]),
+            absorbed: 0,
+            variance: None,
+        }
+    }
+
+    /// Difference every column but the intercept between consecutive periods.
+    fn first_difference(&self) -> Transformed {
+        let (x, y) = (self.x(), self.y());
+        let names = self.data.column_names();
+        let rows: Vec<(usize, usize)> = self
+            .index
+            .lags()
+            .into_iter()
+            .enumerate()
+            .filter_map(|(i, lag)| lag.map(|l| (i, l)))
+            .collect();
+        let mut differenced = RealMatrix::with_shape(rows.len(), x.n_cols());
+        for (r, &(i, l)) in rows.iter().enumerate() {
+            for (j, name) in names.iter().enumerate() {
+                differenced.values[[r, j]] = if name == INTERCEPT_LABEL {
+                    1.0
+                } else {
+                    x.values[[i, j]] - x.values[[l, j]]
+                };
+            }
+        }
+        Transformed {
+            x: differenced,
+            y: rows
+                .iter()
+                .map(|&(i, l)| y.values[[i, 0]] - y.values[[l, 0]])
+                .collect(),
+            columns: (0..x.n_cols()).collect(),
+            index: PanelIndex::new(
+                rows.iter().map(|&(i, _)| self.index.entities[i]).collect(),
+                rows.iter().map(|&(i, _)| self.index.times[i]).collect(),
+            ),
+            absorbed: 0,
+            variance: None,
+        }
+    }
+
+    /// Estimate the Swamy-Arora variance components and quasi-demean every column. Returns the
+    /// transformed data with the variance components.
+    fn random_effects(&self, entities: &Absorbed) -> Result<(Transformed, Components), PanelError> {
+        let (x, y) = (self.x(), self.y());
+        let n = x.n_rows();
+        let (codes, sizes) = (&entities.codes[0], &entities.counts[0]);
+        let n_entities = sizes.len();
+        let residual_variance = |transformed: &Transformed, estimator: &str| {
+            let y = RealMatrix::from_vec(transformed.y.clone(), transformed.y.len(), None);
+            let fit =
+                dqrls(&transformed.x, &y, &Tolerance::default()).map_err(LmFitterError::from)?;
+            let used = fit.rank() + transformed.absorbed;
+            let rows = transformed.y.len();
+            if rows <= used {
+                return Err(PanelError::TooFewObservations {
+                    estimator: estimator.to_string(),
+                    needed: used + 1,
+                    found: rows,
+                });
+            }
+            let rss: f64 = fit.residuals.values.iter().map(|e| e * e).sum();
+            Ok(rss / (rows - used) as f64)
+        };
+        let idiosyncratic = residual_variance(&self.within(entities), "within")?;
+        let between = residual_variance(&self.between(entities), "between")?;
+
+        let harmonic = n_entities as f64 / sizes.iter().map(|&t| 1.0 / t as f64).sum::<f64>();
+        let individual = (between - idiosyncratic / harmonic).max(0.0);
+        let theta: Vec<f64> = sizes
+            .iter()
+            .map(|&t| 1.0 - (idiosyncratic / (t as f64 * individual + idiosyncratic)).sqrt())
+            .collect();
+
+        let quasi_demean = |values: Vec<f64>| -> Vec<f64> {
+            let means = entities.means(0, &values);
+            values
+                .iter()
+                .zip(codes)
+                .map(|(value, &code)| value - theta[code] * means[code])
+                .collect()
+        };
+        let mut transformed = RealMatrix::with_shape(n, x.n_cols());
+        for j in 0..x.n_cols() {
+            let column = quasi_demean(x.values.column(j).to_vec());
+            for (i, value) in column.into_iter().enumerate() {
+                transformed.values[[i, j]] = value;
+            }
+        }
+        Ok((
+            Transformed {
+                x: transformed,
+                y: quasi_demean(y.values.column(0).to_vec()),
+                columns: (0..x.n_cols()).collect(),
+                index: self.index.clone(),
+                absorbed: 0,
+                variance: Some(idiosyncratic),
+            },
+            Components {
+                idiosyncratic,
+                individual,
+                theta,
+            },
+        ))
+    }
+
+    /// Fit the transformed data by least squares, with the covariance from the `vcov` module.
+    fn regress(&self, data: &Transformed, clusters: &[usize]) -> Result<Regression, PanelError> {
+        let n = data.y.len();
+        let y = RealMatrix::from_vec(data.y.clone(), n, None);
+        let fit = dqrls(&data.x, &y, &Tolerance::default()).map_err(LmFitterError::from)?;
+        let rank = fit.rank();
+        if n <= rank + data.absorbed {
+            return Err(PanelError::TooFewObservations {
+                estimator: format!("{:?}", self.estimator),
+                needed: rank + data.absorbed + 1,
+                found: n,
+            });
+        }
+        let df_residual = n - rank - data.absorbed;
+        let residuals: Vec<f64> = fit.residuals.values.column(0).to_vec();
+        let rss: f64 = residuals.iter().map(|e| e * e).sum();
+
+        let covariance = match (self.cluster, self.covariance) {
+            (Some(_), _) => cluster_vcov(&fit, &data.x, 0, clusters)?,
+            (None, CovarianceType::Classical) => {
+                let variance = data.variance.unwrap_or(rss / df_residual as f64);
+                RealMatrix::new(unscaled_covariance(&fit.qr).values * variance)
+            }
+            (None, kind) => vcov(&fit, &data.x, 0, kind),
+        };
+        let mean = data.y.iter().sum::<f64>() / n as f64;
+        let tss: f64 = data.y.iter().map(|v| (v - mean).powi(2)).sum();
+        Ok(Regression {
+            coefficients: fit.coefficients.values.column(0).to_vec(),
+            covariance,
+            residuals,
+            df_residual,
+            r_squared: 1.0 - rss / tss,
+        })
+    }
+}
+
+impl<'a> FitModel for PanelFitter<'a> {
+    /// Fit the model and return the coefficients as a single column.
+    fn fit(&self) -> Result<RealMatrix, LmFitterError> {
+        let coefficients = self
+            .panel()
+            .map_err(|error| match error {
+                PanelError::Fit(error) => error,
+                error => LmFitterError::Panel(Box::new(error)),
+            })?
+            .coefficients;
+        let p = coefficients.len();
+        Ok(RealMatrix::from_vec(coefficients, p, None))
+    }
+
+    fn x(&self) -> &RealMatrix {
+        self.data.x()
+    }
+
+    fn y(&self) -> &RealMatrix {
+        self.data.y()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A balanced panel of 10 entities over 6 periods, where x is correlated with the entity
+    /// effects and z is constant within entities.
+    fn panel_data() -> (Data, PanelIndex) {
+        let (entities, periods) = (10, 6);
+        let n = entities * periods;
+        let mut x = RealMatrix::with_shape(n, 3);
+        let mut y = RealMatrix::with_shape(n, 1);
+        let mut index = PanelIndex::new(Vec::new(), Vec::new());
+        for i in 0..n {
+            let (e, t) = (i / periods, i % periods + 1);
+            let effect = (e as f64 * 2.1).sin();
+            let xi = (i as f64 * 0.77).cos() + 0.5 * effect;
+            let z = (e % 3) as f64;
+            x.values[[i, 0]] = 1.0;
+            x.values[[i, 1]] = xi;
+            x.values[[i, 2]] = z;
+            y.values[[i, 0]] = 1.0 + 2.0 * xi - z + effect + 0.3 * ((i * i) as f64 * 0.37).sin();
+            index.entities.push(100 + e);
+            index.times.push(t);
+        }
+        let names = ["(Intercept)", "x", "z"].map(String::from).to_vec();
+        (Data::new(x, y).with_column_names(names), index)
+    }
+
+    #[test]
+    fn test_within_between_and_first_difference_match_least_squares() {
+        let (data, index) = panel_data();
+        let n = data.x.n_rows();
+        let within = PanelFitter::new(&data, index.clone(), PanelEstimator::Within)
+            .panel()
+            .unwrap();
+        assert_eq!(within.names, vec!["x", "z"]);
+        assert!(within.coefficients[1].is_nan());
+        assert_eq!(within.df_residual, n - 10 - 1);
+
+        // The least squares dummy variable regression gives the same slope and standard error.
+        let mut dummies = RealMatrix::with_shape(n, 11);
+        for i in 0..n {
+            dummies.values[[i, 0]] = data.x.values[[i, 1]];
+            dummies.values[[i, 1 + i / 6]] = 1.0;
+        }
+        let expected = dqrls(&dummies, &data.y, &Tolerance::default()).unwrap();
+        let classical = vcov(&expected, &dummies, 0, CovarianceType::Classical);
+        assert!((within.coefficients[0] - expected.coefficients.values[[0, 0]]).abs() < 1e-10);
+        assert!((within.std_errors[0] - classical.values[[0, 0]].sqrt()).abs() < 1e-10);
+
+        let between = PanelFitter::new(&data, index.clone(), PanelEstimator::Between)
+            .panel()
+            .unwrap();
+        let mut means = RealMatrix::with_shape(10, 3);
+        let mut y_means = RealMatrix::with_shape(10, 1);
+        for i in 0..n {
+            for j in 0..3 {
+                means.values[[i / 6, j]] += data.x.values[[i, j]] / 6.0;
+            }
+            y_means.values[[i / 6, 0]] += data.y.values[[i, 0]] / 6.0;
+        }
+        let expected = dqrls(&means, &y_means, &Tolerance::default()).unwrap();
+        for j in 0..3 {
+            let b = expected.coefficients.values[[j, 0]];
+            assert!((between.coefficients[j] - b).abs() < 1e-10);
+        }
+        assert_eq!(between.index.entities[..2], [100, 101]);
+
+        // Dropping the third period leaves a gap, so only 2 - 1, 5 - 4 and 6 - 5 remain.
+        let rows: Vec<usize> = (0..n).filter(|i| i % 6 != 2).collect();
+        let subset = Data::new(
+            RealMatrix::new(data.x.values.select(ndarray::Axis(0), &rows)),
+            RealMatrix::new(data.y.values.select(ndarray::Axis(0), &rows)),
+        )
+        .with_column_names(data.column_names.clone());
+        let gapped = PanelIndex::new(
+            rows.iter().map(|&i| index.entities[i]).collect(),
+            rows.iter().map(|&i| index.times[i]).collect(),
+        );
+        let fd = PanelFitter::new(&subset, gapped, PanelEstimator::FirstDifference)
+            .panel()
+            .unwrap();
+        assert_eq!(fd.residuals.len(), 30);
+        assert!(fd.coefficients[2].is_nan());
+        assert!((fd.coefficients[1] - 2.0).abs() < 0.3);
+    }
+
+    #[test]
+    fn test_random_effects_is_gls_with_the_estimated_components() {
+        let (data, index) = panel_data();
+        let n = data.x.n_rows();
+        let fit = PanelFitterBuilder::default()
+            .data(&data)
+            .index(index.clone())
+            .estimator(PanelEstimator::RandomEffects)
+            .build()
+            .unwrap()
+            .panel()
+            .unwrap();
+        let (e2, u2) = (
+            fit.idiosyncratic_variance.unwrap(),
+            fit.individual_variance.unwrap(),
+        );
+        assert!(u2 > 0.0);
+
+        // b = (X' Omega^-1 X)^-1 X' Omega^-1 y with Omega = e2 I + u2 J within each entity.
+        let mut omega = RealMatrix::with_shape(n, n);
+        for a in 0..n {
+            for b in 0..n {
+                if a / 6 == b / 6 {
+                    omega.values[[a, b]] = u2 + if a == b { e2 } else { 0.0 };
+                }
+            }
+        }
+        let omega_inverse = omega.inverse().unwrap();
+        let xt = data.x.transpose();
+        let covariance = xt.dot(&omega_inverse).dot(&data.x).inverse().unwrap();
+        let gls = covariance.dot(&xt.dot(&omega_inverse).dot(&data.y));
+        for j in 0..3 {
+            assert!((fit.coefficients[j] - gls.values[[j, 0]]).abs() < 1e-8);
+            assert!((fit.std_errors[j] - covariance.values[[j, j]].sqrt()).abs() < 1e-8);
+        }
+        assert!(fit.to_string().contains("Variance components:"));
+
+        let clustered = PanelFitter {
+            cluster: Some(ClusterBy::Entity),
+            ..PanelFitter::new(&data, index, PanelEstimator::RandomEffects)
+        }
+        .panel()
+        .unwrap();
+        assert_eq!(clustered.coefficients, fit.coefficients);
+        assert!(clustered.std_errors[1] != fit.std_errors[1]);
+    }
+}
@@ -35,6 +35,7 @@
 use super::fit::FitModel;
 use crate::errors::LmFitterError;
 // use crate::fortran::dqrls::FortranDqrls;
+use crate::least_squares::LeastSquaresFit;
 use crate::types::{Data, RealMatrix, Tolerance};
 use derive_builder::Builder;
 
@@ -96,6 +97,12 @@ impl<'a> QrDecompositionFitter<'a> {
         self.tol.value()
     }
 
+    /// Solve the (weighted) least squares problem and return the full result of the pivoted QR
+    /// decomposition, including the rank, the pivot and the effects Q'y.
+    pub fn decompose(&self) -> Result<LeastSquaresFit, LmFitterError> {
+        Ok(self.data.least_squares(&self.tol)?)
+    }
+
     /*     /// Return the solution vector from the QR decomposition.
     fn beta(&self) -> &Vec<f64> {
         &self.beta_array
@@ -114,27 +121,17 @@ impl<'a> QrDecompositionFitter<'a> {
 
 impl<'a> FitModel for QrDecompositionFitter<'a> {
     /// Use the QR decomposition method to fit the linear model to the data.
-    /// Calls the FFI function that wraps the LINPACK dqrls subroutine below.
+    /// Calls the native port of the LINPACK dqrls subroutine below.
     /// The function signature is:
     /// ```fortran
     /// subroutine dqrls(x,n,p,y,ny,tol,b,rsd,qty,k,jpvt,qraux,work)
-    /// ```g
+    /// ```
     ///
     /// # Returns
-    ///
+    /// The coefficients in the original column order. Coefficients of columns that are aliased
+    /// with earlier columns are `NaN`.
     fn fit(&self) -> Result<RealMatrix, LmFitterError> {
-        // let result = self.perform_qr_decomposition();
-        struct Temp {
-            beta: RealMatrix,
-        }
-
-        let result: Result<Temp, LmFitterError> = Ok(Temp {
-            beta: RealMatrix::with_shape(1, 1),
-        });
-        match result {
-            Ok(fitted) => Ok(fitted.beta),
-            Err(_e) => Err(LmFitterError::Unknown),
-        }
+        Ok(self.decompose()?.coefficients)
     }
 
     fn x(&self) -> &RealMatrix {
@@ -144,6 +141,10 @@ impl<'a> FitModel for QrDecompositionFitter<'a> {
     fn y(&self) -> &RealMatrix {
         self.data.y()
     }
+
+    fn tolerance(&self) -> Tolerance {
+        self.tol.clone()
+    }
 }
 
 #[derive(Debug, Builder)]
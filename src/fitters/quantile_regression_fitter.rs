@@ -0,0 +1,987 @@
+//! This module contains the quantile regression fitter, which implements the `FitModel` trait
+//! for the `LinearModelFitter` enum, following R's `quantreg::rq`.
+//!
+//! The coefficients at a quantile tau minimise the check loss sum rho_tau(y - X b), where
+//! rho_tau(r) = r (tau - I(r < 0)). Two solvers of this linear program are provided:
+//!
+//! - The Barrodale-Roberts simplex (`rq.fit.br`) walks between fits that interpolate `p`
+//!   observations. From each such basic fit it follows the steepest descending edge, and the
+//!   step along the edge is a weighted median of the breakpoints, so one iteration can pass
+//!   several vertices. It is exact and fast for problems up to a few thousand observations.
+//! - The Frisch-Newton interior point method (`rq.fit.fnb`) solves the dual problem with
+//!   Mehrotra's predictor-corrector steps, each a weighted least squares fit with the QR solver.
+//!   Its cost grows far more slowly with the number of observations.
+//!
+//! Both solvers also return the regression rank scores, the solution of the dual problem,
+//! which the rank-inversion confidence intervals of `summary.rq` are built from. Prior weights
+//! multiply the rows of x and y, as in `rq`.
+
+// src/fitters/quantile_regression_fitter.rs
+
+use super::fit::FitModel;
+use super::rlm_fitter::weighted_fit;
+use crate::anova::{format_number, format_p_value, write_table};
+use crate::distributions::{normal_cdf, normal_density, normal_quantile};
+use crate::errors::LmFitterError;
+use crate::least_squares::QrDecomposition;
+use crate::types::{Data, RealMatrix, Tolerance};
+use crate::vcov::unscaled_covariance;
+use derive_builder::Builder;
+use ndarray::{Array1, Axis};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::fmt;
+
+/// The number of observations above which `QuantileMethod::Auto` uses the interior point method.
+const AUTO_INTERIOR_POINT: usize = 5000;
+
+/// An enum representing the solvers of the quantile regression linear program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum QuantileMethod {
+    /// Barrodale-Roberts up to 5000 observations, Frisch-Newton beyond.
+    #[default]
+    Auto,
+    /// The Barrodale-Roberts simplex, `method = "br"`.
+    BarrodaleRoberts,
+    /// The Frisch-Newton interior point method, `method = "fn"`.
+    FrischNewton,
+}
+
+/// An enum representing the inference methods of `summary.rq`.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum QuantileInference {
+    /// Confidence intervals at level 1 - alpha by inverting the regression rank score test,
+    /// `se = "rank"`.
+    Rank { alpha: f64 },
+    /// Standard errors from resampling (x, y) pairs, `se = "boot"`.
+    Bootstrap { replicates: usize, seed: u64 },
+    /// Powell's kernel sandwich with the Hall-Sheather bandwidth, `se = "ker"`.
+    #[default]
+    Kernel,
+}
+
+impl QuantileInference {
+    /// Rank inversion at the default alpha = 0.1 of `rq`.
+    pub fn rank() -> Self {
+        QuantileInference::Rank { alpha: 0.1 }
+    }
+
+    /// The bootstrap with 200 replicates and seed 0.
+    pub fn bootstrap() -> Self {
+        QuantileInference::Bootstrap {
+            replicates: 200,
+            seed: 0,
+        }
+    }
+}
+
+/// The result of a quantile regression fit at a single quantile.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuantileFit {
+    /// The quantile.
+    pub tau: f64,
+    /// The coefficients, as a single column.
+    pub coefficients: RealMatrix,
+    /// The residuals y - X b.
+    pub residuals: Vec<f64>,
+    /// The minimised check loss.
+    pub objective: f64,
+    /// The regression rank scores, the solution of the dual problem, each in [0, 1].
+    pub dual: Vec<f64>,
+    /// The solver that was used.
+    pub method: QuantileMethod,
+    /// The number of simplex pivots or interior point steps.
+    pub iterations: usize,
+    /// `true` if the solver reached optimality within `max_iter` iterations.
+    pub converged: bool,
+}
+
+/// The fits at a grid of quantiles, the quantile process of `rq(tau = taus)`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuantileProcess {
+    /// The names of the coefficients.
+    pub names: Vec<String>,
+    /// The fits, one per quantile, in the order of the quantiles.
+    pub fits: Vec<QuantileFit>,
+}
+
+impl QuantileProcess {
+    /// Return the coefficients with one column per quantile.
+    pub fn coefficients(&self) -> RealMatrix {
+        let p = self.names.len();
+        let mut coefficients = RealMatrix::with_shape(p, self.fits.len());
+        for (k, fit) in self.fits.iter().enumerate() {
+            for j in 0..p {
+                coefficients.values[[j, k]] = fit.coefficients.values[[j, 0]];
+            }
+        }
+        coefficients
+    }
+}
+
+impl fmt::Display for QuantileProcess {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let header: Vec<String> = self
+            .fits
+            .iter()
+            .map(|fit| format!("tau= {}", fit.tau))
+            .collect();
+        let header: Vec<&str> = header.iter().map(String::as_str).collect();
+        let labels: Vec<&str> = self.names.iter().map(String::as_str).collect();
+        let coefficients = self.coefficients();
+        let cells: Vec<Vec<String>> = (0..self.names.len())
+            .map(|j| {
+                (0..self.fits.len())
+                    .map(|k| format_number(coefficients.values[[j, k]]))
+                    .collect()
+            })
+            .collect();
+        write_table(f, &labels, &header, &cells)
+    }
+}
+
+/// The coefficient table of `summary.rq`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuantileSummary {
+    /// The quantile.
+    pub tau: f64,
+    /// The names of the coefficients.
+    pub names: Vec<String>,
+    /// The coefficients.
+    pub coefficients: Vec<f64>,
+    /// The standard errors, for kernel and bootstrap inference.
+    pub std_errors: Option<Vec<f64>>,
+    /// The (lower, upper) confidence bounds, for rank inference.
+    pub intervals: Option<Vec<(f64, f64)>>,
+}
+
+impl fmt::Display for QuantileSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "tau: [1] {}", self.tau)?;
+        writeln!(f)?;
+        writeln!(f, "Coefficients:")?;
+        let labels: Vec<&str> = self.names.iter().map(String::as_str).collect();
+        match (&self.intervals, &self.std_errors) {
+            (Some(intervals), _) => {
+                let cells: Vec<Vec<String>> = self
+                    .coefficients
+                    .iter()
+                    .zip(intervals)
+                    .map(|(b, (lower, upper))| {
+                        vec![
+                            format_number(*b),
+                            format_number(*lower),
+                            format_number(*upper),
+                        ]
+                    })
+                    .collect();
+                write_table(
+                    f,
+                    &labels,
+                    &["coefficients", "lower bd", "upper bd"],
+                    &cells,
+                )
+            }
+            (None, Some(std_errors)) => {
+                let cells: Vec<Vec<String>> = self
+                    .coefficients
+                    .iter()
+                    .zip(std_errors)
+                    .map(|(b, se)| {
+                        let t = b / se;
+                        vec![
+                            format_number(*b),
+                            format_number(*se),
+                            format_number(t),
+                            format_p_value(2.0 * (1.0 - normal_cdf(t.abs()))),
+                        ]
+                    })
+                    .collect();
+                write_table(
+                    f,
+                    &labels,
+                    &["Value", "Std. Error", "t value", "Pr(>|t|)"],
+                    &cells,
+                )
+            }
+            (None, None) => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, Builder)]
+pub struct QuantileRegressionFitter<'a> {
+    pub data: &'a Data,
+    /// The quantile, in (0, 1).
+    #[builder(default = "0.5")]
+    pub tau: f64,
+    #[builder(default)]
+    pub method: QuantileMethod,
+    /// The maximum number of simplex pivots or interior point steps.
+    #[builder(default = "10_000")]
+    pub max_iter: usize,
+}
+
+/// A solution of the quantile regression linear program.
+struct Solution {
+    coefficients: Vec<f64>,
+    dual: Vec<f64>,
+    iterations: usize,
+    converged: bool,
+}
+
+impl<'a> QuantileRegressionFitter<'a> {
+    /// Return a new instance of the `QuantileRegressionFitter` struct at the quantile `tau`.
+    pub fn new(data: &'a Data, tau: f64) -> Self {
+        Self {
+            data,
+            tau,
+            method: QuantileMethod::Auto,
+            max_iter: 10_000,
+        }
+    }
+
+    /// Return the x matrix from the data struct.
+    pub fn x(&self) -> &RealMatrix {
+        &self.data.x
+    }
+
+    /// Return the y matrix from the data struct.
+    pub fn y(&self) -> &RealMatrix {
+        &self.data.y
+    }
+
+    /// Fit the model at `tau`.
+    ///
+    /// # Errors
+    /// Returns `LmFitterError::InvalidQuantile` if tau is outside (0, 1),
+    /// `LmFitterError::RankDeficient` if x does not have full column rank, and
+    /// `LmFitterError::MultipleResponses` for more than one response.
+    pub fn quantile_fit(&self) -> Result<QuantileFit, LmFitterError> {
+        self.fit_at(self.tau)
+    }
+
+    /// Fit the model at each quantile in `taus`.
+    ///
+    /// # Errors
+    /// Returns the errors of `quantile_fit` for any of the quantiles.
+    pub fn process(&self, taus: &[f64]) -> Result<QuantileProcess, LmFitterError> {
+        Ok(QuantileProcess {
+            names: self.data.column_names().to_vec(),
+            fits: taus
+                .iter()
+                .map(|&tau| self.fit_at(tau))
+                .collect::<Result<_, _>>()?,
+        })
+    }
+
+    /// Return the coefficient table at `tau` with the chosen inference.
+    ///
+    /// # Errors
+    /// Returns the errors of `quantile_fit`, `LmFitterError::BandwidthTooWide` if the kernel
+    /// bandwidth around tau leaves (0, 1), and `LmFitterError::TooFewReplicates` if fewer than
+    /// two bootstrap resamples have a full-rank model matrix.
+    pub fn summary(&self, inference: QuantileInference) -> Result<QuantileSummary, LmFitterError> {
+        let fit = self.quantile_fit()?;
+        let (x, y) = self.weighted()?;
+        let coefficients: Vec<f64> = fit.coefficients.values.column(0).to_vec();
+        let (std_errors, intervals) = match inference {
+            QuantileInference::Rank { alpha } => (
+                None,
+                Some(
+                    (0..x.n_cols())
+                        .map(|j| self.rank_interval(&x, &y, j, coefficients[j], alpha))
+                        .collect::<Result<_, _>>()?,
+                ),
+            ),
+            QuantileInference::Bootstrap { replicates, seed } => {
+                (Some(self.bootstrap(&x, &y, replicates, seed)?), None)
+            }
+            QuantileInference::Kernel => (Some(self.kernel(&x, &fit)?), None),
+        };
+        Ok(QuantileSummary {
+            tau: self.tau,
+            names: self.data.column_names().to_vec(),
+            coefficients,
+            std_errors,
+            intervals,
+        })
+    }
+
+    /// Fit the model at the quantile `tau`.
+    fn fit_at(&self, tau: f64) -> Result<QuantileFit, LmFitterError> {
+        if !(tau > 0.0 && tau < 1.0) {
+            return Err(LmFitterError::InvalidQuantile { tau });
+        }
+        let (x, y) = self.weighted()?;
+        let method = match self.method {
+            QuantileMethod::Auto if x.n_rows() > AUTO_INTERIOR_POINT => {
+                QuantileMethod::FrischNewton
+            }
+            QuantileMethod::Auto => QuantileMethod::BarrodaleRoberts,
+            method => method,
+        };
+        let solution = self.solve(&x, &y, tau, method)?;
+
+        let (x, y) = (self.data.x(), self.data.y());
+        let b = Array1::from(solution.coefficients.clone());
+        let residuals: Vec<f64> = (0..x.n_rows())
+            .map(|i| y.values[[i, 0]] - x.values.row(i).dot(&b))
+            .collect();
+        let weights = self.data.weights();
+        let objective = residuals
+            .iter()
+            .enumerate()
+            .map(|(i, &r)| weights.map_or(1.0, |w| w[i]) * check_loss(r, tau))
+            .sum();
+        Ok(QuantileFit {
+            tau,
+            coefficients: RealMatrix::from_vec(solution.coefficients, x.n_cols(), None),
+            residuals,
+            objective,
+            dual: solution.dual,
+            method,
+            iterations: solution.iterations,
+            converged: solution.converged,
+        })
+    }
+
+    /// Return x and y with their rows multiplied by the prior weights, after checking that
+    /// there is a single response and that x has full column rank.
+    fn weighted(&self) -> Result<(RealMatrix, RealMatrix), LmFitterError> {
+        if self.data.y().n_cols() != 1 {
+            return Err(LmFitterError::MultipleResponses {
+                found: self.data.y().n_cols(),
+            });
+        }
+        let (mut x, mut y) = (self.data.x().clone(), self.data.y().clone());
+        if let Some(weights) = self.data.weights() {
+            for (i, w) in weights.iter().enumerate() {
+                x.values.row_mut(i).mapv_inplace(|v| v * w);
+                y.values[[i, 0]] *= w;
+            }
+        }
+        let rank = QrDecomposition::new(&x, &Tolerance::default()).rank;
+        if rank < x.n_cols() {
+            return Err(LmFitterError::RankDeficient {
+                rank,
+                columns: x.n_cols(),
+            });
+        }
+        Ok((x, y))
+    }
+
+    /// Solve the linear program with the given solver.
+    fn solve(
+        &self,
+        x: &RealMatrix,
+        y: &RealMatrix,
+        tau: f64,
+        method: QuantileMethod,
+    ) -> Result<Solution, LmFitterError> {
+        match method {
+            QuantileMethod::FrischNewton => frisch_newton(x, y, tau, self.max_iter),
+            _ => barrodale_roberts(x, y, tau, self.max_iter),
+        }
+    }
+
+    /// Return the rank-inversion interval for coefficient `j`: the values b for which the rank
+    /// score test of beta_j = b, with the other coefficients refitted, does not reject at level
+    /// alpha. The bounds are found by doubling outwards from the estimate, then bisecting.
+    fn rank_interval(
+        &self,
+        x: &RealMatrix,
+        y: &RealMatrix,
+        j: usize,
+        estimate: f64,
+        alpha: f64,
+    ) -> Result<(f64, f64), LmFitterError> {
+        let tau = self.tau;
+        let others: Vec<usize> = (0..x.n_cols()).filter(|&k| k != j).collect();
+        let rest = RealMatrix::new(x.values.select(Axis(1), &others));
+        let column = RealMatrix::new(x.values.select(Axis(1), &[j]));
+        let (_, projected) = weighted_fit(&rest, &column, &vec![1.0; x.n_rows()])?;
+        let spread = (projected.iter().map(|v| v * v).sum::<f64>() * tau * (1.0 - tau)).sqrt();
+        let critical = normal_quantile(1.0 - alpha / 2.0);
+
+        let statistic = |b: f64| -> Result<f64, LmFitterError> {
+            let shifted = RealMatrix::new(&y.values - &(&column.values * b));
+            let solution = barrodale_roberts(&rest, &shifted, tau, self.max_iter)?;
+            let score: f64 = projected
+                .iter()
+                .zip(&solution.dual)
+                .map(|(v, a)| v * (a - (1.0 - tau)))
+                .sum();
+            Ok(score.abs() / spread)
+        };
+
+        let mut bounds = [estimate; 2];
+        for (bound, direction) in bounds.iter_mut().zip([-1.0, 1.0]) {
+            let mut step = 0.01 * (1.0 + estimate.abs());
+            let mut inside = estimate;
+            let mut outside = estimate + direction * step;
+            for _ in 0..60 {
+                if statistic(outside)? > critical {
+                    break;
+                }
+                inside = outside;
+                step *= 2.0;
+                outside = estimate + direction * step;
+            }
+            for _ in 0..50 {
+                let middle = (inside + outside) / 2.0;
+                if statistic(middle)? > critical {
+                    outside = middle;
+                } else {
+                    inside = middle;
+                }
+            }
+            *bound = inside;
+        }
+        Ok((bounds[0], bounds[1]))
+    }
+
+    /// Return the standard deviations of the coefficients refitted to resampled (x, y) pairs.
+    /// Resamples whose model matrix is singular are skipped.
+    fn bootstrap(
+        &self,
+        x: &RealMatrix,
+        y: &RealMatrix,
+        replicates: usize,
+        seed: u64,
+    ) -> Result<Vec<f64>, LmFitterError> {
+        let (n, p) = (x.n_rows(), x.n_cols());
+        let method = match self.method {
+            QuantileMethod::FrischNewton => QuantileMethod::FrischNewton,
+            _ => QuantileMethod::BarrodaleRoberts,
+        };
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut draws: Vec<Vec<f64>> = Vec::with_capacity(replicates);
+        for _ in 0..replicates {
+            let rows: Vec<usize> = (0..n).map(|_| rng.gen_range(0..n)).collect();
+            let resampled = RealMatrix::new(x.values.select(Axis(0), &rows));
+            if QrDecomposition::new(&resampled, &Tolerance::default()).rank < p {
+                continue;
+            }
+            let response = RealMatrix::new(y.values.select(Axis(0), &rows));
+            draws.push(
+                self.solve(&resampled, &response, self.tau, method)?
+                    .coefficients,
+            );
+        }
+        if draws.len() < 2 {
+            return Err(LmFitterError::TooFewReplicates { found: draws.len() });
+        }
+        let count = draws.len() as f64;
+        Ok((0..p)
+            .map(|j| {
+                let mean = draws.iter().map(|d| d[j]).sum::<f64>() / count;
+                let sum_sq: f64 = draws.iter().map(|d| (d[j] - mean).powi(2)).sum();
+                (sum_sq / (count - 1.0)).sqrt()
+            })
+            .collect())
+    }
+
+    /// Return Powell's kernel sandwich standard errors, tau (1 - tau) H^-1 X'X H^-1 with
+    /// H = X' diag(f) X and f a normal kernel density estimate of the residuals at zero.
+    fn kernel(&self, x: &RealMatrix, fit: &QuantileFit) -> Result<Vec<f64>, LmFitterError> {
+        let (n, tau) = (x.n_rows(), self.tau);
+        let bandwidth = hall_sheather(n, tau, 0.05);
+        if tau + bandwidth > 1.0 || tau - bandwidth < 0.0 {
+            return Err(LmFitterError::BandwidthTooWide { tau });
+        }
+        let weights = self.data.weights();
+        let residuals: Vec<f64> = fit
+            .residuals
+            .iter()
+            .enumerate()
+            .map(|(i, r)| weights.map_or(1.0, |w| w[i]) * r)
+            .collect();
+        let mean = residuals.iter().sum::<f64>() / n as f64;
+        let sd =
+            (residuals.iter().map(|r| (r - mean).powi(2)).sum::<f64>() / (n - 1) as f64).sqrt();
+        let spread =
+            sd.min((sample_quantile(&residuals, 0.75) - sample_quantile(&residuals, 0.25)) / 1.34);
+        let h = (normal_quantile(tau + bandwidth) - normal_quantile(tau - bandwidth)) * spread;
+        let density: Vec<f64> = residuals
+            .iter()
+            .map(|r| normal_density(r / h) / h)
+            .collect();
+
+        let (weighted, _) = weighted_fit(x, &RealMatrix::with_shape(n, 1), &density)?;
+        let inverse = unscaled_covariance(&weighted.qr);
+        let covariance = inverse.dot(&x.transpose().dot(x)).dot(&inverse);
+        Ok((0..x.n_cols())
+            .map(|j| (tau * (1.0 - tau) * covariance.values[[j, j]]).sqrt())
+            .collect())
+    }
+}
+
+impl<'a> FitModel for QuantileRegressionFitter<'a> {
+    /// Fit the model at `tau` and return its coefficients.
+    fn fit(&self) -> Result<RealMatrix, LmFitterError> {
+        Ok(self.quantile_fit()?.coefficients)
+    }
+
+    fn x(&self) -> &RealMatrix {
+        self.data.x()
+    }
+
+    fn y(&self) -> &RealMatrix {
+        self.data.y()
+    }
+}
+
+/// Return rho_tau(r) = r (tau - I(r < 0)).
+fn check_loss(r: f64, tau: f64) -> f64 {
+    if r < 0.0 {
+        r * (tau - 1.0)
+    } else {
+        r * tau
+    }
+}
+
+/// Return the Hall-Sheather bandwidth of `bandwidth.rq` at level alpha.
+fn hall_sheather(n: usize, tau: f64, alpha: f64) -> f64 {
+    let q = normal_quantile(tau);
+    let f = normal_density(q);
+    (n as f64).powf(-1.0 / 3.0)
+        * normal_quantile(1.0 - alpha / 2.0).powf(2.0 / 3.0)
+        * (1.5 * f * f / (2.0 * q * q + 1.0)).powf(1.0 / 3.0)
+}
+
+/// Return the `p` quantile of `values` by linear interpolation, R's default type 7.
+fn sample_quantile(values: &[f64], p: f64) -> f64 {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    let position = p * (sorted.len() - 1) as f64;
+    let (low, fraction) = (position.floor() as usize, position.fract());
+    match sorted.get(low + 1) {
+        Some(next) => sorted[low] + fraction * (next - sorted[low]),
+        None => sorted[low],
+    }
+}
+
+/// Solve the quantile regression problem by the Barrodale-Roberts simplex.
+///
+/// Each basic fit interpolates the `p` observations in `basis`. Moving the fit so that one of
+/// them leaves its interpolated value, up or down, is an edge; the objective is piecewise
+/// linear along it, with a breakpoint where each other residual changes sign. The edge with the
+/// most negative directional derivative is followed to the breakpoint where the slope turns
+/// non-negative, and the observation at that breakpoint enters the basis.
+fn barrodale_roberts(
+    x: &RealMatrix,
+    y: &RealMatrix,
+    tau: f64,
+    max_iter: usize,
+) -> Result<Solution, LmFitterError> {
+    let (n, p) = (x.n_rows(), x.n_cols());
+    let y: Vec<f64> = y.values.column(0).to_vec();
+    let eps = 1e-10 * y.iter().fold(1.0_f64, |m, v| m.max(v.abs()));
+    let mut basis = initial_basis(x, &y)?;
+    let mut iterations = 0;
+    loop {
+        let rows = RealMatrix::new(x.values.select(Axis(0), &basis));
+        let inverse = rows.inverse().ok_or(LmFitterError::RankDeficient {
+            rank: QrDecomposition::new(&rows, &Tolerance::default()).rank,
+            columns: p,
+        })?;
+        let basic_y = Array1::from_iter(basis.iter().map(|&i| y[i]));
+        let coefficients = inverse.values.dot(&basic_y);
+        let mut residuals: Vec<f64> = (0..n)
+            .map(|i| y[i] - x.values.row(i).dot(&coefficients))
+            .collect();
+        let mut in_basis = vec![false; n];
+        for &i in &basis {
+            residuals[i] = 0.0;
+            in_basis[i] = true;
+        }
+
+        // The steepest descending edge: (slope, basis position, x_i'd for every i).
+        let mut best: Option<(f64, usize, Vec<f64>)> = None;
+        for j in 0..p {
+            let direction = inverse.values.column(j);
+            let g: Vec<f64> = (0..n).map(|i| x.values.row(i).dot(&direction)).collect();
+            for sign in [1.0, -1.0] {
+                let mut slope = if sign > 0.0 { 1.0 - tau } else { tau };
+                let mut size = 1.0;
+                for i in (0..n).filter(|&i| !in_basis[i]) {
+                    let gi = sign * g[i];
+                    slope += if residuals[i] > eps {
+                        -tau * gi
+                    } else if residuals[i] < -eps {
+                        (1.0 - tau) * gi
+                    } else {
+                        (-tau * gi).max((1.0 - tau) * gi)
+                    };
+                    size += gi.abs();
+                }
+                if slope < -1e-12 * size && best.as_ref().is_none_or(|b| slope < b.0) {
+                    best = Some((slope, j, g.iter().map(|v| sign * v).collect()));
+                }
+            }
+        }
+
+        let converged = best.is_none();
+        let Some((mut slope, leaving, g)) = best.filter(|_| iterations < max_iter) else {
+            let dual = rank_scores(x, &inverse, &basis, &residuals, tau, eps);
+            return Ok(Solution {
+                coefficients: coefficients.to_vec(),
+                dual,
+                iterations,
+                converged,
+            });
+        };
+
+        let mut breakpoints: Vec<(f64, usize)> = (0..n)
+            .filter(|&i| !in_basis[i] && residuals[i].abs() > eps && residuals[i] * g[i] > 0.0)
+            .map(|i| (residuals[i] / g[i], i))
+            .collect();
+        breakpoints.sort_by(|a, b| a.0.total_cmp(&b.0));
+        let entering = breakpoints.iter().find_map(|&(_, i)| {
+            slope += g[i].abs();
+            (slope >= 0.0).then_some(i)
+        });
+        match entering {
+            Some(i) => basis[leaving] = i,
+            None => {
+                return Ok(Solution {
+                    coefficients: coefficients.to_vec(),
+                    dual: rank_scores(x, &inverse, &basis, &residuals, tau, eps),
+                    iterations,
+                    converged: false,
+                })
+            }
+        }
+        iterations += 1;
+    }
+}
+
+/// Return `p` observations with linearly independent rows of x, preferring those closest to
+/// the least squares fit.
+fn initial_basis(x: &RealMatrix, y: &[f64]) -> Result<Vec<usize>, LmFitterError> {
+    let (n, p) = (x.n_rows(), x.n_cols());
+    let response = RealMatrix::from_vec(y.to_vec(), n, None);
+    let (_, residuals) = weighted_fit(x, &response, &vec![1.0; n])?;
+    let mut order: Vec<usize> = (0..n).collect();
+    order.sort_by(|&a, &b| residuals[a].abs().total_cmp(&residuals[b].abs()));
+
+    let mut basis = Vec::with_capacity(p);
+    let mut orthogonal: Vec<Array1<f64>> = Vec::with_capacity(p);
+    for i in order {
+        if basis.len() == p {
+            break;
+        }
+        let row = x.values.row(i).to_owned();
+        let norm = row.dot(&row).sqrt();
+        let mut v = row;
+        for u in &orthogonal {
+            let projection = v.dot(u);
+            v = v - u * projection;
+        }
+        let length = v.dot(&v).sqrt();
+        if length > 1e-8 * norm {
+            orthogonal.push(v / length);
+            basis.push(i);
+        }
+    }
+    if basis.len() < p {
+        return Err(LmFitterError::RankDeficient {
+            rank: basis.len(),
+            columns: p,
+        });
+    }
+    Ok(basis)
+}
+
+/// Return the regression rank scores of a basic solution: 1 above the fit, 0 below, and the
+/// basic observations solved from X'a = (1 - tau) X'1.
+fn rank_scores(
+    x: &RealMatrix,
+    inverse: &RealMatrix,
+    basis: &[usize],
+    residuals: &[f64],
+    tau: f64,
+    eps: f64,
+) -> Vec<f64> {
+    let mut dual: Vec<f64> = residuals
+        .iter()
+        .map(|&r| {
+            if r > eps {
+                1.0
+            } else if r < -eps {
+                0.0
+            } else {
+                1.0 - tau
+            }
+        })
+        .collect();
+    let mut target: Array1<f64> = x.values.sum_axis(Axis(0)) * (1.0 - tau);
+    for (i, &a) in dual.iter().enumerate() {
+        if !basis.contains(&i) {
+            target -= &(&x.values.row(i) * a);
+        }
+    }
+    let basic = inverse.values.t().dot(&target);
+    for (&i, a) in basis.iter().zip(basic) {
+        dual[i] = a;
+    }
+    dual
+}
+
+/// Solve the quantile regression problem by the Frisch-Newton interior point method of
+/// Portnoy and Koenker, on the dual problem max y'a subject to X'a = (1 - tau) X'1 and
+/// 0 <= a <= 1. The coefficients are the negated multipliers of the equality constraints.
+fn frisch_newton(
+    x: &RealMatrix,
+    y: &RealMatrix,
+    tau: f64,
+    max_iter: usize,
+) -> Result<Solution, LmFitterError> {
+    const BETA: f64 = 0.99995;
+    const SMALL: f64 = 1e-6;
+    let n = x.n_rows();
+    let c: Vec<f64> = y.values.column(0).iter().map(|v| -v).collect();
+    let b: Array1<f64> = x.values.sum_axis(Axis(0)) * (1.0 - tau);
+
+    let mut a = vec![1.0 - tau; n];
+    let mut s = vec![tau; n];
+    let mut dual = weighted_coefficients(x, &c, &vec![1.0; n])?;
+    let fitted = x.values.dot(&dual);
+    let mut z: Vec<f64> = Vec::with_capacity(n);
+    let mut w: Vec<f64> = Vec::with_capacity(n);
+    for i in 0..n {
+        let r = c[i] - fitted[i];
+        // A zero residual would give an infinite Newton weight; start it slightly inside.
+        let zi = if r == 0.0 { SMALL } else { r.max(0.0) };
+        z.push(zi);
+        w.push(zi - r);
+    }
+    let gap = |a: &[f64], dual: &Array1<f64>, w: &[f64]| -> f64 {
+        dot(&c, a) - dual.dot(&b) + w.iter().sum::<f64>()
+    };
+
+    let mut iterations = 0;
+    while gap(&a, &dual, &w) > SMALL && iterations < max_iter {
+        iterations += 1;
+        let q: Vec<f64> = (0..n).map(|i| 1.0 / (z[i] / a[i] + w[i] / s[i])).collect();
+        let r: Vec<f64> = (0..n).map(|i| z[i] - w[i]).collect();
+
+        // Predictor step.
+        let mut dy = weighted_coefficients(x, &r, &q)?;
+        let xdy = x.values.dot(&dy);
+        let mut da: Vec<f64> = (0..n).map(|i| q[i] * (xdy[i] - r[i])).collect();
+        let mut ds: Vec<f64> = da.iter().map(|v| -v).collect();
+        let mut dz: Vec<f64> = (0..n).map(|i| -z[i] * (da[i] / a[i] + 1.0)).collect();
+        let mut dw: Vec<f64> = (0..n).map(|i| -w[i] * (ds[i] / s[i] + 1.0)).collect();
+        let (mut fp, mut fd) = step_lengths(&a, &da, &s, &ds, &z, &dz, &w, &dw, BETA);
+
+        // Corrector step, centring towards mu.
+        if fp.min(fd) < 1.0 {
+            let mu = dot(&z, &a) + dot(&w, &s);
+            let g: f64 = (0..n)
+                .map(|i| {
+                    (z[i] + fd * dz[i]) * (a[i] + fp * da[i])
+                        + (w[i] + fd * dw[i]) * (s[i] + fp * ds[i])
+                })
+                .sum();
+            let mu = mu * (g / mu).powi(3) / (2.0 * n as f64);
+            let dadz: Vec<f64> = (0..n).map(|i| da[i] * dz[i]).collect();
+            let dsdw: Vec<f64> = (0..n).map(|i| ds[i] * dw[i]).collect();
+            let xi: Vec<f64> = (0..n).map(|i| mu * (1.0 / a[i] - 1.0 / s[i])).collect();
+            let target: Vec<f64> = (0..n).map(|i| r[i] + dadz[i] - dsdw[i] - xi[i]).collect();
+            dy = weighted_coefficients(x, &target, &q)?;
+            let xdy = x.values.dot(&dy);
+            da = (0..n)
+                .map(|i| q[i] * (xdy[i] + xi[i] - r[i] - dadz[i] + dsdw[i]))
+                .collect();
+            ds = da.iter().map(|v| -v).collect();
+            dz = (0..n)
+                .map(|i| mu / a[i] - z[i] - z[i] * da[i] / a[i] - dadz[i])
+                .collect();
+            dw = (0..n)
+                .map(|i| mu / s[i] - w[i] - w[i] * ds[i] / s[i] - dsdw[i])
+                .collect();
+            (fp, fd) = step_lengths(&a, &da, &s, &ds, &z, &dz, &w, &dw, BETA);
+        }
+
+        for i in 0..n {
+            a[i] += fp * da[i];
+            s[i] += fp * ds[i];
+            w[i] += fd * dw[i];
+            z[i] += fd * dz[i];
+        }
+        dual = dual + dy * fd;
+    }
+
+    let converged = gap(&a, &dual, &w) <= SMALL;
+    Ok(Solution {
+        coefficients: dual.iter().map(|v| -v).collect(),
+        dual: a,
+        iterations,
+        converged,
+    })
+}
+
+/// Return the weighted least squares coefficients of `target` on x.
+fn weighted_coefficients(
+    x: &RealMatrix,
+    target: &[f64],
+    weights: &[f64],
+) -> Result<Array1<f64>, LmFitterError> {
+    let response = RealMatrix::from_vec(target.to_vec(), x.n_rows(), None);
+    let (fit, _) = weighted_fit(x, &response, weights)?;
+    Ok(fit
+        .coefficients
+        .values
+        .column(0)
+        .mapv(|v| if v.is_nan() { 0.0 } else { v }))
+}
+
+/// Return the primal and dual step lengths that keep all variables positive, shortened by beta
+/// and capped at 1.
+#[allow(clippy::too_many_arguments)]
+fn step_lengths(
+    a: &[f64],
+    da: &[f64],
+    s: &[f64],
+    ds: &[f64],
+    z: &[f64],
+    dz: &[f64],
+    w: &[f64],
+    dw: &[f64],
+    beta: f64,
+) -> (f64, f64) {
+    let bound = |v: &[f64], dv: &[f64]| -> f64 {
+        v.iter()
+            .zip(dv)
+            .filter(|(_, d)| **d < 0.0)
+            .map(|(v, d)| -v / d)
+            .fold(1e20, f64::min)
+    };
+    let primal = bound(a, da).min(bound(s, ds));
+    let dual = bound(w, dw).min(bound(z, dz));
+    ((beta * primal).min(1.0), (beta * dual).min(1.0))
+}
+
+fn dot(u: &[f64], v: &[f64]) -> f64 {
+    u.iter().zip(v).map(|(a, b)| a * b).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fitters::fit::LinearModelFitter;
+
+    /// y = 1 + 2 t with noise whose spread grows with t, so the quantile lines fan out.
+    fn heteroskedastic(n: usize) -> Data {
+        let x: Vec<f64> = (0..n).flat_map(|i| [1.0, i as f64 / 10.0]).collect();
+        let y: Vec<f64> = (0..n)
+            .map(|i| {
+                let t = i as f64 / 10.0;
+                let noise = ((i * 7919) % 101) as f64 / 50.0 - 1.0;
+                1.0 + 2.0 * t + (0.5 + 0.3 * t) * noise
+            })
+            .collect();
+        Data::new(
+            RealMatrix::from_vec(x, n, Some(2)),
+            RealMatrix::from_vec(y, n, None),
+        )
+        .with_column_names(vec!["(Intercept)".to_string(), "t".to_string()])
+    }
+
+    #[test]
+    fn test_simplex_and_interior_point_agree() {
+        let data = heteroskedastic(80);
+        for tau in [0.25, 0.5, 0.95] {
+            let mut fitter = QuantileRegressionFitter::new(&data, tau);
+            fitter.method = QuantileMethod::BarrodaleRoberts;
+            let simplex = fitter.quantile_fit().unwrap();
+            fitter.method = QuantileMethod::FrischNewton;
+            let interior = fitter.quantile_fit().unwrap();
+            assert!(simplex.converged && interior.converged);
+            fitter.max_iter = 2;
+            let stopped = fitter.quantile_fit().unwrap();
+            assert!(!stopped.converged && stopped.iterations == 2);
+            fitter.max_iter = 10_000;
+            assert!((simplex.objective - interior.objective).abs() < 1e-5 * simplex.objective);
+            assert!(simplex
+                .dual
+                .iter()
+                .all(|&a| (-1e-8..=1.0 + 1e-8).contains(&a)));
+
+            // The simplex solution interpolates p observations and is a minimum.
+            let zeros = simplex.residuals.iter().filter(|r| r.abs() < 1e-9).count();
+            assert!(zeros >= 2);
+            for delta in [[1e-3, 0.0], [0.0, -1e-3], [-1e-3, 1e-3]] {
+                let b = &simplex.coefficients.values;
+                let moved: f64 = (0..80)
+                    .map(|i| {
+                        let fit = (b[[0, 0]] + delta[0]) + (b[[1, 0]] + delta[1]) * i as f64 / 10.0;
+                        check_loss(data.y.values[[i, 0]] - fit, tau)
+                    })
+                    .sum();
+                assert!(moved >= simplex.objective - 1e-12);
+            }
+        }
+    }
+
+    #[test]
+    fn test_intercept_only_fit_is_the_sample_quantile() {
+        let n = 21;
+        let y: Vec<f64> = (0..n).map(|i| ((i * 13) % 21) as f64).collect();
+        let data = Data::new(
+            RealMatrix::from_vec(vec![1.0; n], n, Some(1)),
+            RealMatrix::from_vec(y, n, None),
+        );
+        let fitter = QuantileRegressionFitter::new(&data, 0.5);
+        let process = fitter.process(&[0.1, 0.5, 0.9]).unwrap();
+        assert_eq!(
+            process.coefficients().values.row(0).to_vec(),
+            vec![2.0, 10.0, 18.0]
+        );
+        assert_eq!(
+            LinearModelFitter::QuantileRegression(fitter)
+                .fit()
+                .unwrap()
+                .values[[0, 0]],
+            10.0
+        );
+        assert!(matches!(
+            QuantileRegressionFitter::new(&data, 1.0).quantile_fit(),
+            Err(LmFitterError::InvalidQuantile { .. })
+        ));
+    }
+
+    #[test]
+    fn test_inference_covers_the_slope() {
+        let data = heteroskedastic(100);
+        let fitter = QuantileRegressionFitter::new(&data, 0.5);
+        let kernel = fitter.summary(QuantileInference::Kernel).unwrap();
+        let bootstrap = fitter.summary(QuantileInference::bootstrap()).unwrap();
+        let rank = fitter.summary(QuantileInference::rank()).unwrap();
+
+        let slope = kernel.coefficients[1];
+        assert!((slope - 2.0).abs() < 0.2);
+        let (kernel_se, bootstrap_se) = (
+            kernel.std_errors.as_ref().unwrap()[1],
+            bootstrap.std_errors.as_ref().unwrap()[1],
+        );
+        assert!(kernel_se > 0.0 && bootstrap_se > 0.0);
+        assert!(kernel_se / bootstrap_se < 3.0 && bootstrap_se / kernel_se < 3.0);
+        let (lower, upper) = rank.intervals.as_ref().unwrap()[1];
+        assert!(lower < slope && slope < upper && lower < 2.0 && 2.0 < upper);
+        assert!(rank.to_string().contains("lower bd"));
+        assert_eq!(
+            fitter.summary(QuantileInference::bootstrap()).unwrap(),
+            bootstrap
+        );
+        assert!(matches!(
+            fitter.summary(QuantileInference::Bootstrap {
+                replicates: 1,
+                seed: 0
+            }),
+            Err(LmFitterError::TooFewReplicates { found: 1 })
+        ));
+    }
+}
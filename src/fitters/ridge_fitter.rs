@@ -0,0 +1,502 @@
+//! This module contains the ridge regression fitter, which implements the `FitModel` trait for
+//! the `LinearModelFitter` enum.
+//!
+//! The columns of x other than the intercept are centred and scaled to unit (weighted) variance
+//! before they are penalised, and the coefficients are reported on the original scale with an
+//! unpenalised intercept. λ is on the scale of `MASS::lm.ridge`: each fit minimises
+//! ||y - Z b||² + λ ||b||² for the standardised matrix Z and the centred response.
+//!
+//! The penalised problem is solved for every λ at once from the singular value decomposition
+//! Z = U D V' of the standardised matrix Z, computed once without forming Z'Z, so that the
+//! collinear designs ridge is meant for keep their accuracy: b(λ) = V D (D² + λ)^-1 U'y and the
+//! effective degrees of freedom are sum d² / (d² + λ). With more columns than observations, only
+//! the n directions of the row space are fitted. The penalty is chosen by generalised
+//! cross-validation, closed-form leave-one-out cross-validation, or k-fold cross-validation.
+
+// src/fitters/ridge_fitter.rs
+
+use super::fit::FitModel;
+use super::standardization::Standardization;
+use crate::cv::CrossValidation;
+use crate::errors::LmFitterError;
+use crate::types::{Data, RealMatrix};
+use derive_builder::Builder;
+
+/// The number of penalties in the default path.
+const DEFAULT_PATH_LENGTH: usize = 100;
+
+/// An enum representing the criteria used to choose the ridge penalty.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum LambdaSelection {
+    /// Generalised cross-validation, (RSS / n) / (1 - df / n)^2.
+    #[default]
+    Gcv,
+    /// The leave-one-out mean squared error, computed from the hat values without refitting.
+    LeaveOneOut,
+    /// The mean squared error of k-fold cross-validation.
+    KFold(CrossValidation),
+}
+
+/// The ridge fits for a sequence of penalties, with the one chosen by the selection criterion.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RidgePath {
+    /// The penalties, in decreasing order.
+    pub lambdas: Vec<f64>,
+    /// The coefficients on the original scale, with one column per penalty.
+    pub coefficients: RealMatrix,
+    /// The effective degrees of freedom of each fit, not counting the intercept.
+    pub effective_df: Vec<f64>,
+    /// The value of the selection criterion for each penalty.
+    pub criterion: Vec<f64>,
+    /// The index of the penalty with the lowest criterion.
+    pub selected: usize,
+}
+
+impl RidgePath {
+    /// Return the selected penalty.
+    pub fn lambda(&self) -> f64 {
+        self.lambdas[self.selected]
+    }
+
+    /// Return the effective degrees of freedom of the selected fit.
+    pub fn df(&self) -> f64 {
+        self.effective_df[self.selected]
+    }
+
+    /// Return the coefficients of the selected fit as a column vector.
+    pub fn selected_coefficients(&self) -> RealMatrix {
+        let column = self.coefficients.values.column(self.selected).to_vec();
+        RealMatrix::from_vec(column, self.coefficients.n_rows(), None)
+    }
+}
+
+#[derive(Debug, Builder)]
+pub struct RidgeFitter<'a> {
+    pub data: &'a Data,
+    /// The penalties to try. If `None`, a log-spaced path is derived from the data.
+    #[builder(default)]
+    pub lambdas: Option<Vec<f64>>,
+    #[builder(default)]
+    pub selection: LambdaSelection,
+}
+
+impl<'a> RidgeFitter<'a> {
+    /// Return a new instance of the `RidgeFitter` struct.
+    ///
+    /// # Arguments
+    /// * `data` - The data for the linear model. The intercept is the column assigned to term
+    ///   `0` of `data.terms()`, usually named `(Intercept)`.
+    /// * `lambdas` - The penalties to try. If `None`, 100 penalties are spaced logarithmically
+    ///   from 10 times to 1e-6 times the largest eigenvalue of Z'Z.
+    /// * `selection` - The criterion used to choose the penalty.
+    pub fn new(data: &'a Data, lambdas: Option<Vec<f64>>, selection: LambdaSelection) -> Self {
+        Self {
+            data,
+            lambdas,
+            selection,
+        }
+    }
+
+    /// Return the x matrix from the data struct.
+    pub fn x(&self) -> &RealMatrix {
+        &self.data.x
+    }
+
+    /// Return the y matrix from the data struct.
+    pub fn y(&self) -> &RealMatrix {
+        &self.data.y
+    }
+
+    /// Fit the whole path of penalties and choose one by the selection criterion.
+    ///
+    /// # Errors
+    /// Returns `LmFitterError::MultipleResponses` if the data has more than one response,
+    /// `LmFitterError::NoPenalties` if `lambdas` is empty, `LmFitterError::InvalidPenalty` for
+    /// a negative or non-finite penalty, and `LmFitterError::CrossValidation` if the folds
+    /// cannot be drawn.
+    pub fn path(&self) -> Result<RidgePath, LmFitterError> {
+        let decomposition = Standardized::new(self.data)?;
+        let lambdas = match &self.lambdas {
+            Some(lambdas) => {
+                if lambdas.is_empty() {
+                    return Err(LmFitterError::NoPenalties);
+                }
+                if let Some(&lambda) = lambdas.iter().find(|l| !(l.is_finite() && **l >= 0.0)) {
+                    return Err(LmFitterError::InvalidPenalty { lambda });
+                }
+                let mut lambdas = lambdas.clone();
+                lambdas.sort_by(|a, b| b.total_cmp(a));
+                lambdas
+            }
+            None => decomposition.default_lambdas(),
+        };
+
+        let criterion = match &self.selection {
+            LambdaSelection::Gcv => lambdas.iter().map(|&l| decomposition.gcv(l)).collect(),
+            LambdaSelection::LeaveOneOut => lambdas
+                .iter()
+                .map(|&l| decomposition.leave_one_out(l))
+                .collect(),
+            LambdaSelection::KFold(cv) => self.k_fold(cv, &lambdas)?,
+        };
+        let selected = (0..lambdas.len())
+            .min_by(|&a, &b| criterion[a].total_cmp(&criterion[b]))
+            .unwrap_or(0);
+
+        let p = self.data.x().n_cols();
+        let mut coefficients = RealMatrix::with_shape(p, lambdas.len());
+        for (k, &lambda) in lambdas.iter().enumerate() {
+            coefficients
+                .values
+                .column_mut(k)
+                .assign(&ndarray::Array1::from(decomposition.coefficients(lambda)));
+        }
+
+        Ok(RidgePath {
+            effective_df: lambdas.iter().map(|&l| decomposition.df(l)).collect(),
+            lambdas,
+            coefficients,
+            criterion,
+            selected,
+        })
+    }
+
+    /// Return the mean squared prediction error of each penalty under k-fold cross-validation.
+    fn k_fold(&self, cv: &CrossValidation, lambdas: &[f64]) -> Result<Vec<f64>, LmFitterError> {
+        let n = self.data.x().n_rows();
+        let assignments = cv
+            .fold_assignments(n)
+            .map_err(|e| LmFitterError::CrossValidation(Box::new(e)))?;
+        let y = self.data.y().values.column(0);
+
+        let mut sse = vec![0.0; lambdas.len()];
+        for assignment in &assignments {
+            for fold in 0..cv.folds {
+                let (test, train): (Vec<usize>, Vec<usize>) =
+                    (0..n).partition(|&i| assignment[i] == fold);
+                let decomposition = Standardized::new(&self.data.select_rows(&train))?;
+                for (k, &lambda) in lambdas.iter().enumerate() {
+                    let b = decomposition.coefficients(lambda);
+                    sse[k] += test
+                        .iter()
+                        .map(|&i| {
+                            let row = self.data.x().values.row(i);
+                            let prediction: f64 = row.iter().zip(&b).map(|(x, b)| x * b).sum();
+                            (y[i] - prediction).powi(2)
+                        })
+                        .sum::<f64>();
+                }
+            }
+        }
+        let total = (n * assignments.len()) as f64;
+        Ok(sse.into_iter().map(|s| s / total).collect())
+    }
+}
+
+impl<'a> FitModel for RidgeFitter<'a> {
+    /// Fit the ridge path and return the coefficients for the selected penalty, on the original
+    /// scale of x.
+    fn fit(&self) -> Result<RealMatrix, LmFitterError> {
+        Ok(self.path()?.selected_coefficients())
+    }
+
+    fn x(&self) -> &RealMatrix {
+        self.data.x()
+    }
+
+    fn y(&self) -> &RealMatrix {
+        self.data.y()
+    }
+}
+
+/// The standardised problem and the singular value decomposition that solves it for any
+/// penalty.
+struct Standardized {
+    standardization: Standardization,
+    /// The standardised, weighted response sqrt(w) (y - mean).
+    response: Vec<f64>,
+    /// The standardised, weighted penalised columns multiplied by the right singular vectors,
+    /// Z V = U D.
+    projected: RealMatrix,
+    /// The squared singular values d² of Z, the eigenvalues of Z'Z.
+    eigenvalues: Vec<f64>,
+    /// The right singular vectors V, one column per singular value.
+    eigenvectors: RealMatrix,
+    /// V' Z'y = D U'y.
+    rotated_xty: Vec<f64>,
+}
+
+impl Standardized {
+    fn new(data: &Data) -> Result<Self, LmFitterError> {
+        let standardization = Standardization::new(data)?;
+        let z = standardization.x(data.x(), true);
+        let response = standardization.y(data.y(), true);
+
+        let (u, singular_values, eigenvectors) = z.svd();
+        let mut projected = u;
+        for (mut column, d) in projected
+            .values
+            .columns_mut()
+            .into_iter()
+            .zip(&singular_values)
+        {
+            column *= *d;
+        }
+        let eigenvalues: Vec<f64> = singular_values.iter().map(|d| d * d).collect();
+        let rotated_xty = (0..eigenvalues.len())
+            .map(|k| {
+                projected
+                    .values
+                    .column(k)
+                    .iter()
+                    .zip(&response)
+                    .map(|(a, b)| a * b)
+                    .sum()
+            })
+            .collect();
+
+        Ok(Standardized {
+            standardization,
+            response,
+            projected,
+            eigenvalues,
+            eigenvectors,
+            rotated_xty,
+        })
+    }
+
+    /// Return 100 penalties spaced logarithmically from 10 d²_max down to 1e-6 d²_max.
+    fn default_lambdas(&self) -> Vec<f64> {
+        let largest = self.eigenvalues.first().copied().unwrap_or(1.0).max(1e-8);
+        let (start, end) = ((10.0 * largest).ln(), (1e-6 * largest).ln());
+        (0..DEFAULT_PATH_LENGTH)
+            .map(|k| (start + (end - start) * k as f64 / (DEFAULT_PATH_LENGTH - 1) as f64).exp())
+            .collect()
+    }
+
+    /// Return the standardised coefficients V (D² + λ)^-1 V' Z'y.
+    fn standardized_coefficients(&self, lambda: f64) -> Vec<f64> {
+        let m = self.eigenvalues.len();
+        let shrunk: Vec<f64> = (0..m)
+            .map(|k| self.ratio(k, self.rotated_xty[k], lambda))
+            .collect();
+        (0..self.eigenvectors.n_rows())
+            .map(|a| {
+                (0..m)
+                    .map(|k| self.eigenvectors.values[[a, k]] * shrunk[k])
+                    .sum()
+            })
+            .collect()
+    }
+
+    /// Return `value / (d²_k + λ)`, treating directions with no variance as unfitted.
+    fn ratio(&self, k: usize, value: f64, lambda: f64) -> f64 {
+        let denominator = self.eigenvalues[k] + lambda;
+        if denominator > 0.0 {
+            value / denominator
+        } else {
+            0.0
+        }
+    }
+
+    /// Return the coefficients on the original scale, with the intercept in its own column.
+    fn coefficients(&self, lambda: f64) -> Vec<f64> {
+        self.standardization
+            .original_coefficients(&self.standardized_coefficients(lambda))
+    }
+
+    /// Return the effective degrees of freedom sum d² / (d² + λ) of the penalised columns.
+    fn df(&self, lambda: f64) -> f64 {
+        (0..self.eigenvalues.len())
+            .map(|k| self.ratio(k, self.eigenvalues[k], lambda))
+            .sum()
+    }
+
+    /// Return the weighted residuals sqrt(w) (y - fitted).
+    fn residuals(&self, lambda: f64) -> Vec<f64> {
+        let shrunk: Vec<f64> = (0..self.eigenvalues.len())
+            .map(|k| self.ratio(k, self.rotated_xty[k], lambda))
+            .collect();
+        self.response
+            .iter()
+            .enumerate()
+            .map(|(i, r)| {
+                let row = self.projected.values.row(i);
+                r - row.iter().zip(&shrunk).map(|(a, b)| a * b).sum::<f64>()
+            })
+            .collect()
+    }
+
+    /// Return w / sum(w), the leverage of observation `i` on the intercept.
+    fn intercept_leverage(&self, i: usize) -> f64 {
+        let s = &self.standardization;
+        match s.intercept {
+            Some(_) => s.weights[i] / s.total_weight,
+            None => 0.0,
+        }
+    }
+
+    fn gcv(&self, lambda: f64) -> f64 {
+        let n = self.response.len() as f64;
+        let rss: f64 = self.residuals(lambda).iter().map(|r| r * r).sum();
+        let df = self.df(lambda) + self.standardization.intercept.map_or(0.0, |_| 1.0);
+        (rss / n) / (1.0 - df / n).powi(2)
+    }
+
+    /// Return the mean of the squared leave-one-out residuals e / (1 - h), where the leverage h
+    /// is that of the intercept plus sum (Z V)² / (d² + λ).
+    fn leave_one_out(&self, lambda: f64) -> f64 {
+        let residuals = self.residuals(lambda);
+        let n = residuals.len();
+        (0..n)
+            .map(|i| {
+                let h = self.intercept_leverage(i)
+                    + (0..self.eigenvalues.len())
+                        .map(|k| self.ratio(k, self.projected.values[[i, k]].powi(2), lambda))
+                        .sum::<f64>();
+                (residuals[i] / (1.0 - h)).powi(2)
+            })
+            .sum::<f64>()
+            / n as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fitters::fit::LinearModelFitter;
+    use crate::fitters::qr_decomposition_fitter::QrDecompositionFitter;
+
+    fn data() -> Data {
+        let n = 20;
+        let x: Vec<f64> = (0..n)
+            .flat_map(|i| {
+                let t = i as f64;
+                [1.0, t, t + (t * 0.7).sin(), (t * 1.9).cos()]
+            })
+            .collect();
+        let y: Vec<f64> = (0..n)
+            .map(|i| {
+                let t = i as f64;
+                3.0 + 0.4 * t + 2.0 * (t * 1.9).cos() + 0.3 * (t * 2.3).sin()
+            })
+            .collect();
+        Data::new(
+            RealMatrix::from_vec(x, n, Some(4)),
+            RealMatrix::from_vec(y, n, None),
+        )
+        .with_column_names(
+            ["(Intercept)", "a", "b", "c"]
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_ridge_without_penalty_matches_ols() {
+        let data = data();
+        let ols = QrDecompositionFitter::new(&data, None).fit().unwrap();
+        let ridge = RidgeFitter::new(&data, Some(vec![0.0]), LambdaSelection::Gcv)
+            .fit()
+            .unwrap();
+
+        for j in 0..4 {
+            assert!((ols.values[[j, 0]] - ridge.values[[j, 0]]).abs() < 1e-8);
+        }
+
+        let empty = RidgeFitter::new(&data, Some(vec![]), LambdaSelection::Gcv);
+        assert!(matches!(empty.path(), Err(LmFitterError::NoPenalties)));
+    }
+
+    #[test]
+    fn test_ridge_matches_the_normal_equations() {
+        let data = data();
+        let lambda = 3.0;
+        let path = RidgeFitter::new(&data, Some(vec![lambda]), LambdaSelection::Gcv)
+            .path()
+            .unwrap();
+
+        // Solve (Z'Z + λI) b = Z'y directly on the standardised columns.
+        let s = Standardized::new(&data).unwrap();
+        let (response, s) = (s.response, s.standardization);
+        let mut z = RealMatrix::with_shape(20, 3);
+        for (a, &j) in s.columns.iter().enumerate() {
+            for i in 0..20 {
+                z.values[[i, a]] = (data.x().values[[i, j]] - s.means[a]) / s.scales[a];
+            }
+        }
+        let mut ztz = z.transpose().dot(&z);
+        for a in 0..3 {
+            ztz.values[[a, a]] += lambda;
+        }
+        let zty = z.transpose().dot(&RealMatrix::from_vec(response, 20, None));
+        let b = ztz.inverse().unwrap().dot(&zty);
+        for a in 0..3 {
+            let expected = b.values[[a, 0]] / s.scales[a];
+            assert!((path.coefficients.values[[s.columns[a], 0]] - expected).abs() < 1e-10);
+        }
+        assert!(path.df() < 3.0 && path.df() > 0.0);
+    }
+
+    #[test]
+    fn test_nearly_collinear_columns_match_the_augmented_qr() {
+        // b differs from a by 1e-6 sin(1.3 t), so Z'Z has condition number around 1e14.
+        let n = 20;
+        let x: Vec<f64> = (0..n)
+            .flat_map(|i| {
+                let t = i as f64;
+                [1.0, t, t + 1e-6 * (1.3 * t).sin()]
+            })
+            .collect();
+        let y: Vec<f64> = (0..n)
+            .map(|i| 1.0 + (0.8 * i as f64).cos() + 0.1 * i as f64)
+            .collect();
+        let data = Data::new(
+            RealMatrix::from_vec(x, n, Some(3)),
+            RealMatrix::from_vec(y, n, None),
+        )
+        .with_column_names(["(Intercept)", "a", "b"].map(String::from).to_vec());
+
+        // Solve [Z; sqrt(λ) I] b = [y; 0] by QR as the reference.
+        let lambda: f64 = 1e-9;
+        let s = Standardized::new(&data).unwrap();
+        let z = s.standardization.x(data.x(), true);
+        let mut augmented = RealMatrix::with_shape(n + 2, 2);
+        let mut response = RealMatrix::with_shape(n + 2, 1);
+        for i in 0..n {
+            for a in 0..2 {
+                augmented.values[[i, a]] = z.values[[i, a]];
+            }
+            response.values[[i, 0]] = s.response[i];
+        }
+        for a in 0..2 {
+            augmented.values[[n + a, a]] = lambda.sqrt();
+        }
+        let tol = crate::types::Tolerance::default();
+        let reference = crate::least_squares::dqrls(&augmented, &response, &tol).unwrap();
+        let b = s.standardized_coefficients(lambda);
+        for (a, coefficient) in b.iter().enumerate() {
+            let expected = reference.coefficients.values[[a, 0]];
+            assert!((coefficient - expected).abs() < 1e-6 * expected.abs());
+        }
+    }
+
+    #[test]
+    fn test_penalty_selection_through_the_fitter_enum() {
+        let data = data();
+        for selection in [
+            LambdaSelection::Gcv,
+            LambdaSelection::LeaveOneOut,
+            LambdaSelection::KFold(CrossValidation::new(5).unwrap().with_seed(3)),
+        ] {
+            let fitter = RidgeFitter::new(&data, None, selection);
+            let path = fitter.path().unwrap();
+            assert_eq!(path.lambdas.len(), DEFAULT_PATH_LENGTH);
+            assert!(path.effective_df.windows(2).all(|w| w[0] <= w[1]));
+
+            let coefficients = LinearModelFitter::Ridge(fitter).fit().unwrap();
+            assert_eq!(coefficients, path.selected_coefficients());
+        }
+    }
+}
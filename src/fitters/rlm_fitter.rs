@@ -0,0 +1,408 @@
+//! This module contains the robust M-estimation fitter, which implements the `FitModel` trait for
+//! the `LinearModelFitter` enum, following R's `MASS::rlm`.
+//!
+//! Starting from the least squares fit, each iteration re-estimates the scale of the residuals,
+//! turns the scaled residuals into weights with the psi function of the estimator, and solves
+//! the weighted least squares problem again with the QR solver. The iterations stop when the
+//! relative change in the residuals falls below the tolerance, the default `test.vec = "resid"`
+//! criterion of `rlm`.
+
+// src/fitters/rlm_fitter.rs
+
+use super::fit::FitModel;
+use crate::distributions::{normal_cdf, normal_density};
+use crate::errors::LmFitterError;
+use crate::least_squares::{dqrls, LeastSquaresFit};
+use crate::types::{Data, RealMatrix, Tolerance};
+use derive_builder::Builder;
+
+/// An enum representing the psi functions of the M-estimators.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Psi {
+    /// Huber's psi, linear up to `k` and constant beyond.
+    Huber { k: f64 },
+    /// Hampel's three-part redescending psi.
+    Hampel { a: f64, b: f64, c: f64 },
+    /// Tukey's bisquare, which gives zero weight to residuals beyond `c`.
+    Bisquare { c: f64 },
+}
+
+impl Default for Psi {
+    /// Huber's psi with `k = 1.345`, the default of `rlm`.
+    fn default() -> Self {
+        Psi::Huber { k: 1.345 }
+    }
+}
+
+impl Psi {
+    /// Hampel's psi with the default `a = 2`, `b = 4`, `c = 8` of `psi.hampel`.
+    pub fn hampel() -> Self {
+        Psi::Hampel {
+            a: 2.0,
+            b: 4.0,
+            c: 8.0,
+        }
+    }
+
+    /// Tukey's bisquare with `c = 4.685`, which is 95% efficient at the normal.
+    pub fn bisquare() -> Self {
+        Psi::Bisquare { c: 4.685 }
+    }
+
+    /// Return psi(u), the derivative of the loss at a scaled residual `u`.
+    pub fn psi(&self, u: f64) -> f64 {
+        u * self.weight(u)
+    }
+
+    /// Return psi'(u), the slope of the psi function at a scaled residual `u`.
+    pub fn derivative(&self, u: f64) -> f64 {
+        let u = u.abs();
+        match *self {
+            Psi::Huber { k } => {
+                if u <= k {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+            Psi::Hampel { a, b, c } => {
+                if u <= a {
+                    1.0
+                } else if u <= b {
+                    0.0
+                } else if u <= c {
+                    -a / (c - b)
+                } else {
+                    0.0
+                }
+            }
+            Psi::Bisquare { c } => {
+                if u < c {
+                    let t = (u / c).powi(2);
+                    (1.0 - t) * (1.0 - 5.0 * t)
+                } else {
+                    0.0
+                }
+            }
+        }
+    }
+
+    /// Return the weight psi(u) / u of a scaled residual `u`.
+    pub fn weight(&self, u: f64) -> f64 {
+        let u = u.abs();
+        match *self {
+            Psi::Huber { k } => {
+                if u <= k {
+                    1.0
+                } else {
+                    k / u
+                }
+            }
+            Psi::Hampel { a, b, c } => {
+                if u <= a {
+                    1.0
+                } else if u <= b {
+                    a / u
+                } else if u <= c {
+                    a * (c - u) / ((c - b) * u)
+                } else {
+                    0.0
+                }
+            }
+            Psi::Bisquare { c } => {
+                if u < c {
+                    (1.0 - (u / c).powi(2)).powi(2)
+                } else {
+                    0.0
+                }
+            }
+        }
+    }
+}
+
+/// An enum representing the estimators of the scale of the residuals.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ScaleEstimator {
+    /// The median absolute residual divided by 0.6745, re-estimated at every iteration.
+    #[default]
+    Mad,
+    /// Huber's proposal 2: the scale s solving sum min(r², (k s)²) = (n - p) E[min(Z², k²)].
+    HuberProposal2 { k: f64 },
+}
+
+impl ScaleEstimator {
+    /// Huber's proposal 2 with `k = 1.345`.
+    pub fn huber_proposal_2() -> Self {
+        ScaleEstimator::HuberProposal2 { k: 1.345 }
+    }
+}
+
+/// The result of an M-estimation fit, with its convergence diagnostics.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RlmFit {
+    /// The coefficients, `NaN` for aliased columns.
+    pub coefficients: RealMatrix,
+    /// The residuals y - X b.
+    pub residuals: Vec<f64>,
+    /// The final robustness weights psi(r / s) / (r / s), not including prior weights.
+    pub weights: Vec<f64>,
+    /// The final scale estimate.
+    pub scale: f64,
+    /// The number of weighted least squares fits after the initial one.
+    pub iterations: usize,
+    /// `true` if the convergence criterion fell below the tolerance.
+    pub converged: bool,
+    /// The convergence criterion after each iteration.
+    pub convergence: Vec<f64>,
+}
+
+#[derive(Debug, Builder)]
+pub struct RlmFitter<'a> {
+    pub data: &'a Data,
+    #[builder(default)]
+    pub psi: Psi,
+    #[builder(default)]
+    pub scale_estimator: ScaleEstimator,
+    /// The maximum number of iterations.
+    #[builder(default = "20")]
+    pub max_iter: usize,
+    /// The convergence tolerance on the relative change in the residuals.
+    #[builder(default = "1e-4")]
+    pub acc: f64,
+}
+
+impl<'a> RlmFitter<'a> {
+    /// Return a new instance of the `RlmFitter` struct, with the limits of `rlm` (20 iterations
+    /// and a tolerance of 1e-4).
+    pub fn new(data: &'a Data, psi: Psi, scale_estimator: ScaleEstimator) -> Self {
+        Self {
+            data,
+            psi,
+            scale_estimator,
+            max_iter: 20,
+            acc: 1e-4,
+        }
+    }
+
+    /// Return the x matrix from the data struct.
+    pub fn x(&self) -> &RealMatrix {
+        &self.data.x
+    }
+
+    /// Return the y matrix from the data struct.
+    pub fn y(&self) -> &RealMatrix {
+        &self.data.y
+    }
+
+    /// Run the iteratively reweighted least squares algorithm from the least squares start.
+    ///
+    /// # Errors
+    /// Returns `LmFitterError::MultipleResponses` if the data has more than one response, and
+    /// `LmFitterError::LeastSquares` if a weighted fit fails.
+    pub fn irls(&self) -> Result<RlmFit, LmFitterError> {
+        if self.data.y().n_cols() != 1 {
+            return Err(LmFitterError::MultipleResponses {
+                found: self.data.y().n_cols(),
+            });
+        }
+        let (x, y) = (self.data.x(), self.data.y());
+        let n = x.n_rows();
+        let prior: Vec<f64> = self
+            .data
+            .weights()
+            .map_or_else(|| vec![1.0; n], |weights| weights.to_vec());
+
+        let (start, mut residuals) = weighted_fit(x, y, &prior)?;
+        let mut coefficients = start.coefficients;
+        let n1 = prior.iter().sum::<f64>() - start.qr.rank as f64;
+        let mut scale = weighted_median(
+            &residuals.iter().map(|r| r.abs()).collect::<Vec<f64>>(),
+            &prior,
+        ) / 0.6745;
+        let mut weights = vec![1.0; n];
+        let mut convergence = Vec::new();
+        let mut converged = false;
+
+        for _ in 0..self.max_iter {
+            scale = match self.scale_estimator {
+                ScaleEstimator::Mad => {
+                    let absolute: Vec<f64> = residuals.iter().map(|r| r.abs()).collect();
+                    weighted_median(&absolute, &prior) / 0.6745
+                }
+                ScaleEstimator::HuberProposal2 { k } => {
+                    let gamma = (2.0 * normal_cdf(k) - 1.0) - 2.0 * k * normal_density(k)
+                        + 2.0 * k * k * (1.0 - normal_cdf(k));
+                    let bound = (k * scale).powi(2);
+                    let sum: f64 = residuals
+                        .iter()
+                        .zip(&prior)
+                        .map(|(r, w)| w * (r * r).min(bound))
+                        .sum();
+                    (sum / (n1 * gamma)).sqrt()
+                }
+            };
+            if scale == 0.0 {
+                converged = true;
+                break;
+            }
+
+            weights = residuals
+                .iter()
+                .map(|r| self.psi.weight(r / scale))
+                .collect();
+            let combined: Vec<f64> = weights.iter().zip(&prior).map(|(w, p)| w * p).collect();
+            let previous = residuals;
+            let (fit, updated) = weighted_fit(x, y, &combined)?;
+            (coefficients, residuals) = (fit.coefficients, updated);
+
+            let criterion = relative_change(&previous, &residuals);
+            convergence.push(criterion);
+            if criterion <= self.acc {
+                converged = true;
+                break;
+            }
+        }
+
+        Ok(RlmFit {
+            coefficients,
+            residuals,
+            weights,
+            scale,
+            iterations: convergence.len(),
+            converged,
+            convergence,
+        })
+    }
+}
+
+impl<'a> FitModel for RlmFitter<'a> {
+    /// Fit the M-estimator and return its coefficients. A fit that did not converge within
+    /// `max_iter` iterations still returns its last coefficients; use `irls` to check.
+    fn fit(&self) -> Result<RealMatrix, LmFitterError> {
+        Ok(self.irls()?.coefficients)
+    }
+
+    fn x(&self) -> &RealMatrix {
+        self.data.x()
+    }
+
+    fn y(&self) -> &RealMatrix {
+        self.data.y()
+    }
+}
+
+/// Solve the least squares problem with the rows scaled by sqrt(w), returning the fit to the
+/// scaled problem and the residuals y - X b on the original scale. Zero weights are allowed.
+pub(crate) fn weighted_fit(
+    x: &RealMatrix,
+    y: &RealMatrix,
+    weights: &[f64],
+) -> Result<(LeastSquaresFit, Vec<f64>), LmFitterError> {
+    let mut xw = x.clone();
+    let mut yw = y.clone();
+    for (i, w) in weights.iter().enumerate() {
+        let root = w.sqrt();
+        xw.values.row_mut(i).mapv_inplace(|v| v * root);
+        yw.values.row_mut(i).mapv_inplace(|v| v * root);
+    }
+    let fit = dqrls(&xw, &yw, &Tolerance::default())?;
+    let estimable = fit
+        .coefficients
+        .values
+        .mapv(|b| if b.is_nan() { 0.0 } else { b });
+    let fitted = x.values.dot(&estimable);
+    let residuals = (0..x.n_rows())
+        .map(|i| y.values[[i, 0]] - fitted[[i, 0]])
+        .collect();
+    Ok((fit, residuals))
+}
+
+/// Return the median of `values` with observation weights, the smallest value whose cumulative
+/// weight reaches half of the total.
+pub(crate) fn weighted_median(values: &[f64], weights: &[f64]) -> f64 {
+    let mut order: Vec<usize> = (0..values.len()).collect();
+    order.sort_by(|&a, &b| values[a].total_cmp(&values[b]));
+    let half = weights.iter().sum::<f64>() / 2.0;
+    let mut cumulative = 0.0;
+    for (position, &i) in order.iter().enumerate() {
+        cumulative += weights[i];
+        if cumulative > half {
+            return values[i];
+        }
+        if cumulative == half {
+            // An exact split averages the two middle values, like the unweighted median.
+            return match order.get(position + 1) {
+                Some(&next) => (values[i] + values[next]) / 2.0,
+                None => values[i],
+            };
+        }
+    }
+    f64::NAN
+}
+
+/// Return sqrt(sum (old - new)²) / sqrt(sum old²), the `irls.delta` convergence criterion of
+/// `rlm` applied to the residuals.
+pub(crate) fn relative_change(old: &[f64], new: &[f64]) -> f64 {
+    let change: f64 = old.iter().zip(new).map(|(a, b)| (a - b).powi(2)).sum();
+    let size: f64 = old.iter().map(|a| a * a).sum();
+    (change / size.max(1e-20)).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fitters::fit::LinearModelFitter;
+    use crate::fitters::qr_decomposition_fitter::QrDecompositionFitter;
+
+    /// y = 1 + 2 t plus small noise, with two gross outliers.
+    fn contaminated() -> Data {
+        let n = 20;
+        let x: Vec<f64> = (0..n).flat_map(|i| [1.0, i as f64]).collect();
+        let y: Vec<f64> = (0..n)
+            .map(|i| {
+                let t = i as f64;
+                let outlier = if i == 3 || i == 17 { 40.0 } else { 0.0 };
+                1.0 + 2.0 * t + 0.3 * (t * 1.7).sin() + outlier
+            })
+            .collect();
+        Data::new(
+            RealMatrix::from_vec(x, n, Some(2)),
+            RealMatrix::from_vec(y, n, None),
+        )
+    }
+
+    #[test]
+    fn test_psi_weights() {
+        assert_eq!(Psi::default().weight(1.0), 1.0);
+        assert!((Psi::default().weight(-2.69) - 0.5).abs() < 1e-12);
+        assert!((Psi::hampel().weight(6.0) - 2.0 * 2.0 / (4.0 * 6.0)).abs() < 1e-12);
+        assert_eq!(Psi::hampel().weight(9.0), 0.0);
+        assert_eq!(Psi::bisquare().weight(5.0), 0.0);
+        assert_eq!(weighted_median(&[3.0, 1.0, 2.0, 4.0], &[1.0; 4]), 2.5);
+    }
+
+    #[test]
+    fn test_m_estimators_resist_outliers() {
+        let data = contaminated();
+        let ols = QrDecompositionFitter::new(&data, None).fit().unwrap();
+        let ols_error = (ols.values[[1, 0]] - 2.0).abs();
+
+        for (psi, scale) in [
+            (Psi::default(), ScaleEstimator::Mad),
+            (Psi::default(), ScaleEstimator::huber_proposal_2()),
+            (Psi::hampel(), ScaleEstimator::Mad),
+            (Psi::bisquare(), ScaleEstimator::Mad),
+        ] {
+            let fit = RlmFitter::new(&data, psi, scale).irls().unwrap();
+            assert!(fit.converged);
+            assert!((fit.coefficients.values[[1, 0]] - 2.0).abs() < ols_error / 5.0);
+            assert!(fit.weights[3] < 0.2 && fit.weights[17] < 0.2);
+            assert!(fit.weights[0] > 0.5);
+        }
+
+        let bisquare = RlmFitter::new(&data, Psi::bisquare(), ScaleEstimator::Mad);
+        assert_eq!(bisquare.irls().unwrap().weights[3], 0.0);
+        let coefficients = LinearModelFitter::Rlm(bisquare).fit().unwrap();
+        assert!((coefficients.values[[0, 0]] - 1.0).abs() < 0.5);
+    }
+}
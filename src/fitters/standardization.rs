@@ -0,0 +1,126 @@
+//! This module standardises the data for the penalised fitters. The columns of x other than the
+//! intercept are centred (when there is an intercept) and scaled to unit weighted variance, with
+//! variances divided by the total weight as in `glmnet`, so that penalties treat every column
+//! alike. Columns with no variance are left out and get a zero coefficient.
+
+// src/fitters/standardization.rs
+
+use crate::errors::LmFitterError;
+use crate::types::{Data, RealMatrix};
+
+/// The centring and scaling of the columns of x and of y.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Standardization {
+    /// The number of columns of x.
+    pub n_cols: usize,
+    /// The column of x holding the intercept, if any.
+    pub intercept: Option<usize>,
+    /// The penalised columns of x with non-zero variance.
+    pub columns: Vec<usize>,
+    pub means: Vec<f64>,
+    pub scales: Vec<f64>,
+    pub y_mean: f64,
+    /// The observation weights, all 1 when the data has none.
+    pub weights: Vec<f64>,
+    pub total_weight: f64,
+}
+
+impl Standardization {
+    /// Compute the standardisation of `data`. The intercept is the first column assigned to term
+    /// `0` of `data.terms()`.
+    ///
+    /// # Errors
+    /// Returns `LmFitterError::MultipleResponses` if the data has more than one response.
+    pub fn new(data: &Data) -> Result<Self, LmFitterError> {
+        if data.y().n_cols() != 1 {
+            return Err(LmFitterError::MultipleResponses {
+                found: data.y().n_cols(),
+            });
+        }
+        let x = data.x();
+        let n = x.n_rows();
+        let weights: Vec<f64> = data
+            .weights()
+            .map_or_else(|| vec![1.0; n], |weights| weights.to_vec());
+        let total_weight: f64 = weights.iter().sum();
+        let terms = data.terms();
+        let intercept = (0..x.n_cols()).find(|&j| terms.assign[j] == 0);
+
+        // Without an intercept the columns are scaled but not centred, like glmnet.
+        let mean = |values: ndarray::ArrayView1<f64>| match intercept {
+            Some(_) => values.iter().zip(&weights).map(|(v, w)| v * w).sum::<f64>() / total_weight,
+            None => 0.0,
+        };
+        let mut columns = Vec::new();
+        let mut means = Vec::new();
+        let mut scales = Vec::new();
+        for j in (0..x.n_cols()).filter(|&j| terms.assign[j] != 0) {
+            let column = x.values.column(j);
+            let m = mean(column);
+            let variance = column
+                .iter()
+                .zip(&weights)
+                .map(|(v, w)| w * (v - m).powi(2))
+                .sum::<f64>()
+                / total_weight;
+            if variance > 0.0 {
+                columns.push(j);
+                means.push(m);
+                scales.push(variance.sqrt());
+            }
+        }
+
+        Ok(Standardization {
+            n_cols: x.n_cols(),
+            intercept,
+            columns,
+            means,
+            scales,
+            y_mean: mean(data.y().values.column(0)),
+            weights,
+            total_weight,
+        })
+    }
+
+    /// Return the standardised columns (x - mean) / scale, with each row multiplied by sqrt(w)
+    /// when `weighted` is set.
+    pub fn x(&self, x: &RealMatrix, weighted: bool) -> RealMatrix {
+        let mut z = RealMatrix::with_shape(x.n_rows(), self.columns.len());
+        for (a, &j) in self.columns.iter().enumerate() {
+            for (i, w) in self.weights.iter().enumerate() {
+                let scale = if weighted { w.sqrt() } else { 1.0 };
+                z.values[[i, a]] = scale * (x.values[[i, j]] - self.means[a]) / self.scales[a];
+            }
+        }
+        z
+    }
+
+    /// Return the centred response y - mean, multiplied by sqrt(w) when `weighted` is set.
+    pub fn y(&self, y: &RealMatrix, weighted: bool) -> Vec<f64> {
+        y.values
+            .column(0)
+            .iter()
+            .zip(&self.weights)
+            .map(|(y, w)| {
+                let scale = if weighted { w.sqrt() } else { 1.0 };
+                scale * (y - self.y_mean)
+            })
+            .collect()
+    }
+
+    /// Map coefficients of the standardised columns back to the original scale, with the
+    /// intercept in its own column of x.
+    pub fn original_coefficients(&self, standardized: &[f64]) -> Vec<f64> {
+        let mut coefficients = vec![0.0; self.n_cols];
+        let mut intercept = self.y_mean;
+        for (a, b) in standardized.iter().enumerate() {
+            let b = b / self.scales[a];
+            coefficients[self.columns[a]] = b;
+            intercept -= self.means[a] * b;
+        }
+        if let Some(j) = self.intercept {
+            coefficients[j] = intercept;
+        }
+        coefficients
+    }
+}
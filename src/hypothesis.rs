@@ -0,0 +1,805 @@
+//! This module contains Wald tests of general linear hypotheses L b = c about the coefficients
+//! of a fitted model, like `car::linearHypothesis`, and simultaneous tests of families of
+//! contrasts with multiplicity adjustments, like `multcomp::glht`.
+//!
+//! Hypotheses are given either as a matrix L with a right-hand side c, or as strings such as
+//! `"x1 = x2"` and `"x3 + 2*x4 = 1"` that are parsed against the coefficient names. Every test
+//! can use the classical covariance matrix or a heteroskedasticity-robust sandwich.
+//! `DiagnosticTest` holds the F and chi-squared specification tests that the
+//! instrumental-variables and panel data fitters report.
+
+// src/hypothesis.rs
+
+use crate::anova::{format_number, format_p_value, write_table};
+use crate::distributions::{chi_squared_upper_tail, f_upper_tail};
+use crate::errors::HypothesisError;
+use crate::linear_model::FittedLinearModel;
+use crate::types::RealMatrix;
+use crate::vcov::{vcov, CovarianceType};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use statrs::distribution::{ChiSquared, ContinuousCDF, Normal, StudentsT};
+use std::fmt;
+
+/// Return the Wald statistic (L b - c)' (L V L')^-1 (L b - c) of the hypothesis L b = c, or
+/// `None` if L V L' is singular.
+///
+/// # Arguments
+/// * `l` - The hypothesis matrix, with one row per restriction and one column per coefficient.
+/// * `coefficients` - The estimated coefficients b.
+/// * `vcov` - The covariance matrix V of the coefficients.
+/// * `rhs` - The right-hand side c of the hypothesis.
+pub fn wald_statistic(
+    l: &RealMatrix,
+    coefficients: &[f64],
+    vcov: &RealMatrix,
+    rhs: &[f64],
+) -> Option<f64> {
+    let b = RealMatrix::from_vec(coefficients.to_vec(), coefficients.len(), None);
+    let c = RealMatrix::from_vec(rhs.to_vec(), rhs.len(), None);
+    let discrepancy = l.dot(&b).minus(&c);
+    let middle = l.dot(vcov).dot(&l.transpose()).inverse()?;
+    Some(
+        discrepancy
+            .transpose()
+            .dot(&middle)
+            .dot(&discrepancy)
+            .values[[0, 0]],
+    )
+}
+
+/// A set of linear restrictions L b = c on the coefficients of a model.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LinearHypothesis {
+    /// The hypothesis matrix, with one row per restriction and one column per coefficient.
+    pub l: RealMatrix,
+    /// The right-hand side of each restriction.
+    pub rhs: Vec<f64>,
+    /// A readable description of each restriction, such as `x1 - x2 = 0`.
+    pub labels: Vec<String>,
+}
+
+impl LinearHypothesis {
+    /// Create a hypothesis from a matrix and a right-hand side.
+    ///
+    /// # Arguments
+    /// * `l` - The hypothesis matrix, with one column per coefficient.
+    /// * `rhs` - The right-hand side, with one element per row of `l`.
+    /// * `names` - The names of the coefficients, used to label the restrictions.
+    ///
+    /// # Errors
+    /// Returns `HypothesisError::DimensionMismatch` if `l` does not have one column per name, and
+    /// `HypothesisError::RhsMismatch` if `rhs` does not have one element per row of `l`.
+    pub fn new(l: RealMatrix, rhs: Vec<f64>, names: &[String]) -> Result<Self, HypothesisError> {
+        if l.n_cols() != names.len() {
+            return Err(HypothesisError::DimensionMismatch {
+                expected: names.len(),
+                found: l.n_cols(),
+            });
+        }
+        if l.n_rows() != rhs.len() {
+            return Err(HypothesisError::RhsMismatch {
+                expected: l.n_rows(),
+                found: rhs.len(),
+            });
+        }
+        let labels = (0..l.n_rows())
+            .map(|i| {
+                let row: Vec<f64> = l.values.row(i).to_vec();
+                describe_restriction(&row, rhs[i], names)
+            })
+            .collect();
+        Ok(LinearHypothesis { l, rhs, labels })
+    }
+
+    /// Parse one restriction per string, such as `"x1 = x2"` or `"x3 + 2*x4 = 1"`. A string
+    /// without `=` is tested against zero.
+    ///
+    /// # Errors
+    /// Returns an error if a string cannot be parsed or names an unknown coefficient.
+    pub fn parse(specs: &[&str], names: &[String]) -> Result<Self, HypothesisError> {
+        let mut l = RealMatrix::with_shape(specs.len(), names.len());
+        let mut rhs = Vec::with_capacity(specs.len());
+        for (i, spec) in specs.iter().enumerate() {
+            let (lhs, right) = spec.split_once('=').unwrap_or((spec, "0"));
+            let mut constant = 0.0;
+            for (side, sign) in [(lhs, 1.0), (right, -1.0)] {
+                for (coefficient, name) in parse_side(side, spec)? {
+                    match name {
+                        Some(name) => {
+                            let j = names.iter().position(|n| n == name).ok_or_else(|| {
+                                HypothesisError::UnknownCoefficient {
+                                    name: name.to_string(),
+                                    spec: spec.to_string(),
+                                }
+                            })?;
+                            l.values[[i, j]] += sign * coefficient;
+                        }
+                        None => constant -= sign * coefficient,
+                    }
+                }
+            }
+            rhs.push(constant);
+        }
+        LinearHypothesis::new(l, rhs, names)
+    }
+
+    /// Create the family of all pairwise comparisons among the levels of a treatment-coded
+    /// factor, like the Tukey contrasts of `multcomp`. `columns` are the coefficients of the
+    /// non-baseline levels, so each one is also compared with the baseline.
+    pub fn pairwise(columns: &[usize], names: &[String]) -> Self {
+        let mut rows = Vec::new();
+        for (a, &j) in columns.iter().enumerate() {
+            rows.push(vec![(j, 1.0)]);
+            for &i in &columns[..a] {
+                rows.push(vec![(j, 1.0), (i, -1.0)]);
+            }
+        }
+        LinearHypothesis::from_sparse_rows(&rows, names)
+    }
+
+    /// Create the family of comparisons of each level of a treatment-coded factor with the
+    /// baseline level, like the Dunnett contrasts of `multcomp`.
+    pub fn versus_control(columns: &[usize], names: &[String]) -> Self {
+        let rows: Vec<Vec<(usize, f64)>> = columns.iter().map(|&j| vec![(j, 1.0)]).collect();
+        LinearHypothesis::from_sparse_rows(&rows, names)
+    }
+
+    /// Return the number of restrictions.
+    pub fn n_restrictions(&self) -> usize {
+        self.l.n_rows()
+    }
+
+    fn from_sparse_rows(rows: &[Vec<(usize, f64)>], names: &[String]) -> Self {
+        let mut l = RealMatrix::with_shape(rows.len(), names.len());
+        for (i, row) in rows.iter().enumerate() {
+            for &(j, value) in row {
+                l.values[[i, j]] = value;
+            }
+        }
+        LinearHypothesis::new(l, vec![0.0; rows.len()], names)
+            .expect("the rows are built from the coefficient names")
+    }
+}
+
+/// An enum representing the reference distributions of a Wald test.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum WaldTest {
+    /// The F test, using the residual degrees of freedom.
+    #[default]
+    F,
+    /// The asymptotic chi-squared test.
+    Chisq,
+}
+
+/// The result of a joint test of a linear hypothesis.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HypothesisTestResult {
+    /// The restrictions that were tested.
+    pub hypotheses: Vec<String>,
+    /// The reference distribution of the statistic.
+    pub test: WaldTest,
+    /// The number of restrictions.
+    pub df: usize,
+    /// The residual degrees of freedom of the model.
+    pub res_df: usize,
+    /// The F or chi-squared statistic.
+    pub statistic: f64,
+    /// The upper tail probability of the statistic.
+    pub p_value: f64,
+}
+
+impl fmt::Display for HypothesisTestResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Linear hypothesis test\n\nHypothesis:")?;
+        for hypothesis in &self.hypotheses {
+            writeln!(f, "{hypothesis}")?;
+        }
+        writeln!(f)?;
+        let header = match self.test {
+            WaldTest::F => ["Res.Df", "Df", "F", "Pr(>F)"],
+            WaldTest::Chisq => ["Res.Df", "Df", "Chisq", "Pr(>Chisq)"],
+        };
+        let cells = vec![vec![
+            self.res_df.to_string(),
+            self.df.to_string(),
+            format_number(self.statistic),
+            format_p_value(self.p_value),
+        ]];
+        write_table(f, &[""], &header, &cells)
+    }
+}
+
+/// A test statistic with its degrees of freedom and p-value. `df2` is `None` for a
+/// chi-squared test.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DiagnosticTest {
+    /// The F or chi-squared statistic.
+    pub statistic: f64,
+    /// The numerator degrees of freedom, or the degrees of freedom of a chi-squared test.
+    pub df1: usize,
+    /// The denominator degrees of freedom of an F test.
+    pub df2: Option<usize>,
+    /// The p-value.
+    pub p_value: f64,
+}
+
+impl DiagnosticTest {
+    pub(crate) fn f(statistic: f64, df1: usize, df2: usize) -> Self {
+        DiagnosticTest {
+            statistic,
+            df1,
+            df2: Some(df2),
+            p_value: f_upper_tail(statistic, df1 as f64, df2 as f64),
+        }
+    }
+
+    pub(crate) fn chi_squared(statistic: f64, df: usize) -> Self {
+        DiagnosticTest {
+            statistic,
+            df1: df,
+            df2: None,
+            p_value: chi_squared_upper_tail(statistic, df as f64),
+        }
+    }
+}
+
+/// Test the hypothesis L b = c jointly with a Wald test.
+///
+/// # Arguments
+/// * `model` - The fitted single-response model.
+/// * `hypothesis` - The restrictions to test.
+/// * `test` - Whether to use the F or the chi-squared reference distribution.
+/// * `covariance` - The covariance estimator of the coefficients.
+///
+/// # Errors
+/// Returns an error if the model has several responses, if the hypothesis involves aliased
+/// coefficients, or if the hypothesis is singular.
+pub fn linear_hypothesis(
+    model: &FittedLinearModel,
+    hypothesis: &LinearHypothesis,
+    test: WaldTest,
+    covariance: CovarianceType,
+) -> Result<HypothesisTestResult, HypothesisError> {
+    let estimates = Estimates::new(model, hypothesis, covariance)?;
+    let df = hypothesis.n_restrictions();
+    let statistic = wald_statistic(
+        &estimates.l,
+        &estimates.coefficients,
+        &estimates.vcov,
+        &hypothesis.rhs,
+    )
+    .ok_or(HypothesisError::SingularHypothesis)?;
+
+    let (statistic, p_value) = match test {
+        WaldTest::F => {
+            let f_value = statistic / df as f64;
+            let p_value = f_upper_tail(f_value, df as f64, estimates.res_df as f64);
+            (f_value, p_value)
+        }
+        WaldTest::Chisq => {
+            let p_value = chi_squared_upper_tail(statistic, df as f64);
+            (statistic, p_value)
+        }
+    };
+
+    Ok(HypothesisTestResult {
+        hypotheses: hypothesis.labels.clone(),
+        test,
+        df,
+        res_df: estimates.res_df,
+        statistic,
+        p_value,
+    })
+}
+
+/// An enum representing the multiplicity adjustments for a family of tests.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Adjustment {
+    /// Report the unadjusted p-values.
+    None,
+    /// Multiply each p-value by the number of tests.
+    Bonferroni,
+    /// Holm's step-down version of the Bonferroni adjustment.
+    #[default]
+    Holm,
+    /// Benjamini and Hochberg's control of the false discovery rate.
+    BenjaminiHochberg,
+    /// The single-step adjustment based on the joint multivariate t distribution of the
+    /// statistics, which gives Tukey's and Dunnett's procedures for their contrast families.
+    /// The tail probabilities are estimated by simulation with a seeded generator.
+    SingleStep { seed: u64, draws: usize },
+}
+
+/// The test of one contrast in a family of simultaneous tests.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContrastTest {
+    /// The restriction that was tested.
+    pub label: String,
+    /// The estimate of L b - c for the restriction.
+    pub estimate: f64,
+    /// The standard error of the estimate.
+    pub std_error: f64,
+    /// The t statistic of the estimate.
+    pub t_value: f64,
+    /// The adjusted two-sided p-value.
+    pub p_value: f64,
+}
+
+/// The simultaneous tests of a family of contrasts.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimultaneousTests {
+    /// The multiplicity adjustment applied to the p-values.
+    pub adjustment: Adjustment,
+    /// One test per restriction of the hypothesis.
+    pub rows: Vec<ContrastTest>,
+}
+
+impl fmt::Display for SimultaneousTests {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Simultaneous Tests for General Linear Hypotheses\n")?;
+        let header = ["Estimate", "Std. Error", "t value", "Pr(>|t|)"];
+        let cells: Vec<Vec<String>> = self
+            .rows
+            .iter()
+            .map(|row| {
+                vec![
+                    format_number(row.estimate),
+                    format_number(row.std_error),
+                    format_number(row.t_value),
+                    format_p_value(row.p_value),
+                ]
+            })
+            .collect();
+        let labels: Vec<&str> = self.rows.iter().map(|row| row.label.as_str()).collect();
+        write_table(f, &labels, &header, &cells)?;
+        writeln!(
+            f,
+            "(Adjusted p values reported -- {:?} method)",
+            self.adjustment
+        )
+    }
+}
+
+/// Test each restriction of a hypothesis separately with a t test, adjusting the p-values for
+/// the number of restrictions.
+///
+/// # Errors
+/// Returns an error if the model has several responses or if the hypothesis involves aliased
+/// coefficients, and `HypothesisError::NoDraws` for a single-step adjustment without draws.
+pub fn simultaneous_tests(
+    model: &FittedLinearModel,
+    hypothesis: &LinearHypothesis,
+    covariance: CovarianceType,
+    adjustment: Adjustment,
+) -> Result<SimultaneousTests, HypothesisError> {
+    if let Adjustment::SingleStep { draws: 0, .. } = adjustment {
+        return Err(HypothesisError::NoDraws);
+    }
+    let estimates = Estimates::new(model, hypothesis, covariance)?;
+    let b = RealMatrix::from_vec(
+        estimates.coefficients.clone(),
+        estimates.coefficients.len(),
+        None,
+    );
+    let lb = estimates.l.dot(&b);
+    let lvl = estimates
+        .l
+        .dot(&estimates.vcov)
+        .dot(&estimates.l.transpose());
+
+    let estimate: Vec<f64> = (0..hypothesis.n_restrictions())
+        .map(|i| lb.values[[i, 0]] - hypothesis.rhs[i])
+        .collect();
+    let std_error: Vec<f64> = (0..hypothesis.n_restrictions())
+        .map(|i| lvl.values[[i, i]].sqrt())
+        .collect();
+    let t_value: Vec<f64> = estimate
+        .iter()
+        .zip(&std_error)
+        .map(|(e, s)| e / s)
+        .collect();
+
+    let res_df = estimates.res_df as f64;
+    let p_value = match adjustment {
+        Adjustment::SingleStep { seed, draws } => {
+            single_step_p_values(&estimates, &t_value, &std_error, res_df, seed, draws)?
+        }
+        _ => {
+            let t = StudentsT::new(0.0, 1.0, res_df)
+                .map_err(|_| HypothesisError::SingularHypothesis)?;
+            let raw: Vec<f64> = t_value.iter().map(|t_i| 2.0 * t.sf(t_i.abs())).collect();
+            p_adjust(&raw, adjustment)
+        }
+    };
+
+    let rows = (0..hypothesis.n_restrictions())
+        .map(|i| ContrastTest {
+            label: hypothesis.labels[i].clone(),
+            estimate: estimate[i],
+            std_error: std_error[i],
+            t_value: t_value[i],
+            p_value: p_value[i],
+        })
+        .collect();
+    Ok(SimultaneousTests { adjustment, rows })
+}
+
+/// Adjust a family of p-values for multiple comparisons, like R's `p.adjust`. The single-step
+/// adjustment needs the joint distribution of the statistics, so it leaves the p-values
+/// unadjusted here; use `simultaneous_tests` for it.
+pub fn p_adjust(p_values: &[f64], adjustment: Adjustment) -> Vec<f64> {
+    let m = p_values.len();
+    let mut order: Vec<usize> = (0..m).collect();
+    order.sort_by(|&a, &b| p_values[a].total_cmp(&p_values[b]));
+    let mut adjusted = vec![0.0; m];
+
+    match adjustment {
+        Adjustment::None | Adjustment::SingleStep { .. } => return p_values.to_vec(),
+        Adjustment::Bonferroni => {
+            for (a, &p) in adjusted.iter_mut().zip(p_values) {
+                *a = (p * m as f64).min(1.0);
+            }
+        }
+        Adjustment::Holm => {
+            let mut running_max: f64 = 0.0;
+            for (rank, &i) in order.iter().enumerate() {
+                running_max = running_max.max((p_values[i] * (m - rank) as f64).min(1.0));
+                adjusted[i] = running_max;
+            }
+        }
+        Adjustment::BenjaminiHochberg => {
+            let mut running_min: f64 = 1.0;
+            for (rank, &i) in order.iter().enumerate().rev() {
+                running_min = running_min.min(p_values[i] * m as f64 / (rank + 1) as f64);
+                adjusted[i] = running_min;
+            }
+        }
+    }
+    adjusted
+}
+
+/// The coefficients, their covariance and the hypothesis matrix, restricted to the coefficients
+/// that are not aliased.
+struct Estimates {
+    l: RealMatrix,
+    coefficients: Vec<f64>,
+    vcov: RealMatrix,
+    res_df: usize,
+}
+
+impl Estimates {
+    fn new(
+        model: &FittedLinearModel,
+        hypothesis: &LinearHypothesis,
+        covariance: CovarianceType,
+    ) -> Result<Self, HypothesisError> {
+        let (x, y) = (model.data.x(), model.data.y());
+        if y.n_cols() != 1 {
+            return Err(HypothesisError::MultipleResponses { found: y.n_cols() });
+        }
+        if hypothesis.l.n_cols() != x.n_cols() {
+            return Err(HypothesisError::DimensionMismatch {
+                expected: x.n_cols(),
+                found: hypothesis.l.n_cols(),
+            });
+        }
+
+        let fit = model.least_squares()?;
+        let v = vcov(&fit, &model.data.weighted_x(), 0, covariance);
+        let mut retained: Vec<usize> = fit.qr.pivot[..fit.rank()].to_vec();
+        retained.sort_unstable();
+        for &j in &fit.qr.pivot[fit.rank()..] {
+            if hypothesis
+                .l
+                .values
+                .column(j)
+                .iter()
+                .any(|&value| value != 0.0)
+            {
+                return Err(HypothesisError::AliasedCoefficient {
+                    name: model.data.column_names()[j].clone(),
+                });
+            }
+        }
+
+        let select = |matrix: &RealMatrix, rows: &[usize], columns: &[usize]| {
+            let mut selected = RealMatrix::with_shape(rows.len(), columns.len());
+            for (a, &i) in rows.iter().enumerate() {
+                for (b, &j) in columns.iter().enumerate() {
+                    selected.values[[a, b]] = matrix.values[[i, j]];
+                }
+            }
+            selected
+        };
+        let all_rows: Vec<usize> = (0..hypothesis.n_restrictions()).collect();
+
+        Ok(Estimates {
+            l: select(&hypothesis.l, &all_rows, &retained),
+            coefficients: retained
+                .iter()
+                .map(|&j| fit.coefficients.values[[j, 0]])
+                .collect(),
+            vcov: select(&v, &retained, &retained),
+            res_df: x.n_rows() - fit.rank(),
+        })
+    }
+}
+
+/// Estimate the single-step adjusted p-values P(max_j |T_j| >= |t_i|) by simulating the joint
+/// multivariate t distribution of the statistics. Coefficient draws are taken from N(0, V) and
+/// mapped through L, so families with linearly dependent contrasts (like Tukey's) need no
+/// special handling.
+fn single_step_p_values(
+    estimates: &Estimates,
+    t_value: &[f64],
+    std_error: &[f64],
+    res_df: f64,
+    seed: u64,
+    draws: usize,
+) -> Result<Vec<f64>, HypothesisError> {
+    use rand::distributions::Distribution;
+
+    let chol = estimates
+        .vcov
+        .cholesky()
+        .ok_or(HypothesisError::SingularHypothesis)?;
+    let normal = Normal::new(0.0, 1.0).expect("the standard normal is valid");
+    let chisq = ChiSquared::new(res_df).map_err(|_| HypothesisError::SingularHypothesis)?;
+    let mut rng = StdRng::seed_from_u64(seed);
+
+    let k = estimates.coefficients.len();
+    let mut exceed = vec![0usize; t_value.len()];
+    for _ in 0..draws {
+        let u: Vec<f64> = (0..k).map(|_| normal.sample(&mut rng)).collect();
+        let z = chol.dot(&RealMatrix::from_vec(u, k, None));
+        let scale = (chisq.sample(&mut rng) / res_df).sqrt();
+        let lz = estimates.l.dot(&z);
+        let max_t = (0..t_value.len())
+            .map(|i| (lz.values[[i, 0]] / std_error[i] / scale).abs())
+            .fold(0.0, f64::max);
+        for (count, t_i) in exceed.iter_mut().zip(t_value) {
+            if max_t >= t_i.abs() {
+                *count += 1;
+            }
+        }
+    }
+    Ok(exceed
+        .into_iter()
+        .map(|count| count as f64 / draws as f64)
+        .collect())
+}
+
+/// Split one side of a restriction into signed terms, returning the coefficient of each term
+/// and the coefficient name it multiplies (`None` for a constant).
+fn parse_side<'a>(
+    side: &'a str,
+    spec: &str,
+) -> Result<Vec<(f64, Option<&'a str>)>, HypothesisError> {
+    let invalid = || HypothesisError::InvalidSpecification {
+        spec: spec.to_string(),
+    };
+
+    // Split at top-level signs, keeping signs that belong to an exponent such as `1e-3`.
+    let mut tokens: Vec<(f64, &str)> = Vec::new();
+    let (mut start, mut sign, mut depth) = (0, 1.0, 0i32);
+    for (i, c) in side.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            '+' | '-' if depth == 0 => {
+                let current = side[start..i].trim();
+                let in_exponent = (current.ends_with('e') || current.ends_with('E'))
+                    && current[..current.len() - 1].parse::<f64>().is_ok();
+                if in_exponent {
+                    continue;
+                }
+                if !current.is_empty() {
+                    tokens.push((sign, current));
+                    sign = 1.0;
+                }
+                if c == '-' {
+                    sign = -sign;
+                }
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    let last = side[start..].trim();
+    if last.is_empty() {
+        return Err(invalid());
+    }
+    tokens.push((sign, last));
+
+    tokens
+        .into_iter()
+        .map(|(sign, token)| {
+            let mut coefficient = sign;
+            let mut name = None;
+            let factors: Vec<&str> = if token.contains('*') {
+                token.split('*').map(str::trim).collect()
+            } else {
+                match token.split_once(char::is_whitespace) {
+                    Some((number, rest)) if number.parse::<f64>().is_ok() => {
+                        vec![number, rest.trim()]
+                    }
+                    _ => vec![token],
+                }
+            };
+            for factor in factors {
+                match factor.parse::<f64>() {
+                    Ok(value) => coefficient *= value,
+                    Err(_) if name.is_none() && !factor.is_empty() => name = Some(factor),
+                    Err(_) => return Err(invalid()),
+                }
+            }
+            Ok((coefficient, name))
+        })
+        .collect()
+}
+
+/// Describe one restriction as a readable equation, such as `x1 - 2*x2 = 1`.
+fn describe_restriction(row: &[f64], rhs: f64, names: &[String]) -> String {
+    let mut description = String::new();
+    for (value, name) in row.iter().zip(names).filter(|(value, _)| **value != 0.0) {
+        let magnitude = value.abs();
+        let term = if magnitude == 1.0 {
+            name.clone()
+        } else {
+            format!("{magnitude}*{name}")
+        };
+        match (description.is_empty(), *value < 0.0) {
+            (true, false) => description.push_str(&term),
+            (true, true) => description.push_str(&format!("- {term}")),
+            (false, false) => description.push_str(&format!(" + {term}")),
+            (false, true) => description.push_str(&format!(" - {term}")),
+        }
+    }
+    if description.is_empty() {
+        description.push('0');
+    }
+    format!("{description} = {rhs}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::least_squares::dqrls;
+    use crate::types::{Data, Tolerance};
+
+    fn names() -> Vec<String> {
+        ["(Intercept)", "x1", "x2", "x3"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect()
+    }
+
+    fn data() -> Data {
+        let x = RealMatrix::from_vec(
+            vec![
+                1.0, 1.0, 2.0, 0.5, //
+                1.0, 2.0, 1.0, 1.5, //
+                1.0, 3.0, 4.0, 0.0, //
+                1.0, 4.0, 3.0, 2.0, //
+                1.0, 5.0, 6.0, 1.0, //
+                1.0, 6.0, 5.0, 0.5, //
+                1.0, 7.0, 8.0, 2.5, //
+                1.0, 8.0, 7.0, 1.0,
+            ],
+            8,
+            Some(4),
+        );
+        let y = RealMatrix::from_vec(vec![1.2, 2.3, 2.9, 4.8, 5.1, 5.9, 7.4, 8.2], 8, None);
+        Data::new(x, y).with_column_names(names())
+    }
+
+    #[test]
+    fn test_parse_builds_the_hypothesis_matrix() {
+        let hypothesis =
+            LinearHypothesis::parse(&["x1 = x2", "x3 + 2*x1 = 1", "-1e-1 x3"], &names()).unwrap();
+
+        assert_eq!(
+            hypothesis.l.values.row(0).to_vec(),
+            vec![0.0, 1.0, -1.0, 0.0]
+        );
+        assert_eq!(
+            hypothesis.l.values.row(1).to_vec(),
+            vec![0.0, 2.0, 0.0, 1.0]
+        );
+        assert_eq!(
+            hypothesis.l.values.row(2).to_vec(),
+            vec![0.0, 0.0, 0.0, -0.1]
+        );
+        assert_eq!(hypothesis.rhs, vec![0.0, 1.0, 0.0]);
+        assert_eq!(hypothesis.labels[0], "x1 - x2 = 0");
+
+        assert!(matches!(
+            LinearHypothesis::parse(&["x4 = 0"], &names()),
+            Err(HypothesisError::UnknownCoefficient { .. })
+        ));
+        assert!(matches!(
+            LinearHypothesis::new(hypothesis.l.clone(), vec![0.0], &names()),
+            Err(HypothesisError::RhsMismatch {
+                expected: 3,
+                found: 1
+            })
+        ));
+    }
+
+    #[test]
+    fn test_linear_hypothesis_matches_the_nested_model_f_test() {
+        let data = data();
+        let fit = dqrls(data.x(), data.y(), &Tolerance::default()).unwrap();
+        let model = FittedLinearModel::new(&data, fit.coefficients.clone());
+
+        let hypothesis = LinearHypothesis::parse(&["x2 = 0", "x3 = 0"], &names()).unwrap();
+        let result =
+            linear_hypothesis(&model, &hypothesis, WaldTest::F, CovarianceType::Classical).unwrap();
+
+        let restricted = RealMatrix::new(data.x().values.slice(ndarray::s![.., ..2]).to_owned());
+        let restricted_fit = dqrls(&restricted, data.y(), &Tolerance::default()).unwrap();
+        let rss = |residuals: &RealMatrix| residuals.values.iter().map(|e| e * e).sum::<f64>();
+        let (rss0, rss1) = (rss(&restricted_fit.residuals), rss(&fit.residuals));
+        let expected = ((rss0 - rss1) / 2.0) / (rss1 / 4.0);
+
+        assert_eq!(result.df, 2);
+        assert_eq!(result.res_df, 4);
+        assert!((result.statistic - expected).abs() < 1e-8);
+    }
+
+    #[test]
+    fn test_p_adjust_methods() {
+        let p = [0.01, 0.04, 0.03, 0.005];
+
+        assert_eq!(
+            p_adjust(&p, Adjustment::Bonferroni),
+            vec![0.04, 0.16, 0.12, 0.02]
+        );
+        let holm = p_adjust(&p, Adjustment::Holm);
+        let expected_holm = [0.03, 0.06, 0.06, 0.02];
+        for (a, b) in holm.iter().zip(expected_holm) {
+            assert!((a - b).abs() < 1e-12);
+        }
+        let bh = p_adjust(&p, Adjustment::BenjaminiHochberg);
+        let expected_bh = [0.02, 0.04, 0.04, 0.02];
+        for (a, b) in bh.iter().zip(expected_bh) {
+            assert!((a - b).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn test_single_step_adjustment_lies_between_raw_and_bonferroni() {
+        let data = data();
+        let fit = dqrls(data.x(), data.y(), &Tolerance::default()).unwrap();
+        let model = FittedLinearModel::new(&data, fit.coefficients);
+        let hypothesis = LinearHypothesis::pairwise(&[1, 2, 3], &names());
+
+        let adjusted = |adjustment| {
+            simultaneous_tests(&model, &hypothesis, CovarianceType::Classical, adjustment)
+                .unwrap()
+                .rows
+                .iter()
+                .map(|row| row.p_value)
+                .collect::<Vec<f64>>()
+        };
+        let raw = adjusted(Adjustment::None);
+        let bonferroni = adjusted(Adjustment::Bonferroni);
+        let single_step = adjusted(Adjustment::SingleStep {
+            seed: 42,
+            draws: 20_000,
+        });
+
+        assert_eq!(hypothesis.n_restrictions(), 6);
+        for i in 0..6 {
+            assert!(single_step[i] + 0.01 >= raw[i]);
+            assert!(single_step[i] <= bonferroni[i] + 0.01);
+        }
+
+        let no_draws = Adjustment::SingleStep { seed: 42, draws: 0 };
+        assert!(matches!(
+            simultaneous_tests(&model, &hypothesis, CovarianceType::Classical, no_draws),
+            Err(HypothesisError::NoDraws)
+        ));
+    }
+}
@@ -0,0 +1,473 @@
+//! This module contains a native Rust port of the LINPACK routines that R's `lm.fit` relies on:
+//! `dqrdc2` (Householder QR with R's limited column pivoting), `dqrsl` (applying the stored
+//! reflections) and `dqrls` (the least squares driver that glues the two together).
+//!
+//! The port follows the Fortran in `src/fortran/src/dqrls.f` step by step, so that the pivot
+//! order, the numerical rank and the `effects` vector agree with what R reports. Columns whose
+//! norm falls below `tol` times their original norm are moved to the end of the decomposition,
+//! which is what makes the aliased coefficients of a rank-deficient fit identifiable.
+
+// src/least_squares.rs
+
+use crate::errors::LeastSquaresError;
+use crate::types::{RealMatrix, Tolerance};
+
+/// The compact QR decomposition of a matrix, as returned by LINPACK's `dqrdc2`.
+///
+/// The upper triangle of `qr` holds the R factor, and the strict lower triangle together with
+/// `qraux` holds the Householder vectors that make up Q. The columns are stored in pivoted order:
+/// column `j` of the decomposition is column `pivot[j]` of the original matrix.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QrDecomposition {
+    /// The compact representation of Q and R, with the same shape as the decomposed matrix.
+    pub qr: RealMatrix,
+    /// Auxiliary information needed to recover the Householder reflections.
+    pub qraux: Vec<f64>,
+    /// The zero-based column pivot: column `j` of `qr` is column `pivot[j]` of the input.
+    pub pivot: Vec<usize>,
+    /// The numerical rank of the decomposed matrix.
+    pub rank: usize,
+}
+
+impl QrDecomposition {
+    /// Compute the QR decomposition of `x` using R's limited column pivoting strategy.
+    ///
+    /// # Arguments
+    /// * `x` - The matrix to decompose.
+    /// * `tol` - The tolerance used to decide whether a column is linearly dependent on the
+    ///   columns before it.
+    pub fn new(x: &RealMatrix, tol: &Tolerance) -> Self {
+        let qr = x.values.to_owned();
+        let p = x.n_cols();
+
+        // Compute the norms of the columns of x. `work` keeps the running norm used to
+        // refresh `qraux` and the original norm used for the rank test.
+        let qraux: Vec<f64> = (0..p).map(|j| norm(qr.column(j).iter())).collect();
+        let running_norm = qraux.clone();
+        let original_norm: Vec<f64> = qraux
+            .iter()
+            .map(|&v| if v == 0.0 { 1.0 } else { v })
+            .collect();
+        QrDecomposition::reduce(
+            qr,
+            qraux,
+            running_norm,
+            original_norm,
+            (0..p).collect(),
+            0,
+            tol,
+        )
+    }
+
+    /// Return the decomposition of the matrix made of the first `keep` columns of this
+    /// decomposition, in pivoted order, followed by `columns`. The Householder reflections of the
+    /// kept columns are reused and only `columns` are reduced, which is how a model matrix is
+    /// updated when columns are added after, or removed from among, the retained columns: the
+    /// columns after the first change are passed again as `columns`.
+    ///
+    /// In the result, pivot entries below `keep` are the kept columns and entry `keep + j` is
+    /// column `j` of `columns`, which is what decomposing the whole matrix would give.
+    ///
+    /// # Panics
+    /// Panics if `keep` exceeds the rank of the decomposition or if `columns` does not have one
+    /// row per row of the decomposed matrix.
+    pub fn extend(&self, keep: usize, columns: &RealMatrix, tol: &Tolerance) -> Self {
+        let (n, m) = (self.n_rows(), columns.n_cols());
+        assert!(keep <= self.rank, "Only retained columns can be kept.");
+        assert_eq!(
+            columns.n_rows(),
+            n,
+            "The columns must have one row per row."
+        );
+
+        let mut qr = ndarray::Array2::zeros((n, keep + m));
+        qr.slice_mut(ndarray::s![.., ..keep])
+            .assign(&self.qr.values.slice(ndarray::s![.., ..keep]));
+        let mut qraux = self.qraux[..keep].to_vec();
+        let mut running_norm = qraux.clone();
+        let mut original_norm = vec![1.0; keep];
+
+        // Apply the kept reflections to the new columns, whose norms below the kept rows then
+        // play the part of the running norms of the decomposition.
+        for j in 0..m {
+            let mut column = columns.values.column(j).to_vec();
+            let original = norm(column.iter());
+            for l in 0..keep.min(n.saturating_sub(1)) {
+                self.reflect(l, &mut column);
+            }
+            let remaining = norm(column[keep..].iter());
+            qr.column_mut(keep + j)
+                .assign(&ndarray::Array1::from(column));
+            qraux.push(remaining);
+            running_norm.push(remaining);
+            original_norm.push(if original == 0.0 { 1.0 } else { original });
+        }
+        QrDecomposition::reduce(
+            qr,
+            qraux,
+            running_norm,
+            original_norm,
+            (0..keep + m).collect(),
+            keep,
+            tol,
+        )
+    }
+
+    /// Perform the Householder reduction of `qr` from column `start` on, the columns before it
+    /// being reduced already.
+    fn reduce(
+        mut qr: ndarray::Array2<f64>,
+        mut qraux: Vec<f64>,
+        mut running_norm: Vec<f64>,
+        mut original_norm: Vec<f64>,
+        mut pivot: Vec<usize>,
+        start: usize,
+        tol: &Tolerance,
+    ) -> Self {
+        let (n, p) = qr.dim();
+
+        // Perform the Householder reduction of x.
+        let lup = n.min(p);
+        let mut k = p + 1;
+        for l in start..lup {
+            // Cycle the columns from l to p left-to-right until one with non-negligible norm is
+            // located. The check on k avoids infinite cycling.
+            while l + 1 < k && qraux[l] < original_norm[l] * tol.value() {
+                for i in 0..n {
+                    let t = qr[[i, l]];
+                    for j in (l + 1)..p {
+                        qr[[i, j - 1]] = qr[[i, j]];
+                    }
+                    qr[[i, p - 1]] = t;
+                }
+                pivot[l..].rotate_left(1);
+                qraux[l..].rotate_left(1);
+                running_norm[l..].rotate_left(1);
+                original_norm[l..].rotate_left(1);
+                k -= 1;
+            }
+
+            if l + 1 == n {
+                break;
+            }
+
+            // Compute the Householder transformation for column l.
+            let mut nrmxl = norm((l..n).map(|i| &qr[[i, l]]));
+            if nrmxl == 0.0 {
+                continue;
+            }
+            if qr[[l, l]] != 0.0 {
+                nrmxl = nrmxl.copysign(qr[[l, l]]);
+            }
+            for i in l..n {
+                qr[[i, l]] /= nrmxl;
+            }
+            qr[[l, l]] += 1.0;
+
+            // Apply the transformation to the remaining columns, updating the norms.
+            for j in (l + 1)..p {
+                let dot: f64 = (l..n).map(|i| qr[[i, l]] * qr[[i, j]]).sum();
+                let t = -dot / qr[[l, l]];
+                for i in l..n {
+                    qr[[i, j]] += t * qr[[i, l]];
+                }
+                if qraux[j] == 0.0 {
+                    continue;
+                }
+                let tt = (1.0 - (qr[[l, j]].abs() / qraux[j]).powi(2)).max(0.0);
+                if tt.abs() < 1e-6 {
+                    qraux[j] = norm(((l + 1)..n).map(|i| &qr[[i, j]]));
+                    running_norm[j] = qraux[j];
+                } else {
+                    qraux[j] *= tt.sqrt();
+                }
+            }
+
+            // Save the transformation.
+            qraux[l] = qr[[l, l]];
+            qr[[l, l]] = -nrmxl;
+        }
+
+        QrDecomposition {
+            qr: RealMatrix::new(qr),
+            qraux,
+            pivot,
+            rank: (k - 1).min(n),
+        }
+    }
+
+    /// Return the number of rows of the decomposed matrix.
+    pub fn n_rows(&self) -> usize {
+        self.qr.n_rows()
+    }
+
+    /// Return the number of columns of the decomposed matrix.
+    pub fn n_cols(&self) -> usize {
+        self.qr.n_cols()
+    }
+
+    /// Return the upper triangular R factor, with columns in pivoted order. The matrix has
+    /// `min(n, p)` rows and `p` columns.
+    pub fn r(&self) -> RealMatrix {
+        let (n, p) = (self.n_rows(), self.n_cols());
+        let mut r = RealMatrix::with_shape(n.min(p), p);
+        for i in 0..n.min(p) {
+            for j in i..p {
+                r.values[[i, j]] = self.qr.values[[i, j]];
+            }
+        }
+        r
+    }
+
+    /// Compute Q'y for a vector `y` with one element per row of the decomposed matrix.
+    pub fn qty(&self, y: &[f64]) -> Vec<f64> {
+        let mut qty = y.to_vec();
+        for j in 0..self.n_reflections() {
+            self.reflect(j, &mut qty);
+        }
+        qty
+    }
+
+    /// Compute Qy for a vector `y` with one element per row of the decomposed matrix.
+    pub fn qy(&self, y: &[f64]) -> Vec<f64> {
+        let mut qy = y.to_vec();
+        for j in (0..self.n_reflections()).rev() {
+            self.reflect(j, &mut qy);
+        }
+        qy
+    }
+
+    /// Solve the triangular system R[..k, ..k] b = z[..k] by back substitution, where `k` is the
+    /// rank of the decomposition. The solution is returned in pivoted order.
+    pub fn backsolve(&self, z: &[f64]) -> Vec<f64> {
+        let k = self.rank;
+        let mut b = z[..k].to_vec();
+        for j in (0..k).rev() {
+            b[j] /= self.qr.values[[j, j]];
+            for i in 0..j {
+                b[i] -= b[j] * self.qr.values[[i, j]];
+            }
+        }
+        b
+    }
+
+    /// Return the number of Householder reflections that make up Q for the rank of the
+    /// decomposition, mirroring `ju = min(k, n - 1)` in `dqrsl`.
+    fn n_reflections(&self) -> usize {
+        self.rank.min(self.n_rows().saturating_sub(1))
+    }
+
+    /// Apply the `j`-th Householder reflection to `y` in place.
+    fn reflect(&self, j: usize, y: &mut [f64]) {
+        if self.qraux[j] == 0.0 {
+            return;
+        }
+        let n = self.n_rows();
+        let column = |i: usize| {
+            if i == j {
+                self.qraux[j]
+            } else {
+                self.qr.values[[i, j]]
+            }
+        };
+        let dot: f64 = (j..n).map(|i| column(i) * y[i]).sum();
+        let t = -dot / self.qraux[j];
+        for (i, value) in y.iter_mut().enumerate().skip(j) {
+            *value += t * column(i);
+        }
+    }
+}
+
+/// The result of solving a least squares problem with `dqrls`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LeastSquaresFit {
+    /// The QR decomposition of the model matrix.
+    pub qr: QrDecomposition,
+    /// The coefficients in the original column order, with one column per response. Aliased
+    /// coefficients are reported as `NaN`, the way R reports them as `NA`.
+    pub coefficients: RealMatrix,
+    /// The residuals, with one column per response.
+    pub residuals: RealMatrix,
+    /// The effects Q'y, with one column per response.
+    pub effects: RealMatrix,
+}
+
+impl LeastSquaresFit {
+    /// Return the numerical rank of the model matrix.
+    pub fn rank(&self) -> usize {
+        self.qr.rank
+    }
+
+    /// Return the fitted values, with one column per response.
+    pub fn fitted_values(&self, y: &RealMatrix) -> RealMatrix {
+        y.minus(&self.residuals)
+    }
+
+    /// Solve the least squares problem for every column of `y` from an existing decomposition
+    /// `qr` of the model matrix, the way `dqrls` does after decomposing it.
+    pub fn from_qr(qr: QrDecomposition, y: &RealMatrix) -> Self {
+        let (n, p, ny) = (qr.n_rows(), qr.n_cols(), y.n_cols());
+        let k = qr.rank;
+        let mut coefficients = RealMatrix::with_shape(p, ny);
+        let mut residuals = RealMatrix::with_shape(n, ny);
+        let mut effects = RealMatrix::with_shape(n, ny);
+
+        for jj in 0..ny {
+            let column: Vec<f64> = y.values.column(jj).to_vec();
+            let qty = qr.qty(&column);
+            let b = qr.backsolve(&qty);
+
+            // The residuals are Q applied to Q'y with its first k elements zeroed out.
+            let mut rsd = qty.clone();
+            rsd[..k].iter_mut().for_each(|v| *v = 0.0);
+            let rsd = if k > 0 { qr.qy(&rsd) } else { column };
+
+            for (j, &original) in qr.pivot.iter().enumerate() {
+                coefficients.values[[original, jj]] = if j < k { b[j] } else { f64::NAN };
+            }
+            for i in 0..n {
+                residuals.values[[i, jj]] = rsd[i];
+                effects.values[[i, jj]] = qty[i];
+            }
+        }
+
+        LeastSquaresFit {
+            qr,
+            coefficients,
+            residuals,
+            effects,
+        }
+    }
+}
+
+/// Solve the least squares problem min ||y - xb|| column by column of `y`, the way R's `dqrls`
+/// does.
+///
+/// # Errors
+/// Returns `LeastSquaresError::DimensionMismatch` if `x` and `y` do not have the same number of
+/// rows.
+pub fn dqrls(
+    x: &RealMatrix,
+    y: &RealMatrix,
+    tol: &Tolerance,
+) -> Result<LeastSquaresFit, LeastSquaresError> {
+    let (n, ny) = (x.n_rows(), y.n_cols());
+    if y.n_rows() != n {
+        return Err(LeastSquaresError::DimensionMismatch {
+            expected_rows: n,
+            expected_cols: ny,
+            found_rows: y.n_rows(),
+            found_cols: ny,
+        });
+    }
+
+    Ok(LeastSquaresFit::from_qr(QrDecomposition::new(x, tol), y))
+}
+
+/// Compute the Euclidean norm of a sequence of values.
+fn norm<'a>(values: impl Iterator<Item = &'a f64>) -> f64 {
+    values.map(|v| v * v).sum::<f64>().sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-8, "{a} != {b}");
+    }
+
+    #[test]
+    fn test_dqrls_full_rank() {
+        // y = 1 + 2x exactly
+        let x = RealMatrix::from_vec(vec![1.0, 1.0, 1.0, 2.0, 1.0, 3.0, 1.0, 4.0], 4, Some(2));
+        let y = RealMatrix::from_vec(vec![3.0, 5.0, 7.0, 9.0], 4, None);
+
+        let fit = dqrls(&x, &y, &Tolerance::default()).unwrap();
+
+        assert_eq!(fit.rank(), 2);
+        assert_eq!(fit.qr.pivot, vec![0, 1]);
+        assert_close(fit.coefficients.values[[0, 0]], 1.0);
+        assert_close(fit.coefficients.values[[1, 0]], 2.0);
+        for r in fit.residuals.values.iter() {
+            assert_close(*r, 0.0);
+        }
+    }
+
+    #[test]
+    fn test_dqrls_pivots_dependent_columns_to_the_end() {
+        // The second column is twice the first, so it should be aliased.
+        let x = RealMatrix::from_vec(
+            vec![
+                1.0, 2.0, 1.0, //
+                2.0, 4.0, 0.0, //
+                3.0, 6.0, 1.0, //
+                4.0, 8.0, 0.0,
+            ],
+            4,
+            Some(3),
+        );
+        let y = RealMatrix::from_vec(vec![1.0, 2.0, 2.0, 5.0], 4, None);
+
+        let fit = dqrls(&x, &y, &Tolerance::new(Some(1e-7))).unwrap();
+
+        assert_eq!(fit.rank(), 2);
+        assert_eq!(fit.qr.pivot, vec![0, 2, 1]);
+        assert!(fit.coefficients.values[[1, 0]].is_nan());
+
+        // Q'Q = I, so the effects have the same length as y.
+        let ss_y: f64 = y.values.iter().map(|v| v * v).sum();
+        let ss_effects: f64 = fit.effects.values.iter().map(|v| v * v).sum();
+        assert_close(ss_y, ss_effects);
+    }
+
+    #[test]
+    fn test_dqrls_rejects_mismatched_rows() {
+        let x = RealMatrix::with_shape(3, 2);
+        let y = RealMatrix::with_shape(4, 1);
+
+        assert!(dqrls(&x, &y, &Tolerance::default()).is_err());
+    }
+
+    #[test]
+    fn test_extend_matches_a_fresh_decomposition() {
+        // The third column is aliased with the first two, so it moves to the end.
+        let x = RealMatrix::from_vec(
+            vec![
+                1.0, 1.0, 2.0, 0.5, 3.0, //
+                1.0, 2.0, 3.0, 1.5, 1.0, //
+                1.0, 3.0, 4.0, 0.0, 4.0, //
+                1.0, 4.0, 5.0, 2.0, 1.0, //
+                1.0, 5.0, 6.0, 1.0, 5.0, //
+                1.0, 6.0, 7.0, 0.5, 9.0,
+            ],
+            6,
+            Some(5),
+        );
+        let y = RealMatrix::from_vec(vec![1.2, 2.3, 2.9, 4.8, 5.1, 5.9], 6, None);
+        let tol = Tolerance::default();
+        let columns = |js: &[usize]| RealMatrix::new(x.values.select(ndarray::Axis(1), js));
+        let current = QrDecomposition::new(&columns(&[0, 1, 2, 3]), &tol);
+        assert_eq!((current.rank, current.pivot.clone()), (3, vec![0, 1, 3, 2]));
+
+        // Drop column 3 and add column 4: the first two columns keep their reflections.
+        let updated = current.extend(2, &columns(&[2, 4]), &tol);
+        let fresh = QrDecomposition::new(&columns(&[0, 1, 2, 4]), &tol);
+        assert_eq!((updated.rank, updated.pivot.clone()), (3, vec![0, 1, 3, 2]));
+        for (a, b) in updated.r().values.iter().zip(fresh.r().values.iter()) {
+            assert_close(*a, *b);
+        }
+        let (updated, fresh) = (
+            LeastSquaresFit::from_qr(updated, &y),
+            LeastSquaresFit::from_qr(fresh, &y),
+        );
+        for (a, b) in updated
+            .residuals
+            .values
+            .iter()
+            .zip(fresh.residuals.values.iter())
+        {
+            assert_close(*a, *b);
+        }
+    }
+}
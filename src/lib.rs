@@ -1,12 +1,26 @@
 // src/lib.rs
 
+pub mod alias;
+pub mod anova;
+pub mod cv;
 pub mod data;
+pub mod distributions;
 pub mod errors;
+pub mod family;
 pub mod fitters;
 pub mod fortran;
+pub mod hypothesis;
+pub mod least_squares;
 pub mod linear_model;
+pub mod mixed;
+pub mod model_fit;
+pub mod optim;
+pub mod panel;
 pub mod real_matrix;
+pub mod selection;
+pub mod terms;
 pub mod types;
+pub mod vcov;
 
 pub use data::Data;
 pub use real_matrix::RealMatrix;
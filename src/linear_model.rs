@@ -1,6 +1,15 @@
 // src/linear_model.rs
 
+use crate::alias::Alias;
+use crate::anova::marginal::{marginal_anova, SumOfSquaresType};
+use crate::anova::sequential::sequential_anova;
+use crate::anova::{Anova, AnovaTable};
+use crate::errors::{AnovaError, HypothesisError, LeastSquaresError, LmFitterError};
 use crate::fitters::fit::FitModel;
+use crate::hypothesis::{linear_hypothesis, HypothesisTestResult, LinearHypothesis, WaldTest};
+use crate::least_squares::LeastSquaresFit;
+use crate::types::Tolerance;
+use crate::vcov::CovarianceType;
 use crate::{Data, RealMatrix};
 use std::cmp::Ordering::{Equal, Greater, Less};
 
@@ -58,8 +67,9 @@ impl<'a> LinearModel<'a> {
     pub fn fit(&mut self, fitter: &impl FitModel) {
         match self {
             // If already fitted, re-fit the model.
-            LinearModel::Fitted(_) => {
-                self.update_coefficients(fitter.fit().unwrap());
+            LinearModel::Fitted(fitted) => {
+                fitted.update_coefficients(fitter.fit().unwrap());
+                fitted.tol = fitter.tolerance();
             }
 
             // If unfitted, fit the model and update the enum variant.
@@ -67,6 +77,7 @@ impl<'a> LinearModel<'a> {
                 *self = LinearModel::Fitted(FittedLinearModel {
                     data: unfitted_model.data,
                     coefficients: fitter.fit().unwrap(),
+                    tol: fitter.tolerance(),
                 });
             }
         }
@@ -92,17 +103,43 @@ impl<'a> LinearModel<'a> {
             LinearModel::Unfitted(_) => None,
         }
     }
+
+    pub fn alias(&self) -> Option<Alias> {
+        match self {
+            LinearModel::Fitted(fitted) => Some(fitted.alias()),
+            LinearModel::Unfitted(_) => None,
+        }
+    }
+
+    pub fn anova(&self) -> Option<Result<Anova, LmFitterError>> {
+        match self {
+            LinearModel::Fitted(fitted) => Some(fitted.anova()),
+            LinearModel::Unfitted(_) => None,
+        }
+    }
 }
 
 #[derive(Debug, PartialEq)]
 pub struct FittedLinearModel<'a> {
     pub data: &'a Data,
     pub coefficients: RealMatrix,
+    /// The tolerance the model was fitted with, which determines its rank and aliasing.
+    pub tol: Tolerance,
 }
 
 impl<'a> FittedLinearModel<'a> {
     pub fn new(data: &'a Data, coefficients: RealMatrix) -> Self {
-        FittedLinearModel { data, coefficients }
+        FittedLinearModel {
+            data,
+            coefficients,
+            tol: Tolerance::default(),
+        }
+    }
+
+    /// Return the same model, recording that it was fitted with tolerance `tol`.
+    pub fn with_tolerance(mut self, tol: Tolerance) -> Self {
+        self.tol = tol;
+        self
     }
 
     pub fn update_coefficients(&mut self, coefficients: RealMatrix) {
@@ -110,18 +147,77 @@ impl<'a> FittedLinearModel<'a> {
     }
 
     pub fn predict(&self, x: Option<&RealMatrix>) -> RealMatrix {
+        // Aliased coefficients are NaN; like R, predict from the estimable coefficients only.
+        let estimable = self
+            .coefficients
+            .values
+            .mapv(|b| if b.is_nan() { 0.0 } else { b });
+        let coefficients = RealMatrix::new(estimable);
+
         match x {
             // If x is provided, use it to make predictions.
-            Some(x) => x.dot(&self.coefficients),
+            Some(x) => x.dot(&coefficients),
 
             // If x is not provided, use the data's x matrix to make predictions.
-            None => self.data.x().dot(&self.coefficients),
+            None => self.data.x().dot(&coefficients),
         }
     }
 
     pub fn residuals(&self) -> RealMatrix {
         self.data.y().minus(&self.predict(Some(self.data.x())))
     }
+
+    /// Return the rank of the model, the number of coefficients that are not aliased.
+    pub fn rank(&self) -> usize {
+        self.coefficients
+            .values
+            .column(0)
+            .iter()
+            .filter(|b| !b.is_nan())
+            .count()
+    }
+
+    /// Return the pivoted QR decomposition of the (weighted) least squares problem, with the
+    /// tolerance the model was fitted with, so that the rank and aliasing agree with the fit.
+    pub fn least_squares(&self) -> Result<LeastSquaresFit, LeastSquaresError> {
+        self.data.least_squares(&self.tol)
+    }
+
+    /// Return the exact linear dependencies among the columns of x, naming each aliased column
+    /// and the combination of retained columns that reproduces it.
+    pub fn alias(&self) -> Alias {
+        Alias::new(self.data, &self.tol)
+    }
+
+    /// Return the sequential (Type I) analysis of variance table of the model, computed from the
+    /// effects Q'y and the model terms of the data.
+    pub fn anova(&self) -> Result<Anova, LmFitterError> {
+        let fit = self.least_squares()?;
+        Ok(sequential_anova(&fit, &self.data.terms()))
+    }
+
+    /// Return the Type II or Type III analysis of variance table of the model, like
+    /// `car::Anova`, using `covariance` for the Wald tests of each term.
+    pub fn marginal_anova(
+        &self,
+        ss_type: SumOfSquaresType,
+        covariance: CovarianceType,
+    ) -> Result<AnovaTable, AnovaError> {
+        let fit = self.least_squares()?;
+        let x = self.data.weighted_x();
+        marginal_anova(&fit, &x, &self.data.terms(), ss_type, covariance, &self.tol)
+    }
+
+    /// Test the linear hypothesis L b = c about the coefficients of the model with a Wald test,
+    /// like `car::linearHypothesis`.
+    pub fn linear_hypothesis(
+        &self,
+        hypothesis: &LinearHypothesis,
+        test: WaldTest,
+        covariance: CovarianceType,
+    ) -> Result<HypothesisTestResult, HypothesisError> {
+        linear_hypothesis(self, hypothesis, test, covariance)
+    }
 }
 
 #[derive(Debug, PartialEq)]
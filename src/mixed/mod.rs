@@ -0,0 +1,12 @@
+//! This module contains the building blocks of linear mixed-effects models, which are fitted by
+//! `fitters::lmer_fitter`.
+//!
+//! * `spec`: grouping factors and random-effects terms, parsed from lme4's bar notation such as
+//!   `(1 + x | g)` against the column names of x and the names of the grouping factors.
+//! * `sparse`: a sparse Cholesky factorization for the penalized least squares system of the
+//!   random effects.
+
+// src/mixed/mod.rs
+
+pub mod sparse;
+pub mod spec;
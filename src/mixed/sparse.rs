@@ -0,0 +1,272 @@
+//! This module contains a sparse Cholesky factorization of symmetric positive definite
+//! matrices, for the system Lambda' Z' Z Lambda + I of the random effects of a mixed model.
+//!
+//! Only the lower triangle is stored, column by column. The rows and columns are first permuted
+//! by a minimum degree ordering, the heuristic that AMD approximates: the elimination graph is
+//! updated explicitly, and at each step the node with the fewest neighbours is eliminated,
+//! which keeps the fill of L small. The factorization of P A P' is then left-looking: each
+//! column of L is the column of the matrix less the contributions of the earlier columns that
+//! have a nonzero in its row, so the pattern of L is found as it is computed.
+
+// src/mixed/sparse.rs
+
+use std::collections::{BTreeMap, BTreeSet};
+
+/// A symmetric matrix stored as the lower triangle of each column.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SparseSymmetric {
+    /// The entries on and below the diagonal of each column, by row.
+    columns: Vec<BTreeMap<usize, f64>>,
+}
+
+impl SparseSymmetric {
+    /// Create an `n` x `n` zero matrix.
+    pub fn new(n: usize) -> Self {
+        SparseSymmetric {
+            columns: vec![BTreeMap::new(); n],
+        }
+    }
+
+    /// Return the order of the matrix.
+    pub fn n(&self) -> usize {
+        self.columns.len()
+    }
+
+    /// Add `value` to the entries (i, j) and (j, i).
+    pub fn add(&mut self, i: usize, j: usize, value: f64) {
+        let (row, column) = if i >= j { (i, j) } else { (j, i) };
+        *self.columns[column].entry(row).or_insert(0.0) += value;
+    }
+}
+
+/// The lower triangular Cholesky factor L of P A P' for a sparse symmetric positive definite
+/// matrix A and a fill-reducing permutation P, so that A = (P' L) (P' L)'.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SparseCholesky {
+    /// The nonzeros of each column of L by increasing row, starting with the diagonal.
+    columns: Vec<Vec<(usize, f64)>>,
+    /// The row of A that becomes row k of P A P', for each k.
+    order: Vec<usize>,
+}
+
+impl SparseCholesky {
+    /// Factor `a` as (P' L) (P' L)'. Returns `None` if `a` is not positive definite.
+    pub fn factor(a: &SparseSymmetric) -> Option<Self> {
+        let n = a.n();
+        let order = minimum_degree(a);
+        let mut position = vec![0; n];
+        for (k, &i) in order.iter().enumerate() {
+            position[i] = k;
+        }
+        let mut permuted = SparseSymmetric::new(n);
+        for (j, column) in a.columns.iter().enumerate() {
+            for (&i, &value) in column {
+                permuted.add(position[i], position[j], value);
+            }
+        }
+        let a = &permuted;
+
+        let mut columns: Vec<Vec<(usize, f64)>> = Vec::with_capacity(n);
+        // For each row, the earlier columns with a nonzero in that row, and its position.
+        let mut rows: Vec<Vec<(usize, usize)>> = vec![Vec::new(); n];
+        let mut work = vec![0.0; n];
+        let mut mark = vec![usize::MAX; n];
+        for j in 0..n {
+            let mut pattern = Vec::new();
+            let mut touch = |i: usize, pattern: &mut Vec<usize>, work: &mut [f64]| {
+                if mark[i] != j {
+                    mark[i] = j;
+                    work[i] = 0.0;
+                    pattern.push(i);
+                }
+            };
+            touch(j, &mut pattern, &mut work);
+            for (&i, &value) in &a.columns[j] {
+                touch(i, &mut pattern, &mut work);
+                work[i] += value;
+            }
+            for &(k, position) in &rows[j] {
+                let ljk = columns[k][position].1;
+                for &(i, lik) in &columns[k][position..] {
+                    touch(i, &mut pattern, &mut work);
+                    work[i] -= lik * ljk;
+                }
+            }
+
+            let diagonal = work[j];
+            if diagonal <= 0.0 || !diagonal.is_finite() {
+                return None;
+            }
+            let diagonal = diagonal.sqrt();
+            pattern.sort_unstable();
+            let column: Vec<(usize, f64)> = pattern
+                .iter()
+                .map(|&i| (i, if i == j { diagonal } else { work[i] / diagonal }))
+                .collect();
+            for (position, &(i, _)) in column.iter().enumerate().skip(1) {
+                rows[i].push((j, position));
+            }
+            columns.push(column);
+        }
+        Some(SparseCholesky { columns, order })
+    }
+
+    /// Return the number of nonzeros of L.
+    pub fn nnz(&self) -> usize {
+        self.columns.iter().map(Vec::len).sum()
+    }
+
+    /// Return log |L|^2 = 2 sum log L_jj, the log-determinant of the factored matrix.
+    pub fn log_determinant(&self) -> f64 {
+        self.columns.iter().map(|c| 2.0 * c[0].1.ln()).sum()
+    }
+
+    /// Solve (P' L) x = b in place, that is x = L^-1 P b.
+    pub fn solve_lower(&self, b: &mut [f64]) {
+        let mut x: Vec<f64> = self.order.iter().map(|&i| b[i]).collect();
+        for (j, column) in self.columns.iter().enumerate() {
+            x[j] /= column[0].1;
+            for &(i, value) in &column[1..] {
+                x[i] -= value * x[j];
+            }
+        }
+        b.copy_from_slice(&x);
+    }
+
+    /// Solve (P' L)' x = b in place, that is x = P' L'^-1 b.
+    pub fn solve_upper(&self, b: &mut [f64]) {
+        for (j, column) in self.columns.iter().enumerate().rev() {
+            let sum: f64 = column[1..].iter().map(|&(i, value)| value * b[i]).sum();
+            b[j] = (b[j] - sum) / column[0].1;
+        }
+        let x = b.to_vec();
+        for (k, &i) in self.order.iter().enumerate() {
+            b[i] = x[k];
+        }
+    }
+}
+
+/// Return a minimum degree elimination order of the graph of `a`, breaking ties by the lower
+/// index.
+fn minimum_degree(a: &SparseSymmetric) -> Vec<usize> {
+    let n = a.n();
+    let mut neighbours: Vec<BTreeSet<usize>> = vec![BTreeSet::new(); n];
+    for (j, column) in a.columns.iter().enumerate() {
+        for &i in column.keys().filter(|&&i| i != j) {
+            neighbours[i].insert(j);
+            neighbours[j].insert(i);
+        }
+    }
+    let mut queue: BTreeSet<(usize, usize)> = (0..n).map(|i| (neighbours[i].len(), i)).collect();
+    let mut order = Vec::with_capacity(n);
+    while let Some((_, v)) = queue.pop_first() {
+        // Eliminating v joins its neighbours into a clique.
+        let clique = std::mem::take(&mut neighbours[v]);
+        for &u in &clique {
+            queue.remove(&(neighbours[u].len(), u));
+            neighbours[u].remove(&v);
+            neighbours[u].extend(clique.iter().filter(|&&w| w != u));
+            queue.insert((neighbours[u].len(), u));
+        }
+        order.push(v);
+    }
+    order
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::RealMatrix;
+
+    #[test]
+    fn test_sparse_factor_matches_the_dense_factor() {
+        // An arrowhead matrix with a dense last row, which fills nothing, and a band that does.
+        let n = 6;
+        let mut dense = RealMatrix::with_shape(n, n);
+        let mut sparse = SparseSymmetric::new(n);
+        let mut set = |i: usize, j: usize, value: f64| {
+            dense.values[[i, j]] = value;
+            dense.values[[j, i]] = value;
+            sparse.add(i, j, value);
+        };
+        for i in 0..n {
+            set(i, i, 4.0 + i as f64);
+        }
+        for i in 0..n - 1 {
+            set(n - 1, i, 0.5 + 0.1 * i as f64);
+        }
+        set(2, 0, -1.0);
+        set(3, 1, 0.7);
+        set(4, 2, 0.3);
+
+        // L is the dense factor of the permuted matrix.
+        let factor = SparseCholesky::factor(&sparse).unwrap();
+        let order = &factor.order;
+        let mut permuted = RealMatrix::with_shape(n, n);
+        for i in 0..n {
+            for j in 0..n {
+                permuted.values[[i, j]] = dense.values[[order[i], order[j]]];
+            }
+        }
+        let expected = permuted.cholesky().unwrap();
+        let mut l = RealMatrix::with_shape(n, n);
+        for (j, column) in factor.columns.iter().enumerate() {
+            for &(i, value) in column {
+                l.values[[i, j]] = value;
+            }
+        }
+        for i in 0..n {
+            for j in 0..n {
+                assert!((l.values[[i, j]] - expected.values[[i, j]]).abs() < 1e-12);
+            }
+        }
+        let log_det: f64 = (0..n).map(|j| 2.0 * expected.values[[j, j]].ln()).sum();
+        assert!((factor.log_determinant() - log_det).abs() < 1e-12);
+
+        // Solving L L' x = b recovers x.
+        let x: Vec<f64> = (0..n).map(|i| i as f64 - 2.0).collect();
+        let mut b: Vec<f64> = (0..n)
+            .map(|i| (0..n).map(|j| dense.values[[i, j]] * x[j]).sum())
+            .collect();
+        factor.solve_lower(&mut b);
+        factor.solve_upper(&mut b);
+        for i in 0..n {
+            assert!((b[i] - x[i]).abs() < 1e-12);
+        }
+
+        let mut singular = SparseSymmetric::new(2);
+        singular.add(0, 0, 1.0);
+        singular.add(1, 0, 1.0);
+        singular.add(1, 1, 1.0);
+        assert!(SparseCholesky::factor(&singular).is_none());
+    }
+
+    #[test]
+    fn test_minimum_degree_avoids_fill() {
+        // An arrowhead whose dense row and column come first fills L completely in natural
+        // order; eliminating the hub once it has at most one neighbour leaves no fill.
+        let n = 8;
+        let mut arrow = SparseSymmetric::new(n);
+        arrow.add(0, 0, n as f64);
+        for i in 1..n {
+            arrow.add(i, i, 2.0);
+            arrow.add(i, 0, 1.0);
+        }
+        let factor = SparseCholesky::factor(&arrow).unwrap();
+        assert!(factor.order[n - 2..].contains(&0));
+        assert_eq!(factor.nnz(), 2 * n - 1);
+
+        let x: Vec<f64> = (0..n).map(|i| 1.0 + i as f64).collect();
+        let mut b: Vec<f64> = (0..n)
+            .map(|i| match i {
+                0 => n as f64 * x[0] + x[1..].iter().sum::<f64>(),
+                i => 2.0 * x[i] + x[0],
+            })
+            .collect();
+        factor.solve_lower(&mut b);
+        factor.solve_upper(&mut b);
+        for i in 0..n {
+            assert!((b[i] - x[i]).abs() < 1e-12);
+        }
+    }
+}
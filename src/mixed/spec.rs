@@ -0,0 +1,219 @@
+//! This module describes the random effects of a mixed model. The repository has no formula
+//! language, so random-effects terms are parsed from lme4's bar notation on their own, with
+//! `RandomTerm::parse`, against the column names of x and a list of named grouping factors.
+//!
+//! * `(1 | g)` is a random intercept for each level of `g`, and `(1 + x | g)` adds a random
+//!   slope on column `x`, correlated with the intercept. `(x | g)` also has an intercept; use
+//!   `(0 + x | g)` for a slope alone.
+//! * `(1 | g:h)` groups by the combinations of the levels of `g` and `h`, and the nested form
+//!   `(1 | g/h)` expands to `(1 | g) + (1 | g:h)`.
+//! * Several terms are separated by `+`, such as `(1 | g) + (1 | h)` for crossed factors.
+
+// src/mixed/spec.rs
+
+use crate::errors::MixedModelError;
+use crate::terms::INTERCEPT_LABEL;
+
+/// A grouping factor of a mixed model, with the level of each observation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GroupingFactor {
+    /// The name the factor is referred to by in random-effects terms.
+    pub name: String,
+    /// The level code of each observation.
+    pub levels: Vec<usize>,
+}
+
+impl GroupingFactor {
+    /// Create a new `GroupingFactor` struct.
+    pub fn new(name: &str, levels: Vec<usize>) -> Self {
+        GroupingFactor {
+            name: name.to_string(),
+            levels,
+        }
+    }
+}
+
+/// A random-effects term: a vector of random coefficients for each level of a grouping.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RandomTerm {
+    /// `true` if the term has a random intercept.
+    pub intercept: bool,
+    /// The columns of x with random slopes.
+    pub columns: Vec<usize>,
+    /// The grouping factors, by index, whose combinations of levels form the groups.
+    pub factors: Vec<usize>,
+}
+
+impl RandomTerm {
+    /// Parse random-effects terms in bar notation, such as `"(1 + x | g) + (1 | h/k)"`.
+    ///
+    /// # Errors
+    /// Returns `MixedModelError::InvalidSpecification` if a term is not of the form
+    /// `(effects | grouping)` or has no effects, and `MixedModelError::UnknownColumn` or
+    /// `MixedModelError::UnknownFactor` for names that are not columns of x or factors.
+    pub fn parse(
+        spec: &str,
+        column_names: &[String],
+        factors: &[GroupingFactor],
+    ) -> Result<Vec<RandomTerm>, MixedModelError> {
+        let invalid = || MixedModelError::InvalidSpecification {
+            spec: spec.to_string(),
+        };
+        let mut terms = Vec::new();
+        for piece in split_top_level(spec).ok_or_else(invalid)? {
+            let inner = piece
+                .trim()
+                .strip_prefix('(')
+                .and_then(|rest| rest.strip_suffix(')'))
+                .ok_or_else(invalid)?;
+            let (effects, grouping) = inner.split_once('|').ok_or_else(invalid)?;
+            if grouping.contains('|') {
+                return Err(invalid());
+            }
+
+            let mut intercept = true;
+            let mut columns = Vec::new();
+            for effect in effects.split('+').map(str::trim) {
+                match effect {
+                    "1" => intercept = true,
+                    "0" | "-1" => intercept = false,
+                    "" => return Err(invalid()),
+                    name => {
+                        let column = column_names
+                            .iter()
+                            .position(|c| c == name && c != INTERCEPT_LABEL)
+                            .ok_or_else(|| MixedModelError::UnknownColumn {
+                                name: name.to_string(),
+                                spec: spec.to_string(),
+                            })?;
+                        columns.push(column);
+                    }
+                }
+            }
+            if !intercept && columns.is_empty() {
+                return Err(invalid());
+            }
+
+            // Each level of nesting adds a term grouped by all the factors so far.
+            let mut nested = Vec::new();
+            for level in grouping.split('/') {
+                for name in level.split(':').map(str::trim) {
+                    let factor = factors.iter().position(|f| f.name == name).ok_or_else(|| {
+                        MixedModelError::UnknownFactor {
+                            name: name.to_string(),
+                            spec: spec.to_string(),
+                        }
+                    })?;
+                    nested.push(factor);
+                }
+                terms.push(RandomTerm {
+                    intercept,
+                    columns: columns.clone(),
+                    factors: nested.clone(),
+                });
+            }
+        }
+        Ok(terms)
+    }
+
+    /// Return the number of random coefficients per group.
+    pub fn size(&self) -> usize {
+        usize::from(self.intercept) + self.columns.len()
+    }
+
+    /// Return the names of the random coefficients.
+    pub fn names(&self, column_names: &[String]) -> Vec<String> {
+        let intercept = self.intercept.then(|| INTERCEPT_LABEL.to_string());
+        intercept
+            .into_iter()
+            .chain(self.columns.iter().map(|&j| column_names[j].clone()))
+            .collect()
+    }
+
+    /// Return the name of the grouping, such as `g` or `g:h`.
+    pub fn group_name(&self, factors: &[GroupingFactor]) -> String {
+        self.factors
+            .iter()
+            .map(|&k| factors[k].name.as_str())
+            .collect::<Vec<_>>()
+            .join(":")
+    }
+}
+
+/// Split `spec` at the `+` signs outside parentheses, or return `None` for unbalanced
+/// parentheses.
+fn split_top_level(spec: &str) -> Option<Vec<&str>> {
+    let mut pieces = Vec::new();
+    let (mut depth, mut start) = (0_usize, 0);
+    for (i, c) in spec.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth = depth.checked_sub(1)?,
+            '+' if depth == 0 => {
+                pieces.push(&spec[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    pieces.push(&spec[start..]);
+    (depth == 0).then_some(pieces)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_expands_nested_and_crossed_terms() {
+        let names: Vec<String> = ["(Intercept)", "x", "z"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let factors = vec![
+            GroupingFactor::new("site", vec![0; 4]),
+            GroupingFactor::new("block", vec![0; 4]),
+            GroupingFactor::new("rater", vec![0; 4]),
+        ];
+        let terms =
+            RandomTerm::parse("(1 + x | site/block) + (0 + z | rater)", &names, &factors).unwrap();
+        assert_eq!(
+            terms,
+            vec![
+                RandomTerm {
+                    intercept: true,
+                    columns: vec![1],
+                    factors: vec![0],
+                },
+                RandomTerm {
+                    intercept: true,
+                    columns: vec![1],
+                    factors: vec![0, 1],
+                },
+                RandomTerm {
+                    intercept: false,
+                    columns: vec![2],
+                    factors: vec![2],
+                },
+            ]
+        );
+        assert_eq!(terms[1].group_name(&factors), "site:block");
+        assert_eq!(terms[0].names(&names), vec!["(Intercept)", "x"]);
+
+        let implicit = RandomTerm::parse("(x | site:rater)", &names, &factors).unwrap();
+        assert!(implicit[0].intercept);
+        assert_eq!(implicit[0].factors, vec![0, 2]);
+        assert!(matches!(
+            RandomTerm::parse("(1 + w | site)", &names, &factors),
+            Err(MixedModelError::UnknownColumn { .. })
+        ));
+        assert!(matches!(
+            RandomTerm::parse("(1 | plot)", &names, &factors),
+            Err(MixedModelError::UnknownFactor { .. })
+        ));
+        assert!(matches!(
+            RandomTerm::parse("(1 + x) | site", &names, &factors),
+            Err(MixedModelError::InvalidSpecification { .. })
+        ));
+    }
+}
@@ -0,0 +1,164 @@
+//! This module contains the `ModelFit` trait, which exposes the log-likelihood and the
+//! information criteria derived from it for any fitted model, so that models of different kinds
+//! can be reported on and compared consistently (and selected automatically).
+
+// src/model_fit.rs
+
+use crate::linear_model::FittedLinearModel;
+use std::f64::consts::PI;
+use std::fmt;
+
+/// The maximized log-likelihood of a fitted model, with the number of estimated parameters.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LogLik {
+    /// The value of the log-likelihood.
+    pub value: f64,
+    /// The number of estimated parameters, including any scale parameter.
+    pub df: f64,
+    /// The number of observations the likelihood was computed from.
+    pub nobs: usize,
+}
+
+impl fmt::Display for LogLik {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "'log Lik.' {} (df={})", self.value, self.df)
+    }
+}
+
+/// A trait for the goodness-of-fit measures of a fitted model, mirroring R's `logLik`, `AIC`,
+/// `BIC`, `deviance`, `df.residual` and `nobs` generics.
+pub trait ModelFit {
+    /// Return the maximized log-likelihood of the model.
+    fn log_lik(&self) -> LogLik;
+
+    /// Return the deviance of the model. For a linear model, this is the (weighted) residual sum
+    /// of squares.
+    fn deviance(&self) -> f64;
+
+    /// Return the residual degrees of freedom.
+    fn df_residual(&self) -> f64;
+
+    /// Return the number of observations used to fit the model.
+    fn nobs(&self) -> usize;
+
+    /// Return the Akaike information criterion with penalty `k` per parameter, -2 logLik + k df.
+    /// The usual AIC uses `k = 2`.
+    fn aic(&self, k: f64) -> f64 {
+        let log_lik = self.log_lik();
+        -2.0 * log_lik.value + k * log_lik.df
+    }
+
+    /// Return the Bayesian information criterion, -2 logLik + log(n) df.
+    fn bic(&self) -> f64 {
+        let log_lik = self.log_lik();
+        -2.0 * log_lik.value + (log_lik.nobs as f64).ln() * log_lik.df
+    }
+
+    /// Return the AIC corrected for small samples, AIC + 2 df (df + 1) / (n - df - 1).
+    fn aicc(&self) -> f64 {
+        let log_lik = self.log_lik();
+        let n = log_lik.nobs as f64;
+        self.aic(2.0) + 2.0 * log_lik.df * (log_lik.df + 1.0) / (n - log_lik.df - 1.0)
+    }
+}
+
+impl<'a> ModelFit for FittedLinearModel<'a> {
+    /// Return the Gaussian log-likelihood at the maximum likelihood estimate of the variance,
+    /// like R's `logLik.lm`. With prior weights w, the log-likelihood is
+    /// 0.5 * (sum(log(w)) - n * (log(2 pi) + 1 - log(n) + log(sum(w * e^2)))).
+    ///
+    /// # Panics
+    /// Panics if the model has more than one response.
+    fn log_lik(&self) -> LogLik {
+        assert_eq!(
+            self.data.y().n_cols(),
+            1,
+            "The log-likelihood is only defined for single-response models."
+        );
+        let n = self.nobs() as f64;
+        let sum_log_weights: f64 = self
+            .data
+            .weights()
+            .map_or(0.0, |weights| weights.iter().map(|w| w.ln()).sum());
+        let value =
+            0.5 * (sum_log_weights - n * ((2.0 * PI).ln() + 1.0 - n.ln() + self.deviance().ln()));
+
+        LogLik {
+            value,
+            df: (self.rank() + 1) as f64,
+            nobs: self.nobs(),
+        }
+    }
+
+    fn deviance(&self) -> f64 {
+        let residuals = self.residuals();
+        match self.data.weights() {
+            Some(weights) => residuals
+                .values
+                .iter()
+                .zip(weights)
+                .map(|(e, w)| w * e * e)
+                .sum(),
+            None => residuals.values.iter().map(|e| e * e).sum(),
+        }
+    }
+
+    fn df_residual(&self) -> f64 {
+        (self.nobs() - self.rank()) as f64
+    }
+
+    fn nobs(&self) -> usize {
+        self.data.y().n_rows()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Data, RealMatrix, Tolerance};
+
+    fn data() -> Data {
+        let x = RealMatrix::from_vec(
+            vec![1.0, 1.0, 1.0, 2.0, 1.0, 3.0, 1.0, 4.0, 1.0, 5.0, 1.0, 6.0],
+            6,
+            Some(2),
+        );
+        let y = RealMatrix::from_vec(vec![1.3, 1.9, 3.4, 3.8, 5.3, 5.8], 6, None);
+        Data::new(x, y)
+    }
+
+    #[test]
+    fn test_information_criteria_of_a_linear_model() {
+        let data = data();
+        let fit = data.least_squares(&Tolerance::default()).unwrap();
+        let model = FittedLinearModel::new(&data, fit.coefficients);
+
+        let rss = model.deviance();
+        let n: f64 = 6.0;
+        let expected = -n / 2.0 * ((2.0 * PI).ln() + 1.0 + (rss / n).ln());
+        let log_lik = model.log_lik();
+
+        assert!((log_lik.value - expected).abs() < 1e-10);
+        assert_eq!(log_lik.df, 3.0);
+        assert_eq!(model.df_residual(), 4.0);
+        assert!((model.aic(2.0) - (-2.0 * expected + 6.0)).abs() < 1e-10);
+        assert!((model.bic() - (-2.0 * expected + n.ln() * 3.0)).abs() < 1e-10);
+        assert!((model.aicc() - (model.aic(2.0) + 24.0 / 2.0)).abs() < 1e-10);
+    }
+
+    #[test]
+    fn test_weighted_log_lik_honors_the_weights() {
+        let weights = vec![1.0, 2.0, 1.0, 0.5, 1.0, 3.0];
+        let data = data().with_weights(weights.clone());
+        let fit = data.least_squares(&Tolerance::default()).unwrap();
+        let model = FittedLinearModel::new(&data, fit.coefficients);
+
+        let weighted_rss: f64 = fit.residuals.values.iter().map(|e| e * e).sum();
+        assert!((model.deviance() - weighted_rss).abs() < 1e-10);
+
+        let n: f64 = 6.0;
+        let sum_log_w: f64 = weights.iter().map(|w: &f64| w.ln()).sum();
+        let expected = 0.5 * (sum_log_w - n * ((2.0 * PI).ln() + 1.0 - n.ln() + weighted_rss.ln()));
+        assert!((model.log_lik().value - expected).abs() < 1e-10);
+    }
+}
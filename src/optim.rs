@@ -0,0 +1,182 @@
+//! This module contains a derivative-free minimiser for the likelihoods of the variance and
+//! correlation parameters of the models that profile out their coefficients.
+//!
+//! The minimiser is the Nelder-Mead simplex method with the reflection, expansion and
+//! contraction coefficients of R's `optim`, and its relative convergence criterion on the
+//! spread of the function values over the simplex. Parameters are expected to be unconstrained,
+//! so models map constrained parameters through a transformation first.
+
+// src/optim.rs
+
+use std::cell::Cell;
+
+/// The controls of the Nelder-Mead minimiser.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NelderMeadControl {
+    /// The maximum number of function evaluations.
+    pub max_evaluations: usize,
+    /// The relative tolerance on the spread of the function values, like `optim`'s `reltol`.
+    pub reltol: f64,
+    /// The size of the initial simplex around the starting point.
+    pub step: f64,
+}
+
+impl Default for NelderMeadControl {
+    fn default() -> Self {
+        Self {
+            max_evaluations: 5000,
+            reltol: 1e-10,
+            step: 0.5,
+        }
+    }
+}
+
+/// The result of a minimisation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Minimum {
+    /// The best point found.
+    pub point: Vec<f64>,
+    /// The function value at `point`.
+    pub value: f64,
+    /// The number of function evaluations.
+    pub evaluations: usize,
+    /// `true` if the spread of the simplex fell below the tolerance before the evaluation limit.
+    pub converged: bool,
+}
+
+/// Minimise `f` from `start` by the Nelder-Mead simplex method. Non-finite function values are
+/// treated as infinite, so `f` may return `NaN` or infinity outside its domain, but it must be
+/// finite at `start`. After convergence the search is restarted once from the best point, which
+/// guards against a simplex that has collapsed away from the minimum.
+pub fn nelder_mead(
+    f: &dyn Fn(&[f64]) -> f64,
+    start: &[f64],
+    control: &NelderMeadControl,
+) -> Minimum {
+    let mut minimum = simplex_search(f, start, control, 0);
+    if minimum.converged && !start.is_empty() {
+        let restart = simplex_search(f, &minimum.point.clone(), control, minimum.evaluations);
+        if restart.value <= minimum.value {
+            minimum = restart;
+        } else {
+            minimum.evaluations = restart.evaluations;
+        }
+    }
+    minimum
+}
+
+/// Run one Nelder-Mead search, counting evaluations on from `evaluations`.
+fn simplex_search(
+    f: &dyn Fn(&[f64]) -> f64,
+    start: &[f64],
+    control: &NelderMeadControl,
+    evaluations: usize,
+) -> Minimum {
+    let (alpha, gamma, beta) = (1.0, 2.0, 0.5);
+    let n = start.len();
+    let count = Cell::new(evaluations);
+    let evaluate = |point: &[f64]| -> f64 {
+        count.set(count.get() + 1);
+        let value = f(point);
+        if value.is_finite() {
+            value
+        } else {
+            f64::INFINITY
+        }
+    };
+
+    let mut vertices = vec![start.to_vec()];
+    for j in 0..n {
+        let mut vertex = start.to_vec();
+        vertex[j] += control.step * start[j].abs().max(1.0);
+        vertices.push(vertex);
+    }
+    let mut values: Vec<f64> = vertices.iter().map(|v| evaluate(v)).collect();
+    let mut converged = n == 0;
+    while !converged && count.get() < control.max_evaluations {
+        let mut order: Vec<usize> = (0..=n).collect();
+        order.sort_by(|&a, &b| values[a].total_cmp(&values[b]));
+        vertices = order.iter().map(|&i| vertices[i].clone()).collect();
+        values = order.iter().map(|&i| values[i]).collect();
+        let (best, worst) = (values[0], values[n]);
+        if worst - best <= control.reltol * (best.abs() + control.reltol) {
+            converged = true;
+            break;
+        }
+
+        let centroid: Vec<f64> = (0..n)
+            .map(|j| vertices[..n].iter().map(|v| v[j]).sum::<f64>() / n as f64)
+            .collect();
+        let towards = |coefficient: f64| -> Vec<f64> {
+            (0..n)
+                .map(|j| centroid[j] + coefficient * (vertices[n][j] - centroid[j]))
+                .collect()
+        };
+        let reflected = towards(-alpha);
+        let reflected_value = evaluate(&reflected);
+        if reflected_value < best {
+            let expanded = towards(-gamma);
+            let expanded_value = evaluate(&expanded);
+            (vertices[n], values[n]) = if expanded_value < reflected_value {
+                (expanded, expanded_value)
+            } else {
+                (reflected, reflected_value)
+            };
+        } else if reflected_value < values[n - 1] {
+            (vertices[n], values[n]) = (reflected, reflected_value);
+        } else {
+            let contracted = if reflected_value < worst {
+                towards(-beta)
+            } else {
+                towards(beta)
+            };
+            let contracted_value = evaluate(&contracted);
+            if contracted_value < worst.min(reflected_value) {
+                (vertices[n], values[n]) = (contracted, contracted_value);
+            } else {
+                // Shrink the simplex towards the best vertex.
+                for i in 1..=n {
+                    vertices[i] = (0..n)
+                        .map(|j| vertices[0][j] + 0.5 * (vertices[i][j] - vertices[0][j]))
+                        .collect();
+                    values[i] = evaluate(&vertices[i]);
+                }
+            }
+        }
+    }
+
+    let best = (0..=n)
+        .min_by(|&a, &b| values[a].total_cmp(&values[b]))
+        .unwrap_or(0);
+    Minimum {
+        point: vertices[best].clone(),
+        value: values[best],
+        evaluations: count.get(),
+        converged,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nelder_mead_finds_the_rosenbrock_minimum() {
+        let rosenbrock = |x: &[f64]| 100.0 * (x[1] - x[0] * x[0]).powi(2) + (1.0 - x[0]).powi(2);
+        let minimum = nelder_mead(&rosenbrock, &[-1.2, 1.0], &NelderMeadControl::default());
+        assert!(minimum.converged);
+        assert!((minimum.point[0] - 1.0).abs() < 1e-4);
+        assert!((minimum.point[1] - 1.0).abs() < 1e-4);
+
+        // Values outside the domain are rejected rather than followed.
+        let bounded = |x: &[f64]| {
+            if x[0] <= 0.0 {
+                f64::NAN
+            } else {
+                x[0] - x[0].ln()
+            }
+        };
+        let minimum = nelder_mead(&bounded, &[3.0], &NelderMeadControl::default());
+        assert!((minimum.point[0] - 1.0).abs() < 1e-4);
+    }
+}
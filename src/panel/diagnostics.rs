@@ -0,0 +1,232 @@
+//! This module contains specification tests for panel data models fitted by `PanelFitter`:
+//!
+//! * `hausman`: the Hausman test that the random effects are uncorrelated with the regressors,
+//!   comparing the coefficients of the within and random effects fits, like `plm::phtest`.
+//! * `wooldridge_first_difference`: Wooldridge's test for serial correlation, regressing the
+//!   first-difference residuals on their lag, which should have slope -0.5 if the idiosyncratic
+//!   errors are serially uncorrelated, like Stata's `xtserial`.
+//! * `wooldridge_within`: Wooldridge's test on the residuals of the within fit, whose lag slope
+//!   should be -1 / (T - 1), like `plm::pwartest`.
+//!
+//! Both Wooldridge tests use standard errors clustered by entity and report the squared t
+//! statistic as an F test on 1 and N - 1 degrees of freedom.
+
+// src/panel/diagnostics.rs
+
+use crate::errors::{LmFitterError, PanelError};
+use crate::fitters::panel_fitter::{PanelEstimator, PanelFit};
+use crate::hypothesis::DiagnosticTest;
+use crate::least_squares::dqrls;
+use crate::terms::INTERCEPT_LABEL;
+use crate::types::{RealMatrix, Tolerance};
+use crate::vcov::cluster_vcov;
+
+/// Return the Hausman test of the random effects fit `random` against the within fit `within`:
+/// H = d' (V_within - V_random)^-1 d over the slopes d that both estimate, chi-squared with one
+/// degree of freedom per slope.
+///
+/// # Errors
+/// Returns `PanelError::InvalidTest` if the fits are not a within and a random effects fit, have
+/// no slopes in common, or if V_within - V_random is singular.
+pub fn hausman(within: &PanelFit, random: &PanelFit) -> Result<DiagnosticTest, PanelError> {
+    let invalid = |needed: &str| PanelError::InvalidTest {
+        test: "Hausman".to_string(),
+        needed: needed.to_string(),
+    };
+    if within.estimator != PanelEstimator::Within
+        || random.estimator != PanelEstimator::RandomEffects
+    {
+        return Err(invalid("a within fit and a random effects fit"));
+    }
+    let common: Vec<(usize, usize)> = within
+        .names
+        .iter()
+        .enumerate()
+        .filter(|(a, name)| *name != INTERCEPT_LABEL && !within.coefficients[*a].is_nan())
+        .filter_map(|(a, name)| {
+            let b = random.names.iter().position(|other| other == name)?;
+            (!random.coefficients[b].is_nan()).then_some((a, b))
+        })
+        .collect();
+    if common.is_empty() {
+        return Err(invalid("slopes estimated by both fits"));
+    }
+
+    let k = common.len();
+    let mut difference = RealMatrix::with_shape(k, 1);
+    let mut covariance = RealMatrix::with_shape(k, k);
+    for (r, &(a, b)) in common.iter().enumerate() {
+        difference.values[[r, 0]] = within.coefficients[a] - random.coefficients[b];
+        for (c, &(a2, b2)) in common.iter().enumerate() {
+            covariance.values[[r, c]] =
+                within.covariance.values[[a, a2]] - random.covariance.values[[b, b2]];
+        }
+    }
+    let inverse = covariance
+        .inverse()
+        .ok_or_else(|| invalid("a nonsingular difference of the covariances"))?;
+    let statistic = difference.transpose().dot(&inverse).dot(&difference).values[[0, 0]];
+    Ok(DiagnosticTest::chi_squared(statistic, k))
+}
+
+/// Return Wooldridge's test for serial correlation from a first-difference fit.
+///
+/// # Errors
+/// Returns `PanelError::InvalidTest` if `fit` is not a first-difference fit or too few
+/// residuals have a lag.
+pub fn wooldridge_first_difference(fit: &PanelFit) -> Result<DiagnosticTest, PanelError> {
+    if fit.estimator != PanelEstimator::FirstDifference {
+        return Err(PanelError::InvalidTest {
+            test: "Wooldridge".to_string(),
+            needed: "a first-difference fit".to_string(),
+        });
+    }
+    lag_slope_test(fit, false, -0.5)
+}
+
+/// Return Wooldridge's test for serial correlation from a within fit, with T the mean number of
+/// observations per entity.
+///
+/// # Errors
+/// Returns `PanelError::InvalidTest` if `fit` is not a within fit, the entities have fewer than
+/// three observations on average, or too few residuals have a lag.
+pub fn wooldridge_within(fit: &PanelFit) -> Result<DiagnosticTest, PanelError> {
+    let periods = fit.n_observations as f64 / fit.n_entities as f64;
+    if fit.estimator != PanelEstimator::Within || periods <= 2.0 {
+        return Err(PanelError::InvalidTest {
+            test: "Wooldridge".to_string(),
+            needed: "a within fit with more than two periods per entity".to_string(),
+        });
+    }
+    lag_slope_test(fit, true, -1.0 / (periods - 1.0))
+}
+
+/// Regress the residuals of `fit` on their lag, with an intercept if `intercept`, and test that
+/// the slope equals `null` with standard errors clustered by entity.
+fn lag_slope_test(
+    fit: &PanelFit,
+    intercept: bool,
+    null: f64,
+) -> Result<DiagnosticTest, PanelError> {
+    let rows: Vec<(usize, usize)> = fit
+        .index
+        .lags()
+        .into_iter()
+        .enumerate()
+        .filter_map(|(i, lag)| lag.map(|l| (i, l)))
+        .collect();
+    let p = usize::from(intercept) + 1;
+    let mut x = RealMatrix::with_shape(rows.len(), p);
+    let mut y = RealMatrix::with_shape(rows.len(), 1);
+    let mut clusters = Vec::with_capacity(rows.len());
+    for (r, &(i, l)) in rows.iter().enumerate() {
+        if intercept {
+            x.values[[r, 0]] = 1.0;
+        }
+        x.values[[r, p - 1]] = fit.residuals[l];
+        y.values[[r, 0]] = fit.residuals[i];
+        clusters.push(fit.index.entities[i]);
+    }
+    let mut groups = clusters.clone();
+    groups.sort_unstable();
+    groups.dedup();
+    if rows.len() <= p || groups.len() < 2 {
+        return Err(PanelError::InvalidTest {
+            test: "Wooldridge".to_string(),
+            needed: "lagged residuals from at least two entities".to_string(),
+        });
+    }
+
+    let regression = dqrls(&x, &y, &Tolerance::default()).map_err(LmFitterError::from)?;
+    let covariance = cluster_vcov(&regression, &x, 0, &clusters)?;
+    let slope = regression.coefficients.values[[p - 1, 0]];
+    let t = (slope - null) / covariance.values[[p - 1, p - 1]].sqrt();
+    Ok(DiagnosticTest::f(t * t, 1, groups.len() - 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fitters::panel_fitter::PanelFitter;
+    use crate::panel::PanelIndex;
+    use crate::types::Data;
+
+    /// A panel of 40 entities over 8 periods with AR(1) idiosyncratic errors with coefficient
+    /// `rho`, and entity effects correlated with x.
+    fn panel(rho: f64) -> (Data, PanelIndex) {
+        let (entities, periods) = (40, 8);
+        let mut state: u64 = 12345;
+        let mut noise = || {
+            // The sum of four uniforms from a linear congruential generator, centered.
+            (0..4)
+                .map(|_| {
+                    state = state
+                        .wrapping_mul(6364136223846793005)
+                        .wrapping_add(1442695040888963407);
+                    (state >> 11) as f64 / (1u64 << 53) as f64
+                })
+                .sum::<f64>()
+                - 2.0
+        };
+        let n = entities * periods;
+        let mut x = RealMatrix::with_shape(n, 2);
+        let mut y = RealMatrix::with_shape(n, 1);
+        let mut index = PanelIndex::new(Vec::new(), Vec::new());
+        for e in 0..entities {
+            let effect = noise();
+            let mut error = noise();
+            for t in 0..periods {
+                let i = e * periods + t;
+                error = rho * error + noise();
+                let xi = noise() + effect;
+                x.values[[i, 0]] = 1.0;
+                x.values[[i, 1]] = xi;
+                y.values[[i, 0]] = 1.0 + xi + 2.0 * effect + error;
+                index.entities.push(e);
+                index.times.push(t);
+            }
+        }
+        let names = ["(Intercept)", "x"].map(String::from).to_vec();
+        (Data::new(x, y).with_column_names(names), index)
+    }
+
+    #[test]
+    fn test_hausman_and_serial_correlation_tests() {
+        let (data, index) = panel(0.0);
+        let fit = |estimator| {
+            PanelFitter::new(&data, index.clone(), estimator)
+                .panel()
+                .unwrap()
+        };
+        let (within, random) = (
+            fit(PanelEstimator::Within),
+            fit(PanelEstimator::RandomEffects),
+        );
+        let test = hausman(&within, &random).unwrap();
+        assert_eq!((test.df1, test.df2), (1, None));
+        assert!(test.p_value < 0.01);
+        assert!(hausman(&random, &within).is_err());
+
+        // Serially uncorrelated errors pass both tests, and strongly correlated ones fail them.
+        let first_difference = fit(PanelEstimator::FirstDifference);
+        let test = wooldridge_first_difference(&first_difference).unwrap();
+        assert_eq!((test.df1, test.df2), (1, Some(39)));
+        assert!(test.p_value > 0.01);
+        assert!(wooldridge_within(&within).unwrap().p_value > 0.01);
+
+        let (data, index) = panel(0.9);
+        let fit = |estimator| {
+            PanelFitter::new(&data, index.clone(), estimator)
+                .panel()
+                .unwrap()
+        };
+        let test = wooldridge_first_difference(&fit(PanelEstimator::FirstDifference)).unwrap();
+        assert!(test.p_value < 0.01);
+        assert!(
+            wooldridge_within(&fit(PanelEstimator::Within))
+                .unwrap()
+                .p_value
+                < 0.01
+        );
+    }
+}
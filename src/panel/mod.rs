@@ -0,0 +1,110 @@
+//! This module contains the building blocks of panel data models, which are fitted by
+//! `fitters::panel_fitter`. The repository has no data frame, so a panel is a `Data` struct
+//! together with a `PanelIndex` giving the entity and the time period of each row.
+//!
+//! * `PanelIndex`: the entity and time of each observation, with the renumbered entities and
+//!   the lag of each observation within its entity.
+//! * `diagnostics`: the Hausman test of fixed against random effects and Wooldridge's tests for
+//!   serial correlation of the idiosyncratic errors.
+
+// src/panel/mod.rs
+
+pub mod diagnostics;
+
+use crate::errors::PanelError;
+use std::collections::{HashMap, HashSet};
+
+/// The entity and time period of each observation of a panel.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PanelIndex {
+    /// The entity code of each observation.
+    pub entities: Vec<usize>,
+    /// The time period of each observation. Consecutive periods differ by one.
+    pub times: Vec<usize>,
+}
+
+impl PanelIndex {
+    /// Create a new `PanelIndex` struct.
+    pub fn new(entities: Vec<usize>, times: Vec<usize>) -> Self {
+        PanelIndex { entities, times }
+    }
+
+    /// Return the number of observations.
+    pub fn n(&self) -> usize {
+        self.entities.len()
+    }
+
+    /// Check that there is one entity and one time per observation, and no more than one
+    /// observation per entity and time.
+    ///
+    /// # Errors
+    /// Returns `PanelError::IndexMismatch` or `PanelError::DuplicateObservation`.
+    pub fn validate(&self, n: usize) -> Result<(), PanelError> {
+        for found in [self.entities.len(), self.times.len()] {
+            if found != n {
+                return Err(PanelError::IndexMismatch { expected: n, found });
+            }
+        }
+        let mut seen = HashSet::new();
+        for (&entity, &time) in self.entities.iter().zip(&self.times) {
+            if !seen.insert((entity, time)) {
+                return Err(PanelError::DuplicateObservation { entity, time });
+            }
+        }
+        Ok(())
+    }
+
+    /// Return the entity of each observation renumbered 0, 1, ... in order of first appearance,
+    /// with the number of entities.
+    pub fn entity_codes(&self) -> (Vec<usize>, usize) {
+        let mut renumbered: HashMap<usize, usize> = HashMap::new();
+        let codes = self
+            .entities
+            .iter()
+            .map(|&entity| {
+                let next = renumbered.len();
+                *renumbered.entry(entity).or_insert(next)
+            })
+            .collect();
+        (codes, renumbered.len())
+    }
+
+    /// Return, for each observation, the row of the same entity in the previous time period, or
+    /// `None` at the start of the entity's series and after a gap.
+    pub fn lags(&self) -> Vec<Option<usize>> {
+        let rows: HashMap<(usize, usize), usize> = self
+            .entities
+            .iter()
+            .zip(&self.times)
+            .enumerate()
+            .map(|(i, (&entity, &time))| ((entity, time), i))
+            .collect();
+        self.entities
+            .iter()
+            .zip(&self.times)
+            .map(|(&entity, &time)| {
+                let previous = time.checked_sub(1)?;
+                rows.get(&(entity, previous)).copied()
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lags_skip_gaps_and_entity_boundaries() {
+        let index = PanelIndex::new(vec![7, 7, 7, 3, 3], vec![1, 2, 4, 2, 1]);
+        assert!(index.validate(5).is_ok());
+        assert_eq!(index.entity_codes(), (vec![0, 0, 0, 1, 1], 2));
+        assert_eq!(index.lags(), vec![None, Some(0), None, Some(4), None]);
+
+        let duplicated = PanelIndex::new(vec![1, 1], vec![2, 2]);
+        assert!(matches!(
+            duplicated.validate(2),
+            Err(PanelError::DuplicateObservation { entity: 1, time: 2 })
+        ));
+    }
+}
@@ -128,4 +128,186 @@ impl RealMatrix {
     pub fn ndim(&self) -> usize {
         self.values.ndim()
     }
+
+    /// Return the inverse of a square matrix, computed by Gauss-Jordan elimination with partial
+    /// pivoting. Returns `None` if the matrix is not square or is numerically singular.
+    pub fn inverse(&self) -> Option<RealMatrix> {
+        let n = self.n_rows();
+        if n != self.n_cols() {
+            return None;
+        }
+
+        let mut a = self.values.to_owned();
+        let mut inv = Array2::<f64>::eye(n);
+        let scale = a.iter().fold(0.0_f64, |acc, v| acc.max(v.abs()));
+        for col in 0..n {
+            let pivot =
+                (col..n).max_by(|&i, &j| a[[i, col]].abs().total_cmp(&a[[j, col]].abs()))?;
+            if a[[pivot, col]].abs() <= f64::EPSILON * scale * n as f64 {
+                return None;
+            }
+            for j in 0..n {
+                a.swap([col, j], [pivot, j]);
+                inv.swap([col, j], [pivot, j]);
+            }
+
+            let d = a[[col, col]];
+            for j in 0..n {
+                a[[col, j]] /= d;
+                inv[[col, j]] /= d;
+            }
+            for i in (0..n).filter(|&i| i != col) {
+                let factor = a[[i, col]];
+                if factor == 0.0 {
+                    continue;
+                }
+                for j in 0..n {
+                    a[[i, j]] -= factor * a[[col, j]];
+                    inv[[i, j]] -= factor * inv[[col, j]];
+                }
+            }
+        }
+        Some(RealMatrix::new(inv))
+    }
+
+    /// Return the lower triangular Cholesky factor L of a symmetric positive definite matrix,
+    /// such that L L' equals the matrix. Returns `None` if the matrix is not square or not
+    /// positive definite.
+    pub fn cholesky(&self) -> Option<RealMatrix> {
+        let n = self.n_rows();
+        if n != self.n_cols() {
+            return None;
+        }
+
+        let mut l = Array2::<f64>::zeros((n, n));
+        for j in 0..n {
+            let diagonal = self.values[[j, j]] - (0..j).map(|k| l[[j, k]].powi(2)).sum::<f64>();
+            if diagonal <= 0.0 {
+                return None;
+            }
+            l[[j, j]] = diagonal.sqrt();
+            for i in (j + 1)..n {
+                let off_diagonal: f64 = (0..j).map(|k| l[[i, k]] * l[[j, k]]).sum();
+                l[[i, j]] = (self.values[[i, j]] - off_diagonal) / l[[j, j]];
+            }
+        }
+        Some(RealMatrix::new(l))
+    }
+
+    /// Return the eigenvalues, in decreasing order, and the matching unit eigenvectors (as
+    /// columns) of a symmetric matrix, computed by cyclic Jacobi rotations. Returns `None` if the
+    /// matrix is not square.
+    pub fn symmetric_eigen(&self) -> Option<(Vec<f64>, RealMatrix)> {
+        let n = self.n_rows();
+        if n != self.n_cols() {
+            return None;
+        }
+
+        let mut a = self.values.to_owned();
+        let mut v = Array2::<f64>::eye(n);
+        let scale = a.iter().map(|x| x * x).sum::<f64>().sqrt();
+        for _ in 0..100 {
+            let off_diagonal: f64 = (0..n)
+                .flat_map(|i| (0..n).filter(move |&j| j != i).map(move |j| (i, j)))
+                .map(|(i, j)| a[[i, j]].powi(2))
+                .sum();
+            if off_diagonal.sqrt() <= 1e-15 * scale {
+                break;
+            }
+            for p in 0..n {
+                for q in (p + 1)..n {
+                    if a[[p, q]] == 0.0 {
+                        continue;
+                    }
+                    // Choose the rotation that zeroes a[p, q].
+                    let theta = (a[[q, q]] - a[[p, p]]) / (2.0 * a[[p, q]]);
+                    let t = theta.signum() / (theta.abs() + (theta * theta + 1.0).sqrt());
+                    let c = 1.0 / (t * t + 1.0).sqrt();
+                    let s = t * c;
+                    for k in 0..n {
+                        let (akp, akq) = (a[[k, p]], a[[k, q]]);
+                        a[[k, p]] = c * akp - s * akq;
+                        a[[k, q]] = s * akp + c * akq;
+                    }
+                    for k in 0..n {
+                        let (apk, aqk) = (a[[p, k]], a[[q, k]]);
+                        a[[p, k]] = c * apk - s * aqk;
+                        a[[q, k]] = s * apk + c * aqk;
+                    }
+                    for k in 0..n {
+                        let (vkp, vkq) = (v[[k, p]], v[[k, q]]);
+                        v[[k, p]] = c * vkp - s * vkq;
+                        v[[k, q]] = s * vkp + c * vkq;
+                    }
+                }
+            }
+        }
+
+        let mut order: Vec<usize> = (0..n).collect();
+        order.sort_by(|&i, &j| a[[j, j]].total_cmp(&a[[i, i]]));
+        let values = order.iter().map(|&i| a[[i, i]]).collect();
+        let vectors = v.select(ndarray::Axis(1), &order);
+        Some((values, RealMatrix::new(vectors)))
+    }
+
+    /// Return the thin singular value decomposition U D V' of the matrix: the singular values
+    /// in decreasing order, with U (n x r) and V (p x r) for r = min(n, p). The decomposition is
+    /// computed by one-sided Jacobi rotations of the columns, which never forms the cross product
+    /// of the matrix and so keeps the small singular values accurate. Columns of U whose singular
+    /// value is zero are zero.
+    pub fn svd(&self) -> (RealMatrix, Vec<f64>, RealMatrix) {
+        if self.n_rows() < self.n_cols() {
+            let (v, d, u) = self.transpose().svd();
+            return (u, d, v);
+        }
+
+        // Rotate pairs of columns of A V until they are orthogonal; they are then U D.
+        let p = self.n_cols();
+        let mut a = self.values.to_owned();
+        let mut v = Array2::<f64>::eye(p);
+        for _ in 0..100 {
+            let mut rotated = false;
+            for i in 0..p {
+                for j in (i + 1)..p {
+                    let alpha = a.column(i).dot(&a.column(i));
+                    let beta = a.column(j).dot(&a.column(j));
+                    let gamma = a.column(i).dot(&a.column(j));
+                    if gamma == 0.0 || gamma.abs() <= f64::EPSILON * (alpha * beta).sqrt() {
+                        continue;
+                    }
+                    rotated = true;
+                    // Choose the rotation that makes columns i and j orthogonal.
+                    let zeta = (beta - alpha) / (2.0 * gamma);
+                    let t = zeta.signum() / (zeta.abs() + (zeta * zeta + 1.0).sqrt());
+                    let c = 1.0 / (t * t + 1.0).sqrt();
+                    let s = t * c;
+                    for matrix in [&mut a, &mut v] {
+                        for k in 0..matrix.nrows() {
+                            let (mki, mkj) = (matrix[[k, i]], matrix[[k, j]]);
+                            matrix[[k, i]] = c * mki - s * mkj;
+                            matrix[[k, j]] = s * mki + c * mkj;
+                        }
+                    }
+                }
+            }
+            if !rotated {
+                break;
+            }
+        }
+
+        let norms: Vec<f64> = (0..p)
+            .map(|j| a.column(j).dot(&a.column(j)).sqrt())
+            .collect();
+        let mut order: Vec<usize> = (0..p).collect();
+        order.sort_by(|&i, &j| norms[j].total_cmp(&norms[i]));
+        let mut u = a.select(ndarray::Axis(1), &order);
+        for (mut column, &j) in u.columns_mut().into_iter().zip(&order) {
+            if norms[j] > 0.0 {
+                column /= norms[j];
+            }
+        }
+        let d = order.iter().map(|&j| norms[j]).collect();
+        let v = v.select(ndarray::Axis(1), &order);
+        (RealMatrix::new(u), d, RealMatrix::new(v))
+    }
 }
@@ -0,0 +1,465 @@
+//! This module implements exact best-subset regression by branch and bound, like R's
+//! `leaps::regsubsets`.
+//!
+//! The search never touches the n rows of the data after the first step: the QR decomposition
+//! of [X y] reduces the problem to its (p + 1) x (p + 1) R factor, whose cross-products equal
+//! those of [X y]. Every node of the search tree is the set of models that contain a fixed set
+//! of columns and any subset of the free columns. Decomposing the columns of a node in order
+//! gives the residual sum of squares of every prefix at once (the Furnival-Wilson trick), and the
+//! residual sum of squares of the whole node is a lower bound for all of its models, so a node is
+//! skipped as soon as that bound is no better than the models already kept for every size it
+//! could produce.
+
+// src/selection/leaps.rs
+
+use crate::anova::{format_number, write_table};
+use crate::errors::SelectionError;
+use crate::least_squares::QrDecomposition;
+use crate::types::{Data, RealMatrix, Tolerance};
+use crate::vcov::unscaled_covariance;
+use std::fmt;
+
+/// The largest number of candidate columns the search accepts.
+pub const MAX_SUBSET_COLUMNS: usize = 64;
+
+/// An enum representing the criteria used to rank models of different sizes.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum SubsetCriterion {
+    /// The residual sum of squares, which always favours the largest model.
+    Rss,
+    /// The adjusted coefficient of determination.
+    AdjustedRSquared,
+    /// Mallows' Cp, using the residual variance of the model with every column.
+    Cp,
+    /// The Bayesian information criterion n log(RSS / RSS0) + size log(n), as in `leaps`.
+    #[default]
+    Bic,
+}
+
+/// One model found by `best_subsets`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SubsetModel {
+    /// The candidate columns in the model, as indices into the columns of x.
+    pub columns: Vec<usize>,
+    /// The names of the candidate columns in the model.
+    pub names: Vec<String>,
+    /// The number of candidate columns in the model, not counting forced columns.
+    pub size: usize,
+    /// The residual sum of squares.
+    pub rss: f64,
+    /// The coefficient of determination relative to the model with only the forced columns.
+    pub r_squared: f64,
+    /// The adjusted coefficient of determination.
+    pub adj_r_squared: f64,
+    /// Mallows' Cp.
+    pub cp: f64,
+    /// The Bayesian information criterion.
+    pub bic: f64,
+}
+
+impl SubsetModel {
+    /// Return the value of `criterion` for the model, oriented so that lower is better.
+    fn score(&self, criterion: SubsetCriterion) -> f64 {
+        match criterion {
+            SubsetCriterion::Rss => self.rss,
+            SubsetCriterion::AdjustedRSquared => -self.adj_r_squared,
+            SubsetCriterion::Cp => self.cp,
+            SubsetCriterion::Bic => self.bic,
+        }
+    }
+}
+
+/// The best models of each size, like the summary of R's `regsubsets`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BestSubsets {
+    /// The names of the candidate columns.
+    pub candidates: Vec<String>,
+    /// The models, ordered by size and then by residual sum of squares.
+    pub models: Vec<SubsetModel>,
+}
+
+impl BestSubsets {
+    /// Return the models with `size` candidate columns, best first.
+    pub fn of_size(&self, size: usize) -> Vec<&SubsetModel> {
+        self.models.iter().filter(|m| m.size == size).collect()
+    }
+
+    /// Return every model, ordered from best to worst by `criterion`.
+    pub fn ranked(&self, criterion: SubsetCriterion) -> Vec<&SubsetModel> {
+        let mut models: Vec<&SubsetModel> = self.models.iter().collect();
+        models.sort_by(|a, b| a.score(criterion).total_cmp(&b.score(criterion)));
+        models
+    }
+
+    /// Return the best model by `criterion`.
+    pub fn best(&self, criterion: SubsetCriterion) -> Option<&SubsetModel> {
+        self.ranked(criterion).into_iter().next()
+    }
+}
+
+/// Print the models as a table of included columns, like R's `summary.regsubsets`.
+impl fmt::Display for BestSubsets {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Subset selection\n")?;
+        let mut header: Vec<&str> = self.candidates.iter().map(String::as_str).collect();
+        header.extend(["RSS", "Adj R2", "Cp", "BIC"]);
+
+        let mut rank = 0;
+        let mut labels = Vec::new();
+        let mut cells = Vec::new();
+        for (i, model) in self.models.iter().enumerate() {
+            rank = match i {
+                0 => 1,
+                _ if self.models[i - 1].size == model.size => rank + 1,
+                _ => 1,
+            };
+            labels.push(format!("{} ({})", model.size, rank));
+            let mut row: Vec<String> = self
+                .candidates
+                .iter()
+                .map(|name| match model.names.contains(name) {
+                    true => "*".to_string(),
+                    false => String::new(),
+                })
+                .collect();
+            row.extend([model.rss, model.adj_r_squared, model.cp, model.bic].map(format_number));
+            cells.push(row);
+        }
+        let labels: Vec<&str> = labels.iter().map(String::as_str).collect();
+        write_table(f, &labels, &header, &cells)
+    }
+}
+
+/// Find the `nbest` models with the lowest residual sum of squares for each number of candidate
+/// columns up to `nvmax`.
+///
+/// The intercept columns (term `0` of `data.terms()`) are forced into every model, and every
+/// other column of x is a candidate. Within a size the models are ordered by residual sum of
+/// squares, which orders them by every criterion of `SubsetCriterion` at once.
+///
+/// # Arguments
+/// * `data` - The data, with the observation weights if any.
+/// * `nbest` - The number of models kept for each size.
+/// * `nvmax` - The largest number of candidate columns in a model. If `None`, every size is
+///   searched.
+///
+/// # Errors
+/// Returns `SelectionError::NoModelsKept` if `nbest` is zero,
+/// `SelectionError::MultipleResponses` if `data` has more than one response, and
+/// `SelectionError::TooManyColumns` if there are more than `MAX_SUBSET_COLUMNS` candidates.
+pub fn best_subsets(
+    data: &Data,
+    nbest: usize,
+    nvmax: Option<usize>,
+) -> Result<BestSubsets, SelectionError> {
+    if nbest == 0 {
+        return Err(SelectionError::NoModelsKept);
+    }
+    if data.y().n_cols() != 1 {
+        return Err(SelectionError::MultipleResponses {
+            found: data.y().n_cols(),
+        });
+    }
+    let terms = data.terms();
+    let (base, candidates): (Vec<usize>, Vec<usize>) =
+        (0..data.x().n_cols()).partition(|&j| terms.assign[j] == 0);
+    if candidates.len() > MAX_SUBSET_COLUMNS {
+        return Err(SelectionError::TooManyColumns {
+            found: candidates.len(),
+            max: MAX_SUBSET_COLUMNS,
+        });
+    }
+
+    let mut search = Search::new(data, base, candidates, nbest, nvmax);
+    let free: Vec<usize> = (0..search.candidates.len()).collect();
+    search.explore(&[], &free);
+    Ok(search.summarise(data))
+}
+
+/// The state of the branch and bound search.
+struct Search {
+    /// The R factor of [X y], with its columns in the original order of x and y last.
+    reduced: RealMatrix,
+    /// The last column of `reduced`.
+    response: Vec<f64>,
+    /// The squared norm of `response`.
+    total: f64,
+    /// The columns of x in every model.
+    base: Vec<usize>,
+    /// The candidate columns of x. Subsets refer to them by their position in this vector.
+    candidates: Vec<usize>,
+    nbest: usize,
+    nvmax: usize,
+    /// For each size, the best subsets found so far as bit masks, sorted by residual sum of
+    /// squares.
+    best: Vec<Vec<(u64, f64)>>,
+    tol: Tolerance,
+}
+
+impl Search {
+    fn new(
+        data: &Data,
+        base: Vec<usize>,
+        candidates: Vec<usize>,
+        nbest: usize,
+        nvmax: Option<usize>,
+    ) -> Self {
+        let x = data.weighted_x();
+        let y = data.weighted_y();
+        let p = x.n_cols();
+        let mut xy = RealMatrix::with_shape(x.n_rows(), p + 1);
+        xy.values.slice_mut(ndarray::s![.., ..p]).assign(&x.values);
+        xy.values.column_mut(p).assign(&y.values.column(0));
+
+        // The Householder reduction runs over every column whatever the rank, so R'R = [X y]'[X y]
+        // once the pivoting is undone.
+        let tol = Tolerance::default();
+        let qr = QrDecomposition::new(&xy, &tol);
+        let r = qr.r();
+        let mut reduced = RealMatrix::with_shape(r.n_rows(), p + 1);
+        for (j, &original) in qr.pivot.iter().enumerate() {
+            reduced
+                .values
+                .column_mut(original)
+                .assign(&r.values.column(j));
+        }
+        let response = reduced.values.column(p).to_vec();
+
+        let nvmax = nvmax.unwrap_or(candidates.len()).min(candidates.len());
+        Search {
+            total: response.iter().map(|v| v * v).sum(),
+            reduced,
+            response,
+            base,
+            candidates,
+            nbest,
+            nvmax,
+            best: vec![Vec::new(); nvmax + 1],
+            tol,
+        }
+    }
+
+    /// Decompose the base columns followed by the candidates `order`, returning the
+    /// decomposition and the effects of the response.
+    fn decompose(&self, order: &[usize]) -> (QrDecomposition, Vec<f64>) {
+        let columns: Vec<usize> = self
+            .base
+            .iter()
+            .copied()
+            .chain(order.iter().map(|&i| self.candidates[i]))
+            .collect();
+        let x = RealMatrix::new(self.reduced.values.select(ndarray::Axis(1), &columns));
+        let qr = QrDecomposition::new(&x, &self.tol);
+        let effects = qr.qty(&self.response);
+        (qr, effects)
+    }
+
+    /// Return the residual sum of squares after projecting onto the first `k` pivoted columns.
+    fn rss(&self, effects: &[f64], k: usize) -> f64 {
+        (self.total - effects[..k].iter().map(|e| e * e).sum::<f64>()).max(0.0)
+    }
+
+    /// Search the models that contain `forced` and any subset of `free`.
+    fn explore(&mut self, forced: &[usize], free: &[usize]) {
+        let (qr, effects) = self.decompose(&[forced, free].concat());
+        let bound = self.rss(&effects, qr.rank);
+        let sizes = forced.len().max(1)..=(forced.len() + free.len()).min(self.nvmax);
+        if sizes.clone().all(|size| {
+            self.best[size].len() == self.nbest && self.best[size][self.nbest - 1].1 <= bound
+        }) {
+            return;
+        }
+
+        // Order the free columns by the increase in the residual sum of squares when each is
+        // dropped from the node's largest model, so that the prefixes are good models.
+        let covariance = unscaled_covariance(&qr);
+        let pivoted = qr.backsolve(&effects);
+        let mut coefficients = vec![f64::NAN; qr.n_cols()];
+        for (j, &b) in pivoted.iter().enumerate() {
+            coefficients[qr.pivot[j]] = b;
+        }
+        let offset = self.base.len() + forced.len();
+        let importance = |i: usize| {
+            let j = offset + i;
+            let value = coefficients[j].powi(2) / covariance.values[[j, j]];
+            if value.is_nan() {
+                0.0
+            } else {
+                value
+            }
+        };
+        let mut positions: Vec<usize> = (0..free.len()).collect();
+        positions.sort_by(|&a, &b| importance(b).total_cmp(&importance(a)));
+        let sorted: Vec<usize> = positions.iter().map(|&i| free[i]).collect();
+
+        let order = [forced, &sorted].concat();
+        let (qr, effects) = self.decompose(&order);
+        for k in offset..=qr.rank {
+            let prefix = &qr.pivot[..k];
+            if prefix.iter().filter(|&&j| j < offset).count() < offset {
+                continue;
+            }
+            let mask = prefix
+                .iter()
+                .filter(|&&j| j >= self.base.len())
+                .fold(0u64, |mask, &j| mask | 1 << order[j - self.base.len()]);
+            self.insert(mask, self.rss(&effects, k));
+        }
+
+        if forced.len() < self.nvmax {
+            for (i, &column) in sorted.iter().enumerate() {
+                let mut child = forced.to_vec();
+                child.push(column);
+                self.explore(&child, &sorted[i + 1..]);
+            }
+        }
+    }
+
+    /// Keep the subset `mask` if it is among the best of its size found so far.
+    fn insert(&mut self, mask: u64, rss: f64) {
+        let size = mask.count_ones() as usize;
+        if size == 0 || size > self.nvmax {
+            return;
+        }
+        let models = &mut self.best[size];
+        if models.iter().any(|&(m, _)| m == mask) {
+            return;
+        }
+        if models.len() == self.nbest {
+            if models[self.nbest - 1].1 <= rss {
+                return;
+            }
+            models.pop();
+        }
+        let position = models.partition_point(|&(_, r)| r <= rss);
+        models.insert(position, (mask, rss));
+    }
+
+    /// Compute the summary statistics of the models that were kept.
+    fn summarise(&self, data: &Data) -> BestSubsets {
+        let n = data.x().n_rows() as f64;
+        let (qr, effects) = self.decompose(&[]);
+        let null_rss = self.rss(&effects, qr.rank);
+        let n_base = qr.rank as f64;
+
+        let all: Vec<usize> = (0..self.candidates.len()).collect();
+        let (qr, effects) = self.decompose(&all);
+        let sigma2 = self.rss(&effects, qr.rank) / (n - qr.rank as f64);
+
+        let names = data.column_names();
+        let models = self
+            .best
+            .iter()
+            .enumerate()
+            .flat_map(|(size, models)| models.iter().map(move |&(mask, rss)| (size, mask, rss)))
+            .map(|(size, mask, rss)| {
+                let columns: Vec<usize> = (0..self.candidates.len())
+                    .filter(|&i| mask & (1 << i) != 0)
+                    .map(|i| self.candidates[i])
+                    .collect();
+                let s = size as f64;
+                SubsetModel {
+                    names: columns.iter().map(|&j| names[j].clone()).collect(),
+                    columns,
+                    size,
+                    rss,
+                    r_squared: 1.0 - rss / null_rss,
+                    adj_r_squared: 1.0 - (rss / (n - n_base - s)) / (null_rss / (n - n_base)),
+                    cp: rss / sigma2 + 2.0 * (s + n_base) - n,
+                    bic: n * (rss / null_rss).ln() + s * n.ln(),
+                }
+            })
+            .collect();
+
+        BestSubsets {
+            candidates: self.candidates.iter().map(|&j| names[j].clone()).collect(),
+            models,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::least_squares::dqrls;
+
+    fn data() -> Data {
+        let n = 15;
+        let mut values = Vec::new();
+        let mut y = Vec::new();
+        for i in 0..n {
+            let t = i as f64;
+            let row = [
+                1.0,
+                t,
+                (t * 1.7).sin(),
+                ((i * i) % 7) as f64,
+                (t * 0.3).cos(),
+                ((i * 5) % 4) as f64,
+                t.sqrt(),
+            ];
+            y.push(2.0 + 0.5 * row[1] - 3.0 * row[2] + row[5] + 0.2 * (t * 2.3).sin());
+            values.extend(row);
+        }
+        Data::new(
+            RealMatrix::from_vec(values, n, Some(7)),
+            RealMatrix::from_vec(y, n, None),
+        )
+        .with_column_names(
+            ["(Intercept)", "a", "b", "c", "d", "e", "f"]
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+        )
+    }
+
+    fn brute_force(data: &Data, size: usize) -> Vec<f64> {
+        let mut rss: Vec<f64> = (0u32..64)
+            .filter(|mask| mask.count_ones() as usize == size)
+            .map(|mask| {
+                let columns: Vec<usize> = std::iter::once(0)
+                    .chain((0..6).filter(|i| mask & (1 << i) != 0).map(|i| i + 1))
+                    .collect();
+                let x = RealMatrix::new(data.x().values.select(ndarray::Axis(1), &columns));
+                let fit = dqrls(&x, data.y(), &Tolerance::default()).unwrap();
+                fit.residuals.values.iter().map(|r| r * r).sum()
+            })
+            .collect();
+        rss.sort_by(f64::total_cmp);
+        rss
+    }
+
+    #[test]
+    fn test_best_subsets_match_exhaustive_search() {
+        let data = data();
+        let subsets = best_subsets(&data, 3, None).unwrap();
+
+        for size in 1..=6 {
+            let expected = brute_force(&data, size);
+            let found = subsets.of_size(size);
+            assert_eq!(found.len(), expected.len().min(3));
+            for (model, rss) in found.iter().zip(&expected) {
+                assert!((model.rss - rss).abs() < 1e-8);
+            }
+        }
+        assert_eq!(subsets.of_size(3)[0].names, vec!["a", "b", "e"]);
+    }
+
+    #[test]
+    fn test_criteria_rank_models_across_sizes() {
+        let data = data();
+        let subsets = best_subsets(&data, 1, Some(4)).unwrap();
+
+        assert_eq!(subsets.models.len(), 4);
+        assert_eq!(subsets.best(SubsetCriterion::Rss).unwrap().size, 4);
+        assert_eq!(
+            subsets.best(SubsetCriterion::Bic).unwrap().names,
+            vec!["a", "b", "e"]
+        );
+        assert!(subsets.to_string().contains("3 (1)"));
+        assert!(matches!(
+            best_subsets(&data, 0, None),
+            Err(SelectionError::NoModelsKept)
+        ));
+    }
+}
@@ -0,0 +1,33 @@
+//! This module contains model selection procedures built on the least squares machinery.
+//!
+//! * `step`: R's `add1`, `drop1` and `step`, searching over model terms by AIC or BIC while
+//!   respecting marginality.
+//! * `leaps`: exact best-subset regression by branch and bound, like `leaps::regsubsets`.
+
+// src/selection/mod.rs
+
+pub mod leaps;
+pub mod step;
+
+/// An enum representing the information criteria used to compare candidate models.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Criterion {
+    /// The Akaike information criterion, with a penalty of 2 per parameter.
+    #[default]
+    Aic,
+    /// The Bayesian information criterion, with a penalty of log(n) per parameter.
+    Bic,
+    /// A criterion with a custom penalty per parameter, like the `k` argument of R's `step`.
+    Penalty(f64),
+}
+
+impl Criterion {
+    /// Return the penalty per parameter for a model fitted to `n` observations.
+    pub fn penalty(&self, n: usize) -> f64 {
+        match self {
+            Criterion::Aic => 2.0,
+            Criterion::Bic => (n as f64).ln(),
+            Criterion::Penalty(k) => *k,
+        }
+    }
+}
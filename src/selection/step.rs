@@ -0,0 +1,638 @@
+//! This module implements R's `drop1`, `add1` and `step` for linear models.
+//!
+//! The repository has no formula language, so a model is described by a subset of the terms of a
+//! `Data` struct holding every candidate column, and a scope is given as lists of term labels.
+//! The intercept columns (term `0`) are part of every model.
+//!
+//! Candidate models are scored without refitting them. Dropping a term changes the residual sum
+//! of squares by the Wald statistic b' V^-1 b of its coefficients, where V is the relevant block
+//! of (X'X)^-1 from the current QR decomposition. Adding a term changes it by the squared
+//! projection of the current residuals onto the new columns after they are orthogonalised
+//! against the current model with Q. The decomposition of the model chosen at each step is
+//! updated rather than recomputed: the retained columns ahead of the changed term keep their
+//! Householder reflections, and only the remaining columns are reduced again.
+
+// src/selection/step.rs
+
+use crate::anova::{format_number, write_table};
+use crate::errors::SelectionError;
+use crate::least_squares::{LeastSquaresFit, QrDecomposition};
+use crate::selection::Criterion;
+use crate::terms::Terms;
+use crate::types::{Data, RealMatrix, Tolerance};
+use crate::vcov::unscaled_covariance;
+use std::fmt;
+
+/// The range of models searched by `step`, given as sets of term indices into `data.terms()`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scope {
+    /// The terms that every model must contain.
+    pub lower: Vec<usize>,
+    /// The terms that any model may contain.
+    pub upper: Vec<usize>,
+}
+
+impl Scope {
+    /// Create a scope from the labels of the terms in the smallest and largest models.
+    ///
+    /// # Errors
+    /// Returns `SelectionError::UnknownTerm` if a label is not a term of `terms`.
+    pub fn new(terms: &Terms, lower: &[&str], upper: &[&str]) -> Result<Self, SelectionError> {
+        Ok(Scope {
+            lower: resolve(terms, lower)?,
+            upper: resolve(terms, upper)?,
+        })
+    }
+
+    /// Create the scope ranging from the intercept-only model to the model with every term.
+    pub fn full(terms: &Terms) -> Self {
+        Scope {
+            lower: Vec::new(),
+            upper: (1..=terms.n_terms()).collect(),
+        }
+    }
+}
+
+/// An enum representing the moves considered at each step of `step`.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Direction {
+    /// Consider both adding and dropping a term.
+    #[default]
+    Both,
+    /// Only consider dropping a term.
+    Backward,
+    /// Only consider adding a term.
+    Forward,
+}
+
+/// One row of a `drop1`, `add1` or `step` table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SelectionRow {
+    /// The term added or dropped, or `<none>` for the current model.
+    pub change: String,
+    /// The number of coefficients added or dropped. `None` for the current model.
+    pub df: Option<usize>,
+    /// The change in the residual sum of squares. `None` for the current model.
+    pub sum_sq: Option<f64>,
+    /// The residual sum of squares of the candidate model.
+    pub rss: f64,
+    /// The information criterion of the candidate model.
+    pub aic: f64,
+}
+
+/// A table of candidate models, like the output of R's `drop1` and `add1`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SelectionTable {
+    /// The heading printed above the table.
+    pub heading: String,
+    /// The rows of the table, starting with the current model for `drop1` and `add1`.
+    pub rows: Vec<SelectionRow>,
+}
+
+impl SelectionTable {
+    /// Return the row of the table for `change`, if there is one.
+    pub fn row(&self, change: &str) -> Option<&SelectionRow> {
+        self.rows.iter().find(|row| row.change == change)
+    }
+}
+
+impl fmt::Display for SelectionTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}\n", self.heading)?;
+        let header = ["Df", "Sum of Sq", "RSS", "AIC"];
+        let cells: Vec<Vec<String>> = self
+            .rows
+            .iter()
+            .map(|row| {
+                vec![
+                    row.df.map(|df| df.to_string()).unwrap_or_default(),
+                    row.sum_sq.map(format_number).unwrap_or_default(),
+                    format_number(row.rss),
+                    format_number(row.aic),
+                ]
+            })
+            .collect();
+        let labels: Vec<&str> = self.rows.iter().map(|row| row.change.as_str()).collect();
+        write_table(f, &labels, &header, &cells)
+    }
+}
+
+/// The model chosen by `step`, together with the path taken and the printed trace.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StepResult {
+    /// The terms of the selected model, as indices into `data.terms()`.
+    pub terms: Vec<usize>,
+    /// The labels of the terms of the selected model.
+    pub labels: Vec<String>,
+    /// The information criterion of the selected model.
+    pub aic: f64,
+    /// The moves made, starting with the `<none>` row of the starting model, like R's
+    /// `step(...)$anova`.
+    pub path: Vec<SelectionRow>,
+    /// The table of candidate models considered at each step.
+    pub trace: Vec<SelectionTable>,
+}
+
+/// Print the trace of the search, like R's `step(trace = 1)`.
+impl fmt::Display for StepResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for table in &self.trace {
+            writeln!(f, "{table}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Compute the table of single term deletions from the model with terms `model`, like R's
+/// `drop1`. Only terms outside `scope.lower` that are not contained in another term of the model
+/// are dropped.
+///
+/// # Errors
+/// Returns `SelectionError::UnknownTerm` if a label in `model` is unknown, and
+/// `SelectionError::MultipleResponses` if `data` has more than one response.
+pub fn drop1(
+    data: &Data,
+    model: &[&str],
+    scope: &Scope,
+    criterion: Criterion,
+) -> Result<SelectionTable, SelectionError> {
+    let current = CurrentModel::new(data, resolve(&data.terms(), model)?)?;
+    let k = criterion.penalty(current.n_obs());
+    let mut rows = vec![current.none_row(k)];
+    rows.extend(current.drops(scope, k));
+    Ok(SelectionTable {
+        heading: format!("Single term deletions\n\nModel:\n{}", current.describe()),
+        rows,
+    })
+}
+
+/// Compute the table of single term additions to the model with terms `model`, like R's `add1`.
+/// Only terms of `scope.upper` whose lower-order relatives are all in the model are added.
+///
+/// # Errors
+/// Returns `SelectionError::UnknownTerm` if a label in `model` is unknown, and
+/// `SelectionError::MultipleResponses` if `data` has more than one response.
+pub fn add1(
+    data: &Data,
+    model: &[&str],
+    scope: &Scope,
+    criterion: Criterion,
+) -> Result<SelectionTable, SelectionError> {
+    let current = CurrentModel::new(data, resolve(&data.terms(), model)?)?;
+    let k = criterion.penalty(current.n_obs());
+    let mut rows = vec![current.none_row(k)];
+    rows.extend(current.adds(scope, k));
+    Ok(SelectionTable {
+        heading: format!("Single term additions\n\nModel:\n{}", current.describe()),
+        rows,
+    })
+}
+
+/// Select a model by stepwise search over the terms of `data`, like R's `step`.
+///
+/// Starting from the model with terms `start`, each step moves to the candidate with the lowest
+/// information criterion, until no move improves on the current model or `max_steps` moves have
+/// been made.
+///
+/// # Arguments
+/// * `data` - The data holding every column in `scope.upper`, with its terms.
+/// * `start` - The labels of the terms of the starting model.
+/// * `scope` - The smallest and largest models considered.
+/// * `direction` - Whether terms are added, dropped, or both.
+/// * `criterion` - The information criterion that is minimised.
+/// * `max_steps` - The maximum number of moves.
+///
+/// # Errors
+/// Returns `SelectionError::UnknownTerm` if a label in `start` is unknown, and
+/// `SelectionError::MultipleResponses` if `data` has more than one response.
+pub fn step(
+    data: &Data,
+    start: &[&str],
+    scope: &Scope,
+    direction: Direction,
+    criterion: Criterion,
+    max_steps: usize,
+) -> Result<StepResult, SelectionError> {
+    let mut current = CurrentModel::new(data, resolve(&data.terms(), start)?)?;
+    let k = criterion.penalty(current.n_obs());
+    let mut path = vec![current.none_row(k)];
+    let mut trace = Vec::new();
+
+    for step in 0..max_steps {
+        let none = current.none_row(k);
+        let mut rows = Vec::new();
+        if direction != Direction::Forward {
+            rows.extend(current.drops(scope, k).into_iter().map(|row| SelectionRow {
+                change: format!("- {}", row.change),
+                ..row
+            }));
+        }
+        if direction != Direction::Backward {
+            rows.extend(current.adds(scope, k).into_iter().map(|row| SelectionRow {
+                change: format!("+ {}", row.change),
+                ..row
+            }));
+        }
+        rows.push(none.clone());
+        rows.sort_by(|a, b| a.aic.total_cmp(&b.aic));
+
+        let heading = match step {
+            0 => "Start",
+            _ => "Step",
+        };
+        trace.push(SelectionTable {
+            heading: format!(
+                "{heading}:  AIC={}\n{}",
+                format_number(none.aic),
+                current.describe()
+            ),
+            rows: rows.clone(),
+        });
+
+        let best = &rows[0];
+        if best.df.is_none() || best.aic >= none.aic - 1e-7 {
+            break;
+        }
+        let term = current.term_index(&best.change[2..]);
+        let mut terms = current.terms.clone();
+        if best.change.starts_with('-') {
+            terms.retain(|&t| t != term);
+        } else {
+            terms.push(term);
+            terms.sort_unstable();
+        }
+        path.push(best.clone());
+        current = current.update(terms);
+    }
+
+    Ok(StepResult {
+        labels: current
+            .terms
+            .iter()
+            .map(|&t| current.all_terms.label(t).to_string())
+            .collect(),
+        aic: current.none_row(k).aic,
+        terms: current.terms,
+        path,
+        trace,
+    })
+}
+
+/// Map term labels to their indices in `terms`.
+fn resolve(terms: &Terms, labels: &[&str]) -> Result<Vec<usize>, SelectionError> {
+    let mut indices = labels
+        .iter()
+        .map(|label| {
+            (1..=terms.n_terms())
+                .find(|&t| terms.label(t) == *label)
+                .ok_or_else(|| SelectionError::UnknownTerm {
+                    label: label.to_string(),
+                })
+        })
+        .collect::<Result<Vec<usize>, SelectionError>>()?;
+    indices.sort_unstable();
+    indices.dedup();
+    Ok(indices)
+}
+
+/// The fit of the current model of a search, with everything needed to score its neighbours.
+struct CurrentModel<'a> {
+    data: &'a Data,
+    all_terms: Terms,
+    /// The terms of the model, sorted, as indices into `all_terms`.
+    terms: Vec<usize>,
+    /// The columns of `data.x()` in the model, in the order they were decomposed.
+    columns: Vec<usize>,
+    /// The rows of x and y scaled by the square roots of the weights.
+    x: RealMatrix,
+    qr: QrDecomposition,
+    /// The coefficients in the order of `columns`, `NaN` for aliased columns.
+    coefficients: Vec<f64>,
+    residuals: Vec<f64>,
+    rss: f64,
+    tol: Tolerance,
+}
+
+impl<'a> CurrentModel<'a> {
+    fn new(data: &'a Data, terms: Vec<usize>) -> Result<Self, SelectionError> {
+        if data.y().n_cols() != 1 {
+            return Err(SelectionError::MultipleResponses {
+                found: data.y().n_cols(),
+            });
+        }
+        let columns = data.term_columns(&terms);
+        let x = data.weighted_x();
+        let tol = Tolerance::default();
+        let qr = QrDecomposition::new(&select_columns(&x, &columns), &tol);
+        Ok(CurrentModel::solve(data, terms, columns, x, qr, tol))
+    }
+
+    /// Move to the model with terms `terms`, which differs from this one by a term, updating
+    /// the QR decomposition: the retained columns ahead of the first dropped column keep their
+    /// Householder reflections, and only the columns after it and the added columns are reduced.
+    fn update(self, terms: Vec<usize>) -> Self {
+        let wanted = self.data.term_columns(&terms);
+        let ordered: Vec<usize> = self.qr.pivot.iter().map(|&j| self.columns[j]).collect();
+        let keep = ordered[..self.qr.rank]
+            .iter()
+            .take_while(|j| wanted.contains(j))
+            .count();
+        let rest: Vec<usize> = wanted
+            .iter()
+            .copied()
+            .filter(|j| !ordered[..keep].contains(j))
+            .collect();
+
+        let qr = self
+            .qr
+            .extend(keep, &select_columns(&self.x, &rest), &self.tol);
+        let columns = ordered[..keep].iter().chain(&rest).copied().collect();
+        CurrentModel::solve(self.data, terms, columns, self.x, qr, self.tol)
+    }
+
+    /// Solve for the coefficients and residuals of the model from its decomposition.
+    fn solve(
+        data: &'a Data,
+        terms: Vec<usize>,
+        columns: Vec<usize>,
+        x: RealMatrix,
+        qr: QrDecomposition,
+        tol: Tolerance,
+    ) -> Self {
+        let fit = LeastSquaresFit::from_qr(qr, &data.weighted_y());
+        let residuals = fit.residuals.values.column(0).to_vec();
+        let rss = residuals.iter().map(|r| r * r).sum();
+
+        CurrentModel {
+            data,
+            all_terms: data.terms(),
+            terms,
+            columns,
+            x,
+            coefficients: fit.coefficients.values.column(0).to_vec(),
+            qr: fit.qr,
+            residuals,
+            rss,
+            tol,
+        }
+    }
+
+    /// Return the number of observations.
+    fn n_obs(&self) -> usize {
+        self.data.x().n_rows()
+    }
+
+    /// Return the information criterion n log(RSS / n) + k edf of a model, like R's
+    /// `extractAIC.lm`.
+    fn criterion(&self, rss: f64, edf: usize, k: f64) -> f64 {
+        let n = self.n_obs() as f64;
+        n * (rss / n).ln() + k * edf as f64
+    }
+
+    fn none_row(&self, k: f64) -> SelectionRow {
+        SelectionRow {
+            change: "<none>".to_string(),
+            df: None,
+            sum_sq: None,
+            rss: self.rss,
+            aic: self.criterion(self.rss, self.qr.rank, k),
+        }
+    }
+
+    /// Return the index of the term labelled `label`.
+    fn term_index(&self, label: &str) -> usize {
+        (1..=self.all_terms.n_terms())
+            .find(|&t| self.all_terms.label(t) == label)
+            .expect("Candidate rows are labelled by known terms.")
+    }
+
+    /// Score every term that may be dropped without breaking marginality.
+    fn drops(&self, scope: &Scope, k: f64) -> Vec<SelectionRow> {
+        let covariance = unscaled_covariance(&self.qr);
+        self.terms
+            .iter()
+            .filter(|&&t| !scope.lower.contains(&t))
+            .filter(|&&t| !self.terms.iter().any(|&u| self.all_terms.contains(u, t)))
+            .map(|&t| {
+                let positions: Vec<usize> = (0..self.columns.len())
+                    .filter(|&j| {
+                        self.all_terms.assign[self.columns[j]] == t
+                            && !self.coefficients[j].is_nan()
+                    })
+                    .collect();
+                let sum_sq = self.wald_sum_of_squares(&covariance, &positions);
+                let rss = self.rss + sum_sq;
+                SelectionRow {
+                    change: self.all_terms.label(t).to_string(),
+                    df: Some(positions.len()),
+                    sum_sq: Some(sum_sq),
+                    rss,
+                    aic: self.criterion(rss, self.qr.rank - positions.len(), k),
+                }
+            })
+            .collect()
+    }
+
+    /// Return b' V^-1 b for the coefficients at `positions`, the increase in the residual sum of
+    /// squares when they are constrained to zero.
+    fn wald_sum_of_squares(&self, covariance: &RealMatrix, positions: &[usize]) -> f64 {
+        if positions.is_empty() {
+            return 0.0;
+        }
+        let m = positions.len();
+        let mut v = RealMatrix::with_shape(m, m);
+        for (a, &i) in positions.iter().enumerate() {
+            for (b, &j) in positions.iter().enumerate() {
+                v.values[[a, b]] = covariance.values[[i, j]];
+            }
+        }
+        let v_inverse = v
+            .inverse()
+            .expect("The retained block of (X'X)^-1 is invertible.");
+        let b: Vec<f64> = positions.iter().map(|&j| self.coefficients[j]).collect();
+        (0..m)
+            .flat_map(|a| (0..m).map(move |c| (a, c)))
+            .map(|(a, c)| b[a] * v_inverse.values[[a, c]] * b[c])
+            .sum()
+    }
+
+    /// Score every term of the scope that may be added without breaking marginality.
+    fn adds(&self, scope: &Scope, k: f64) -> Vec<SelectionRow> {
+        let candidates: Vec<usize> = scope
+            .upper
+            .iter()
+            .copied()
+            .filter(|t| !self.terms.contains(t))
+            .collect();
+        candidates
+            .iter()
+            .filter(|&&t| {
+                candidates
+                    .iter()
+                    .chain(&self.terms)
+                    .all(|&u| !self.all_terms.contains(t, u) || self.terms.contains(&u))
+            })
+            .map(|&t| {
+                let (df, sum_sq) = self.projected_sum_of_squares(&self.all_terms.columns(t));
+                let rss = (self.rss - sum_sq).max(0.0);
+                SelectionRow {
+                    change: self.all_terms.label(t).to_string(),
+                    df: Some(df),
+                    sum_sq: Some(sum_sq),
+                    rss,
+                    aic: self.criterion(rss, self.qr.rank + df, k),
+                }
+            })
+            .collect()
+    }
+
+    /// Orthogonalise the columns `new_columns` of x against the current model and return the
+    /// number of linearly independent directions they add, together with the decrease in the
+    /// residual sum of squares from projecting the residuals onto them.
+    fn projected_sum_of_squares(&self, new_columns: &[usize]) -> (usize, f64) {
+        let n = self.n_obs();
+        let mut orthogonal = RealMatrix::with_shape(n, new_columns.len());
+        for (a, &j) in new_columns.iter().enumerate() {
+            let column = self.x.values.column(j).to_vec();
+            let mut qtz = self.qr.qty(&column);
+            qtz[..self.qr.rank].iter_mut().for_each(|v| *v = 0.0);
+            let residual = self.qr.qy(&qtz);
+
+            // A column already spanned by the model leaves only rounding error behind, which
+            // the rank test of the decomposition below would judge against its own tiny norm.
+            let original = column.iter().map(|v| v * v).sum::<f64>().sqrt();
+            let remaining = residual.iter().map(|v| v * v).sum::<f64>().sqrt();
+            if remaining > 1e-7 * original {
+                for (i, value) in residual.into_iter().enumerate() {
+                    orthogonal.values[[i, a]] = value;
+                }
+            }
+        }
+
+        let qr = QrDecomposition::new(&orthogonal, &self.tol);
+        let rank = qr.rank;
+        let effects = qr.qty(&self.residuals);
+        (rank, effects[..rank].iter().map(|e| e * e).sum())
+    }
+
+    /// Describe the model the way R prints its formula.
+    fn describe(&self) -> String {
+        let mut labels: Vec<&str> = self
+            .terms
+            .iter()
+            .map(|&t| self.all_terms.label(t))
+            .collect();
+        if labels.is_empty() {
+            labels.push("1");
+        }
+        if !self.all_terms.has_intercept() {
+            labels.push("-1");
+        }
+        format!("y ~ {}", labels.join(" + "))
+    }
+}
+
+/// Return the columns `columns` of `x`.
+fn select_columns(x: &RealMatrix, columns: &[usize]) -> RealMatrix {
+    RealMatrix::new(x.values.select(ndarray::Axis(1), columns))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn data() -> Data {
+        // y depends on a and on the interaction a:b, but not on c.
+        let rows: Vec<[f64; 5]> = (0..12)
+            .map(|i| {
+                let a = (i % 4) as f64;
+                let b = ((i / 4) % 3) as f64;
+                let c = ((i * i) % 7) as f64;
+                [1.0, a, b, a * b, c]
+            })
+            .collect();
+        let noise = [
+            0.3, -0.2, 0.1, 0.4, -0.5, 0.2, -0.1, 0.3, -0.3, 0.1, 0.2, -0.4,
+        ];
+        let y: Vec<f64> = rows
+            .iter()
+            .zip(noise)
+            .map(|(row, e)| 1.0 + 2.0 * row[1] + 0.8 * row[3] + e)
+            .collect();
+        let x = RealMatrix::from_vec(rows.concat(), 12, Some(5));
+        Data::new(x, RealMatrix::from_vec(y, 12, None)).with_column_names(
+            ["(Intercept)", "a", "b", "a:b", "c"]
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+        )
+    }
+
+    fn refit_rss(data: &Data, labels: &[&str]) -> f64 {
+        let terms = resolve(&data.terms(), labels).unwrap();
+        CurrentModel::new(data, terms).unwrap().rss
+    }
+
+    #[test]
+    fn test_drop1_and_add1_match_refitted_models() {
+        let data = data();
+        let scope = Scope::full(&data.terms());
+
+        let dropped = drop1(&data, &["a", "b", "c"], &scope, Criterion::Aic).unwrap();
+        let row = dropped.row("c").unwrap();
+        assert!((row.rss - refit_rss(&data, &["a", "b"])).abs() < 1e-8);
+        assert_eq!(row.df, Some(1));
+
+        let added = add1(&data, &["a", "b"], &scope, Criterion::Aic).unwrap();
+        let row = added.row("a:b").unwrap();
+        assert!((row.rss - refit_rss(&data, &["a", "b", "a:b"])).abs() < 1e-8);
+        let n = 12.0_f64;
+        let aic = n * (row.rss / n).ln() + 2.0 * 4.0;
+        assert!((row.aic - aic).abs() < 1e-8);
+    }
+
+    #[test]
+    fn test_updated_decompositions_match_refitted_models() {
+        let data = data();
+        let terms = |labels: &[&str]| resolve(&data.terms(), labels).unwrap();
+        let current = CurrentModel::new(&data, terms(&["a", "b", "c"])).unwrap();
+
+        let dropped = current.update(terms(&["a", "c"]));
+        assert!((dropped.rss - refit_rss(&data, &["a", "c"])).abs() < 1e-8);
+        let added = dropped.update(terms(&["a", "b", "a:b", "c"]));
+        assert!((added.rss - refit_rss(&data, &["a", "b", "a:b", "c"])).abs() < 1e-8);
+        assert_eq!(added.qr.rank, 5);
+    }
+
+    #[test]
+    fn test_marginality_is_respected() {
+        let data = data();
+        let scope = Scope::full(&data.terms());
+
+        // a and b are contained in a:b, so only a:b and c may be dropped.
+        let dropped = drop1(&data, &["a", "b", "a:b", "c"], &scope, Criterion::Aic).unwrap();
+        assert!(dropped.row("a").is_none());
+        assert!(dropped.row("a:b").is_some());
+
+        // a:b cannot be added before b.
+        let added = add1(&data, &["a"], &scope, Criterion::Aic).unwrap();
+        assert!(added.row("a:b").is_none());
+        assert!(added.row("b").is_some());
+    }
+
+    #[test]
+    fn test_step_finds_the_interaction_model() {
+        let data = data();
+        let scope = Scope::new(&data.terms(), &["a"], &["a", "b", "a:b", "c"]).unwrap();
+
+        let result = step(&data, &["a"], &scope, Direction::Both, Criterion::Bic, 10).unwrap();
+
+        assert_eq!(result.labels, vec!["a", "b", "a:b"]);
+        assert_eq!(result.path[0].change, "<none>");
+        assert!(result.to_string().starts_with("Start:  AIC="));
+        assert!(matches!(
+            Scope::new(&data.terms(), &[], &["d"]),
+            Err(SelectionError::UnknownTerm { .. })
+        ));
+    }
+}
@@ -0,0 +1,99 @@
+//! This module describes how the columns of the x matrix group into model terms. It plays the
+//! role of the `assign` attribute and the term labels that R attaches to a model matrix: a
+//! factor with three levels contributes several columns, but a single term, to an ANOVA table.
+
+// src/terms.rs
+
+/// The label R uses for the intercept term.
+pub const INTERCEPT_LABEL: &str = "(Intercept)";
+
+/// A struct mapping each column of the x matrix to the model term it belongs to.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Terms {
+    /// The labels of the non-intercept terms. Term `i` (for `i >= 1`) is labelled `labels[i - 1]`.
+    pub labels: Vec<String>,
+    /// The term of each column of x. Term `0` is the intercept.
+    pub assign: Vec<usize>,
+}
+
+impl Terms {
+    /// Create a new `Terms` struct.
+    ///
+    /// # Arguments
+    /// * `labels` - The labels of the non-intercept terms, in order.
+    /// * `assign` - The term index of each column of x, where `0` marks the intercept and `i`
+    ///   refers to `labels[i - 1]`.
+    ///
+    /// # Panics
+    /// Panics if a column is assigned to a term that has no label.
+    pub fn new(labels: Vec<String>, assign: Vec<usize>) -> Self {
+        assert!(
+            assign.iter().all(|&term| term <= labels.len()),
+            "Every column must be assigned to the intercept or to a labelled term."
+        );
+        Terms { labels, assign }
+    }
+
+    /// Create a `Terms` struct with one term per column, labelled by the column name. A column
+    /// named `(Intercept)` is treated as the intercept.
+    pub fn one_per_column(column_names: &[String]) -> Self {
+        let mut labels = Vec::new();
+        let assign = column_names
+            .iter()
+            .map(|name| {
+                if name == INTERCEPT_LABEL {
+                    0
+                } else {
+                    labels.push(name.clone());
+                    labels.len()
+                }
+            })
+            .collect();
+        Terms { labels, assign }
+    }
+
+    /// Return the number of non-intercept terms.
+    pub fn n_terms(&self) -> usize {
+        self.labels.len()
+    }
+
+    /// Return `true` if any column belongs to the intercept.
+    pub fn has_intercept(&self) -> bool {
+        self.assign.contains(&0)
+    }
+
+    /// Return the label of `term`, where term `0` is the intercept.
+    pub fn label(&self, term: usize) -> &str {
+        match term {
+            0 => INTERCEPT_LABEL,
+            _ => &self.labels[term - 1],
+        }
+    }
+
+    /// Return the indices of the columns of x that belong to `term`.
+    pub fn columns(&self, term: usize) -> Vec<usize> {
+        (0..self.assign.len())
+            .filter(|&j| self.assign[j] == term)
+            .collect()
+    }
+
+    /// Return the factors of `term`, the parts of its label separated by `:`.
+    pub fn factors(&self, term: usize) -> Vec<&str> {
+        match term {
+            0 => Vec::new(),
+            _ => self.labels[term - 1].split(':').collect(),
+        }
+    }
+
+    /// Return `true` if `term` is a higher-order relative of `other`, i.e. an interaction that
+    /// contains every factor of `other` (such as `a:b` for `a`).
+    pub fn contains(&self, term: usize, other: usize) -> bool {
+        let factors = self.factors(term);
+        term != other
+            && other != 0
+            && self
+                .factors(other)
+                .iter()
+                .all(|factor| factors.contains(factor))
+    }
+}
@@ -0,0 +1,257 @@
+//! This module computes the variance-covariance matrix of the coefficients of a least squares
+//! fit, either under the classical homoskedastic assumption or with a heteroskedasticity-robust
+//! sandwich estimator (HC0 to HC3, as in R's `sandwich::vcovHC`), or clustered by a grouping
+//! of the observations (as in `sandwich::vcovCL`).
+//!
+//! Everything is computed from the pivoted QR decomposition: (X'X)^-1 = R^-1 R^-T over the
+//! retained columns, and the hat values are the squared row norms of the first `rank` columns
+//! of Q. Rows and columns of aliased coefficients are `NaN`, like R's `vcov(complete = TRUE)`.
+
+// src/vcov.rs
+
+use crate::errors::LmFitterError;
+use crate::least_squares::{LeastSquaresFit, QrDecomposition};
+use crate::types::RealMatrix;
+use std::collections::HashMap;
+
+/// An enum representing the available estimators of the covariance of the coefficients.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum CovarianceType {
+    /// The classical estimator sigma^2 (X'X)^-1.
+    #[default]
+    Classical,
+    /// White's estimator, weighting each observation by its squared residual.
+    HC0,
+    /// HC0 scaled by n / (n - rank).
+    HC1,
+    /// HC0 with squared residuals divided by 1 - h.
+    HC2,
+    /// HC0 with squared residuals divided by (1 - h)^2.
+    HC3,
+}
+
+/// Return (X'X)^-1 for the retained columns, in the original column order. Rows and columns of
+/// aliased columns are `NaN`.
+pub fn unscaled_covariance(qr: &QrDecomposition) -> RealMatrix {
+    let (p, k) = (qr.n_cols(), qr.rank);
+
+    // Invert R column by column, then form R^-1 R^-T in pivoted order.
+    let mut r_inverse = RealMatrix::with_shape(k, k);
+    for j in 0..k {
+        let mut unit = vec![0.0; k];
+        unit[j] = 1.0;
+        for (i, value) in qr.backsolve(&unit).into_iter().enumerate() {
+            r_inverse.values[[i, j]] = value;
+        }
+    }
+    let pivoted = r_inverse.dot(&r_inverse.transpose());
+
+    let mut covariance = RealMatrix::new(ndarray::Array2::from_elem((p, p), f64::NAN));
+    for a in 0..k {
+        for b in 0..k {
+            covariance.values[[qr.pivot[a], qr.pivot[b]]] = pivoted.values[[a, b]];
+        }
+    }
+    covariance
+}
+
+/// Return the diagonal of the hat matrix X (X'X)^-1 X', the leverage of each observation.
+pub fn hat_values(qr: &QrDecomposition) -> Vec<f64> {
+    let n = qr.n_rows();
+    let mut hat = vec![0.0; n];
+    for j in 0..qr.rank {
+        let mut unit = vec![0.0; n];
+        unit[j] = 1.0;
+        for (h, q) in hat.iter_mut().zip(qr.qy(&unit)) {
+            *h += q * q;
+        }
+    }
+    hat
+}
+
+/// Return the covariance matrix of the coefficients of the `response`-th column of a least
+/// squares fit.
+///
+/// # Arguments
+/// * `fit` - The result of `dqrls` for the model.
+/// * `x` - The model matrix the fit was computed from.
+/// * `response` - The column of y whose coefficients are of interest.
+/// * `kind` - The covariance estimator to use.
+pub fn vcov(
+    fit: &LeastSquaresFit,
+    x: &RealMatrix,
+    response: usize,
+    kind: CovarianceType,
+) -> RealMatrix {
+    let (n, rank) = (fit.qr.n_rows(), fit.rank());
+    let bread = unscaled_covariance(&fit.qr);
+    let residuals: Vec<f64> = fit.residuals.values.column(response).to_vec();
+
+    let omega: Vec<f64> = match kind {
+        CovarianceType::Classical => {
+            let sigma2 = residuals.iter().map(|e| e * e).sum::<f64>() / (n - rank) as f64;
+            return RealMatrix::new(bread.values * sigma2);
+        }
+        CovarianceType::HC0 => residuals.iter().map(|e| e * e).collect(),
+        CovarianceType::HC1 => {
+            let scale = n as f64 / (n - rank) as f64;
+            residuals.iter().map(|e| e * e * scale).collect()
+        }
+        CovarianceType::HC2 => residuals
+            .iter()
+            .zip(hat_values(&fit.qr))
+            .map(|(e, h)| e * e / (1.0 - h))
+            .collect(),
+        CovarianceType::HC3 => residuals
+            .iter()
+            .zip(hat_values(&fit.qr))
+            .map(|(e, h)| e * e / (1.0 - h).powi(2))
+            .collect(),
+    };
+
+    // meat = X' diag(omega) X, computed over the retained columns only.
+    let p = x.n_cols();
+    let retained = &fit.qr.pivot[..rank];
+    let mut meat = RealMatrix::with_shape(p, p);
+    for (i, w) in omega.iter().enumerate() {
+        for &a in retained {
+            for &b in retained {
+                meat.values[[a, b]] += w * x.values[[i, a]] * x.values[[i, b]];
+            }
+        }
+    }
+    sandwich(&bread, &meat, retained)
+}
+
+/// Return the cluster-robust covariance matrix of the coefficients of the `response`-th column
+/// of a least squares fit, with the CR1 small-sample correction G / (G - 1) (n - 1) / (n - rank)
+/// of Stata's `vce(cluster)` and `sandwich::vcovCL`.
+///
+/// # Arguments
+/// * `fit` - The result of `dqrls` for the model.
+/// * `x` - The model matrix the fit was computed from.
+/// * `response` - The column of y whose coefficients are of interest.
+/// * `clusters` - The cluster of each observation.
+///
+/// # Errors
+/// Returns `LmFitterError::TooFewClusters` if there are fewer than two clusters, for which the
+/// correction is undefined.
+pub fn cluster_vcov(
+    fit: &LeastSquaresFit,
+    x: &RealMatrix,
+    response: usize,
+    clusters: &[usize],
+) -> Result<RealMatrix, LmFitterError> {
+    let (n, rank, p) = (fit.qr.n_rows(), fit.rank(), x.n_cols());
+    let bread = unscaled_covariance(&fit.qr);
+    let retained = &fit.qr.pivot[..rank];
+
+    // The score X_g' e_g of each cluster.
+    let mut scores: HashMap<usize, Vec<f64>> = HashMap::new();
+    for (i, &cluster) in clusters.iter().enumerate() {
+        let residual = fit.residuals.values[[i, response]];
+        let score = scores.entry(cluster).or_insert_with(|| vec![0.0; p]);
+        for &a in retained {
+            score[a] += x.values[[i, a]] * residual;
+        }
+    }
+    if scores.len() < 2 {
+        return Err(LmFitterError::TooFewClusters {
+            found: scores.len(),
+        });
+    }
+    let g = scores.len() as f64;
+    let scale = g / (g - 1.0) * (n - 1) as f64 / (n - rank) as f64;
+    let mut meat = RealMatrix::with_shape(p, p);
+    for score in scores.values() {
+        for &a in retained {
+            for &b in retained {
+                meat.values[[a, b]] += scale * score[a] * score[b];
+            }
+        }
+    }
+    Ok(sandwich(&bread, &meat, retained))
+}
+
+/// Multiply bread * meat * bread on the retained columns, leaving aliased entries NaN.
+fn sandwich(bread: &RealMatrix, meat: &RealMatrix, retained: &[usize]) -> RealMatrix {
+    let p = bread.n_cols();
+    let mut sandwich = RealMatrix::new(ndarray::Array2::from_elem((p, p), f64::NAN));
+    for &a in retained {
+        for &b in retained {
+            let mut value = 0.0;
+            for &c in retained {
+                for &d in retained {
+                    value += bread.values[[a, c]] * meat.values[[c, d]] * bread.values[[d, b]];
+                }
+            }
+            sandwich.values[[a, b]] = value;
+        }
+    }
+    sandwich
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::least_squares::dqrls;
+    use crate::types::Tolerance;
+
+    #[test]
+    fn test_classical_vcov_of_a_simple_regression() {
+        let x = RealMatrix::from_vec(
+            vec![1.0, 1.0, 1.0, 2.0, 1.0, 3.0, 1.0, 4.0, 1.0, 5.0],
+            5,
+            Some(2),
+        );
+        let y = RealMatrix::from_vec(vec![1.1, 1.9, 3.2, 3.8, 5.0], 5, None);
+        let fit = dqrls(&x, &y, &Tolerance::default()).unwrap();
+
+        let v = vcov(&fit, &x, 0, CovarianceType::Classical);
+
+        // Var(slope) = sigma^2 / Sxx, with Sxx = 10.
+        let rss: f64 = fit.residuals.values.iter().map(|e| e * e).sum();
+        let sigma2 = rss / 3.0;
+        assert!((v.values[[1, 1]] - sigma2 / 10.0).abs() < 1e-12);
+        assert!((v.values[[0, 1]] - v.values[[1, 0]]).abs() < 1e-12);
+
+        let hat = hat_values(&fit.qr);
+        assert!((hat.iter().sum::<f64>() - 2.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_hc0_vcov_matches_the_sandwich_formula() {
+        let x = RealMatrix::from_vec(vec![1.0, 1.0, 1.0, 1.0], 4, None);
+        let y = RealMatrix::from_vec(vec![1.0, 2.0, 4.0, 7.0], 4, None);
+        let fit = dqrls(&x, &y, &Tolerance::default()).unwrap();
+
+        let v = vcov(&fit, &x, 0, CovarianceType::HC0);
+
+        // For an intercept-only model, HC0 is sum(e^2) / n^2.
+        let sum_e2: f64 = fit.residuals.values.iter().map(|e| e * e).sum();
+        assert!((v.values[[0, 0]] - sum_e2 / 16.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_cluster_vcov_with_singleton_clusters_is_hc1() {
+        let x = RealMatrix::from_vec(
+            vec![1.0, 0.5, 1.0, 1.5, 1.0, 2.0, 1.0, 3.5, 1.0, 4.0, 1.0, 6.0],
+            6,
+            Some(2),
+        );
+        let y = RealMatrix::from_vec(vec![1.0, 2.5, 2.0, 4.5, 3.9, 7.2], 6, None);
+        let fit = dqrls(&x, &y, &Tolerance::default()).unwrap();
+
+        // With one observation per cluster, G / (G - 1) (n - 1) / (n - k) is n / (n - k).
+        let clustered = cluster_vcov(&fit, &x, 0, &[0, 1, 2, 3, 4, 5]).unwrap();
+        let hc1 = vcov(&fit, &x, 0, CovarianceType::HC1);
+        for (a, b) in clustered.values.iter().zip(hc1.values.iter()) {
+            assert!((a - b).abs() < 1e-12);
+        }
+
+        assert!(matches!(
+            cluster_vcov(&fit, &x, 0, &[7; 6]),
+            Err(LmFitterError::TooFewClusters { found: 1 })
+        ));
+    }
+}